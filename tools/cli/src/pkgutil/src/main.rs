@@ -6,29 +6,40 @@
 
 //! Rustica Package Manager (pkg)
 //!
-//! Package manager for installing, updating, and managing software packages.
+//! An apt-style front end for installing, updating, and managing software
+//! packages. All commands go through `rpg_core::PackageManager`, so `pkg`
+//! and `rpg` share the same registry, sources and transaction history.
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Result};
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use rpg_core::plan::format_size;
+use rpg_core::{
+    InstallOptions, PackageManager, Plan, RegistryLock, RemoveOptions, Scope, TransactionResult,
+    UpdateInfo,
+};
+use std::io::{BufRead, Write};
 
 /// Rustica Package Manager
 #[derive(Parser, Debug)]
 #[command(name = "pkg")]
 #[command(about = "Rustica Package Manager", long_about = None)]
 struct Args {
+    /// Manage apps for the current user instead of the system
+    #[arg(long, global = true)]
+    user: bool,
+
+    /// Wait for another package manager to release the registry lock
+    #[arg(long, global = true)]
+    wait: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    /// Update package list
-    Update {
-        /// Force update even if up-to-date
-        #[arg(short, long)]
-        force: bool,
-    },
+    /// Refresh package lists and report available upgrades
+    Update,
 
     /// Install packages
     Install {
@@ -51,9 +62,20 @@ enum Commands {
         #[arg(required = true)]
         packages: Vec<String>,
 
-        /// Remove dependencies
+        /// Also remove configuration and data files
         #[arg(short, long)]
         purge: bool,
+
+        /// Assume yes
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Remove dependencies nothing needs anymore
+    Autoremove {
+        /// Assume yes
+        #[arg(short, long)]
+        yes: bool,
     },
 
     /// Search for packages
@@ -89,284 +111,477 @@ enum Commands {
     },
 }
 
-#[derive(Debug, Clone)]
-struct Package {
-    name: String,
-    version: String,
-    description: String,
-    size: u64,
-    dependencies: Vec<String>,
-    checksum: String,
+impl Commands {
+    /// Check if the command changes installed packages
+    fn modifies_registry(&self) -> bool {
+        match self {
+            Self::Install { download_only, .. } => !download_only,
+            Self::Remove { .. } | Self::Autoremove { .. } | Self::Upgrade { .. } => true,
+            Self::Update
+            | Self::Search { .. }
+            | Self::RepoList
+            | Self::Info { .. }
+            | Self::List { .. } => false,
+        }
+    }
 }
 
-#[derive(Debug)]
-struct Repository {
-    name: String,
-    url: String,
-    enabled: bool,
-}
+/// Ask a yes/no question, defaulting to no
+///
+/// Fails without a terminal or an answer, so scripts must pass `--yes`.
+fn confirm(question: &str) -> Result<bool> {
+    use std::io::IsTerminal;
+
+    if !std::io::stdin().is_terminal() {
+        bail!("Cannot ask for confirmation without a terminal (use --yes)");
+    }
 
-#[derive(Debug)]
-struct PackageManager {
-    config_dir: PathBuf,
-    cache_dir: PathBuf,
-    package_dir: PathBuf,
-    repositories: Vec<Repository>,
+    read_answer(question, &mut std::io::stdin().lock())
 }
 
-impl PackageManager {
-    fn new() -> Result<Self> {
-        let config_dir = PathBuf::from("/etc/rustica");
-        let cache_dir = PathBuf::from("/var/cache/rpg");
-        let package_dir = PathBuf::from("/var/lib/rpg");
-
-        // Create directories
-        std::fs::create_dir_all(&cache_dir)?;
-        std::fs::create_dir_all(&package_dir)?;
-
-        let mut pm = Self {
-            config_dir,
-            cache_dir,
-            package_dir,
-            repositories: Vec::new(),
-        };
+/// Ask a yes/no question, reading the answer from `input`
+fn read_answer(question: &str, input: &mut impl BufRead) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
 
-        // Load repositories
-        pm.load_repositories()?;
-
-        Ok(pm)
-    }
-
-    fn load_repositories(&mut self) -> Result<()> {
-        let sources_file = self.config_dir.join("sources.list");
-
-        if !sources_file.exists() {
-            // Default repositories
-            self.repositories = vec![
-                Repository {
-                    name: "kernel".to_string(),
-                    url: "http://rustux.com/kernel".to_string(),
-                    enabled: true,
-                },
-                Repository {
-                    name: "rustica".to_string(),
-                    url: "http://rustux.com/rustica".to_string(),
-                    enabled: true,
-                },
-                Repository {
-                    name: "apps".to_string(),
-                    url: "http://rustux.com/apps".to_string(),
-                    enabled: true,
-                },
-            ];
-
-            // Save default repositories
-            self.save_repositories()?;
-            return Ok(());
-        }
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        bail!("No answer to the confirmation prompt");
+    }
 
-        let content = std::fs::read_to_string(&sources_file)?;
-        self.repositories = content
-            .lines()
-            .filter(|line| !line.trim().is_empty() && !line.trim().starts_with('#'))
-            .map(|line| {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                Repository {
-                    name: parts.get(0).unwrap_or(&"").to_string(),
-                    url: parts.get(1).unwrap_or(&"").to_string(),
-                    enabled: parts.get(2).map(|&s| s != "disabled").unwrap_or(true),
-                }
-            })
-            .collect();
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
 
-        Ok(())
-    }
+/// Show a plan and ask whether to carry it out
+fn confirm_plan(plan: &Plan, yes: bool) -> Result<bool> {
+    print!("{}", plan);
 
-    fn save_repositories(&self) -> Result<()> {
-        let sources_file = self.config_dir.join("sources.list");
+    plan.check_space()?;
 
-        let mut content = String::from("# Rustica Package Repositories\n");
-        content.push_str("# Format: name url [enabled|disabled]\n\n");
+    if plan.is_empty() {
+        return Ok(false);
+    }
 
-        for repo in &self.repositories {
-            let status = if repo.enabled { "enabled" } else { "disabled" };
-            content.push_str(&format!("{} {} {}\n", repo.name, repo.url, status));
+    Ok(yes || confirm("Do you want to continue?")?)
+}
+
+/// Turn a failed transaction into an error
+fn check(result: TransactionResult, what: &str) -> Result<Vec<String>> {
+    match result {
+        TransactionResult::Success {
+            activated,
+            requires_reboot,
+        } => {
+            if !requires_reboot.is_empty() {
+                println!("Reboot required for: {}", requires_reboot.join(", "));
+            }
+            Ok(activated)
+        }
+        TransactionResult::Failed { error, .. } => bail!("{} failed: {}", what, error),
+        TransactionResult::RolledBack { reason, .. } => {
+            bail!("{} rolled back: {}", what, reason)
         }
+    }
+}
 
-        std::fs::write(&sources_file, content)?;
-        Ok(())
+async fn update(pm: &PackageManager) -> Result<()> {
+    println!("Reading package lists...");
+    let available = pm.available_packages().await?;
+    let info = pm.check_updates().await?;
+    pm.publish_updates(&info).await;
+
+    for error in &info.errors {
+        log::warn!("{}", error);
     }
 
-    fn update_repositories(&self, _force: bool) -> Result<()> {
-        println!("Updating package lists...");
+    print!("{}", update_report(available.len(), &info));
 
-        for repo in &self.repositories {
-            if !repo.enabled {
-                continue;
-            }
+    Ok(())
+}
 
-            println!("  Fetching from {}...", repo.name);
+/// Describe how many packages there are and how many can be upgraded
+///
+/// Updates held back by a pin or hold are counted separately, since
+/// `pkg upgrade` leaves them alone.
+fn update_report(packages: usize, info: &UpdateInfo) -> String {
+    let mut report = format!("{} packages available.\n", packages);
+    if info.available.is_empty() {
+        report.push_str("All packages are up to date.\n");
+    } else {
+        report.push_str(&format!(
+            "{} packages can be upgraded. Run 'pkg upgrade' to install them.\n",
+            info.available.len()
+        ));
+    }
+    if !info.held.is_empty() {
+        report.push_str(&format!(
+            "{} packages are held back by a pin.\n",
+            info.held.len()
+        ));
+    }
+    report
+}
 
-            // In production, would:
-            // 1. Fetch package index from repo URL
-            // 2. Parse package metadata
-            // 3. Update local cache
+async fn install(
+    pm: &PackageManager,
+    packages: &[String],
+    yes: bool,
+    download_only: bool,
+) -> Result<()> {
+    let mut targets = Vec::new();
+    for package in packages {
+        targets.push((package, pm.resolve_kind(package).await?));
+    }
 
-            // For now, simulate
-            println!("    {} packages", 100);
+    if download_only {
+        for (package, kind) in targets {
+            let path = pm.download_package(package, None, kind).await?;
+            println!("Downloaded {} to {}", package, path.display());
         }
+        return Ok(());
+    }
 
-        println!("Done.");
-        Ok(())
+    let mut plan = Plan::new();
+    for (package, kind) in &targets {
+        plan.extend(
+            pm.plan_install(package, None, *kind, InstallOptions::default())
+                .await?,
+        );
+    }
+    if !confirm_plan(&plan.with_disk_usage(), yes)? {
+        return Ok(());
     }
 
-    fn search_packages(&self, query: &str, _name_only: bool) -> Result<()> {
-        println!("Searching for '{}'...", query);
+    for (package, kind) in targets {
+        let activated = check(pm.install_package(package, None, kind).await?, "Install")?;
+        if !activated.is_empty() {
+            println!("Installed: {}", activated.join(", "));
+        }
+    }
 
-        // In production, would search local package cache
-        // For now, show sample results
+    Ok(())
+}
 
-        let results = vec![
-            Package {
-                name: "rustica-shell".to_string(),
-                version: "0.1.0".to_string(),
-                description: "Rustica shell (POSIX-compatible)".to_string(),
-                size: 1024 * 512,
-                dependencies: vec![],
-                checksum: String::new(),
-            },
-            Package {
-                name: "networkutils".to_string(),
-                version: "0.1.0".to_string(),
-                description: "Networking utilities (ip, ping, hostname, nslookup)".to_string(),
-                size: 1024 * 256,
-                dependencies: vec!["rustica-runtime".to_string()],
-                checksum: String::new(),
-            },
-        ];
+async fn remove(pm: &PackageManager, packages: &[String], purge: bool, yes: bool) -> Result<()> {
+    let options = RemoveOptions {
+        purge,
+        ..RemoveOptions::default()
+    };
 
-        for pkg in results {
-            println!("  {} - {}", pkg.name, pkg.version);
-            println!("    {}", pkg.description);
-            println!();
-        }
+    let mut plan = Plan::new();
+    for package in packages {
+        plan.extend(pm.plan_remove(package, options).await?);
+    }
+    if !confirm_plan(&plan, yes)? {
+        return Ok(());
+    }
 
-        Ok(())
+    for package in packages {
+        let removed = check(pm.remove_package_with(package, options).await?, "Remove")?;
+        println!("Removed: {}", removed.join(", "));
     }
 
-    fn install_package(&self, package: &str, _yes: bool, _download_only: bool) -> Result<()> {
-        println!("Installing {}...", package);
+    Ok(())
+}
 
-        // In production, would:
-        // 1. Check if package exists
-        // 2. Download package file
-        // 3. Verify checksum
-        // 4. Extract to install directory
-        // 5. Run post-install script
-        // 6. Update package database
+async fn autoremove(pm: &PackageManager, yes: bool) -> Result<()> {
+    if !confirm_plan(&pm.plan_autoremove().await?, yes)? {
+        return Ok(());
+    }
 
-        println!("  Downloading...");
-        println!("  Extracting...");
-        println!("  Configuring...");
-        println!("  Done.");
+    let removed = check(pm.autoremove().await?, "Autoremove")?;
+    if !removed.is_empty() {
+        println!("Removed: {}", removed.join(", "));
+    }
 
-        Ok(())
+    Ok(())
+}
+
+async fn upgrade(pm: &PackageManager, yes: bool) -> Result<()> {
+    let info = pm.check_updates().await?;
+    pm.publish_updates(&info).await;
+    for error in &info.errors {
+        log::warn!("{}", error);
     }
 
-    fn remove_package(&self, package: &str, _purge: bool) -> Result<()> {
-        println!("Removing {}...", package);
+    if !info.held.is_empty() {
+        let held: Vec<&str> = info.held.iter().map(|u| u.name.as_str()).collect();
+        println!("Held back: {}", held.join(", "));
+    }
+    if info.available.is_empty() {
+        println!("All packages are up to date.");
+        return Ok(());
+    }
 
-        // In production, would:
-        // 1. Check for dependents
-        // 2. Run pre-remove script
-        // 3. Remove files
-        // 4. Update package database
+    if !confirm_plan(&pm.plan_updates(&info).await?, yes)? {
+        return Ok(());
+    }
+
+    let result = pm.install_updates(&info).await?;
+    if !result.requires_reboot.is_empty() {
+        println!("Reboot required for: {}", result.requires_reboot.join(", "));
+    }
+    if !result.failed.is_empty() {
+        for (package, error) in &result.failed {
+            eprintln!("Failed to upgrade {}: {}", package, error);
+        }
+        bail!("{} packages failed to upgrade", result.failed.len());
+    }
+
+    Ok(())
+}
 
-        println!("  Done.");
+async fn search(pm: &PackageManager, query: &str, name_only: bool) -> Result<()> {
+    let query = query.to_lowercase();
 
-        Ok(())
+    for package in pm.available_packages().await? {
+        let description = package.description.as_deref().unwrap_or_default();
+        let matches = package.name.to_lowercase().contains(&query)
+            || (!name_only && description.to_lowercase().contains(&query));
+        if !matches {
+            continue;
+        }
+
+        let status = match &package.installed {
+            Some(v) if *v == package.version => " [installed]".to_string(),
+            Some(v) => format!(" [installed: {}]", v),
+            None => String::new(),
+        };
+        println!("{} {}{}", package.name, package.version, status);
+        if !description.is_empty() {
+            println!("  {}", description);
+        }
     }
 
-    fn show_package_info(&self, package: &str) -> Result<()> {
-        println!("Package: {}", package);
-        println!("Version: 0.1.0");
-        println!("Description: Sample package");
-        println!("Size: 512 KB");
-        println!("Dependencies: none");
+    Ok(())
+}
 
-        Ok(())
+async fn info(pm: &PackageManager, package: &str) -> Result<()> {
+    let available = pm
+        .available_packages()
+        .await?
+        .into_iter()
+        .find(|p| p.name == package);
+    let installed = pm
+        .list_installed()
+        .await?
+        .into_iter()
+        .find(|p| p.name == package);
+
+    if available.is_none() && installed.is_none() {
+        bail!("Package not found: {}", package);
     }
 
-    fn list_installed(&self, pattern: Option<&str>) -> Result<()> {
-        println!("Installed packages:");
+    println!("Package: {}", package);
+    if let Some(installed) = &installed {
+        println!("Installed: {} ({})", installed.version, installed.reason);
+    }
+    if let Some(available) = &available {
+        println!("Candidate: {}", available.version);
+        println!("Kind: {}", available.kind);
+        println!("Source: {}", available.source);
+        println!("Download size: {}", format_size(available.size));
+        if let Some(size) = available.installed_size {
+            println!("Installed size: {}", format_size(size));
+        }
+        if available.dependencies.is_empty() {
+            println!("Dependencies: none");
+        } else {
+            println!("Dependencies: {}", available.dependencies.join(", "));
+        }
+        if let Some(description) = &available.description {
+            println!("Description: {}", description);
+        }
+    } else if let Some(installed) = &installed {
+        println!("Kind: {}", installed.kind);
+    }
 
-        // In production, would read from package database
-        let packages = vec![
-            "rustica-shell 0.1.0",
-            "coreutils 0.1.0",
-            "sysutils 0.1.0",
-            "networkutils 0.1.0",
-        ];
+    Ok(())
+}
 
-        for pkg in packages {
-            if let Some(pat) = pattern {
-                if pkg.contains(pat) {
-                    println!("  {}", pkg);
-                }
-            } else {
-                println!("  {}", pkg);
-            }
+async fn list(pm: &PackageManager, pattern: Option<&str>) -> Result<()> {
+    for package in pm.list_installed().await? {
+        if pattern.is_some_and(|p| !package.name.contains(p)) {
+            continue;
         }
+        println!("{} {} [{}]", package.name, package.version, package.reason);
+    }
 
-        Ok(())
+    Ok(())
+}
+
+async fn repo_list(pm: &PackageManager) -> Result<()> {
+    println!("Configured repositories:");
+    for source in pm.sources().await {
+        let status = if source.enabled { "[enabled]" } else { "[disabled]" };
+        println!(
+            "  {} - {} ({}, {}) {}",
+            source.name, source.url, source.source_type, source.channel, status
+        );
     }
+
+    Ok(())
 }
 
 #[tokio::main]
 async fn run() -> Result<()> {
     let args = Args::parse();
-    let pm = PackageManager::new()?;
+    let scope = if args.user { Scope::User } else { Scope::System };
+
+    // Held until the command finishes
+    let _lock = if args.command.modifies_registry() {
+        Some(RegistryLock::acquire_at(scope.lock_path()?, args.wait)?)
+    } else {
+        None
+    };
+
+    let pm = PackageManager::for_scope(scope)?;
 
     match args.command {
-        Commands::Update { force } => {
-            pm.update_repositories(force)?;
-        }
-        Commands::Install { packages, yes, download_only } => {
-            for package in packages {
-                pm.install_package(&package, yes, download_only)?;
-            }
-        }
-        Commands::Remove { packages, purge } => {
-            for package in packages {
-                pm.remove_package(&package, purge)?;
-            }
-        }
-        Commands::Search { query, name_only } => {
-            pm.search_packages(&query, name_only)?;
-        }
-        Commands::RepoList => {
-            println!("Configured repositories:");
-            for repo in &pm.repositories {
-                let status = if repo.enabled { "[enabled]" } else { "[disabled]" };
-                println!("  {} - {} {}", repo.name, repo.url, status);
-            }
-        }
-        Commands::Upgrade { yes } => {
-            println!("Upgrading all packages...");
-            // In production, would check for updates and install them
-            println!("  All packages are up to date.");
-        }
-        Commands::Info { package } => {
-            pm.show_package_info(&package)?;
-        }
-        Commands::List { pattern } => {
-            pm.list_installed(pattern.as_deref())?;
-        }
+        Commands::Update => update(&pm).await?,
+        Commands::Install {
+            packages,
+            yes,
+            download_only,
+        } => install(&pm, &packages, yes, download_only).await?,
+        Commands::Remove {
+            packages,
+            purge,
+            yes,
+        } => remove(&pm, &packages, purge, yes).await?,
+        Commands::Autoremove { yes } => autoremove(&pm, yes).await?,
+        Commands::Search { query, name_only } => search(&pm, &query, name_only).await?,
+        Commands::RepoList => repo_list(&pm).await?,
+        Commands::Upgrade { yes } => upgrade(&pm, yes).await?,
+        Commands::Info { package } => info(&pm, &package).await?,
+        Commands::List { pattern } => list(&pm, pattern.as_deref()).await?,
     }
 
     Ok(())
 }
 
 fn main() -> Result<()> {
+    env_logger::init();
     run()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rpg_core::fetch::{PackageEntry, RepositoryIndex};
+    use rpg_core::registry::PackageRegistry;
+    use rpg_core::{MemoryBackend, PackageKind, PinConfig, Source, SourcesConfig, Version};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    const REPO: &str = "http://repo.test/apps";
+
+    /// Create a package manager under `root` with `installed` apps active
+    /// and a repository listing `listed` as name and version pairs
+    fn test_manager(
+        root: &TempDir,
+        installed: &[&str],
+        listed: &[(&str, &str)],
+    ) -> PackageManager {
+        let under = |path: &str| root.path().join(path.trim_start_matches('/'));
+
+        SourcesConfig {
+            sources: vec![Source::new("apps".to_string(), REPO.to_string(), "apps".to_string())],
+            ..SourcesConfig::default()
+        }
+        .save_to_path(under(rpg_core::sources::SOURCES_PATH))
+        .unwrap();
+
+        let mut registry = PackageRegistry::new();
+        for name in installed {
+            registry.register_package(name.to_string(), Version::new(1, 0, 0));
+            registry.set_active(name.to_string(), Version::new(1, 0, 0));
+        }
+        registry
+            .save_to_path(under(rpg_core::layout::META_DIR).join("registry.json"))
+            .unwrap();
+
+        let packages = listed
+            .iter()
+            .map(|(name, version)| PackageEntry {
+                name: name.to_string(),
+                version: version.to_string(),
+                description: None,
+                kind: Some(PackageKind::App),
+                arch: rpg_core::arch::host_arch(),
+                size: 0,
+                installed_size: None,
+                sha256: "0".repeat(64),
+                signature: String::new(),
+                dependencies: Vec::new(),
+                path: format!("{}/{}.rpg", name, version),
+                source: String::new(),
+            })
+            .collect();
+        let backend = Arc::new(MemoryBackend::new());
+        backend.insert_index(
+            format!("{}/index.json", REPO),
+            &RepositoryIndex {
+                name: "apps".to_string(),
+                version: "1".to_string(),
+                last_updated: None,
+                packages,
+                source: String::new(),
+            },
+        );
+
+        PackageManager::at_root(root.path()).unwrap().with_backend(backend)
+    }
+
+    /// Hold a package at its installed version
+    fn hold(root: &TempDir, name: &str) {
+        let mut pins = PinConfig::default();
+        pins.hold(name);
+        pins.save_to_path(root.path().join(rpg_core::config::PINS_PATH.trim_start_matches('/')))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_counts_held_packages_separately() {
+        let root = TempDir::new().unwrap();
+        hold(&root, "viewer");
+        let pm = test_manager(
+            &root,
+            &["editor", "viewer"],
+            &[("editor", "1.1.0"), ("viewer", "2.0.0"), ("game", "3.0.0")],
+        );
+
+        let info = pm.check_updates().await.unwrap();
+        let report = update_report(pm.available_packages().await.unwrap().len(), &info);
+        assert_eq!(
+            report,
+            "3 packages available.\n\
+             1 packages can be upgraded. Run 'pkg upgrade' to install them.\n\
+             1 packages are held back by a pin.\n"
+        );
+        update(&pm).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_skips_held_packages() {
+        let root = TempDir::new().unwrap();
+        hold(&root, "viewer");
+        let pm = test_manager(&root, &["viewer"], &[("viewer", "2.0.0"), ("game", "3.0.0")]);
+
+        // Nothing is left to upgrade, so there is nothing to confirm
+        let info = pm.check_updates().await.unwrap();
+        assert!(info.available.is_empty());
+        assert_eq!(
+            update_report(2, &info).lines().nth(1),
+            Some("All packages are up to date.")
+        );
+        upgrade(&pm, false).await.unwrap();
+    }
+
+    #[test]
+    fn test_confirm_needs_an_answer() {
+        assert!(read_answer("Continue?", &mut "".as_bytes()).is_err());
+        assert!(read_answer("Continue?", &mut "Yes\n".as_bytes()).unwrap());
+        assert!(!read_answer("Continue?", &mut "\n".as_bytes()).unwrap());
+    }
+}
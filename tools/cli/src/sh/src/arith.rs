@@ -0,0 +1,339 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Arithmetic expansion
+//!
+//! Expressions are evaluated on signed 64-bit integers with the C operators
+//! POSIX requires: unary `+ - ! ~`, binary `* / % + - << >> < <= > >= == !=
+//! & ^ | && ||`, `?:` and the assignments `= *= /= %= += -= <<= >>= &= ^= |=`.
+//! A name stands for the value of the variable, zero if it is unset or empty.
+
+use anyhow::{anyhow, bail, Result};
+use std::fmt;
+
+use crate::exec::Shell;
+
+/// Operators by spelling, longest first
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "&&", "||", "==", "!=", "<=", ">=", "<<", ">>", "*=", "/=", "%=", "+=", "-=",
+    "&=", "^=", "|=", "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~", "?", ":", "=",
+    "(", ")",
+];
+
+/// Binary operators by precedence, lowest first
+const BINARY: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+/// Assignment operators
+const ASSIGNMENTS: &[&str] = &[
+    "=", "*=", "/=", "%=", "+=", "-=", "<<=", ">>=", "&=", "^=", "|=",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "'{}'", n),
+            Token::Name(name) => write!(f, "'{}'", name),
+            Token::Op(op) => write!(f, "'{}'", op),
+        }
+    }
+}
+
+/// Evaluate an expression whose parameters have already been expanded
+pub fn evaluate(shell: &mut Shell, expression: &str) -> Result<i64> {
+    let tokens = tokenize(expression).map_err(|e| anyhow!("{}: {}", expression.trim(), e))?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+
+    let mut evaluator = Evaluator {
+        shell,
+        tokens,
+        index: 0,
+    };
+    let value = evaluator
+        .assignment(true)
+        .and_then(|value| match evaluator.peek() {
+            Some(token) => bail!("syntax error near {}", token),
+            None => Ok(value),
+        });
+    value.map_err(|e| anyhow!("{}: {}", expression.trim(), e))
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = expression.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_alphanumeric() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            tokens.push(match c.is_ascii_digit() {
+                true => {
+                    Token::Number(number(word).ok_or_else(|| anyhow!("bad number '{}'", word))?)
+                }
+                false => Token::Name(word.to_string()),
+            });
+            len
+        } else {
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(**op))
+                .ok_or_else(|| anyhow!("unexpected '{}'", c))?;
+            tokens.push(Token::Op(op));
+            op.len()
+        };
+        rest = rest[len..].trim_start();
+    }
+
+    Ok(tokens)
+}
+
+/// Parse a decimal, octal (`017`) or hexadecimal (`0x1f`) constant
+fn number(s: &str) -> Option<i64> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        return i64::from_str_radix(hex, 16).ok();
+    }
+    match s.strip_prefix('0') {
+        Some(octal) if !octal.is_empty() => i64::from_str_radix(octal, 8).ok(),
+        _ => s.parse().ok(),
+    }
+}
+
+/// Recursive-descent evaluator
+///
+/// Operands that are skipped, such as the right side of `0 && x=1`, are
+/// parsed with `eval` unset so they have no effect.
+struct Evaluator<'a> {
+    shell: &'a mut Shell,
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Evaluator<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn expect(&mut self, op: &str) -> Result<()> {
+        match self.peek() {
+            Some(Token::Op(found)) if *found == op => {
+                self.index += 1;
+                Ok(())
+            }
+            Some(token) => bail!("expected '{}' before {}", op, token),
+            None => bail!("expected '{}'", op),
+        }
+    }
+
+    fn assignment(&mut self, eval: bool) -> Result<i64> {
+        let (Some(Token::Name(name)), Some(&Token::Op(op))) =
+            (self.tokens.get(self.index), self.tokens.get(self.index + 1))
+        else {
+            return self.conditional(eval);
+        };
+        if !ASSIGNMENTS.contains(&op) {
+            return self.conditional(eval);
+        }
+
+        let name = name.clone();
+        self.index += 2;
+        let mut value = self.assignment(eval)?;
+        if !eval {
+            return Ok(0);
+        }
+        if op != "=" {
+            value = apply(&op[..op.len() - 1], self.variable(&name)?, value)?;
+        }
+        self.shell.set_var(&name, &value.to_string());
+        Ok(value)
+    }
+
+    fn conditional(&mut self, eval: bool) -> Result<i64> {
+        let condition = self.binary(0, eval)?;
+        if self.peek() != Some(&Token::Op("?")) {
+            return Ok(condition);
+        }
+
+        self.index += 1;
+        let then = self.assignment(eval && condition != 0)?;
+        self.expect(":")?;
+        let otherwise = self.conditional(eval && condition == 0)?;
+        Ok(if condition != 0 { then } else { otherwise })
+    }
+
+    fn binary(&mut self, level: usize, eval: bool) -> Result<i64> {
+        let Some(ops) = BINARY.get(level) else {
+            return self.unary(eval);
+        };
+
+        let mut left = self.binary(level + 1, eval)?;
+        while let Some(&Token::Op(op)) = self.peek() {
+            if !ops.contains(&op) {
+                break;
+            }
+            self.index += 1;
+
+            // The right side of `&&` and `||` only runs when it decides
+            let needed = match op {
+                "&&" => left != 0,
+                "||" => left == 0,
+                _ => true,
+            };
+            let right = self.binary(level + 1, eval && needed)?;
+            left = if eval { apply(op, left, right)? } else { 0 };
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self, eval: bool) -> Result<i64> {
+        let Some(token) = self.peek().cloned() else {
+            bail!("expression expected");
+        };
+        self.index += 1;
+
+        match token {
+            Token::Number(n) => Ok(n),
+            Token::Name(name) if eval => self.variable(&name),
+            Token::Name(_) => Ok(0),
+            Token::Op("(") => {
+                let value = self.assignment(eval)?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            Token::Op(op @ ("+" | "-" | "!" | "~")) => {
+                let value = self.unary(eval)?;
+                Ok(match op {
+                    "+" => value,
+                    "-" => value.wrapping_neg(),
+                    "!" => (value == 0) as i64,
+                    _ => !value,
+                })
+            }
+            Token::Op(_) => bail!("syntax error near {}", token),
+        }
+    }
+
+    fn variable(&self, name: &str) -> Result<i64> {
+        match self.shell.var(name) {
+            Some(value) if !value.trim().is_empty() => number(value.trim())
+                .ok_or_else(|| anyhow!("{}: bad number '{}'", name, value.trim())),
+            _ => Ok(0),
+        }
+    }
+}
+
+/// Apply a binary operator
+fn apply(op: &str, left: i64, right: i64) -> Result<i64> {
+    Ok(match op {
+        "||" => (left != 0 || right != 0) as i64,
+        "&&" => (left != 0 && right != 0) as i64,
+        "|" => left | right,
+        "^" => left ^ right,
+        "&" => left & right,
+        "==" => (left == right) as i64,
+        "!=" => (left != right) as i64,
+        "<" => (left < right) as i64,
+        "<=" => (left <= right) as i64,
+        ">" => (left > right) as i64,
+        ">=" => (left >= right) as i64,
+        "<<" => left.wrapping_shl(right as u32),
+        ">>" => left.wrapping_shr(right as u32),
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "*" => left.wrapping_mul(right),
+        "/" | "%" if right == 0 => bail!("division by zero"),
+        "/" => left.wrapping_div(right),
+        "%" => left.wrapping_rem(right),
+        _ => bail!("unknown operator '{}'", op),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let mut shell = Shell::new("sh");
+        shell.set_var("n", "7");
+        shell.set_var("empty", "");
+
+        let cases = [
+            ("", 0),
+            ("1 + 2 * 3", 7),
+            ("(1 + 2) * 3", 9),
+            ("-n % 4 + empty + unset", -3),
+            ("1 << 4 | 0x0f ^ 010", 23),
+            ("!0 && ~0 == -1", 1),
+            ("n > 5 ? n < 10 ? 2 : 1 : 0", 2),
+            ("7 / 2 - 7 % 2", 2),
+        ];
+        for (expression, value) in cases {
+            assert_eq!(
+                evaluate(&mut shell, expression).unwrap(),
+                value,
+                "{}",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn test_assignments() {
+        let mut shell = Shell::new("sh");
+        assert_eq!(evaluate(&mut shell, "i = j = 2").unwrap(), 2);
+        assert_eq!(evaluate(&mut shell, "i += 3").unwrap(), 5);
+        assert_eq!(evaluate(&mut shell, "i <<= 1").unwrap(), 10);
+        assert_eq!(shell.var("i").as_deref(), Some("10"));
+        assert_eq!(shell.var("j").as_deref(), Some("2"));
+
+        // Operands that are skipped have no effect
+        assert_eq!(evaluate(&mut shell, "0 && (k = 1)").unwrap(), 0);
+        assert_eq!(evaluate(&mut shell, "1 || (k = 1)").unwrap(), 1);
+        assert_eq!(evaluate(&mut shell, "1 ? 2 : (k = 1 / 0)").unwrap(), 2);
+        assert_eq!(shell.var("k"), None);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut shell = Shell::new("sh");
+        shell.set_var("word", "abc");
+
+        let cases = [
+            ("1 / 0", "1 / 0: division by zero"),
+            ("1 +", "1 +: expression expected"),
+            ("(1", "(1: expected ')'"),
+            ("1 2", "1 2: syntax error near '2'"),
+            ("09", "09: bad number '09'"),
+            ("1 @ 2", "1 @ 2: unexpected '@'"),
+            ("word + 1", "word + 1: word: bad number 'abc'"),
+        ];
+        for (expression, message) in cases {
+            let e = evaluate(&mut shell, expression).unwrap_err();
+            assert_eq!(e.to_string(), message);
+        }
+    }
+}
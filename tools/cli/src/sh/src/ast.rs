@@ -0,0 +1,229 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Abstract syntax tree of shell programs
+
+use std::fmt;
+use std::rc::Rc;
+
+/// Position in the source (1-based)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Pos {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A program or compound list: commands run one after another
+pub type Program = Vec<Item>;
+
+/// An and-or list, optionally run in the background (`&`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    pub and_or: AndOr,
+    pub background: bool,
+}
+
+/// Pipelines joined by `&&` and `||`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AndOr {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+}
+
+/// Operator between two pipelines of an and-or list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    /// `&&`: run if the previous pipeline succeeded
+    And,
+    /// `||`: run if the previous pipeline failed
+    Or,
+}
+
+/// Commands joined by `|`, optionally negated with `!`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pipeline {
+    pub negated: bool,
+    pub commands: Vec<Command>,
+}
+
+/// A single command of a pipeline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Simple(SimpleCommand),
+    Compound(CompoundCommand, Vec<Redirect>),
+    FunctionDef(FunctionDef),
+}
+
+/// Assignments followed by a command name and its arguments
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimpleCommand {
+    pub assignments: Vec<Assignment>,
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+}
+
+/// An I/O redirection such as `2>>log`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    /// Descriptor written before the operator, if any
+    pub fd: Option<i32>,
+    pub kind: RedirectKind,
+    /// File name, descriptor for `<&` and `>&`, or here-document text
+    pub target: Word,
+}
+
+/// Redirection operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectKind {
+    /// `<`: read from a file (default descriptor 0)
+    Input,
+    /// `>`: write to a file, truncating it (default descriptor 1)
+    Output,
+    /// `>>`: append to a file (default descriptor 1)
+    Append,
+    /// `<>`: open a file for reading and writing (default descriptor 0)
+    ReadWrite,
+    /// `<&`: duplicate an input descriptor, or close with `-`
+    DupInput,
+    /// `>&`: duplicate an output descriptor, or close with `-`
+    DupOutput,
+    /// `&>`: write both output and errors to a file
+    OutputAndError,
+    /// `<<` or `<<-`: read the here-document kept as the target
+    HereDoc,
+}
+
+impl RedirectKind {
+    /// Get the descriptor redirected when none is given
+    pub fn default_fd(self) -> i32 {
+        match self {
+            Self::Input | Self::ReadWrite | Self::DupInput | Self::HereDoc => 0,
+            _ => 1,
+        }
+    }
+}
+
+/// `name=value`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    pub name: String,
+    pub value: Word,
+}
+
+/// `name() compound-command`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionDef {
+    pub name: String,
+    /// Compound command with its redirections
+    pub body: Rc<Command>,
+}
+
+/// Commands with their own grammar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompoundCommand {
+    /// `{ list; }`
+    BraceGroup(Program),
+    /// `( list )`
+    Subshell(Program),
+    /// `if list; then list; [elif list; then list;]... [else list;] fi`
+    If {
+        branches: Vec<(Program, Program)>,
+        otherwise: Option<Program>,
+    },
+    /// `while list; do list; done` or `until list; do list; done`
+    Loop {
+        until: bool,
+        condition: Program,
+        body: Program,
+    },
+    /// `for name [in word...]; do list; done`
+    For {
+        name: String,
+        words: Option<Vec<Word>>,
+        body: Program,
+    },
+    /// `case word in [(]pattern[|pattern]...) list;; ... esac`
+    Case { word: Word, arms: Vec<CaseArm> },
+}
+
+/// One arm of a `case` command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseArm {
+    pub patterns: Vec<Word>,
+    pub body: Program,
+}
+
+/// A word, kept in parts so quoting is known at expansion time
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Word {
+    pub parts: Vec<WordPart>,
+}
+
+/// Part of a word
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WordPart {
+    /// Unquoted text
+    Literal(String),
+    /// Text in single quotes or escaped with a backslash
+    Quoted(String),
+    /// Parts in double quotes, expanded without field splitting
+    DoubleQuoted(Vec<WordPart>),
+    /// `$name` or `${...}`
+    Param(Param),
+    /// `$(list)` or `` `list` ``
+    CommandSubst(Rc<Program>),
+    /// `$((expression))`, the expression expanded before it is evaluated
+    Arithmetic(Word),
+}
+
+/// A parameter expansion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Param {
+    pub name: String,
+    pub op: ParamOp,
+}
+
+/// What a parameter expansion does with the value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamOp {
+    /// `$name` or `${name}`
+    Value,
+    /// `${#name}`
+    Length,
+    /// `${name-word}` or, with `colon`, `${name:-word}`
+    Default { colon: bool, word: Word },
+    /// `${name=word}` or `${name:=word}`
+    Assign { colon: bool, word: Word },
+    /// `${name?word}` or `${name:?word}`
+    Error { colon: bool, word: Word },
+    /// `${name+word}` or `${name:+word}`
+    Alternative { colon: bool, word: Word },
+}
+
+impl Word {
+    /// Get the text of a word made of a single unquoted literal
+    ///
+    /// Reserved words and names are only recognized in this form.
+    pub fn as_literal(&self) -> Option<&str> {
+        match self.parts.as_slice() {
+            [WordPart::Literal(s)] => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Check whether a string is a valid variable or function name
+pub fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
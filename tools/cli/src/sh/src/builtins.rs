@@ -0,0 +1,267 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Built-in commands
+
+use anyhow::{bail, Context, Result};
+use std::env;
+use std::path::Path;
+
+use crate::ast::is_name;
+use crate::exec::{Flow, Shell};
+
+/// Built-in commands
+pub const BUILTINS: &[&str] = &[
+    ":",        // Do nothing, successfully
+    "break",    // Leave a loop
+    "cd",       // Change directory
+    "continue", // Start the next loop iteration
+    "echo",     // Echo arguments
+    "exit",     // Exit shell
+    "export",   // Export environment variable
+    "false",    // Fail
+    "help",     // Show help
+    "pwd",      // Print working directory
+    "return",   // Return from a function
+    "set",      // Set options or positional parameters
+    "shift",    // Shift positional parameters
+    "true",     // Succeed
+    "unset",    // Unset variable or function
+];
+
+/// Check if command is a built-in
+pub fn is_builtin(cmd: &str) -> bool {
+    BUILTINS.contains(&cmd)
+}
+
+/// Execute built-in command, returning its exit status
+pub fn run(shell: &mut Shell, cmd: &str, args: &[String]) -> i32 {
+    let result = match cmd {
+        ":" | "true" => Ok(0),
+        "false" => Ok(1),
+        "break" => builtin_break(shell, args, false),
+        "continue" => builtin_break(shell, args, true),
+        "cd" => builtin_cd(args),
+        "pwd" => builtin_pwd(),
+        "echo" => builtin_echo(args),
+        "export" => builtin_export(shell, args),
+        "unset" => builtin_unset(shell, args),
+        "exit" => builtin_exit(shell, args),
+        "return" => builtin_return(shell, args),
+        "set" => builtin_set(shell, args),
+        "shift" => builtin_shift(shell, args),
+        "help" => builtin_help(),
+        _ => Err(anyhow::anyhow!("unknown built-in: {}", cmd)),
+    };
+
+    match result {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("sh: {:#}", e);
+            1
+        }
+    }
+}
+
+/// Parse an optional numeric argument
+fn number(cmd: &str, args: &[String]) -> Result<Option<i32>> {
+    match args.first() {
+        None => Ok(None),
+        Some(arg) => match arg.parse() {
+            Ok(n) => Ok(Some(n)),
+            Err(_) => bail!("{}: {}: numeric argument required", cmd, arg),
+        },
+    }
+}
+
+/// Built-in: break/continue - Leave or restart loops
+fn builtin_break(shell: &mut Shell, args: &[String], next: bool) -> Result<i32> {
+    let cmd = if next { "continue" } else { "break" };
+    if shell.loop_depth == 0 {
+        bail!("{}: only meaningful in a loop", cmd);
+    }
+
+    let levels = number(cmd, args)?.unwrap_or(1);
+    if levels < 1 {
+        bail!("{}: {}: loop count out of range", cmd, levels);
+    }
+
+    let levels = (levels as usize).min(shell.loop_depth);
+    shell.flow = Some(match next {
+        true => Flow::Continue(levels),
+        false => Flow::Break(levels),
+    });
+    Ok(0)
+}
+
+/// Built-in: cd - Change directory
+fn builtin_cd(args: &[String]) -> Result<i32> {
+    let target = if args.is_empty() {
+        // Default to home directory
+        env::var("HOME").unwrap_or_else(|_| "/root".to_string())
+    } else {
+        args[0].clone()
+    };
+
+    env::set_current_dir(Path::new(&target))
+        .with_context(|| format!("cd: {}", target))?;
+
+    Ok(0)
+}
+
+/// Built-in: pwd - Print working directory
+fn builtin_pwd() -> Result<i32> {
+    let cwd = env::current_dir()
+        .context("pwd: cannot get current directory")?;
+
+    println!("{}", cwd.display());
+    Ok(0)
+}
+
+/// Built-in: echo - Echo arguments
+fn builtin_echo(args: &[String]) -> Result<i32> {
+    match args.split_first() {
+        Some((flag, rest)) if flag == "-n" => print!("{}", rest.join(" ")),
+        _ => println!("{}", args.join(" ")),
+    }
+    Ok(0)
+}
+
+/// Built-in: export - Export variables to the environment
+fn builtin_export(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    if args.is_empty() {
+        let mut vars: Vec<_> = env::vars().collect();
+        vars.sort();
+        for (key, value) in vars {
+            println!("export {}='{}'", key, value.replace('\'', "'\\''"));
+        }
+        return Ok(0);
+    }
+
+    for arg in args {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if !is_name(name) {
+            bail!("export: {}: not a valid identifier", name);
+        }
+        shell.export(name, value);
+    }
+    Ok(0)
+}
+
+/// Built-in: unset - Unset variables or, with `-f`, functions
+fn builtin_unset(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let (functions, names) = match args.split_first() {
+        Some((flag, rest)) if flag == "-f" => (true, rest),
+        Some((flag, rest)) if flag == "-v" => (false, rest),
+        _ => (false, args),
+    };
+
+    for name in names {
+        if functions {
+            shell.functions.remove(name);
+        } else {
+            shell.unset(name);
+        }
+    }
+    Ok(0)
+}
+
+/// Built-in: exit - Exit shell
+fn builtin_exit(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    shell.flow = Some(Flow::Exit);
+    match number("exit", args) {
+        Ok(status) => Ok(status.unwrap_or(shell.status)),
+        Err(e) => {
+            eprintln!("sh: {}", e);
+            Ok(2)
+        }
+    }
+}
+
+/// Built-in: return - Return from a function
+fn builtin_return(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    if shell.function_depth == 0 {
+        bail!("return: can only be used in a function");
+    }
+
+    let status = number("return", args)?.unwrap_or(shell.status);
+    shell.flow = Some(Flow::Return);
+    Ok(status)
+}
+
+/// Built-in: set - Set options or positional parameters
+fn builtin_set(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    if args.is_empty() {
+        let mut vars: Vec<_> = env::vars().chain(shell.vars.clone()).collect();
+        vars.sort();
+        for (key, value) in vars {
+            println!("{}='{}'", key, value.replace('\'', "'\\''"));
+        }
+        return Ok(0);
+    }
+
+    let mut i = 0;
+    while let Some(arg) = args.get(i) {
+        match arg.as_str() {
+            "--" => {
+                shell.positional = args[i + 1..].to_vec();
+                return Ok(0);
+            }
+            "-o" | "+o" => {
+                let enable = arg == "-o";
+                match args.get(i + 1).map(String::as_str) {
+                    Some("pipefail") => shell.pipefail = enable,
+                    Some(name) => bail!("set: {}: invalid option name", name),
+                    None => {
+                        let flag = if shell.pipefail { '-' } else { '+' };
+                        println!("set {}o pipefail", flag);
+                    }
+                }
+                i += 2;
+            }
+            _ if arg.starts_with('-') || arg.starts_with('+') => {
+                bail!("set: {}: invalid option", arg);
+            }
+            _ => {
+                shell.positional = args[i..].to_vec();
+                return Ok(0);
+            }
+        }
+    }
+    Ok(0)
+}
+
+/// Built-in: shift - Drop the first positional parameters
+fn builtin_shift(shell: &mut Shell, args: &[String]) -> Result<i32> {
+    let count = number("shift", args)?.unwrap_or(1);
+    if count < 0 || count as usize > shell.positional.len() {
+        bail!("shift: {}: shift count out of range", count);
+    }
+
+    shell.positional.drain(..count as usize);
+    Ok(0)
+}
+
+/// Built-in: help - Show help
+fn builtin_help() -> Result<i32> {
+    println!("Rustica Shell v0.1.0");
+    println!();
+    println!("Built-in commands:");
+    for builtin in BUILTINS {
+        println!("  {}", builtin);
+    }
+    println!();
+    println!("External commands:");
+    println!("  ls, cat, cp, mv, rm, mkdir, touch, ps, kill, dmesg, uname, date");
+    println!("  ip, ping, hostname, nslookup");
+    println!("  pkg, fwctl, svc, system-check");
+    println!();
+    println!("For more information on a command, see its documentation.");
+    Ok(0)
+}
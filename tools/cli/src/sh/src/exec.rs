@@ -0,0 +1,592 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Evaluator walking the syntax tree
+//!
+//! Subshells, command substitutions, background commands and each command
+//! of a pipeline run in a forked copy of the shell, so changes they make to
+//! variables or the working directory stay there.
+
+use anyhow::Result;
+use nix::fcntl::OFlag;
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{close, dup2, fork, pipe2, ForkResult, Pid};
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::ExitStatusExt;
+use std::panic::{self, AssertUnwindSafe};
+use std::process::{Command as Process, ExitStatus};
+use std::rc::Rc;
+
+use crate::ast::*;
+use crate::builtins;
+use crate::expand::pattern_matches;
+use crate::redirect::Redirections;
+
+/// Pending change of control flow, unwound by the enclosing construct
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Leave this many enclosing loops
+    Break(usize),
+    /// Start the next iteration of the loop this many levels out
+    Continue(usize),
+    /// Leave the current function
+    Return,
+    /// Leave the shell
+    Exit,
+}
+
+/// Shell state
+pub struct Shell {
+    /// Variables that are not exported to the environment
+    pub(crate) vars: HashMap<String, String>,
+    pub(crate) functions: HashMap<String, Rc<Command>>,
+    /// `$0`
+    pub(crate) name: String,
+    /// `$1`, `$2`, ...
+    pub(crate) positional: Vec<String>,
+    /// `$?`
+    pub(crate) status: i32,
+    /// `$!`
+    pub(crate) last_background: Option<Pid>,
+    pub(crate) flow: Option<Flow>,
+    /// Number of loops around the command being run
+    pub(crate) loop_depth: usize,
+    /// Number of function calls around the command being run
+    pub(crate) function_depth: usize,
+    /// `set -o pipefail`: a pipeline fails if any of its commands does
+    pub(crate) pipefail: bool,
+    /// Status of the last command substitution of the command being expanded
+    pub(crate) substitution_status: Option<i32>,
+}
+
+impl Shell {
+    /// Create a shell named `name` (`$0`)
+    pub fn new(name: &str) -> Self {
+        Self {
+            vars: HashMap::new(),
+            functions: HashMap::new(),
+            name: name.to_string(),
+            positional: Vec::new(),
+            status: 0,
+            last_background: None,
+            flow: None,
+            loop_depth: 0,
+            function_depth: 0,
+            pipefail: false,
+            substitution_status: None,
+        }
+    }
+
+    /// Check whether `exit` was run
+    pub fn exiting(&self) -> bool {
+        self.flow == Some(Flow::Exit)
+    }
+
+    /// Get a variable, exported or not
+    pub fn var(&self, name: &str) -> Option<String> {
+        self.vars.get(name).cloned().or_else(|| env::var(name).ok())
+    }
+
+    /// Set a variable, keeping it exported if it already is
+    pub fn set_var(&mut self, name: &str, value: &str) {
+        if env::var_os(name).is_some() {
+            env::set_var(name, value);
+        } else {
+            self.vars.insert(name.to_string(), value.to_string());
+        }
+    }
+
+    /// Export a variable, setting it first if a value is given
+    pub fn export(&mut self, name: &str, value: Option<&str>) {
+        let value = value
+            .map(str::to_string)
+            .or_else(|| self.vars.get(name).cloned());
+        self.vars.remove(name);
+        if let Some(value) = value {
+            env::set_var(name, value);
+        }
+    }
+
+    /// Remove a variable
+    pub fn unset(&mut self, name: &str) {
+        self.vars.remove(name);
+        env::remove_var(name);
+    }
+
+    /// Get the value of a parameter by name, including special parameters
+    pub fn param(&self, name: &str) -> Option<String> {
+        match name {
+            "?" => Some(self.status.to_string()),
+            "$" => Some(std::process::id().to_string()),
+            "!" => self.last_background.map(|pid| pid.to_string()),
+            "#" => Some(self.positional.len().to_string()),
+            "-" => Some(String::new()),
+            "@" | "*" => Some(self.positional.join(" ")),
+            "0" => Some(self.name.clone()),
+            _ if name.bytes().all(|b| b.is_ascii_digit()) => {
+                let index: usize = name.parse().ok()?;
+                self.positional.get(index.checked_sub(1)?).cloned()
+            }
+            _ => self.var(name),
+        }
+    }
+
+    /// Run a program, returning the status of the last command
+    pub fn run_program(&mut self, program: &Program) -> i32 {
+        let mut status = 0;
+
+        for item in program {
+            status = if item.background {
+                self.run_background(&item.and_or)
+            } else {
+                self.run_and_or(&item.and_or)
+            };
+            if self.flow.is_some() {
+                break;
+            }
+        }
+
+        status
+    }
+
+    fn run_and_or(&mut self, and_or: &AndOr) -> i32 {
+        let mut status = self.run_pipeline(&and_or.first);
+
+        for (connector, pipeline) in &and_or.rest {
+            if self.flow.is_some() {
+                break;
+            }
+            if (*connector == Connector::And) == (status == 0) {
+                status = self.run_pipeline(pipeline);
+            }
+        }
+
+        status
+    }
+
+    fn run_pipeline(&mut self, pipeline: &Pipeline) -> i32 {
+        let mut status = match pipeline.commands.as_slice() {
+            [command] => self.run_command(command),
+            commands => self.run_piped(commands),
+        };
+
+        if pipeline.negated {
+            status = (status == 0) as i32;
+        }
+        self.status = status;
+        status
+    }
+
+    /// Run the commands of a pipeline, each in its own process
+    fn run_piped(&mut self, commands: &[Command]) -> i32 {
+        let mut children = Vec::with_capacity(commands.len());
+        let mut input: Option<OwnedFd> = None;
+
+        for (i, command) in commands.iter().enumerate() {
+            let (next_input, output) = if i + 1 < commands.len() {
+                match pipe() {
+                    Ok((read, write)) => (Some(read), Some(write)),
+                    Err(e) => {
+                        eprintln!("sh: pipe: {}", e);
+                        break;
+                    }
+                }
+            } else {
+                (None, None)
+            };
+
+            let child = self.fork(|shell| {
+                if let Some(fd) = &input {
+                    let _ = dup2(fd.as_raw_fd(), 0);
+                }
+                if let Some(fd) = &output {
+                    let _ = dup2(fd.as_raw_fd(), 1);
+                }
+                // Only the copies on stdin and stdout may stay open, or the
+                // other end would never see end of file or a broken pipe
+                for fd in [&input, &output, &next_input].into_iter().flatten() {
+                    let _ = close(fd.as_raw_fd());
+                }
+                shell.run_command(command)
+            });
+
+            match child {
+                Ok(child) => children.push(child),
+                Err(e) => {
+                    eprintln!("sh: fork: {}", e);
+                    break;
+                }
+            }
+            input = next_input;
+        }
+        drop(input);
+
+        let statuses: Vec<i32> = children.into_iter().map(wait).collect();
+        if statuses.len() < commands.len() {
+            return 1;
+        }
+        match self.pipefail {
+            true => statuses.iter().rev().find(|s| **s != 0).copied().unwrap_or(0),
+            false => statuses.last().copied().unwrap_or(0),
+        }
+    }
+
+    fn run_command(&mut self, command: &Command) -> i32 {
+        match command {
+            Command::Simple(simple) => self.run_simple(simple),
+            Command::Compound(compound, redirects) => {
+                let _redirections = match Redirections::apply(self, redirects) {
+                    Ok(redirections) => redirections,
+                    Err(e) => {
+                        eprintln!("sh: {:#}", e);
+                        return 1;
+                    }
+                };
+                self.run_compound(compound)
+            }
+            Command::FunctionDef(def) => {
+                self.functions.insert(def.name.clone(), def.body.clone());
+                0
+            }
+        }
+    }
+
+    fn run_simple(&mut self, command: &SimpleCommand) -> i32 {
+        self.substitution_status = None;
+        let (args, assignments) = match self.expand_simple(command) {
+            Ok(expanded) => expanded,
+            Err(e) => {
+                eprintln!("sh: {}", e);
+                return 1;
+            }
+        };
+
+        let _redirections = match Redirections::apply(self, &command.redirects) {
+            Ok(redirections) => redirections,
+            Err(e) => {
+                eprintln!("sh: {:#}", e);
+                return 1;
+            }
+        };
+
+        // Without a command, the status is that of the last substitution
+        let Some((name, args)) = args.split_first() else {
+            for (name, value) in &assignments {
+                self.set_var(name, value);
+            }
+            return self.substitution_status.unwrap_or(0);
+        };
+
+        // Functions and builtins run in the shell itself, so assignments
+        // before them stay set like those of POSIX special builtins
+        if let Some(body) = self.functions.get(name).cloned() {
+            for (name, value) in &assignments {
+                self.set_var(name, value);
+            }
+            return self.call_function(&body, args);
+        }
+        if builtins::is_builtin(name) {
+            for (name, value) in &assignments {
+                self.set_var(name, value);
+            }
+            return builtins::run(self, name, args);
+        }
+
+        self.run_external(name, args, &assignments)
+    }
+
+    /// Expand the words and assignment values of a simple command
+    #[allow(clippy::type_complexity)]
+    fn expand_simple(
+        &mut self,
+        command: &SimpleCommand,
+    ) -> Result<(Vec<String>, Vec<(String, String)>)> {
+        let mut assignments = Vec::with_capacity(command.assignments.len());
+        for assignment in &command.assignments {
+            let value = self.expand_string(&assignment.value)?;
+            assignments.push((assignment.name.clone(), value));
+        }
+
+        let args = self.expand_words(&command.words)?;
+        Ok((args, assignments))
+    }
+
+    fn run_external(&mut self, name: &str, args: &[String], env: &[(String, String)]) -> i32 {
+        let _ = io::stdout().flush();
+
+        let result = Process::new(name)
+            .args(args)
+            .envs(env.iter().map(|(k, v)| (k, v)))
+            .status();
+
+        match result {
+            Ok(status) => exit_code(status),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                eprintln!("sh: {}: command not found", name);
+                127
+            }
+            Err(e) => {
+                eprintln!("sh: {}: {}", name, e);
+                126
+            }
+        }
+    }
+
+    fn call_function(&mut self, body: &Command, args: &[String]) -> i32 {
+        let positional = std::mem::replace(&mut self.positional, args.to_vec());
+        // `break` and `continue` do not reach loops around the call
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        self.function_depth += 1;
+
+        let status = self.run_command(body);
+
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+        self.positional = positional;
+        if self.flow == Some(Flow::Return) {
+            self.flow = None;
+        }
+        status
+    }
+
+    fn run_compound(&mut self, command: &CompoundCommand) -> i32 {
+        match command {
+            CompoundCommand::BraceGroup(list) => self.run_program(list),
+            CompoundCommand::Subshell(list) => self.run_subshell(list),
+            CompoundCommand::If {
+                branches,
+                otherwise,
+            } => {
+                for (condition, body) in branches {
+                    let status = self.run_program(condition);
+                    if self.flow.is_some() {
+                        return status;
+                    }
+                    if status == 0 {
+                        return self.run_program(body);
+                    }
+                }
+                match otherwise {
+                    Some(body) => self.run_program(body),
+                    None => 0,
+                }
+            }
+            CompoundCommand::Loop {
+                until,
+                condition,
+                body,
+            } => self.run_loop(*until, condition, body),
+            CompoundCommand::For { name, words, body } => self.run_for(name, words, body),
+            CompoundCommand::Case { word, arms } => self.run_case(word, arms),
+        }
+    }
+
+    fn run_loop(&mut self, until: bool, condition: &Program, body: &Program) -> i32 {
+        let mut status = 0;
+        self.loop_depth += 1;
+
+        loop {
+            let result = self.run_program(condition);
+            if self.flow.is_some() {
+                if self.leave_loop() {
+                    break;
+                }
+                continue;
+            }
+            if (result == 0) == until {
+                break;
+            }
+
+            status = self.run_program(body);
+            if self.leave_loop() {
+                break;
+            }
+        }
+
+        self.loop_depth -= 1;
+        status
+    }
+
+    fn run_for(&mut self, name: &str, words: &Option<Vec<Word>>, body: &Program) -> i32 {
+        let items = match words {
+            Some(words) => match self.expand_words(words) {
+                Ok(items) => items,
+                Err(e) => {
+                    eprintln!("sh: {}", e);
+                    return 1;
+                }
+            },
+            None => self.positional.clone(),
+        };
+
+        let mut status = 0;
+        self.loop_depth += 1;
+
+        for item in items {
+            self.set_var(name, &item);
+            status = self.run_program(body);
+            if self.leave_loop() {
+                break;
+            }
+        }
+
+        self.loop_depth -= 1;
+        status
+    }
+
+    /// Handle `break` and `continue` after a loop iteration
+    ///
+    /// Returns whether to leave the loop.
+    fn leave_loop(&mut self) -> bool {
+        match self.flow {
+            Some(Flow::Break(n)) => {
+                self.flow = (n > 1).then(|| Flow::Break(n - 1));
+                true
+            }
+            Some(Flow::Continue(n)) if n > 1 => {
+                self.flow = Some(Flow::Continue(n - 1));
+                true
+            }
+            Some(Flow::Continue(_)) => {
+                self.flow = None;
+                false
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    fn run_case(&mut self, word: &Word, arms: &[CaseArm]) -> i32 {
+        let result = self.expand_string(word).and_then(|value| {
+            for arm in arms {
+                for pattern in &arm.patterns {
+                    if pattern_matches(&self.expand_pattern(pattern)?, &value) {
+                        return Ok(Some(&arm.body));
+                    }
+                }
+            }
+            Ok(None)
+        });
+
+        match result {
+            Ok(Some(body)) => self.run_program(body),
+            Ok(None) => 0,
+            Err(e) => {
+                eprintln!("sh: {}", e);
+                1
+            }
+        }
+    }
+
+    fn run_subshell(&mut self, program: &Program) -> i32 {
+        match self.fork(|shell| shell.run_program(program)) {
+            Ok(child) => wait(child),
+            Err(e) => {
+                eprintln!("sh: fork: {}", e);
+                1
+            }
+        }
+    }
+
+    /// Run a command substitution, returning its output without trailing
+    /// newlines
+    pub fn capture(&mut self, program: &Program) -> Result<String> {
+        let (read, write) = pipe()?;
+        let child = self.fork(|shell| {
+            let _ = dup2(write.as_raw_fd(), 1);
+            let _ = close(write.as_raw_fd());
+            let _ = close(read.as_raw_fd());
+            shell.run_program(program)
+        })?;
+        drop(write);
+
+        let mut output = Vec::new();
+        let result = std::fs::File::from(read).read_to_end(&mut output);
+        let status = wait(child);
+        self.status = status;
+        self.substitution_status = Some(status);
+        result?;
+
+        let mut output = String::from_utf8_lossy(&output).into_owned();
+        output.truncate(output.trim_end_matches('\n').len());
+        Ok(output)
+    }
+
+    fn run_background(&mut self, and_or: &AndOr) -> i32 {
+        match self.fork(|shell| shell.run_and_or(and_or)) {
+            Ok(child) => {
+                self.last_background = Some(child);
+                0
+            }
+            Err(e) => {
+                eprintln!("sh: fork: {}", e);
+                1
+            }
+        }
+    }
+
+    /// Run `f` in a forked copy of the shell, which exits with its status
+    fn fork(&mut self, f: impl FnOnce(&mut Shell) -> i32) -> nix::Result<Pid> {
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+
+        // SAFETY: the shell is single-threaded, and the child only runs
+        // shell code before exiting
+        match unsafe { fork() }? {
+            ForkResult::Child => {
+                // Never unwind into the parent's frames, whose descriptors
+                // may have been closed here
+                let status = panic::catch_unwind(AssertUnwindSafe(|| f(self))).unwrap_or(101);
+                let _ = io::stdout().flush();
+                std::process::exit(status);
+            }
+            ForkResult::Parent { child } => Ok(child),
+        }
+    }
+
+    /// Collect background commands that have finished
+    pub fn reap_background(&self) {
+        let flags = Some(WaitPidFlag::WNOHANG);
+        while let Ok(status) = waitpid(Pid::from_raw(-1), flags) {
+            if status == WaitStatus::StillAlive {
+                break;
+            }
+        }
+    }
+}
+
+/// Create a pipe whose ends are not inherited by the commands run
+fn pipe() -> nix::Result<(OwnedFd, OwnedFd)> {
+    let (read, write) = pipe2(OFlag::O_CLOEXEC)?;
+    // SAFETY: both descriptors were just created and are owned here
+    unsafe { Ok((OwnedFd::from_raw_fd(read), OwnedFd::from_raw_fd(write))) }
+}
+
+/// Wait for a child and get its exit status
+fn wait(child: Pid) -> i32 {
+    loop {
+        match waitpid(child, None) {
+            Ok(WaitStatus::Exited(_, code)) => return code,
+            Ok(WaitStatus::Signaled(_, signal, _)) => return 128 + signal as i32,
+            Ok(_) => continue,
+            Err(nix::errno::Errno::EINTR) => continue,
+            Err(e) => {
+                eprintln!("sh: wait: {}", e);
+                return 1;
+            }
+        }
+    }
+}
+
+/// Get the shell status of a finished process
+fn exit_code(status: ExitStatus) -> i32 {
+    status
+        .code()
+        .unwrap_or_else(|| 128 + status.signal().unwrap_or_default())
+}
@@ -0,0 +1,305 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Word expansion: tilde, parameters, command substitution, arithmetic,
+//! field splitting and quote removal
+//!
+//! Pathname expansion is not performed; patterns are only matched by
+//! `case`.
+
+use anyhow::{bail, Result};
+
+use crate::arith;
+use crate::ast::{is_name, Param, ParamOp, Word, WordPart};
+use crate::exec::Shell;
+
+/// Field separators when `IFS` is unset
+const DEFAULT_IFS: &str = " \t\n";
+
+/// Fields being built from the parts of a word
+#[derive(Default)]
+struct Fields {
+    fields: Vec<String>,
+    current: String,
+    /// The current field came from quotes, so it is kept even if empty
+    keep: bool,
+}
+
+impl Fields {
+    fn push(&mut self, s: &str, quoted: bool) {
+        self.current.push_str(s);
+        self.keep |= quoted;
+    }
+
+    /// Push the result of an unquoted expansion, splitting it on `ifs`
+    fn split(&mut self, s: &str, ifs: &str) {
+        if ifs.is_empty() {
+            return self.push(s, false);
+        }
+        for (i, piece) in s.split(|c| ifs.contains(c)).enumerate() {
+            if i > 0 {
+                self.finish();
+            }
+            self.current.push_str(piece);
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.keep || !self.current.is_empty() {
+            self.fields.push(std::mem::take(&mut self.current));
+        }
+        self.keep = false;
+    }
+}
+
+impl Shell {
+    /// Expand words into command arguments
+    pub fn expand_words(&mut self, words: &[Word]) -> Result<Vec<String>> {
+        let ifs = self.var("IFS").unwrap_or_else(|| DEFAULT_IFS.to_string());
+        let mut fields = Fields::default();
+
+        for word in words {
+            for (i, part) in word.parts.iter().enumerate() {
+                match part {
+                    WordPart::Literal(s) if i == 0 => fields.push(&tilde(s), false),
+                    WordPart::Literal(s) => fields.push(s, false),
+                    WordPart::Quoted(s) => fields.push(s, true),
+                    WordPart::DoubleQuoted(inner) => self.expand_quoted(inner, &mut fields)?,
+                    WordPart::Param(param) if is_all_positional(param) => {
+                        for (i, arg) in self.positional.clone().iter().enumerate() {
+                            if i > 0 {
+                                fields.finish();
+                            }
+                            fields.split(arg, &ifs);
+                        }
+                    }
+                    WordPart::Param(_) | WordPart::CommandSubst(_) | WordPart::Arithmetic(_) => {
+                        let value = self.expansion(part)?;
+                        fields.split(&value, &ifs);
+                    }
+                }
+            }
+            fields.finish();
+        }
+
+        Ok(fields.fields)
+    }
+
+    /// Expand double-quoted parts into the current field
+    fn expand_quoted(&mut self, parts: &[WordPart], fields: &mut Fields) -> Result<()> {
+        // "$@" with no positional parameters expands to no field at all
+        if let [WordPart::Param(param)] = parts {
+            if param.name == "@" && param.op == ParamOp::Value && self.positional.is_empty() {
+                return Ok(());
+            }
+        }
+
+        fields.keep = true;
+        for part in parts {
+            match part {
+                WordPart::Param(param) if param.name == "@" && param.op == ParamOp::Value => {
+                    for (i, arg) in self.positional.clone().iter().enumerate() {
+                        if i > 0 {
+                            fields.finish();
+                            fields.keep = true;
+                        }
+                        fields.push(arg, true);
+                    }
+                }
+                _ => {
+                    let mut text = String::new();
+                    self.flatten(std::slice::from_ref(part), true, false, &mut text)?;
+                    fields.push(&text, true);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Expand a word into a single string, without field splitting
+    pub fn expand_string(&mut self, word: &Word) -> Result<String> {
+        let mut text = String::new();
+        self.flatten(&word.parts, false, false, &mut text)?;
+        Ok(text)
+    }
+
+    /// Expand a word into a pattern, escaping characters that were quoted
+    pub fn expand_pattern(&mut self, word: &Word) -> Result<String> {
+        let mut text = String::new();
+        self.flatten(&word.parts, false, true, &mut text)?;
+        Ok(text)
+    }
+
+    fn flatten(
+        &mut self,
+        parts: &[WordPart],
+        quoted: bool,
+        pattern: bool,
+        out: &mut String,
+    ) -> Result<()> {
+        let escape = |s: &str, out: &mut String| {
+            for c in s.chars() {
+                if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+        };
+
+        for (i, part) in parts.iter().enumerate() {
+            match part {
+                WordPart::Literal(s) if quoted && pattern => escape(s, out),
+                WordPart::Literal(s) if i == 0 && !quoted => out.push_str(&tilde(s)),
+                WordPart::Literal(s) => out.push_str(s),
+                WordPart::Quoted(s) if pattern => escape(s, out),
+                WordPart::Quoted(s) => out.push_str(s),
+                WordPart::DoubleQuoted(inner) => self.flatten(inner, true, pattern, out)?,
+                WordPart::Param(_) | WordPart::CommandSubst(_) | WordPart::Arithmetic(_) => {
+                    let value = self.expansion(part)?;
+                    if quoted && pattern {
+                        escape(&value, out);
+                    } else {
+                        out.push_str(&value);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the text of a parameter, command or arithmetic expansion
+    fn expansion(&mut self, part: &WordPart) -> Result<String> {
+        match part {
+            WordPart::Param(param) => Ok(self.param_value(param)?.unwrap_or_default()),
+            WordPart::CommandSubst(program) => self.capture(program),
+            WordPart::Arithmetic(expression) => {
+                let expression = self.expand_string(expression)?;
+                Ok(arith::evaluate(self, &expression)?.to_string())
+            }
+            _ => self.expand_string(&Word {
+                parts: vec![part.clone()],
+            }),
+        }
+    }
+
+    /// Get the value of a parameter expansion, `None` if it is unset
+    fn param_value(&mut self, param: &Param) -> Result<Option<String>> {
+        let value = self.param(&param.name);
+        let missing = |colon: bool| match &value {
+            None => true,
+            Some(v) => colon && v.is_empty(),
+        };
+
+        Ok(match &param.op {
+            ParamOp::Value => value,
+            ParamOp::Length => Some(value.unwrap_or_default().chars().count().to_string()),
+            ParamOp::Default { colon, word } if missing(*colon) => Some(self.expand_string(word)?),
+            ParamOp::Assign { colon, word } if missing(*colon) => {
+                if !is_name(&param.name) {
+                    bail!("${}: cannot assign in this way", param.name);
+                }
+                let word = self.expand_string(word)?;
+                self.set_var(&param.name, &word);
+                Some(word)
+            }
+            ParamOp::Error { colon, word } if missing(*colon) => {
+                let message = self.expand_string(word)?;
+                if message.is_empty() {
+                    bail!("{}: parameter null or not set", param.name);
+                }
+                bail!("{}: {}", param.name, message);
+            }
+            ParamOp::Alternative { colon, word } => match missing(*colon) {
+                true => None,
+                false => Some(self.expand_string(word)?),
+            },
+            _ => value,
+        })
+    }
+}
+
+/// Check for an unquoted `$@` or `$*`, which expand to one field per argument
+fn is_all_positional(param: &Param) -> bool {
+    matches!(param.name.as_str(), "@" | "*") && param.op == ParamOp::Value
+}
+
+/// Expand a leading `~` to the home directory
+fn tilde(s: &str) -> std::borrow::Cow<'_, str> {
+    let rest = match s.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+        _ => return s.into(),
+    };
+    match std::env::var("HOME") {
+        Ok(home) => format!("{}{}", home, rest).into(),
+        Err(_) => s.into(),
+    }
+}
+
+/// Match text against a shell pattern (`*`, `?`, `[...]` and `\` escapes)
+pub fn pattern_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    matches_at(&pattern, &text)
+}
+
+fn matches_at(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => (0..=text.len()).any(|i| matches_at(&pattern[1..], &text[i..])),
+        Some('?') => !text.is_empty() && matches_at(&pattern[1..], &text[1..]),
+        Some('[') => match (bracket(&pattern[1..]), text.first()) {
+            (Some((set, rest)), Some(c)) => set.contains(*c) && matches_at(rest, &text[1..]),
+            (Some(_), None) => false,
+            (None, _) => text.first() == Some(&'[') && matches_at(&pattern[1..], &text[1..]),
+        },
+        Some('\\') if pattern.len() > 1 => {
+            text.first() == Some(&pattern[1]) && matches_at(&pattern[2..], &text[1..])
+        }
+        Some(c) => text.first() == Some(c) && matches_at(&pattern[1..], &text[1..]),
+    }
+}
+
+/// A bracket expression such as `[a-z]` or `[!0-9]`
+struct BracketSet {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+}
+
+impl BracketSet {
+    fn contains(&self, c: char) -> bool {
+        self.ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c)) != self.negated
+    }
+}
+
+/// Parse a bracket expression after its `[`, returning the rest of the pattern
+fn bracket(pattern: &[char]) -> Option<(BracketSet, &[char])> {
+    let mut i = 0;
+    let negated = matches!(pattern.first(), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut ranges = Vec::new();
+    let start = i;
+    loop {
+        let mut c = *pattern.get(i)?;
+        if c == ']' && i > start {
+            return Some((BracketSet { negated, ranges }, &pattern[i + 1..]));
+        }
+        if c == '\\' {
+            i += 1;
+            c = *pattern.get(i)?;
+        }
+        i += 1;
+
+        if pattern.get(i) == Some(&'-') && !matches!(pattern.get(i + 1), None | Some(']')) {
+            ranges.push((c, pattern[i + 1]));
+            i += 2;
+        } else {
+            ranges.push((c, c));
+        }
+    }
+}
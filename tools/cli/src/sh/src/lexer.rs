@@ -0,0 +1,947 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Tokenizer following the POSIX token recognition rules
+//!
+//! Reserved words are not recognized here: whether `if` or `}` is a
+//! keyword depends on where it appears, so the parser decides. Command
+//! substitutions are parsed as soon as they are lexed, and the body of a
+//! here-document is read at the first newline after its operator.
+
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::{Param, ParamOp, Pos, Program, Word, WordPart};
+use crate::parser::parse_at;
+
+/// Syntax error with the position it was found at
+#[derive(Debug, Clone)]
+pub struct SyntaxError {
+    pub pos: Pos,
+    pub message: String,
+    /// The input ended early; more lines may complete it
+    pub incomplete: bool,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: syntax error: {}", self.pos, self.message)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// Operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Semi,
+    DoubleSemi,
+    Amp,
+    AndIf,
+    OrIf,
+    Pipe,
+    LParen,
+    RParen,
+    Less,
+    Great,
+    DoubleLess,
+    DoubleLessDash,
+    DoubleGreat,
+    LessAnd,
+    GreatAnd,
+    LessGreat,
+    AndGreat,
+}
+
+impl Op {
+    /// Operators by spelling, longest first
+    const ALL: &'static [(&'static str, Op)] = &[
+        ("<<-", Op::DoubleLessDash),
+        ("&&", Op::AndIf),
+        ("||", Op::OrIf),
+        (";;", Op::DoubleSemi),
+        ("<<", Op::DoubleLess),
+        (">>", Op::DoubleGreat),
+        ("<&", Op::LessAnd),
+        (">&", Op::GreatAnd),
+        ("<>", Op::LessGreat),
+        ("&>", Op::AndGreat),
+        (";", Op::Semi),
+        ("&", Op::Amp),
+        ("|", Op::Pipe),
+        ("(", Op::LParen),
+        (")", Op::RParen),
+        ("<", Op::Less),
+        (">", Op::Great),
+    ];
+
+    /// Get the spelling of the operator
+    pub fn as_str(self) -> &'static str {
+        Self::ALL
+            .iter()
+            .find(|(_, op)| *op == self)
+            .map(|(s, _)| *s)
+            .unwrap_or_default()
+    }
+}
+
+/// Kind of token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    Word(Word),
+    /// Digits directly before `<` or `>`, naming the descriptor to redirect
+    IoNumber(i32),
+    Op(Op),
+    /// Delimiter of a here-document, replaced by its body once it is read
+    HereDoc(Word),
+    Newline,
+    Eof,
+}
+
+/// A token and where it starts
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub pos: Pos,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            TokenKind::Word(word) => match word.as_literal() {
+                Some(s) => write!(f, "'{}'", s),
+                None => write!(f, "word"),
+            },
+            TokenKind::IoNumber(fd) => write!(f, "'{}'", fd),
+            TokenKind::Op(op) => write!(f, "'{}'", op.as_str()),
+            TokenKind::HereDoc(_) => write!(f, "here-document"),
+            TokenKind::Newline => write!(f, "newline"),
+            TokenKind::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+/// Split source text found at `start` into tokens
+pub fn tokenize(input: &str, start: Pos) -> Result<Vec<Token>, SyntaxError> {
+    let mut lexer = Lexer::new(input, start);
+
+    let mut tokens: Vec<Token> = Vec::new();
+    // Delimiters of the here-documents whose body follows the next newline
+    let mut here_docs = Vec::new();
+    loop {
+        let token = lexer.next_token()?;
+        match (&token.kind, tokens.last().map(|t| &t.kind)) {
+            (
+                TokenKind::Word(_),
+                Some(TokenKind::Op(op @ (Op::DoubleLess | Op::DoubleLessDash))),
+            ) => {
+                here_docs.push((tokens.len(), *op == Op::DoubleLessDash));
+            }
+            (TokenKind::Newline, _) => {
+                for (index, strip_tabs) in here_docs.drain(..) {
+                    let delimiter = &tokens[index];
+                    let TokenKind::Word(word) = &delimiter.kind else {
+                        unreachable!("here-document delimiters are words");
+                    };
+                    let body = lexer.here_doc(word, strip_tabs, delimiter.pos)?;
+                    tokens[index].kind = TokenKind::HereDoc(body);
+                }
+            }
+            (TokenKind::Eof, _) => {
+                if let Some((index, _)) = here_docs.first() {
+                    return Err(lexer.unterminated(tokens[*index].pos, "here-document"));
+                }
+                tokens.push(token);
+                return Ok(tokens);
+            }
+            _ => {}
+        }
+        tokens.push(token);
+    }
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    index: usize,
+    pos: Pos,
+}
+
+impl Lexer {
+    fn new(input: &str, pos: Pos) -> Self {
+        Self {
+            chars: input.chars().collect(),
+            index: 0,
+            pos,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.index + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        if c == '\n' {
+            self.pos.line += 1;
+            self.pos.column = 1;
+        } else {
+            self.pos.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, pos: Pos, message: impl Into<String>) -> SyntaxError {
+        SyntaxError {
+            pos,
+            message: message.into(),
+            incomplete: false,
+        }
+    }
+
+    fn unterminated(&self, pos: Pos, what: &str) -> SyntaxError {
+        SyntaxError {
+            pos,
+            message: format!("unterminated {}", what),
+            incomplete: true,
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Token, SyntaxError> {
+        self.skip_blanks_and_comments();
+
+        let pos = self.pos;
+        let kind = match self.peek() {
+            None => TokenKind::Eof,
+            Some('\n') => {
+                self.bump();
+                TokenKind::Newline
+            }
+            Some(_) => match self.operator() {
+                Some(op) => TokenKind::Op(op),
+                None => {
+                    let word = self.word()?;
+                    let fd = word
+                        .as_literal()
+                        .filter(|s| s.bytes().all(|b| b.is_ascii_digit()))
+                        .and_then(|s| s.parse().ok());
+                    match fd {
+                        Some(fd) if matches!(self.peek(), Some('<' | '>')) => {
+                            TokenKind::IoNumber(fd)
+                        }
+                        _ => TokenKind::Word(word),
+                    }
+                }
+            },
+        };
+
+        Ok(Token { kind, pos })
+    }
+
+    fn skip_blanks_and_comments(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ') | Some('\t') => {
+                    self.bump();
+                }
+                Some('\\') if self.peek_at(1) == Some('\n') => {
+                    self.bump();
+                    self.bump();
+                }
+                Some('#') => {
+                    while !matches!(self.peek(), None | Some('\n')) {
+                        self.bump();
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn operator(&mut self) -> Option<Op> {
+        let (spelling, op) = Op::ALL.iter().find(|(s, _)| {
+            s.chars()
+                .enumerate()
+                .all(|(i, c)| self.peek_at(i) == Some(c))
+        })?;
+
+        for _ in 0..spelling.len() {
+            self.bump();
+        }
+        Some(*op)
+    }
+
+    fn word(&mut self) -> Result<Word, SyntaxError> {
+        let mut parts = Vec::new();
+
+        while let Some(c) = self.peek() {
+            if is_blank(c) || c == '\n' || is_operator_start(c) {
+                break;
+            }
+            self.word_part(&mut parts)?;
+        }
+
+        Ok(Word { parts })
+    }
+
+    /// Lex the part of a word starting at the current character
+    fn word_part(&mut self, parts: &mut Vec<WordPart>) -> Result<(), SyntaxError> {
+        let pos = self.pos;
+        match self.peek() {
+            Some('\'') => {
+                self.bump();
+                let mut text = String::new();
+                loop {
+                    match self.bump() {
+                        Some('\'') => break,
+                        Some(c) => text.push(c),
+                        None => return Err(self.unterminated(pos, "single quote")),
+                    }
+                }
+                parts.push(WordPart::Quoted(text));
+            }
+            Some('"') => {
+                self.bump();
+                let inner = self.double_quoted(pos)?;
+                parts.push(WordPart::DoubleQuoted(inner));
+            }
+            Some('\\') => {
+                self.bump();
+                match self.bump() {
+                    Some('\n') => {}
+                    Some(c) => push_quoted(parts, c),
+                    None => push_literal(parts, '\\'),
+                }
+            }
+            Some('$') => self.dollar(parts)?,
+            Some('`') => {
+                self.bump();
+                let program = self.backquoted(pos, false)?;
+                parts.push(WordPart::CommandSubst(Rc::new(program)));
+            }
+            Some(c) => {
+                self.bump();
+                push_literal(parts, c);
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Lex the inside of double quotes, after the opening quote
+    fn double_quoted(&mut self, start: Pos) -> Result<Vec<WordPart>, SyntaxError> {
+        let mut parts = Vec::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.unterminated(start, "double quote")),
+                Some('"') => {
+                    self.bump();
+                    return Ok(parts);
+                }
+                Some('\\') => {
+                    self.bump();
+                    match self.bump() {
+                        Some('\n') => {}
+                        Some(c @ ('$' | '`' | '"' | '\\')) => push_literal(&mut parts, c),
+                        Some(c) => {
+                            push_literal(&mut parts, '\\');
+                            push_literal(&mut parts, c);
+                        }
+                        None => return Err(self.unterminated(start, "double quote")),
+                    }
+                }
+                Some('$') => self.dollar(&mut parts)?,
+                Some('`') => {
+                    let pos = self.pos;
+                    self.bump();
+                    let program = self.backquoted(pos, true)?;
+                    parts.push(WordPart::CommandSubst(Rc::new(program)));
+                }
+                Some(c) => {
+                    self.bump();
+                    push_literal(&mut parts, c);
+                }
+            }
+        }
+    }
+
+    /// Lex a `$` expansion, or a lone `$`
+    fn dollar(&mut self, parts: &mut Vec<WordPart>) -> Result<(), SyntaxError> {
+        let pos = self.pos;
+        self.bump();
+
+        match self.peek() {
+            Some('{') => {
+                self.bump();
+                let param = self.braced_param(pos)?;
+                parts.push(WordPart::Param(param));
+            }
+            Some('(') if self.peek_at(1) == Some('(') => {
+                self.bump();
+                self.bump();
+                let expression = self.arithmetic(pos)?;
+                parts.push(WordPart::Arithmetic(expression));
+            }
+            Some('(') => {
+                self.bump();
+                let program = self.command_subst(pos)?;
+                parts.push(WordPart::CommandSubst(Rc::new(program)));
+            }
+            Some(c) if is_special_param(c) || c.is_ascii_digit() => {
+                self.bump();
+                parts.push(WordPart::Param(Param {
+                    name: c.to_string(),
+                    op: ParamOp::Value,
+                }));
+            }
+            Some(c) if c == '_' || c.is_ascii_alphabetic() => {
+                let name = self.name();
+                parts.push(WordPart::Param(Param {
+                    name,
+                    op: ParamOp::Value,
+                }));
+            }
+            _ => push_literal(parts, '$'),
+        }
+        Ok(())
+    }
+
+    /// Lex `$(list)` after the opening parenthesis, parsing the list
+    fn command_subst(&mut self, start: Pos) -> Result<Program, SyntaxError> {
+        let inner = self.pos;
+        let mut text = String::new();
+        let mut depth = 0;
+
+        loop {
+            let Some(c) = self.bump() else {
+                return Err(self.unterminated(start, "'$('"));
+            };
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 0 => break,
+                ')' => depth -= 1,
+                '\\' => {
+                    text.push(c);
+                    if let Some(c) = self.bump() {
+                        text.push(c);
+                    }
+                    continue;
+                }
+                '\'' | '"' | '`' => {
+                    // Parentheses in quotes do not count
+                    text.push(c);
+                    loop {
+                        match self.bump() {
+                            None => return Err(self.unterminated(start, "'$('")),
+                            Some('\\') if c != '\'' => {
+                                text.push('\\');
+                                if let Some(escaped) = self.bump() {
+                                    text.push(escaped);
+                                }
+                            }
+                            Some(close) if close == c => break,
+                            Some(other) => text.push(other),
+                        }
+                    }
+                }
+                _ => {}
+            }
+            text.push(c);
+        }
+
+        parse_nested(&text, inner)
+    }
+
+    /// Lex `` `list` `` after the opening backquote, parsing the list
+    ///
+    /// A backslash only escapes `$`, `` ` `` and `\`, and `"` as well when
+    /// the substitution is in double quotes.
+    fn backquoted(&mut self, start: Pos, in_quotes: bool) -> Result<Program, SyntaxError> {
+        let inner = self.pos;
+        let mut text = String::new();
+
+        loop {
+            match self.bump() {
+                None => return Err(self.unterminated(start, "backquote")),
+                Some('`') => break,
+                Some('\\') => match self.bump() {
+                    None => return Err(self.unterminated(start, "backquote")),
+                    Some(c @ ('$' | '`' | '\\')) => text.push(c),
+                    Some('"') if in_quotes => text.push('"'),
+                    Some(c) => {
+                        text.push('\\');
+                        text.push(c);
+                    }
+                },
+                Some(c) => text.push(c),
+            }
+        }
+
+        parse_nested(&text, inner)
+    }
+
+    /// Lex `$((expression))` after the opening parentheses
+    fn arithmetic(&mut self, start: Pos) -> Result<Word, SyntaxError> {
+        let mut parts = Vec::new();
+        let mut depth = 0;
+
+        loop {
+            match self.peek() {
+                None => return Err(self.unterminated(start, "'$(('")),
+                Some(')') if depth == 0 => {
+                    self.bump();
+                    if self.bump() != Some(')') {
+                        return Err(self.error(start, "missing '))'"));
+                    }
+                    return Ok(Word { parts });
+                }
+                Some(c @ ('(' | ')')) => {
+                    depth = if c == '(' { depth + 1 } else { depth - 1 };
+                    self.bump();
+                    push_literal(&mut parts, c);
+                }
+                Some(_) => self.word_part(&mut parts)?,
+            }
+        }
+    }
+
+    /// Read the body of a here-document, which starts at the current position
+    ///
+    /// Unless part of the delimiter is quoted, the body is expanded like a
+    /// double-quoted word in which `"` is not special.
+    fn here_doc(
+        &mut self,
+        delimiter: &Word,
+        strip_tabs: bool,
+        start: Pos,
+    ) -> Result<Word, SyntaxError> {
+        let (end, quoted) = delimiter_text(delimiter);
+        let body_pos = self.pos;
+        let mut body = String::new();
+
+        loop {
+            if self.peek().is_none() {
+                return Err(self.unterminated(start, "here-document"));
+            }
+            let mut line = String::new();
+            while let Some(c) = self.bump().filter(|c| *c != '\n') {
+                line.push(c);
+            }
+            let line = match strip_tabs {
+                true => line.trim_start_matches('\t'),
+                false => &line,
+            };
+            if line == end {
+                break;
+            }
+            body.push_str(line);
+            body.push('\n');
+        }
+
+        if quoted {
+            return Ok(Word {
+                parts: vec![WordPart::Quoted(body)],
+            });
+        }
+
+        let mut lexer = Lexer::new(&body, body_pos);
+        let mut parts = Vec::new();
+        while let Some(c) = lexer.peek() {
+            match c {
+                '\\' => {
+                    lexer.bump();
+                    match lexer.bump() {
+                        Some('\n') => {}
+                        Some(c @ ('$' | '`' | '\\')) => push_literal(&mut parts, c),
+                        Some(c) => {
+                            push_literal(&mut parts, '\\');
+                            push_literal(&mut parts, c);
+                        }
+                        None => push_literal(&mut parts, '\\'),
+                    }
+                }
+                '$' | '`' => lexer.word_part(&mut parts)?,
+                _ => {
+                    lexer.bump();
+                    push_literal(&mut parts, c);
+                }
+            }
+        }
+        Ok(Word {
+            parts: vec![WordPart::DoubleQuoted(parts)],
+        })
+    }
+
+    fn name(&mut self) -> String {
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            if c != '_' && !c.is_ascii_alphanumeric() {
+                break;
+            }
+            name.push(c);
+            self.bump();
+        }
+        name
+    }
+
+    /// Lex `${...}`, after the opening brace
+    fn braced_param(&mut self, start: Pos) -> Result<Param, SyntaxError> {
+        let length = self.peek() == Some('#') && self.peek_at(1) != Some('}');
+        if length {
+            self.bump();
+        }
+
+        let name = match self.peek() {
+            Some(c) if c == '_' || c.is_ascii_alphabetic() => self.name(),
+            Some(c) if c.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some(c) = self.peek().filter(char::is_ascii_digit) {
+                    digits.push(c);
+                    self.bump();
+                }
+                digits
+            }
+            Some(c) if is_special_param(c) => {
+                self.bump();
+                c.to_string()
+            }
+            None => return Err(self.unterminated(start, "'${'")),
+            Some(_) => return Err(self.error(start, "bad substitution")),
+        };
+
+        if length {
+            return match self.bump() {
+                Some('}') => Ok(Param {
+                    name,
+                    op: ParamOp::Length,
+                }),
+                None => Err(self.unterminated(start, "'${'")),
+                Some(_) => Err(self.error(start, "bad substitution")),
+            };
+        }
+
+        let colon = self.peek() == Some(':');
+        if colon {
+            self.bump();
+        }
+
+        let op = match self.bump() {
+            Some('}') if !colon => {
+                return Ok(Param {
+                    name,
+                    op: ParamOp::Value,
+                })
+            }
+            Some(c @ ('-' | '=' | '?' | '+')) => c,
+            None => return Err(self.unterminated(start, "'${'")),
+            Some(_) => return Err(self.error(start, "bad substitution")),
+        };
+
+        let mut parts = Vec::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.unterminated(start, "'${'")),
+                Some('}') => {
+                    self.bump();
+                    break;
+                }
+                Some(_) => self.word_part(&mut parts)?,
+            }
+        }
+
+        let word = Word { parts };
+        let op = match op {
+            '-' => ParamOp::Default { colon, word },
+            '=' => ParamOp::Assign { colon, word },
+            '?' => ParamOp::Error { colon, word },
+            _ => ParamOp::Alternative { colon, word },
+        };
+        Ok(Param { name, op })
+    }
+}
+
+/// Parse the list of a command substitution found at `start`
+///
+/// Its text ends at the closing parenthesis or backquote, so running out of
+/// input there is an error rather than a reason to read more.
+fn parse_nested(text: &str, start: Pos) -> Result<Program, SyntaxError> {
+    parse_at(text, start).map_err(|e| SyntaxError {
+        incomplete: false,
+        ..e
+    })
+}
+
+/// Get the text ending a here-document, and whether any of it was quoted
+fn delimiter_text(word: &Word) -> (String, bool) {
+    fn collect(parts: &[WordPart], text: &mut String, quoted: &mut bool) {
+        for part in parts {
+            match part {
+                WordPart::Literal(s) => text.push_str(s),
+                WordPart::Quoted(s) => {
+                    text.push_str(s);
+                    *quoted = true;
+                }
+                WordPart::DoubleQuoted(inner) => {
+                    collect(inner, text, quoted);
+                    *quoted = true;
+                }
+                WordPart::Param(param) => {
+                    text.push('$');
+                    text.push_str(&param.name);
+                }
+                WordPart::CommandSubst(_) | WordPart::Arithmetic(_) => {}
+            }
+        }
+    }
+
+    let mut text = String::new();
+    let mut quoted = false;
+    collect(&word.parts, &mut text, &mut quoted);
+    (text, quoted)
+}
+
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn is_operator_start(c: char) -> bool {
+    matches!(c, ';' | '&' | '|' | '(' | ')' | '<' | '>')
+}
+
+fn is_special_param(c: char) -> bool {
+    matches!(c, '?' | '#' | '$' | '!' | '@' | '*' | '-')
+}
+
+fn push_literal(parts: &mut Vec<WordPart>, c: char) {
+    match parts.last_mut() {
+        Some(WordPart::Literal(s)) => s.push(c),
+        _ => parts.push(WordPart::Literal(c.to_string())),
+    }
+}
+
+fn push_quoted(parts: &mut Vec<WordPart>, c: char) {
+    match parts.last_mut() {
+        Some(WordPart::Quoted(s)) => s.push(c),
+        _ => parts.push(WordPart::Quoted(c.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(input: &str) -> Vec<TokenKind> {
+        tokenize(input, Pos { line: 1, column: 1 })
+            .unwrap()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect()
+    }
+
+    fn words(input: &str) -> Vec<Word> {
+        lex(input)
+            .into_iter()
+            .filter_map(|kind| match kind {
+                TokenKind::Word(word) => Some(word),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn error(input: &str) -> SyntaxError {
+        tokenize(input, Pos { line: 1, column: 1 }).unwrap_err()
+    }
+
+    fn literal(s: &str) -> WordPart {
+        WordPart::Literal(s.to_string())
+    }
+
+    fn quoted(s: &str) -> WordPart {
+        WordPart::Quoted(s.to_string())
+    }
+
+    fn param(name: &str) -> WordPart {
+        WordPart::Param(Param {
+            name: name.to_string(),
+            op: ParamOp::Value,
+        })
+    }
+
+    #[test]
+    fn test_operators_and_io_numbers() {
+        assert_eq!(
+            lex("a&&b||c;;d 2>&1 >>f <<-x\n\tx\n"),
+            vec![
+                TokenKind::Word(Word {
+                    parts: vec![literal("a")]
+                }),
+                TokenKind::Op(Op::AndIf),
+                TokenKind::Word(Word {
+                    parts: vec![literal("b")]
+                }),
+                TokenKind::Op(Op::OrIf),
+                TokenKind::Word(Word {
+                    parts: vec![literal("c")]
+                }),
+                TokenKind::Op(Op::DoubleSemi),
+                TokenKind::Word(Word {
+                    parts: vec![literal("d")]
+                }),
+                TokenKind::IoNumber(2),
+                TokenKind::Op(Op::GreatAnd),
+                TokenKind::Word(Word {
+                    parts: vec![literal("1")]
+                }),
+                TokenKind::Op(Op::DoubleGreat),
+                TokenKind::Word(Word {
+                    parts: vec![literal("f")]
+                }),
+                TokenKind::Op(Op::DoubleLessDash),
+                TokenKind::HereDoc(Word {
+                    parts: vec![WordPart::DoubleQuoted(vec![])]
+                }),
+                TokenKind::Newline,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(
+            words(r#"'a b'"c $x \$ \q"\ d e\#f # comment"#),
+            vec![
+                Word {
+                    parts: vec![
+                        quoted("a b"),
+                        WordPart::DoubleQuoted(vec![literal("c "), param("x"), literal(" $ \\q"),]),
+                        quoted(" "),
+                        literal("d"),
+                    ]
+                },
+                Word {
+                    parts: vec![literal("e"), quoted("#"), literal("f")]
+                },
+            ]
+        );
+
+        // A line continuation joins words, a quoted newline is kept
+        assert_eq!(
+            words("ab\\\ncd 'x\ny'"),
+            vec![
+                Word {
+                    parts: vec![literal("abcd")]
+                },
+                Word {
+                    parts: vec![quoted("x\ny")]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expansions() {
+        let [word] = words("${#a}${b:-$c}$1$?$").try_into().unwrap();
+        assert_eq!(
+            word.parts,
+            vec![
+                WordPart::Param(Param {
+                    name: "a".to_string(),
+                    op: ParamOp::Length,
+                }),
+                WordPart::Param(Param {
+                    name: "b".to_string(),
+                    op: ParamOp::Default {
+                        colon: true,
+                        word: Word {
+                            parts: vec![param("c")]
+                        },
+                    },
+                }),
+                param("1"),
+                param("?"),
+                literal("$"),
+            ]
+        );
+
+        // Parentheses in quotes do not end a command substitution
+        let [word] = words("$(echo ')' \"(\")x`echo \\`y\\``")
+            .try_into()
+            .unwrap();
+        assert!(matches!(
+            word.parts.as_slice(),
+            [WordPart::CommandSubst(first), WordPart::Literal(x), WordPart::CommandSubst(second)]
+                if first.len() == 1 && x == "x" && second.len() == 1
+        ));
+
+        let [word] = words("$((a * (b + 1)))").try_into().unwrap();
+        assert_eq!(
+            word.parts,
+            vec![WordPart::Arithmetic(Word {
+                parts: vec![literal("a * (b + 1)")]
+            })]
+        );
+    }
+
+    #[test]
+    fn test_here_documents() {
+        let tokens = lex("cat <<A <<'B'; echo\n$x \\$y\nA\n$x\nB\nnext\n");
+        assert_eq!(
+            tokens[2],
+            TokenKind::HereDoc(Word {
+                parts: vec![WordPart::DoubleQuoted(vec![param("x"), literal(" $y\n")])]
+            })
+        );
+        assert_eq!(
+            tokens[4],
+            TokenKind::HereDoc(Word {
+                parts: vec![quoted("$x\n")]
+            })
+        );
+        // The bodies are skipped, lexing goes on after the last one
+        assert_eq!(
+            tokens[5..],
+            [
+                TokenKind::Op(Op::Semi),
+                TokenKind::Word(Word {
+                    parts: vec![literal("echo")]
+                }),
+                TokenKind::Newline,
+                TokenKind::Word(Word {
+                    parts: vec![literal("next")]
+                }),
+                TokenKind::Newline,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let e = error("echo ok\necho 'open");
+        assert_eq!(e.pos, Pos { line: 2, column: 6 });
+        assert!(e.incomplete);
+
+        for input in ["\"a", "${a", "$(a", "`a", "$((1", "cat <<x\nbody\n"] {
+            assert!(error(input).incomplete, "{}", input);
+        }
+
+        let e = error("x ${a%b}");
+        assert_eq!(e.pos, Pos { line: 1, column: 3 });
+        assert_eq!(e.message, "bad substitution");
+        assert!(!e.incomplete);
+
+        // Errors inside a command substitution are reported where they are,
+        // and more input would not fix them
+        let e = error("a\nb $(echo;\n fi)");
+        assert_eq!(e.pos, Pos { line: 3, column: 2 });
+        assert!(!e.incomplete);
+    }
+}
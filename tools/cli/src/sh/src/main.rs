@@ -7,14 +7,29 @@
 //! Rustica Shell (sh)
 //!
 //! A minimal POSIX-compatible shell implementation in Rust.
+//! Input is tokenized and parsed into a syntax tree, which is then evaluated,
+//! with pipelines, I/O redirection, here-documents and command and arithmetic
+//! substitution.
 //! Provides command execution, built-in commands, and job control.
 
+mod arith;
+mod ast;
+mod builtins;
+mod exec;
+mod expand;
+mod lexer;
+mod parser;
+mod redirect;
+
 use anyhow::{Context, Result};
 use clap::Parser;
+use nix::sys::signal::{signal, SigHandler, Signal};
 use std::env;
 use std::io::{self, BufRead, Write};
 use std::path::Path;
-use std::process::{Command, ExitCode};
+use std::process::ExitCode;
+
+use crate::exec::Shell;
 
 /// Rustica Shell - Command Interpreter
 #[derive(Parser, Debug)]
@@ -34,34 +49,29 @@ struct Args {
     interactive: bool,
 }
 
-/// Built-in commands
-const BUILTINS: &[&str] = &[
-    "cd",    // Change directory
-    "pwd",   // Print working directory
-    "echo",  // Echo arguments
-    "export", // Export environment variable
-    "unset", // Unset environment variable
-    "exit",  // Exit shell
-    "help",  // Show help
-];
-
 fn main() -> ExitCode {
     let args = Args::parse();
 
     // Set up logging
     env_logger::init();
 
+    // Die when writing to a closed pipe, like the commands the shell runs,
+    // rather than failing every builtin that prints.
+    // SAFETY: restores the default disposition; no handler is installed
+    let _ = unsafe { signal(Signal::SIGPIPE, SigHandler::SigDfl) };
+
     // Run shell
-    if let Err(e) = run_shell(args) {
-        eprintln!("sh: error: {}", e);
-        return ExitCode::FAILURE;
+    match run_shell(args) {
+        Ok(status) => ExitCode::from(status as u8),
+        Err(e) => {
+            eprintln!("sh: error: {}", e);
+            ExitCode::FAILURE
+        }
     }
-
-    ExitCode::SUCCESS
 }
 
-/// Main shell execution loop
-fn run_shell(args: Args) -> Result<()> {
+/// Main shell execution loop, returning the exit status
+fn run_shell(args: Args) -> Result<i32> {
     // Display splash screen on first interactive start
     if args.interactive || (args.command.is_none() && args.file.is_none()) {
         display_splash();
@@ -69,16 +79,18 @@ fn run_shell(args: Args) -> Result<()> {
 
     // Execute file script
     if let Some(file) = args.file {
-        return execute_script(&file);
+        let mut shell = Shell::new(&file);
+        return execute_script(&mut shell, &file);
     }
 
     // Execute single command
+    let mut shell = Shell::new("sh");
     if let Some(cmd) = args.command {
-        return execute_command_line(&cmd);
+        return Ok(execute_source(&mut shell, &cmd, None));
     }
 
     // Interactive mode
-    run_interactive()
+    run_interactive(&mut shell)
 }
 
 /// Display shell splash screen
@@ -102,7 +114,7 @@ fn display_splash() {
 }
 
 /// Run interactive shell
-fn run_interactive() -> Result<()> {
+fn run_interactive(shell: &mut Shell) -> Result<i32> {
     let stdin = io::stdin();
     let mut stdout = io::stdout();
     let mut lines = stdin.lock().lines();
@@ -117,18 +129,25 @@ fn run_interactive() -> Result<()> {
     // Change to home directory
     let _ = env::set_current_dir(&home);
 
-    loop {
-        // Display prompt
-        let cwd = env::current_dir()
-            .and_then(|p| p.canonicalize())
-            .unwrap_or_else(|_| Path::new("/").to_path_buf());
-
-        let prompt = format!(
-            "\x1b[1;32mroot\x1b[0m@\x1b[1;34mrustica\x1b[0m:\x1b[1;36m{}\x1b[0m# ",
-            cwd.display()
-        );
+    // Lines of a command that is not complete yet
+    let mut pending = String::new();
 
-        print!("{}", prompt);
+    loop {
+        shell.reap_background();
+
+        // Display prompt, or the continuation prompt inside a command
+        if pending.is_empty() {
+            let cwd = env::current_dir()
+                .and_then(|p| p.canonicalize())
+                .unwrap_or_else(|_| Path::new("/").to_path_buf());
+
+            print!(
+                "\x1b[1;32mroot\x1b[0m@\x1b[1;34mrustica\x1b[0m:\x1b[1;36m{}\x1b[0m# ",
+                cwd.display()
+            );
+        } else {
+            print!("> ");
+        }
         stdout.flush()?;
 
         // Read line
@@ -141,216 +160,59 @@ fn run_interactive() -> Result<()> {
             None => break, // EOF
         };
 
-        // Skip empty lines
-        let line = line.trim();
-        if line.is_empty() {
-            continue;
-        }
-
-        // Execute command
-        if let Err(e) = execute_command_line(line) {
-            eprintln!("sh: {}", e);
-        }
-    }
-
-    println!(); // Newline after exit
-    Ok(())
-}
-
-/// Execute a single command line
-fn execute_command_line(line: &str) -> Result<()> {
-    let parts = parse_command_line(line)?;
-
-    if parts.is_empty() {
-        return Ok(());
-    }
-
-    let cmd = &parts[0];
-    let args = &parts[1..];
-
-    // Check for built-in commands
-    if is_builtin(cmd) {
-        return execute_builtin(cmd, args);
-    }
-
-    // Execute external command
-    execute_external(cmd, args)
-}
-
-/// Parse command line into parts
-fn parse_command_line(line: &str) -> Result<Vec<String>> {
-    let mut parts = Vec::new();
-    let mut current = String::new();
-    let mut in_quote = false;
-    let mut escape = false;
-
-    for ch in line.chars() {
-        if escape {
-            current.push(ch);
-            escape = false;
-        } else if ch == '\\' {
-            escape = true;
-        } else if ch == '"' {
-            in_quote = !in_quote;
-        } else if ch.is_whitespace() && !in_quote {
-            if !current.is_empty() {
-                parts.push(current.clone());
-                current.clear();
+        pending.push_str(&line);
+        pending.push('\n');
+
+        // Read more lines until the command is complete
+        let program = match parser::parse(&pending) {
+            Ok(program) => program,
+            Err(e) if e.incomplete => continue,
+            Err(e) => {
+                eprintln!("sh: {}", e);
+                shell.status = 2;
+                pending.clear();
+                continue;
             }
-        } else {
-            current.push(ch);
-        }
-    }
-
-    if !current.is_empty() {
-        parts.push(current);
-    }
-
-    Ok(parts)
-}
-
-/// Check if command is a built-in
-fn is_builtin(cmd: &str) -> bool {
-    BUILTINS.contains(&cmd)
-}
-
-/// Execute built-in command
-fn execute_builtin(cmd: &str, args: &[String]) -> Result<()> {
-    match cmd {
-        "cd" => builtin_cd(args),
-        "pwd" => builtin_pwd(),
-        "echo" => builtin_echo(args),
-        "export" => builtin_export(args),
-        "unset" => builtin_unset(args),
-        "exit" => builtin_exit(args),
-        "help" => builtin_help(),
-        _ => anyhow::bail!("unknown built-in: {}", cmd),
-    }
-}
-
-/// Built-in: cd - Change directory
-fn builtin_cd(args: &[String]) -> Result<()> {
-    let target = if args.is_empty() {
-        // Default to home directory
-        env::var("HOME").unwrap_or_else(|_| "/root".to_string())
-    } else {
-        args[0].clone()
-    };
-
-    let path = Path::new(&target);
-
-    // Handle ~ expansion
-    let expanded = if target.starts_with('~') {
-        let home = env::var("HOME").unwrap_or_else(|_| "/root".to_string());
-        Path::new(&home).join(target.trim_start_matches('~').trim_start_matches('/'))
-    } else {
-        path.to_path_buf()
-    };
-
-    env::set_current_dir(&expanded)
-        .with_context(|| format!("cd: {}", target))?;
-
-    Ok(())
-}
-
-/// Built-in: pwd - Print working directory
-fn builtin_pwd() -> Result<()> {
-    let cwd = env::current_dir()
-        .context("pwd: cannot get current directory")?;
-
-    println!("{}", cwd.display());
-    Ok(())
-}
-
-/// Built-in: echo - Echo arguments
-fn builtin_echo(args: &[String]) -> Result<()> {
-    println!("{}", args.join(" "));
-    Ok(())
-}
+        };
+        pending.clear();
 
-/// Built-in: export - Set environment variable
-fn builtin_export(args: &[String]) -> Result<()> {
-    for arg in args {
-        if let Some((key, value)) = arg.split_once('=') {
-            env::set_var(key, value);
-        } else {
-            // Print environment variable
-            if let Ok(value) = env::var(arg) {
-                println!("{}={}", arg, value);
-            }
+        shell.run_program(&program);
+        if shell.exiting() {
+            return Ok(shell.status);
         }
     }
-    Ok(())
-}
 
-/// Built-in: unset - Unset environment variable
-fn builtin_unset(args: &[String]) -> Result<()> {
-    for arg in args {
-        env::remove_var(arg);
+    if !pending.is_empty() {
+        if let Err(e) = parser::parse(&pending) {
+            eprintln!("sh: {}", e);
+        }
     }
-    Ok(())
-}
-
-/// Built-in: exit - Exit shell
-fn builtin_exit(_args: &[String]) -> Result<()> {
-    std::process::exit(0);
-}
 
-/// Built-in: help - Show help
-fn builtin_help() -> Result<()> {
-    println!("Rustica Shell v0.1.0");
-    println!();
-    println!("Built-in commands:");
-    for builtin in BUILTINS {
-        println!("  {}", builtin);
-    }
-    println!();
-    println!("External commands:");
-    println!("  ls, cat, cp, mv, rm, mkdir, touch, ps, kill, dmesg, uname, date");
-    println!("  ip, ping, hostname, nslookup");
-    println!("  pkg, fwctl, svc, system-check");
-    println!();
-    println!("For more information on a command, see its documentation.");
-    Ok(())
+    println!(); // Newline after exit
+    Ok(shell.status)
 }
 
-/// Execute external command
-fn execute_external(cmd: &str, args: &[String]) -> Result<()> {
-    let result = Command::new(cmd)
-        .args(args)
-        .spawn();
-
-    match result {
-        Ok(mut child) => {
-            child.wait()
-                .context(format!("{}: execution failed", cmd))?;
-            Ok(())
-        }
+/// Parse and run source text, returning its exit status
+///
+/// Syntax errors are reported with their position, prefixed by `origin`
+/// when the source came from a file.
+fn execute_source(shell: &mut Shell, source: &str, origin: Option<&str>) -> i32 {
+    match parser::parse(source) {
+        Ok(program) => shell.run_program(&program),
         Err(e) => {
-            if e.kind() == io::ErrorKind::NotFound {
-                anyhow::bail!("{}: command not found", cmd);
-            } else {
-                anyhow::bail!("{}: {}", cmd, e);
+            match origin {
+                Some(origin) => eprintln!("sh: {}:{}", origin, e),
+                None => eprintln!("sh: {}", e),
             }
+            2
         }
     }
 }
 
 /// Execute script file
-fn execute_script(path: &str) -> Result<()> {
+fn execute_script(shell: &mut Shell, path: &str) -> Result<i32> {
     let content = std::fs::read_to_string(path)
         .with_context(|| format!("cannot read script: {}", path))?;
 
-    for line in content.lines() {
-        let line = line.trim();
-
-        // Skip comments and empty lines
-        if line.is_empty() || line.starts_with('#') {
-            continue;
-        }
-
-        execute_command_line(line)?;
-    }
-
-    Ok(())
+    Ok(execute_source(shell, &content, Some(path)))
 }
@@ -0,0 +1,767 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Recursive-descent parser for the POSIX shell grammar
+
+use std::rc::Rc;
+
+use crate::ast::*;
+use crate::lexer::{tokenize, Op, SyntaxError, Token, TokenKind};
+
+/// Words that are reserved at the start of a command
+const RESERVED: &[&str] = &[
+    "!", "{", "}", "case", "do", "done", "elif", "else", "esac", "fi", "for", "if", "in",
+    "then", "until", "while",
+];
+
+/// Parse source text into a program
+pub fn parse(input: &str) -> Result<Program, SyntaxError> {
+    parse_at(input, Pos { line: 1, column: 1 })
+}
+
+/// Parse source text found at `start`, such as a command substitution
+pub fn parse_at(input: &str, start: Pos) -> Result<Program, SyntaxError> {
+    let mut parser = Parser {
+        tokens: tokenize(input, start)?,
+        index: 0,
+    };
+
+    let program = parser.list()?;
+    if !parser.at_eof() {
+        return Err(parser.unexpected());
+    }
+    Ok(program)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index.min(self.tokens.len() - 1)]
+    }
+
+    fn peek_at(&self, offset: usize) -> &TokenKind {
+        let index = (self.index + offset).min(self.tokens.len() - 1);
+        &self.tokens[index].kind
+    }
+
+    fn bump(&mut self) -> Token {
+        let token = self.peek().clone();
+        if self.index < self.tokens.len() - 1 {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn at_eof(&self) -> bool {
+        self.peek().kind == TokenKind::Eof
+    }
+
+    fn at_op(&self, op: Op) -> bool {
+        self.peek().kind == TokenKind::Op(op)
+    }
+
+    /// Get the reserved word at the current token, if it is one
+    fn reserved(&self) -> Option<&'static str> {
+        match &self.peek().kind {
+            TokenKind::Word(word) => {
+                let s = word.as_literal()?;
+                RESERVED.iter().find(|r| **r == s).copied()
+            }
+            _ => None,
+        }
+    }
+
+    fn at_reserved(&self, word: &str) -> bool {
+        self.reserved() == Some(word)
+    }
+
+    /// Error for the current token
+    fn unexpected(&self) -> SyntaxError {
+        let token = self.peek();
+        SyntaxError {
+            pos: token.pos,
+            message: format!("unexpected {}", token),
+            incomplete: token.kind == TokenKind::Eof,
+        }
+    }
+
+    /// Error for the current token when something else was required
+    fn expected(&self, what: &str) -> SyntaxError {
+        let mut error = self.unexpected();
+        error.message = format!("{}, expected {}", error.message, what);
+        error
+    }
+
+    fn expect_reserved(&mut self, word: &str) -> Result<(), SyntaxError> {
+        if !self.at_reserved(word) {
+            return Err(self.expected(&format!("'{}'", word)));
+        }
+        self.bump();
+        Ok(())
+    }
+
+    fn expect_op(&mut self, op: Op) -> Result<(), SyntaxError> {
+        if !self.at_op(op) {
+            return Err(self.expected(&format!("'{}'", op.as_str())));
+        }
+        self.bump();
+        Ok(())
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek().kind == TokenKind::Newline {
+            self.bump();
+        }
+    }
+
+    /// Check whether the current token ends a list
+    fn at_list_end(&self) -> bool {
+        match &self.peek().kind {
+            TokenKind::Eof => true,
+            TokenKind::Op(op) => matches!(op, Op::RParen | Op::DoubleSemi),
+            TokenKind::Word(_) => matches!(
+                self.reserved(),
+                Some("}" | "do" | "done" | "elif" | "else" | "esac" | "fi" | "then")
+            ),
+            TokenKind::IoNumber(_) | TokenKind::HereDoc(_) | TokenKind::Newline => false,
+        }
+    }
+
+    /// Parse and-or lists separated by `;`, `&` or newlines
+    fn list(&mut self) -> Result<Program, SyntaxError> {
+        let mut items = Vec::new();
+
+        loop {
+            self.skip_newlines();
+            if self.at_list_end() {
+                return Ok(items);
+            }
+
+            let and_or = self.and_or()?;
+            let background = self.at_op(Op::Amp);
+            items.push(Item { and_or, background });
+
+            match self.peek().kind {
+                TokenKind::Op(Op::Semi) | TokenKind::Op(Op::Amp) | TokenKind::Newline => {
+                    self.bump();
+                }
+                _ if self.at_list_end() => return Ok(items),
+                _ => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    /// Parse the list of a compound command, which may not be empty
+    fn compound_list(&mut self) -> Result<Program, SyntaxError> {
+        let list = self.list()?;
+        if list.is_empty() {
+            return Err(self.expected("a command"));
+        }
+        Ok(list)
+    }
+
+    fn and_or(&mut self) -> Result<AndOr, SyntaxError> {
+        let first = self.pipeline()?;
+        let mut rest = Vec::new();
+
+        loop {
+            let connector = match self.peek().kind {
+                TokenKind::Op(Op::AndIf) => Connector::And,
+                TokenKind::Op(Op::OrIf) => Connector::Or,
+                _ => return Ok(AndOr { first, rest }),
+            };
+            self.bump();
+            self.skip_newlines();
+            rest.push((connector, self.pipeline()?));
+        }
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, SyntaxError> {
+        let negated = self.at_reserved("!");
+        if negated {
+            self.bump();
+        }
+
+        let mut commands = vec![self.command()?];
+        while self.at_op(Op::Pipe) {
+            self.bump();
+            self.skip_newlines();
+            commands.push(self.command()?);
+        }
+
+        Ok(Pipeline { negated, commands })
+    }
+
+    fn command(&mut self) -> Result<Command, SyntaxError> {
+        if self.at_op(Op::LParen) || self.reserved().is_some() {
+            let command = self.compound_command()?;
+            return Ok(Command::Compound(command, self.redirects()?));
+        }
+
+        if let Some(def) = self.function_def()? {
+            return Ok(Command::FunctionDef(def));
+        }
+
+        self.simple_command().map(Command::Simple)
+    }
+
+    fn compound_command(&mut self) -> Result<CompoundCommand, SyntaxError> {
+        if self.at_op(Op::LParen) {
+            self.bump();
+            let list = self.compound_list()?;
+            self.expect_op(Op::RParen)?;
+            return Ok(CompoundCommand::Subshell(list));
+        }
+
+        match self.reserved() {
+            Some("{") => {
+                self.bump();
+                let list = self.compound_list()?;
+                self.expect_reserved("}")?;
+                Ok(CompoundCommand::BraceGroup(list))
+            }
+            Some("if") => self.if_command(),
+            Some(keyword @ ("while" | "until")) => {
+                self.bump();
+                let condition = self.compound_list()?;
+                let body = self.do_group()?;
+                Ok(CompoundCommand::Loop {
+                    until: keyword == "until",
+                    condition,
+                    body,
+                })
+            }
+            Some("for") => self.for_command(),
+            Some("case") => self.case_command(),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn if_command(&mut self) -> Result<CompoundCommand, SyntaxError> {
+        self.expect_reserved("if")?;
+        let mut branches = Vec::new();
+        let mut otherwise = None;
+
+        loop {
+            let condition = self.compound_list()?;
+            self.expect_reserved("then")?;
+            let body = self.compound_list()?;
+            branches.push((condition, body));
+
+            match self.reserved() {
+                Some("elif") => {
+                    self.bump();
+                }
+                Some("else") => {
+                    self.bump();
+                    otherwise = Some(self.compound_list()?);
+                    self.expect_reserved("fi")?;
+                    break;
+                }
+                _ => {
+                    self.expect_reserved("fi")?;
+                    break;
+                }
+            }
+        }
+
+        Ok(CompoundCommand::If {
+            branches,
+            otherwise,
+        })
+    }
+
+    fn do_group(&mut self) -> Result<Program, SyntaxError> {
+        self.expect_reserved("do")?;
+        let body = self.compound_list()?;
+        self.expect_reserved("done")?;
+        Ok(body)
+    }
+
+    fn for_command(&mut self) -> Result<CompoundCommand, SyntaxError> {
+        self.expect_reserved("for")?;
+        let name = self.name()?;
+
+        self.skip_newlines();
+        let mut words = None;
+        if self.at_reserved("in") {
+            self.bump();
+            let mut list = Vec::new();
+            while let TokenKind::Word(word) = &self.peek().kind {
+                list.push(word.clone());
+                self.bump();
+            }
+            words = Some(list);
+            self.sequential_separator()?;
+        } else if self.at_op(Op::Semi) {
+            self.bump();
+            self.skip_newlines();
+        }
+
+        let body = self.do_group()?;
+        Ok(CompoundCommand::For { name, words, body })
+    }
+
+    /// Parse the `;` or newlines ending a word list
+    fn sequential_separator(&mut self) -> Result<(), SyntaxError> {
+        match self.peek().kind {
+            TokenKind::Op(Op::Semi) | TokenKind::Newline => {
+                self.bump();
+                self.skip_newlines();
+                Ok(())
+            }
+            _ => Err(self.expected("';' or newline")),
+        }
+    }
+
+    fn case_command(&mut self) -> Result<CompoundCommand, SyntaxError> {
+        self.expect_reserved("case")?;
+        let word = self.word()?;
+        self.skip_newlines();
+        self.expect_reserved("in")?;
+
+        let mut arms = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.at_reserved("esac") {
+                self.bump();
+                return Ok(CompoundCommand::Case { word, arms });
+            }
+
+            if self.at_op(Op::LParen) {
+                self.bump();
+            }
+            let mut patterns = vec![self.word()?];
+            while self.at_op(Op::Pipe) {
+                self.bump();
+                patterns.push(self.word()?);
+            }
+            self.expect_op(Op::RParen)?;
+
+            let body = self.list()?;
+            arms.push(CaseArm { patterns, body });
+
+            if self.at_op(Op::DoubleSemi) {
+                self.bump();
+            } else if !self.at_reserved("esac") {
+                return Err(self.expected("';;' or 'esac'"));
+            }
+        }
+    }
+
+    /// Parse `name() compound-command`, if that is what follows
+    fn function_def(&mut self) -> Result<Option<FunctionDef>, SyntaxError> {
+        let name = match &self.peek().kind {
+            TokenKind::Word(word) => match word.as_literal() {
+                Some(name) if is_name(name) => name.to_string(),
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        if *self.peek_at(1) != TokenKind::Op(Op::LParen) {
+            return Ok(None);
+        }
+
+        self.bump();
+        self.bump();
+        self.expect_op(Op::RParen)?;
+        self.skip_newlines();
+
+        if !self.at_op(Op::LParen) && self.reserved().is_none() {
+            return Err(self.expected("a compound command"));
+        }
+        let body = self.compound_command()?;
+        let body = Rc::new(Command::Compound(body, self.redirects()?));
+        Ok(Some(FunctionDef { name, body }))
+    }
+
+    fn simple_command(&mut self) -> Result<SimpleCommand, SyntaxError> {
+        let mut command = SimpleCommand::default();
+
+        loop {
+            if let Some(redirect) = self.redirect()? {
+                command.redirects.push(redirect);
+                continue;
+            }
+
+            let TokenKind::Word(word) = &self.peek().kind else {
+                break;
+            };
+            match assignment(word) {
+                Some(assignment) if command.words.is_empty() => {
+                    command.assignments.push(assignment);
+                }
+                _ => command.words.push(word.clone()),
+            }
+            self.bump();
+        }
+
+        if command.assignments.is_empty()
+            && command.words.is_empty()
+            && command.redirects.is_empty()
+        {
+            return Err(self.expected("a command"));
+        }
+        Ok(command)
+    }
+
+    /// Parse the redirections following a compound command
+    fn redirects(&mut self) -> Result<Vec<Redirect>, SyntaxError> {
+        let mut redirects = Vec::new();
+        while let Some(redirect) = self.redirect()? {
+            redirects.push(redirect);
+        }
+        Ok(redirects)
+    }
+
+    /// Parse a redirection, if one follows
+    fn redirect(&mut self) -> Result<Option<Redirect>, SyntaxError> {
+        let (fd, offset) = match self.peek().kind {
+            TokenKind::IoNumber(fd) => (Some(fd), 1),
+            _ => (None, 0),
+        };
+
+        let kind = match self.peek_at(offset) {
+            TokenKind::Op(Op::Less) => RedirectKind::Input,
+            TokenKind::Op(Op::Great) => RedirectKind::Output,
+            TokenKind::Op(Op::DoubleGreat) => RedirectKind::Append,
+            TokenKind::Op(Op::LessGreat) => RedirectKind::ReadWrite,
+            TokenKind::Op(Op::LessAnd) => RedirectKind::DupInput,
+            TokenKind::Op(Op::GreatAnd) => RedirectKind::DupOutput,
+            TokenKind::Op(Op::AndGreat) if fd.is_none() => RedirectKind::OutputAndError,
+            TokenKind::Op(Op::DoubleLess | Op::DoubleLessDash) => RedirectKind::HereDoc,
+            _ => return Ok(None),
+        };
+
+        for _ in 0..=offset {
+            self.bump();
+        }
+        let target = match &self.peek().kind {
+            TokenKind::Word(word) => word.clone(),
+            TokenKind::HereDoc(body) => body.clone(),
+            _ if kind == RedirectKind::HereDoc => {
+                return Err(self.expected("a here-document delimiter"));
+            }
+            _ => return Err(self.expected("a file name")),
+        };
+        self.bump();
+
+        Ok(Some(Redirect { fd, kind, target }))
+    }
+
+    fn word(&mut self) -> Result<Word, SyntaxError> {
+        match &self.peek().kind {
+            TokenKind::Word(word) => {
+                let word = word.clone();
+                self.bump();
+                Ok(word)
+            }
+            _ => Err(self.expected("a word")),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, SyntaxError> {
+        if let TokenKind::Word(word) = &self.peek().kind {
+            if let Some(name) = word.as_literal().filter(|s| is_name(s)) {
+                let name = name.to_string();
+                self.bump();
+                return Ok(name);
+            }
+        }
+        Err(self.expected("a name"))
+    }
+}
+
+/// Split `name=value` into an assignment
+fn assignment(word: &Word) -> Option<Assignment> {
+    let WordPart::Literal(first) = word.parts.first()? else {
+        return None;
+    };
+    let (name, value) = first.split_once('=')?;
+    if !is_name(name) {
+        return None;
+    }
+
+    let mut parts = Vec::with_capacity(word.parts.len());
+    if !value.is_empty() {
+        parts.push(WordPart::Literal(value.to_string()));
+    }
+    parts.extend(word.parts[1..].iter().cloned());
+
+    Some(Assignment {
+        name: name.to_string(),
+        value: Word { parts },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(s: &str) -> Word {
+        Word {
+            parts: vec![WordPart::Literal(s.to_string())],
+        }
+    }
+
+    fn simple(words: &[&str]) -> Command {
+        Command::Simple(SimpleCommand {
+            words: words.iter().map(|w| word(w)).collect(),
+            ..Default::default()
+        })
+    }
+
+    fn item(commands: Vec<Command>) -> Item {
+        Item {
+            and_or: AndOr {
+                first: Pipeline {
+                    negated: false,
+                    commands,
+                },
+                rest: Vec::new(),
+            },
+            background: false,
+        }
+    }
+
+    /// Get the only command of a one-command program
+    fn parse_command(input: &str) -> Command {
+        let [item] = parse(input).unwrap().try_into().unwrap();
+        let [command] = item.and_or.first.commands.try_into().unwrap();
+        command
+    }
+
+    #[test]
+    fn test_lists() {
+        let program = parse("a; ! b | c && d || e &\n\nf").unwrap();
+        assert_eq!(program.len(), 3);
+        assert_eq!(program[0], item(vec![simple(&["a"])]));
+
+        let and_or = &program[1].and_or;
+        assert!(program[1].background);
+        assert!(and_or.first.negated);
+        assert_eq!(and_or.first.commands, vec![simple(&["b"]), simple(&["c"])]);
+        let connectors: Vec<_> = and_or.rest.iter().map(|(c, _)| *c).collect();
+        assert_eq!(connectors, vec![Connector::And, Connector::Or]);
+
+        assert_eq!(program[2], item(vec![simple(&["f"])]));
+        assert!(parse("\n# only a comment\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_simple_commands() {
+        let Command::Simple(command) = parse_command("A=1 B= cmd C=3 2>>log <in") else {
+            panic!("not a simple command");
+        };
+        let names: Vec<_> = command
+            .assignments
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["A", "B"]);
+        assert_eq!(command.assignments[1].value, Word::default());
+        assert_eq!(command.words, vec![word("cmd"), word("C=3")]);
+        assert_eq!(
+            command.redirects,
+            vec![
+                Redirect {
+                    fd: Some(2),
+                    kind: RedirectKind::Append,
+                    target: word("log"),
+                },
+                Redirect {
+                    fd: None,
+                    kind: RedirectKind::Input,
+                    target: word("in"),
+                },
+            ]
+        );
+
+        // Reserved words are only reserved where a command starts
+        assert_eq!(
+            parse_command("echo if then fi"),
+            simple(&["echo", "if", "then", "fi"])
+        );
+    }
+
+    #[test]
+    fn test_compound_commands() {
+        assert_eq!(
+            parse_command("if a; then b; elif c\nthen d; else e; fi"),
+            Command::Compound(
+                CompoundCommand::If {
+                    branches: vec![
+                        (
+                            vec![item(vec![simple(&["a"])])],
+                            vec![item(vec![simple(&["b"])])]
+                        ),
+                        (
+                            vec![item(vec![simple(&["c"])])],
+                            vec![item(vec![simple(&["d"])])]
+                        ),
+                    ],
+                    otherwise: Some(vec![item(vec![simple(&["e"])])]),
+                },
+                Vec::new(),
+            )
+        );
+
+        assert_eq!(
+            parse_command("until a; do b; done >out"),
+            Command::Compound(
+                CompoundCommand::Loop {
+                    until: true,
+                    condition: vec![item(vec![simple(&["a"])])],
+                    body: vec![item(vec![simple(&["b"])])],
+                },
+                vec![Redirect {
+                    fd: None,
+                    kind: RedirectKind::Output,
+                    target: word("out"),
+                }],
+            )
+        );
+
+        assert_eq!(
+            parse_command("for x in a b\ndo c; done"),
+            Command::Compound(
+                CompoundCommand::For {
+                    name: "x".to_string(),
+                    words: Some(vec![word("a"), word("b")]),
+                    body: vec![item(vec![simple(&["c"])])],
+                },
+                Vec::new(),
+            )
+        );
+
+        assert_eq!(
+            parse_command("case $x in\n(a | b) c;;\n*) ;; esac"),
+            Command::Compound(
+                CompoundCommand::Case {
+                    word: Word {
+                        parts: vec![WordPart::Param(Param {
+                            name: "x".to_string(),
+                            op: ParamOp::Value,
+                        })]
+                    },
+                    arms: vec![
+                        CaseArm {
+                            patterns: vec![word("a"), word("b")],
+                            body: vec![item(vec![simple(&["c"])])],
+                        },
+                        CaseArm {
+                            patterns: vec![word("*")],
+                            body: Vec::new(),
+                        },
+                    ],
+                },
+                Vec::new(),
+            )
+        );
+
+        assert_eq!(
+            parse_command("f() { ( a ); }"),
+            Command::FunctionDef(FunctionDef {
+                name: "f".to_string(),
+                body: Rc::new(Command::Compound(
+                    CompoundCommand::BraceGroup(vec![item(vec![Command::Compound(
+                        CompoundCommand::Subshell(vec![item(vec![simple(&["a"])])]),
+                        Vec::new(),
+                    )])]),
+                    Vec::new(),
+                )),
+            })
+        );
+    }
+
+    #[test]
+    fn test_here_document_redirects() {
+        let Command::Simple(command) = parse_command("cat <<EOF 3<<-'END'\n$x\nEOF\n\tEND\n")
+        else {
+            panic!("not a simple command");
+        };
+        let kinds: Vec<_> = command.redirects.iter().map(|r| (r.fd, r.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (None, RedirectKind::HereDoc),
+                (Some(3), RedirectKind::HereDoc)
+            ]
+        );
+        assert_eq!(
+            command.redirects[1].target.parts,
+            vec![WordPart::Quoted(String::new())]
+        );
+    }
+
+    #[test]
+    fn test_command_substitution() {
+        let Command::Simple(command) = parse_command("echo $(a | b; c)") else {
+            panic!("not a simple command");
+        };
+        let [WordPart::CommandSubst(program)] = command.words[1].parts.as_slice() else {
+            panic!("not a command substitution");
+        };
+        assert_eq!(program.len(), 2);
+        assert_eq!(program[0].and_or.first.commands.len(), 2);
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let cases = [
+            ("a &&\n|| b", 2, 1, "unexpected '||', expected a command"),
+            (
+                "if a; then b; done",
+                1,
+                15,
+                "unexpected 'done', expected 'fi'",
+            ),
+            ("{ }", 1, 3, "unexpected '}', expected a command"),
+            (
+                "for 1 in a; do b; done",
+                1,
+                5,
+                "unexpected '1', expected a name",
+            ),
+            ("a >", 1, 4, "unexpected end of input, expected a file name"),
+            ("a\n  b)", 2, 4, "unexpected ')'"),
+            ("f() a", 1, 5, "unexpected 'a', expected a compound command"),
+        ];
+
+        for (input, line, column, message) in cases {
+            let e = parse(input).unwrap_err();
+            assert_eq!((e.pos.line, e.pos.column), (line, column), "{}", input);
+            assert_eq!(e.message, message, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_incomplete_input() {
+        for input in [
+            "if a; then",
+            "while a\ndo b",
+            "for x in a b;",
+            "case x in a)",
+            "{ a",
+            "(a",
+            "a &&",
+            "a |",
+            "f()",
+            "cat <<EOF\nline",
+        ] {
+            assert!(parse(input).unwrap_err().incomplete, "{}", input);
+        }
+
+        // Misplaced tokens are errors that more input cannot fix
+        for input in ["a )", "then", "a; ;", "a >\n"] {
+            assert!(!parse(input).unwrap_err().incomplete, "{}", input);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! I/O redirection
+//!
+//! Redirections are applied to the shell's own descriptors, so builtins and
+//! the external commands they start see them alike. Replaced descriptors are
+//! saved out of the way and put back when the command is done.
+
+use anyhow::{bail, Context, Result};
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+use nix::sys::memfd::{memfd_create, MemFdCreateFlag};
+use nix::unistd::{close, dup2};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+
+use crate::ast::{Redirect, RedirectKind};
+use crate::exec::Shell;
+
+/// Lowest descriptor used to save a redirected one
+const SAVED_FD_MIN: RawFd = 10;
+
+/// Descriptors replaced by redirections, restored when dropped
+#[derive(Default)]
+pub struct Redirections {
+    /// Each replaced descriptor and its saved copy, `None` if it was closed
+    saved: Vec<(RawFd, Option<OwnedFd>)>,
+}
+
+impl Redirections {
+    /// Apply redirections in order
+    ///
+    /// If one fails, those already applied are undone.
+    pub fn apply(shell: &mut Shell, redirects: &[Redirect]) -> Result<Self> {
+        let mut applied = Self::default();
+        for redirect in redirects {
+            applied.redirect(shell, redirect)?;
+        }
+        Ok(applied)
+    }
+
+    fn redirect(&mut self, shell: &mut Shell, redirect: &Redirect) -> Result<()> {
+        let target = shell.expand_string(&redirect.target)?;
+        let fd = redirect.fd.unwrap_or(redirect.kind.default_fd());
+
+        let mut options = OpenOptions::new();
+        match redirect.kind {
+            RedirectKind::Input => options.read(true),
+            RedirectKind::Output | RedirectKind::OutputAndError => {
+                options.write(true).create(true).truncate(true)
+            }
+            RedirectKind::Append => options.append(true).create(true),
+            RedirectKind::ReadWrite => options.read(true).write(true).create(true),
+            RedirectKind::DupInput | RedirectKind::DupOutput => {
+                return self.duplicate(fd, &target);
+            }
+            RedirectKind::HereDoc => return self.here_doc(fd, &target),
+        };
+
+        let mut targets = vec![fd];
+        if redirect.kind == RedirectKind::OutputAndError {
+            targets.push(2);
+        }
+        for fd in &targets {
+            self.save(*fd)?;
+        }
+
+        let file = options.open(&target).with_context(|| target.clone())?;
+        let source = file.as_raw_fd();
+        for fd in &targets {
+            move_to(source, *fd)?;
+        }
+
+        // The file landed on a descriptor it was meant to replace
+        if targets.contains(&source) {
+            let _ = file.into_raw_fd();
+        }
+        Ok(())
+    }
+
+    /// Apply `fd<&source` or `fd>&source`, or close `fd` if `source` is `-`
+    fn duplicate(&mut self, fd: RawFd, source: &str) -> Result<()> {
+        self.save(fd)?;
+        if source == "-" {
+            let _ = close(fd);
+            return Ok(());
+        }
+
+        let source: RawFd = match source.parse() {
+            Ok(source) if fcntl(source, FcntlArg::F_GETFD).is_ok() => source,
+            _ => bail!("{}: bad file descriptor", source),
+        };
+        move_to(source, fd)
+    }
+
+    /// Make `fd` read the text of a here-document
+    fn here_doc(&mut self, fd: RawFd, text: &str) -> Result<()> {
+        self.save(fd)?;
+
+        // A file in memory rather than a pipe, which could not hold a long
+        // text without a process to write it
+        let mut file = File::from(memfd_create(c"sh-here-doc", MemFdCreateFlag::MFD_CLOEXEC)?);
+        file.write_all(text.as_bytes())?;
+        file.seek(SeekFrom::Start(0))?;
+
+        move_to(file.as_raw_fd(), fd)?;
+        if file.as_raw_fd() == fd {
+            let _ = file.into_raw_fd();
+        }
+        Ok(())
+    }
+
+    /// Save a descriptor before replacing it, once per command
+    fn save(&mut self, fd: RawFd) -> Result<()> {
+        if self.saved.iter().any(|(saved, _)| *saved == fd) {
+            return Ok(());
+        }
+
+        let _ = io::stdout().flush();
+        let copy = match fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(SAVED_FD_MIN)) {
+            // SAFETY: the descriptor was just created and is owned here
+            Ok(copy) => Some(unsafe { OwnedFd::from_raw_fd(copy) }),
+            Err(nix::errno::Errno::EBADF) => None,
+            Err(e) => bail!("{}: cannot save descriptor: {}", fd, e),
+        };
+        self.saved.push((fd, copy));
+        Ok(())
+    }
+}
+
+impl Drop for Redirections {
+    fn drop(&mut self) {
+        let _ = io::stdout().flush();
+        for (fd, copy) in self.saved.drain(..).rev() {
+            match copy {
+                Some(copy) => {
+                    let _ = dup2(copy.as_raw_fd(), fd);
+                }
+                None => {
+                    let _ = close(fd);
+                }
+            }
+        }
+    }
+}
+
+/// Make `fd` refer to what `source` refers to, inherited by child processes
+fn move_to(source: RawFd, fd: RawFd) -> Result<()> {
+    if source == fd {
+        fcntl(fd, FcntlArg::F_SETFD(FdFlag::empty()))?;
+    } else {
+        dup2(source, fd).with_context(|| format!("{}: cannot redirect", fd))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,123 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! End-to-end tests of pipelines and redirections, run through `sh -c`
+
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Run a command string, returning its output and exit status
+fn sh(source: &str) -> (String, i32) {
+    let output = Command::new(env!("CARGO_BIN_EXE_sh"))
+        .arg("-c")
+        .arg(source)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    (stdout, output.status.code().unwrap())
+}
+
+/// A directory of its own for a test, emptied first
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sh-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_pipeline_status() {
+    // The last command decides, unless pipefail is set
+    assert_eq!(sh("exit 3 | true").1, 0);
+    assert_eq!(sh("true | exit 3").1, 3);
+    assert_eq!(sh("! true | false; echo $?"), ("0\n".to_string(), 0));
+
+    // With pipefail the last command to fail decides
+    assert_eq!(sh("set -o pipefail; exit 2 | exit 3 | true").1, 3);
+    assert_eq!(sh("set -o pipefail; exit 2 | true | true").1, 2);
+    assert_eq!(sh("set -o pipefail; true | true").1, 0);
+    assert_eq!(
+        sh("set -o pipefail; set +o pipefail; exit 2 | true; echo $?"),
+        ("0\n".to_string(), 0)
+    );
+
+    // Data flows through every command
+    assert_eq!(
+        sh("echo a b | tr ' ' '\\n' | sort -r"),
+        ("b\na\n".to_string(), 0)
+    );
+}
+
+#[test]
+fn test_redirections_apply_in_order() {
+    // 2>&1 copies where output goes at that point, not where it ends up
+    let group = "{ echo out; echo err >&2; }";
+    assert_eq!(sh(&format!("{} 2>&1 >/dev/null", group)).0, "err\n");
+    assert_eq!(sh(&format!("{} >/dev/null 2>&1", group)).0, "");
+
+    let dir = scratch("order");
+    let file = dir.join("log").display().to_string();
+    let (output, _) = sh(&format!(
+        "echo one >{0}; echo two >>{0}; {1} >>{0} 2>&1; cat <{0}",
+        file, group
+    ));
+    assert_eq!(output, "one\ntwo\nout\nerr\n");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_descriptors_restored_after_builtins_and_groups() {
+    let dir = scratch("restore");
+    let file = dir.join("out").display().to_string();
+
+    // Output goes back to the shell's once the redirected command is done
+    let (output, status) = sh(&format!(
+        "echo -n builtin >{0}; echo after; {{ echo group; }} >>{0}; echo again; \
+         f() {{ echo function; }}; f >>{0}; echo last",
+        file
+    ));
+    assert_eq!((output.as_str(), status), ("after\nagain\nlast\n", 0));
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        "builtingroup\nfunction\n"
+    );
+
+    // Also when a later redirection of the same command fails
+    let (output, status) = sh(&format!(
+        "echo lost >{} <{}/missing; echo kept $?",
+        file,
+        dir.display()
+    ));
+    assert_eq!((output.as_str(), status), ("kept 1\n", 0));
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "");
+
+    // And a closed descriptor is open again afterwards
+    assert_eq!(sh("{ echo closed; } >&-; echo open").0, "open\n");
+    assert_eq!(sh("echo never >&7; echo $?").0, "1\n");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_here_documents() {
+    let (output, _) = sh("x=1; cat <<EOF; cat <<'EOF'\n$x\nEOF\n$x\nEOF\n");
+    assert_eq!(output, "1\n$x\n");
+
+    // A here-document longer than a pipe can hold
+    let dir = scratch("here-doc");
+    let script = dir.join("long.sh");
+    std::fs::write(
+        &script,
+        format!("wc -c <<EOF\n{}\nEOF\n", "x".repeat(200_000)),
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_sh"))
+        .arg("-f")
+        .arg(&script)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "200001");
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -8,6 +8,7 @@
 
 use anyhow::Result;
 use clap::Parser;
+use rpg_core::{RebootRequired, Scope, Severity, UpdateSummary};
 use std::fs;
 use std::path::Path;
 
@@ -58,6 +59,7 @@ fn main() -> Result<()> {
             "disk" => results.push(check_disk()),
             "network" => results.push(check_network()),
             "services" => results.push(check_services()),
+            "updates" => results.push(check_updates()),
             _ => {
                 eprintln!("Unknown component: {}", component);
                 return Ok(());
@@ -70,6 +72,7 @@ fn main() -> Result<()> {
         results.push(check_disk());
         results.push(check_network());
         results.push(check_services());
+        results.push(check_updates());
     }
 
     // Print results
@@ -305,3 +308,53 @@ fn check_services() -> CheckResult {
         message: format!("{} of {} essential services running", running, total),
     }
 }
+
+fn check_updates() -> CheckResult {
+    let state_dir = match Scope::System.state_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            return CheckResult {
+                name: "Updates".to_string(),
+                status: CheckStatus::Warning,
+                message: format!("Cannot find the update state: {}", e),
+            }
+        }
+    };
+
+    let mut status = CheckStatus::Ok;
+    let mut messages = Vec::new();
+
+    if let Some(reboot) = RebootRequired::load(&state_dir) {
+        status = CheckStatus::Warning;
+        messages.push(format!(
+            "Reboot required for: {}",
+            reboot.packages.join(", ")
+        ));
+    }
+
+    match UpdateSummary::load(&state_dir) {
+        Some(summary) => {
+            if summary.security > 0 {
+                status = match summary.severity {
+                    Some(severity) if severity >= Severity::High => CheckStatus::Critical,
+                    _ => CheckStatus::Warning,
+                };
+                messages.push(format!(
+                    "{} update(s) available, {} security",
+                    summary.available, summary.security
+                ));
+            } else if summary.available > 0 {
+                messages.push(format!("{} update(s) available", summary.available));
+            } else {
+                messages.push("System is up to date".to_string());
+            }
+        }
+        None => messages.push("No update check recorded yet".to_string()),
+    }
+
+    CheckResult {
+        name: "Updates".to_string(),
+        status,
+        message: messages.join("; "),
+    }
+}
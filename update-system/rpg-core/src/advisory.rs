@@ -0,0 +1,307 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Security advisories
+//!
+//! Repositories may publish `advisories.json` next to their index, signed
+//! by the source's trusted key in `advisories.json.sig`:
+//!
+//! ```json
+//! {
+//!   "advisories": [
+//!     {
+//!       "id": "RSA-2025-0001",
+//!       "package": "editor",
+//!       "affected": ">=1.0.0, <1.4.2",
+//!       "severity": "high",
+//!       "fixed_in": "1.4.2",
+//!       "summary": "Heap overflow when opening crafted files"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Affected ranges use [`VersionConstraint`] syntax.
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::FetchBackend;
+use crate::fetch::{FetchError, FetchOptions};
+use crate::signature::{PackageSignature, SignatureVerifier};
+use crate::sources::Source;
+use crate::version::{Version, VersionConstraint};
+
+/// Advisory feed file name, relative to the source URL
+pub const ADVISORIES_FILE: &str = "advisories.json";
+
+/// Advisory feed signature file name, relative to the source URL
+pub const ADVISORIES_SIG_FILE: &str = "advisories.json.sig";
+
+/// How serious an advisory is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Critical => "critical",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for Severity {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            "critical" => Ok(Self::Critical),
+            _ => Err(crate::Error::Other(format!(
+                "Unknown severity: {} (expected low, medium, high or critical)",
+                s
+            ))),
+        }
+    }
+}
+
+/// A known vulnerability in a range of package versions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Advisory {
+    /// Advisory identifier
+    pub id: String,
+    /// Affected package
+    pub package: String,
+    /// Affected versions, as a version constraint
+    pub affected: String,
+    /// Severity
+    pub severity: Severity,
+    /// First version with the fix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixed_in: Option<String>,
+    /// One-line description
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// Link to the full advisory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl Advisory {
+    /// Check if a version of the package is affected
+    ///
+    /// Advisories with an invalid range match nothing.
+    pub fn affects(&self, version: &Version) -> bool {
+        VersionConstraint::new(&self.affected)
+            .map(|c| c.satisfies(version))
+            .unwrap_or(false)
+    }
+
+    /// Check if moving from one version to another fixes the advisory
+    pub fn fixed_by(&self, from: &Version, to: &Version) -> bool {
+        self.affects(from) && !self.affects(to)
+    }
+}
+
+/// A signed list of advisories
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdvisoryFeed {
+    #[serde(default)]
+    pub advisories: Vec<Advisory>,
+}
+
+impl AdvisoryFeed {
+    /// Parse a feed
+    pub fn parse(bytes: &[u8]) -> crate::Result<Self> {
+        let feed: Self = serde_json::from_slice(bytes)
+            .map_err(|e| crate::Error::Serialization(e.to_string()))?;
+
+        for advisory in &feed.advisories {
+            VersionConstraint::new(&advisory.affected).map_err(|_| {
+                crate::Error::Other(format!(
+                    "Advisory {} has invalid affected range: {}",
+                    advisory.id, advisory.affected
+                ))
+            })?;
+        }
+
+        Ok(feed)
+    }
+
+    /// Parse a feed after checking its signature
+    pub fn parse_signed(bytes: &[u8], signature: &str, trusted_key: &str) -> crate::Result<Self> {
+        let verifier = SignatureVerifier::from_base64(trusted_key)?;
+        let signature = PackageSignature::from_base64(signature.trim())?;
+        verifier.verify(bytes, &signature)?;
+
+        Self::parse(bytes)
+    }
+
+    /// Get the advisories affecting a package version
+    pub fn affecting<'a>(
+        &'a self,
+        package: &'a str,
+        version: &'a Version,
+    ) -> impl Iterator<Item = &'a Advisory> + 'a {
+        self.advisories
+            .iter()
+            .filter(move |a| a.package == package && a.affects(version))
+    }
+
+    /// Add the advisories of another feed, skipping known IDs
+    pub fn merge(&mut self, other: AdvisoryFeed) {
+        for advisory in other.advisories {
+            if !self.advisories.iter().any(|a| a.id == advisory.id) {
+                self.advisories.push(advisory);
+            }
+        }
+    }
+}
+
+/// Fetch the advisory feed of a source
+///
+/// Returns `Ok(None)` if the source does not publish one. The feed must
+/// carry a valid signature by the source's trusted key. Sources without a
+/// key are not asked for advisories at all, since an unverified feed could
+/// make any update look like an urgent security fix.
+pub async fn fetch_advisories(
+    backend: &dyn FetchBackend,
+    source: &Source,
+    options: &FetchOptions,
+) -> crate::Result<Option<AdvisoryFeed>> {
+    let Some(key) = &source.trusted_key else {
+        log::warn!(
+            "Source {} has no trusted key, ignoring its advisories",
+            source.name
+        );
+        return Ok(None);
+    };
+    let options = options.for_source(source);
+
+    let bytes = match backend
+        .fetch_range(&source.file_url(ADVISORIES_FILE), 0, None, &options)
+        .await
+    {
+        Ok(bytes) => bytes,
+        Err(FetchError::NotFound(_)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let signature = backend
+        .fetch_range(&source.file_url(ADVISORIES_SIG_FILE), 0, None, &options)
+        .await
+        .map_err(|e| {
+            crate::Error::SignatureVerification(format!(
+                "advisories of {} are not signed: {}",
+                source.name, e
+            ))
+        })?;
+
+    AdvisoryFeed::parse_signed(&bytes, &String::from_utf8_lossy(&signature), key)
+        .map(Some)
+        .map_err(|e| {
+            crate::Error::SignatureVerification(format!("advisories of {}: {}", source.name, e))
+        })
+}
+
+/// An installed package with a known advisory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditFinding {
+    /// Package name
+    pub package: String,
+    /// Installed (active) version
+    pub installed: Version,
+    /// The advisory
+    pub advisory: Advisory,
+}
+
+/// Result of auditing installed packages
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AuditReport {
+    /// Findings, most severe first
+    pub findings: Vec<AuditFinding>,
+    /// Sources whose advisories could not be fetched
+    pub errors: Vec<String>,
+}
+
+impl AuditReport {
+    /// Get the highest severity found
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(|f| f.advisory.severity).max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signature::KeyPair;
+
+    const FEED: &str = r#"{
+        "advisories": [
+            {
+                "id": "RSA-2025-0001",
+                "package": "editor",
+                "affected": ">=1.0.0, <1.4.2",
+                "severity": "high",
+                "fixed_in": "1.4.2"
+            },
+            {
+                "id": "RSA-2025-0002",
+                "package": "viewer",
+                "affected": "<2.0.0",
+                "severity": "low"
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_advisory_ranges() {
+        let feed = AdvisoryFeed::parse(FEED.as_bytes()).unwrap();
+        let editor = &feed.advisories[0];
+
+        assert!(editor.affects(&Version::new(1, 3, 0)));
+        assert!(!editor.affects(&Version::new(1, 4, 2)));
+        assert!(editor.fixed_by(&Version::new(1, 3, 0), &Version::new(1, 5, 0)));
+        assert!(!editor.fixed_by(&Version::new(0, 9, 0), &Version::new(1, 5, 0)));
+
+        let v = Version::new(1, 2, 0);
+        assert_eq!(feed.affecting("editor", &v).count(), 1);
+        assert_eq!(feed.affecting("viewer", &v).count(), 1);
+        assert_eq!(feed.affecting("kernel", &v).count(), 0);
+
+        assert!(Severity::Critical > Severity::High);
+        assert_eq!("HIGH".parse::<Severity>().unwrap(), Severity::High);
+
+        let invalid = FEED.replace("<2.0.0", "not a range");
+        assert!(AdvisoryFeed::parse(invalid.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_signed_feed() {
+        let key = KeyPair::generate();
+        let signature = key.sign(FEED.as_bytes()).to_base64();
+        let public = key.export_public();
+
+        assert!(AdvisoryFeed::parse_signed(FEED.as_bytes(), &signature, &public).is_ok());
+
+        let tampered = FEED.replace("high", "low");
+        assert!(AdvisoryFeed::parse_signed(tampered.as_bytes(), &signature, &public).is_err());
+
+        let other = KeyPair::generate().export_public();
+        assert!(AdvisoryFeed::parse_signed(FEED.as_bytes(), &signature, &other).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! CPU architecture detection and package matching
+
+/// Architectures packages can be built for
+pub const SUPPORTED_ARCHES: &[&str] = &["x86_64", "aarch64", "riscv64"];
+
+/// Architecture names for packages that install on any host
+pub const NOARCH: &[&str] = &["noarch", "any"];
+
+/// Get the architecture of the running host
+pub fn host_arch() -> String {
+    normalize(std::env::consts::ARCH)
+}
+
+/// Normalize an architecture name, resolving common aliases
+pub fn normalize(arch: &str) -> String {
+    match arch.to_lowercase().as_str() {
+        "amd64" | "x64" => "x86_64".to_string(),
+        "arm64" => "aarch64".to_string(),
+        "riscv64gc" => "riscv64".to_string(),
+        "all" => "noarch".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Check if an architecture name marks an architecture-independent package
+pub fn is_noarch(arch: &str) -> bool {
+    NOARCH.contains(&normalize(arch).as_str())
+}
+
+/// Validate an architecture name given as a target
+pub fn validate(arch: &str) -> crate::Result<String> {
+    let arch = normalize(arch);
+
+    if SUPPORTED_ARCHES.contains(&arch.as_str()) {
+        Ok(arch)
+    } else {
+        Err(crate::Error::Other(format!(
+            "Unsupported architecture: {} (expected one of: {})",
+            arch,
+            SUPPORTED_ARCHES.join(", ")
+        )))
+    }
+}
+
+/// Check if a package built for `package_arch` can be installed on `target_arch`
+pub fn is_compatible(package_arch: &str, target_arch: &str) -> bool {
+    is_noarch(package_arch) || normalize(package_arch) == normalize(target_arch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arch_compatibility() {
+        assert!(is_compatible("x86_64", "x86_64"));
+        assert!(is_compatible("amd64", "x86_64"));
+        assert!(is_compatible("noarch", "riscv64"));
+        assert!(is_compatible("any", "aarch64"));
+        assert!(!is_compatible("aarch64", "x86_64"));
+    }
+
+    #[test]
+    fn test_arch_validate() {
+        assert_eq!(validate("arm64").unwrap(), "aarch64");
+        assert!(validate("sparc").is_err());
+        assert!(!host_arch().is_empty());
+    }
+}
@@ -1,404 +1,633 @@
-// Copyright 2025 The Rustux Authors
-//
-// Use of this source code is governed by a MIT-style
-// license that can be found in the LICENSE file or at
-// https://opensource.org/licenses/MIT
-
-//! RPG package archive format
-//!
-//! Packages are tar.gz archives with the following structure:
-//! ```
-//! package.rpg
-//! ├── metadata.json          # Package metadata
-//! ├── files/                 # Actual files to install
-//! │   ├── usr/
-//! │   ├── bin/
-//! │   └── ...
-//! ├── scripts/               # Installation scripts (optional)
-//! │   ├── pre-install.sh
-//! │   ├── post-install.sh
-//! │   └── pre-remove.sh
-//! └── signature.sig          # Detached signature (optional)
-//! ```
-
-use serde::{Deserialize, Serialize};
-use std::fs::{self, File};
-use std::io::{BufReader, Read};
-use std::path::{Path, PathBuf};
-use tempfile::TempDir;
-
-use crate::package::{PackageKind, PackageMetadata};
-use crate::signature::PackageSignature;
-use crate::version::Version;
-
-/// Package archive
-#[derive(Debug, Clone)]
-pub struct PackageArchive {
-    /// Path to the archive file
-    pub path: PathBuf,
-    /// Package metadata
-    pub metadata: PackageMetadata,
-}
-
-/// Package manifest (metadata.json)
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PackageManifest {
-    /// Package name
-    pub name: String,
-
-    /// Package version
-    pub version: String,
-
-    /// Package description
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub description: Option<String>,
-
-    /// Package kind
-    #[serde(rename = "type")]
-    pub kind: String,
-
-    /// Architecture (x86_64, aarch64, riscv64)
-    pub arch: String,
-
-    /// Dependencies
-    #[serde(default)]
-    pub dependencies: Vec<String>,
-
-    /// Conflicts with
-    #[serde(default)]
-    pub conflicts: Vec<String>,
-
-    /// Package size in bytes
-    pub size: u64,
-
-    /// SHA-256 checksum
-    pub sha256: String,
-
-    /// Download URL
-    pub url: String,
-
-    /// Maintainer
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub maintainer: Option<String>,
-
-    /// Homepage
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub homepage: Option<String>,
-
-    /// License
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub license: Option<String>,
-
-    /// Installation size (uncompressed)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub installed_size: Option<u64>,
-
-    /// Files list
-    #[serde(default)]
-    pub files: Vec<String>,
-
-    /// Directories to create
-    #[serde(default)]
-    pub directories: Vec<String>,
-
-    /// Pre-install script
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub pre_install: Option<String>,
-
-    /// Post-install script
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub post_install: Option<String>,
-
-    /// Pre-remove script
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub pre_remove: Option<String>,
-
-    /// Signature (base64)
-    pub signature: String,
-}
-
-impl PackageManifest {
-    /// Create a new manifest
-    pub fn new(
-        name: String,
-        version: String,
-        kind: PackageKind,
-        arch: String,
-        size: u64,
-        sha256: String,
-        url: String,
-        signature: PackageSignature,
-    ) -> Self {
-        Self {
-            name,
-            version,
-            description: None,
-            kind: kind.as_str().to_string(),
-            arch,
-            dependencies: Vec::new(),
-            conflicts: Vec::new(),
-            size,
-            sha256,
-            url,
-            maintainer: None,
-            homepage: None,
-            license: None,
-            installed_size: None,
-            files: Vec::new(),
-            directories: Vec::new(),
-            pre_install: None,
-            post_install: None,
-            pre_remove: None,
-            signature: signature.to_base64(),
-        }
-    }
-
-    /// Convert to PackageMetadata
-    pub fn to_metadata(&self) -> crate::Result<PackageMetadata> {
-        use crate::signature::PackageSignature;
-
-        let version = Version::parse(&self.version)?;
-        let kind = PackageKind::from_str(&self.kind)?;
-        let signature = PackageSignature::from_base64(&self.signature)?;
-
-        Ok(PackageMetadata::new(
-            self.name.clone(),
-            version,
-            kind,
-            self.size,
-            self.sha256.clone(),
-            signature,
-            self.url.clone(),
-        ))
-    }
-}
-
-impl PackageArchive {
-    /// Open an existing package archive
-    pub fn open(path: impl AsRef<Path>) -> crate::Result<Self> {
-        let path = path.as_ref();
-
-        if !path.exists() {
-            return Err(crate::Error::PackageNotFound(path.display().to_string()));
-        }
-
-        // Extract and read metadata
-        let metadata = Self::extract_metadata(path)?;
-
-        Ok(Self {
-            path: path.to_path_buf(),
-            metadata,
-        })
-    }
-
-    /// Create a new package archive
-    pub fn create(
-        path: impl AsRef<Path>,
-        manifest: PackageManifest,
-        files: &[PathBuf],
-    ) -> crate::Result<Self> {
-        let path = path.as_ref();
-
-        // Create temporary directory for staging
-        let temp_dir = TempDir::new()?;
-        let staging_dir = temp_dir.path();
-
-        // Write manifest
-        let manifest_path = staging_dir.join("metadata.json");
-        let manifest_json = serde_json::to_string_pretty(&manifest)
-            .map_err(|e| crate::Error::Serialization(e.to_string()))?;
-        fs::write(&manifest_path, manifest_json)?;
-
-        // Copy files
-        let files_dir = staging_dir.join("files");
-        fs::create_dir_all(&files_dir)?;
-
-        for file_path in files {
-            if file_path.is_file() {
-                let dest = files_dir.join(
-                    file_path
-                        .strip_prefix("/")
-                        .unwrap_or(file_path),
-                );
-                if let Some(parent) = dest.parent() {
-                    fs::create_dir_all(parent)?;
-                }
-                fs::copy(file_path, dest)?;
-            }
-        }
-
-        // Create tar.gz archive
-        let tar_gz = File::create(path)?;
-        let enc = flate2::write::GzEncoder::new(tar_gz, flate2::Compression::default());
-        let mut tar = tar::Builder::new(enc);
-
-        tar.append_dir_all(".", staging_dir)?;
-
-        // Finish the archive
-        let enc = tar.into_inner()?;
-        enc.finish()?;
-
-        // Read back the metadata
-        let metadata = manifest.to_metadata()?;
-
-        Ok(Self {
-            path: path.to_path_buf(),
-            metadata,
-        })
-    }
-
-    /// Extract metadata from package
-    fn extract_metadata(path: &Path) -> crate::Result<PackageMetadata> {
-        let file = File::open(path)?;
-        let buf_reader = BufReader::new(file);
-        let decoder = flate2::read::GzDecoder::new(buf_reader);
-        let mut tar_archive = tar::Archive::new(decoder);
-
-        // Find metadata.json
-        for entry in tar_archive.entries()? {
-            let mut entry = entry?;
-            let path = entry.path()?;
-
-            if path.ends_with("metadata.json") {
-                let mut contents = String::new();
-                entry.read_to_string(&mut contents)?;
-                let manifest: PackageManifest = serde_json::from_str(&contents)
-                    .map_err(|e| crate::Error::Serialization(e.to_string()))?;
-                return manifest.to_metadata();
-            }
-        }
-
-        Err(crate::Error::Other(
-            "metadata.json not found in package".to_string(),
-        ))
-    }
-
-    /// Extract package to a directory
-    pub fn extract(&self, dest: impl AsRef<Path>) -> crate::Result<()> {
-        let dest = dest.as_ref();
-
-        // Create destination directory
-        fs::create_dir_all(dest)?;
-
-        let file = File::open(&self.path)?;
-        let buf_reader = BufReader::new(file);
-        let decoder = flate2::read::GzDecoder::new(buf_reader);
-        let mut tar_archive = tar::Archive::new(decoder);
-
-        tar_archive.unpack(dest)?;
-
-        Ok(())
-    }
-
-    /// Extract files to a directory
-    pub fn extract_files(&self, dest: impl AsRef<Path>) -> crate::Result<()> {
-        let dest = dest.as_ref();
-
-        // Extract to temp directory first
-        let temp_dir = TempDir::new()?;
-        self.extract(temp_dir.path())?;
-
-        // Move files/ directory to destination
-        let files_source = temp_dir.path().join("files");
-        if files_source.exists() {
-            for entry in fs::read_dir(files_source)? {
-                let entry = entry?;
-                let target = dest.join(entry.file_name());
-                fs::rename(entry.path(), target)?;
-            }
-        }
-
-        Ok(())
-    }
-
-    /// Get list of files in package
-    pub fn list_files(&self) -> crate::Result<Vec<String>> {
-        let temp_dir = TempDir::new()?;
-        self.extract(temp_dir.path())?;
-
-        let manifest_path = temp_dir.path().join("metadata.json");
-        let manifest_json = fs::read_to_string(manifest_path)?;
-        let manifest: PackageManifest = serde_json::from_str(&manifest_json)
-            .map_err(|e| crate::Error::Serialization(e.to_string()))?;
-
-        Ok(manifest.files)
-    }
-
-    /// Verify package signature
-    pub fn verify_signature(&self, public_key: &str) -> crate::Result<bool> {
-        use crate::signature::SignatureVerifier;
-
-        let verifier = SignatureVerifier::from_base64(public_key)?;
-
-        // Read the package file
-        let mut file = File::open(&self.path)?;
-        let mut contents = Vec::new();
-        file.read_to_end(&mut contents)?;
-
-        // Verify signature
-        verifier.verify(&contents, &self.metadata.signature).map(|_| true)
-    }
-
-    /// Get package manifest
-    pub fn manifest(&self) -> crate::Result<PackageManifest> {
-        let temp_dir = TempDir::new()?;
-        self.extract(temp_dir.path())?;
-
-        let manifest_path = temp_dir.path().join("metadata.json");
-        let manifest_json = fs::read_to_string(manifest_path)?;
-        serde_json::from_str(&manifest_json)
-            .map_err(|e| crate::Error::Serialization(e.to_string()))
-    }
-}
-
-/// Create a package from a directory
-pub fn create_package(
-    source_dir: impl AsRef<Path>,
-    output_path: impl AsRef<Path>,
-    manifest: PackageManifest,
-) -> crate::Result<PackageArchive> {
-    let source_dir = source_dir.as_ref();
-
-    // Collect all files in the directory
-    let mut files = Vec::new();
-    if source_dir.exists() {
-        for entry in walkdir::WalkDir::new(source_dir)
-            .follow_links(true)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            let path = entry.path();
-            if path.is_file() {
-                files.push(path.to_path_buf());
-            }
-        }
-    }
-
-    PackageArchive::create(output_path, manifest, &files)
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::signature::KeyPair;
-
-    #[test]
-    fn test_manifest_creation() {
-        let key = KeyPair::generate();
-        let signature = key.sign(b"test");
-
-        let manifest = PackageManifest::new(
-            "test".to_string(),
-            "1.0.0".to_string(),
-            PackageKind::App,
-            "x86_64".to_string(),
-            1024,
-            "0".repeat(64),
-            "https://example.com/test.rpg".to_string(),
-            signature,
-        );
-
-        assert_eq!(manifest.name, "test");
-        assert_eq!(manifest.kind, "app");
-    }
-}
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! RPG package archive format
+//!
+//! Packages are tar.gz archives with the following structure:
+//! ```text
+//! package.rpg
+//! ├── metadata.json          # Package metadata
+//! ├── files/                 # Actual files to install
+//! │   ├── usr/
+//! │   ├── bin/
+//! │   └── ...
+//! ├── scripts/               # Installation scripts (optional)
+//! │   ├── pre-install.sh
+//! │   ├── post-install.sh
+//! │   └── pre-remove.sh
+//! └── signature.sig          # Detached signature (optional)
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+use crate::package::{Dependency, PackageKind, PackageMetadata};
+use crate::signature::PackageSignature;
+use crate::trigger::Trigger;
+use crate::version::Version;
+
+/// Prefix of the staging directory [`PackageArchive::extract_files`] creates
+/// next to its destination
+const STAGING_PREFIX: &str = ".rpg-extract-";
+
+/// Package archive
+#[derive(Debug, Clone)]
+pub struct PackageArchive {
+    /// Path to the archive file
+    pub path: PathBuf,
+    /// Package metadata
+    pub metadata: PackageMetadata,
+    /// Architecture the package was built for
+    pub arch: String,
+}
+
+/// Package manifest (metadata.json)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageManifest {
+    /// Package name
+    pub name: String,
+
+    /// Package version
+    pub version: String,
+
+    /// Package description
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Package kind
+    #[serde(rename = "type")]
+    pub kind: String,
+
+    /// Architecture (x86_64, aarch64, riscv64)
+    pub arch: String,
+
+    /// Dependencies
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+
+    /// Conflicts with
+    #[serde(default)]
+    pub conflicts: Vec<String>,
+
+    /// Config files, as absolute paths under `/etc`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conffiles: Vec<String>,
+
+    /// Handlers to run when transactions change matching paths
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<Trigger>,
+
+    /// Package size in bytes
+    pub size: u64,
+
+    /// SHA-256 checksum
+    pub sha256: String,
+
+    /// Download URL
+    pub url: String,
+
+    /// Maintainer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maintainer: Option<String>,
+
+    /// Homepage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+
+    /// License
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+
+    /// Installation size (uncompressed)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub installed_size: Option<u64>,
+
+    /// Files list
+    #[serde(default)]
+    pub files: Vec<String>,
+
+    /// Directories to create
+    #[serde(default)]
+    pub directories: Vec<String>,
+
+    /// Pre-install script
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_install: Option<String>,
+
+    /// Post-install script
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_install: Option<String>,
+
+    /// Pre-remove script
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_remove: Option<String>,
+
+    /// Signature (base64)
+    pub signature: String,
+}
+
+impl PackageManifest {
+    /// Create a new manifest
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        name: String,
+        version: String,
+        kind: PackageKind,
+        arch: String,
+        size: u64,
+        sha256: String,
+        url: String,
+        signature: PackageSignature,
+    ) -> Self {
+        Self {
+            name,
+            version,
+            description: None,
+            kind: kind.as_str().to_string(),
+            arch,
+            dependencies: Vec::new(),
+            conflicts: Vec::new(),
+            conffiles: Vec::new(),
+            triggers: Vec::new(),
+            size,
+            sha256,
+            url,
+            maintainer: None,
+            homepage: None,
+            license: None,
+            installed_size: None,
+            files: Vec::new(),
+            directories: Vec::new(),
+            pre_install: None,
+            post_install: None,
+            pre_remove: None,
+            signature: signature.to_base64(),
+        }
+    }
+
+    /// Convert to PackageMetadata
+    pub fn to_metadata(&self) -> crate::Result<PackageMetadata> {
+        use crate::signature::PackageSignature;
+
+        let version = Version::parse(&self.version)?;
+        let kind: PackageKind = self.kind.parse()?;
+        let signature = PackageSignature::from_base64(&self.signature)?;
+
+        let mut metadata = PackageMetadata::new(
+            self.name.clone(),
+            version,
+            kind,
+            self.size,
+            self.sha256.clone(),
+            signature,
+            self.url.clone(),
+        );
+
+        metadata.description = self.description.clone();
+        metadata.author = self.maintainer.clone();
+        metadata.homepage = self.homepage.clone();
+        metadata.license = self.license.clone();
+
+        for dependency in &self.dependencies {
+            let dependency = Dependency::parse(dependency)?;
+            metadata
+                .dependencies
+                .insert(dependency.name, dependency.constraint.requirement);
+        }
+
+        for conffile in &self.conffiles {
+            crate::conffile::validate_path(conffile)?;
+        }
+        metadata.conffiles = self.conffiles.clone();
+
+        for trigger in &self.triggers {
+            trigger.validate()?;
+        }
+        metadata.triggers = self.triggers.clone();
+
+        Ok(metadata)
+    }
+}
+
+impl PackageArchive {
+    /// Open an existing package archive
+    pub fn open(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+
+        if !path.exists() {
+            return Err(crate::Error::PackageNotFound(path.display().to_string()));
+        }
+
+        // Extract and read metadata
+        let manifest = Self::extract_manifest(path)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            metadata: manifest.to_metadata()?,
+            arch: manifest.arch,
+        })
+    }
+
+    /// Create a new package archive
+    ///
+    /// Files are stored under their absolute path, relative to the root.
+    pub fn create(
+        path: impl AsRef<Path>,
+        manifest: PackageManifest,
+        files: &[PathBuf],
+    ) -> crate::Result<Self> {
+        let entries: Vec<(&Path, &Path)> = files
+            .iter()
+            .map(|file| (file.as_path(), file.strip_prefix("/").unwrap_or(file)))
+            .collect();
+        Self::create_from(path.as_ref(), manifest, &entries)
+    }
+
+    /// Create a package archive from `(source, path in files/)` pairs
+    fn create_from(
+        path: &Path,
+        manifest: PackageManifest,
+        files: &[(&Path, &Path)],
+    ) -> crate::Result<Self> {
+        // Create temporary directory for staging
+        let temp_dir = TempDir::new()?;
+        let staging_dir = temp_dir.path();
+
+        // Write manifest
+        let manifest_path = staging_dir.join("metadata.json");
+        let manifest_json = serde_json::to_string_pretty(&manifest)
+            .map_err(|e| crate::Error::Serialization(e.to_string()))?;
+        fs::write(&manifest_path, manifest_json)?;
+
+        // Copy files
+        let files_dir = staging_dir.join("files");
+        fs::create_dir_all(&files_dir)?;
+
+        for (file_path, relative) in files {
+            if file_path.is_file() {
+                let dest = files_dir.join(relative);
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(file_path, dest)?;
+            }
+        }
+
+        // Create tar.gz archive
+        let tar_gz = File::create(path)?;
+        let enc = flate2::write::GzEncoder::new(tar_gz, flate2::Compression::default());
+        let mut tar = tar::Builder::new(enc);
+
+        tar.append_dir_all(".", staging_dir)?;
+
+        // Finish the archive
+        let enc = tar.into_inner()?;
+        enc.finish()?;
+
+        // Read back the metadata
+        let metadata = manifest.to_metadata()?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            metadata,
+            arch: manifest.arch,
+        })
+    }
+
+    /// Extract the manifest from package
+    fn extract_manifest(path: &Path) -> crate::Result<PackageManifest> {
+        let file = File::open(path)?;
+        let buf_reader = BufReader::new(file);
+        let decoder = flate2::read::GzDecoder::new(buf_reader);
+        let mut tar_archive = tar::Archive::new(decoder);
+
+        // Find metadata.json
+        for entry in tar_archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?;
+
+            if path.ends_with("metadata.json") {
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents)?;
+                return serde_json::from_str(&contents)
+                    .map_err(|e| crate::Error::Serialization(e.to_string()));
+            }
+        }
+
+        Err(crate::Error::Other(
+            "metadata.json not found in package".to_string(),
+        ))
+    }
+
+    /// Check that the package can be installed on the target architecture
+    pub fn check_arch(&self, target_arch: &str) -> crate::Result<()> {
+        if crate::arch::is_compatible(&self.arch, target_arch) {
+            Ok(())
+        } else {
+            Err(crate::Error::ArchitectureMismatch(format!(
+                "{} is built for {}, but the target architecture is {}",
+                self.metadata.id(),
+                self.arch,
+                target_arch
+            )))
+        }
+    }
+
+    /// Extract package to a directory
+    pub fn extract(&self, dest: impl AsRef<Path>) -> crate::Result<()> {
+        let dest = dest.as_ref();
+
+        // Create destination directory
+        fs::create_dir_all(dest)?;
+
+        let file = File::open(&self.path)?;
+        let buf_reader = BufReader::new(file);
+        let decoder = flate2::read::GzDecoder::new(buf_reader);
+        let mut tar_archive = tar::Archive::new(decoder);
+
+        tar_archive.unpack(dest)?;
+
+        Ok(())
+    }
+
+    /// Extract files to a directory
+    ///
+    /// The archive is unpacked into a staging directory next to `dest`, so
+    /// moving the files into place is a rename on the same filesystem. A
+    /// new `dest` appears complete or not at all. An existing `dest` that
+    /// already has every file is left alone, as it may be an active version;
+    /// an incomplete one is replaced whole.
+    pub fn extract_files(&self, dest: impl AsRef<Path>) -> crate::Result<()> {
+        let dest = dest.as_ref();
+        let parent = dest
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        fs::create_dir_all(parent)?;
+
+        let staging = tempfile::Builder::new()
+            .prefix(STAGING_PREFIX)
+            .tempdir_in(parent)?;
+        self.extract(staging.path())?;
+
+        let files_source = staging.path().join("files");
+        if !files_source.exists() {
+            return Ok(());
+        }
+        if !dest.exists() {
+            fs::rename(&files_source, dest)?;
+            return Ok(());
+        }
+
+        if has_all_files(dest, &files_source) {
+            return Ok(());
+        }
+
+        // Move the old directory aside, then the new one in
+        let aside = tempfile::Builder::new()
+            .prefix(STAGING_PREFIX)
+            .tempdir_in(parent)?;
+        let old = aside.path().join("old");
+        fs::rename(dest, &old)?;
+        if let Err(e) = fs::rename(&files_source, dest) {
+            let _ = fs::rename(&old, dest);
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    /// Get list of files in package
+    pub fn list_files(&self) -> crate::Result<Vec<String>> {
+        let temp_dir = TempDir::new()?;
+        self.extract(temp_dir.path())?;
+
+        let manifest_path = temp_dir.path().join("metadata.json");
+        let manifest_json = fs::read_to_string(manifest_path)?;
+        let manifest: PackageManifest = serde_json::from_str(&manifest_json)
+            .map_err(|e| crate::Error::Serialization(e.to_string()))?;
+
+        Ok(manifest.files)
+    }
+
+    /// Verify package signature
+    pub fn verify_signature(&self, public_key: &str) -> crate::Result<bool> {
+        use crate::signature::SignatureVerifier;
+
+        let verifier = SignatureVerifier::from_base64(public_key)?;
+
+        // Read the package file
+        let mut file = File::open(&self.path)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+
+        // Verify signature
+        verifier
+            .verify(&contents, &self.metadata.signature)
+            .map(|_| true)
+    }
+
+    /// Get package manifest
+    pub fn manifest(&self) -> crate::Result<PackageManifest> {
+        Self::extract_manifest(&self.path)
+    }
+}
+
+/// Check if `dir` has every file of the extracted tree `files`
+fn has_all_files(dir: &Path, files: &Path) -> bool {
+    walkdir::WalkDir::new(files)
+        .min_depth(1)
+        .into_iter()
+        .all(|entry| match entry {
+            Ok(entry) if entry.file_type().is_dir() => true,
+            Ok(entry) => entry
+                .path()
+                .strip_prefix(files)
+                .map(|relative| dir.join(relative).symlink_metadata().is_ok())
+                .unwrap_or(false),
+            Err(_) => false,
+        })
+}
+
+/// Create a package from a directory
+pub fn create_package(
+    source_dir: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    manifest: PackageManifest,
+) -> crate::Result<PackageArchive> {
+    let source_dir = source_dir.as_ref();
+
+    // Collect all files in the directory
+    let mut files = Vec::new();
+    if source_dir.exists() {
+        for entry in walkdir::WalkDir::new(source_dir)
+            .follow_links(true)
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            let path = entry.path();
+            if path.is_file() {
+                files.push(path.to_path_buf());
+            }
+        }
+    }
+
+    // Keep the tree's layout below files/
+    let entries: Vec<(&Path, &Path)> = files
+        .iter()
+        .map(|file| {
+            (
+                file.as_path(),
+                file.strip_prefix(source_dir).unwrap_or(file),
+            )
+        })
+        .collect();
+    PackageArchive::create_from(output_path.as_ref(), manifest, &entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signature::KeyPair;
+
+    #[test]
+    fn test_manifest_creation() {
+        let key = KeyPair::generate();
+        let signature = key.sign(b"test");
+
+        let manifest = PackageManifest::new(
+            "test".to_string(),
+            "1.0.0".to_string(),
+            PackageKind::App,
+            "x86_64".to_string(),
+            1024,
+            "0".repeat(64),
+            "https://example.com/test.rpg".to_string(),
+            signature,
+        );
+
+        assert_eq!(manifest.name, "test");
+        assert_eq!(manifest.kind, "app");
+    }
+
+    #[test]
+    fn test_archive_arch_check() {
+        let key = KeyPair::generate();
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("src");
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::write(source_dir.join("hello"), b"hello").unwrap();
+
+        let manifest = PackageManifest::new(
+            "hello".to_string(),
+            "1.0.0".to_string(),
+            PackageKind::App,
+            "aarch64".to_string(),
+            5,
+            "0".repeat(64),
+            "https://example.com/hello.rpg".to_string(),
+            key.sign(b"hello"),
+        );
+
+        let output = temp_dir.path().join("hello.rpg");
+        create_package(&source_dir, &output, manifest).unwrap();
+
+        let archive = PackageArchive::open(&output).unwrap();
+        assert_eq!(archive.arch, "aarch64");
+        assert!(archive.check_arch("aarch64").is_ok());
+        assert!(matches!(
+            archive.check_arch("x86_64"),
+            Err(crate::Error::ArchitectureMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_extract_files_stages_next_to_destination() {
+        let key = KeyPair::generate();
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("src");
+        fs::create_dir_all(source_dir.join("bin")).unwrap();
+        fs::write(source_dir.join("bin/hello"), b"hello").unwrap();
+
+        let manifest = PackageManifest::new(
+            "hello".to_string(),
+            "1.0.0".to_string(),
+            PackageKind::App,
+            "noarch".to_string(),
+            5,
+            "0".repeat(64),
+            "https://example.com/hello.rpg".to_string(),
+            key.sign(b"hello"),
+        );
+        let output = temp_dir.path().join("hello.rpg");
+        create_package(&source_dir, &output, manifest).unwrap();
+
+        let app_dir = temp_dir.path().join("apps/hello");
+        let archive = PackageArchive::open(&output).unwrap();
+        archive.extract_files(app_dir.join("1.0.0")).unwrap();
+
+        assert_eq!(fs::read(app_dir.join("1.0.0/bin/hello")).unwrap(), b"hello");
+        let entries: Vec<_> = fs::read_dir(&app_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, vec!["1.0.0"]);
+    }
+
+    #[test]
+    fn test_extract_files_twice_into_the_same_destination() {
+        let key = KeyPair::generate();
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("src");
+        fs::create_dir_all(source_dir.join("bin")).unwrap();
+        fs::write(source_dir.join("bin/hello"), b"hello").unwrap();
+        fs::write(source_dir.join("README"), b"readme").unwrap();
+
+        let manifest = PackageManifest::new(
+            "hello".to_string(),
+            "1.0.0".to_string(),
+            PackageKind::App,
+            "noarch".to_string(),
+            5,
+            "0".repeat(64),
+            "https://example.com/hello.rpg".to_string(),
+            key.sign(b"hello"),
+        );
+        let output = temp_dir.path().join("hello.rpg");
+        create_package(&source_dir, &output, manifest).unwrap();
+
+        let app_dir = temp_dir.path().join("apps/hello");
+        let dest = app_dir.join("1.0.0");
+        let archive = PackageArchive::open(&output).unwrap();
+        archive.extract_files(&dest).unwrap();
+
+        // A complete directory is reused as is
+        let inode = |path: &Path| {
+            use std::os::unix::fs::MetadataExt;
+            fs::metadata(path).unwrap().ino()
+        };
+        let before = inode(&dest.join("bin/hello"));
+        archive.extract_files(&dest).unwrap();
+        assert_eq!(inode(&dest.join("bin/hello")), before);
+
+        // An incomplete one is replaced whole
+        fs::remove_file(dest.join("bin/hello")).unwrap();
+        fs::write(dest.join("stray"), b"stray").unwrap();
+        archive.extract_files(&dest).unwrap();
+        assert_eq!(fs::read(dest.join("bin/hello")).unwrap(), b"hello");
+        assert_eq!(fs::read(dest.join("README")).unwrap(), b"readme");
+        assert!(!dest.join("stray").exists());
+
+        let entries: Vec<_> = fs::read_dir(&app_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, vec!["1.0.0"]);
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Credentials for private repositories
+//!
+//! Credentials are kept out of the world-readable sources file, in
+//! `/etc/rpg/auth.toml`, keyed by source name:
+//!
+//! ```toml
+//! [internal]
+//! username = "deploy"
+//! password = "secret"
+//!
+//! [partner]
+//! token = "eyJhbGciOi..."
+//! ```
+//!
+//! The file must not be readable by group or others, and must belong to
+//! root or the user running rpg.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// Default credentials file path
+pub const AUTH_PATH: &str = "/etc/rpg/auth.toml";
+
+/// Credentials for one source
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Credentials {
+    /// HTTP basic authentication
+    Basic { username: String, password: String },
+    /// HTTP bearer token
+    Bearer { token: String },
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print secrets in logs
+        match self {
+            Self::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .finish_non_exhaustive(),
+            Self::Bearer { .. } => f.debug_struct("Bearer").finish_non_exhaustive(),
+        }
+    }
+}
+
+/// Credentials of all sources (source name -> credentials)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AuthConfig {
+    pub sources: BTreeMap<String, Credentials>,
+}
+
+impl AuthConfig {
+    /// Load credentials from the default path
+    pub fn load() -> crate::Result<Self> {
+        Self::load_from_path(AUTH_PATH)
+    }
+
+    /// Load credentials from a specific path
+    ///
+    /// A missing file means no credentials. A file that other users could
+    /// read is refused rather than used.
+    pub fn load_from_path(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        check_permissions(path)?;
+
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            crate::Error::Other(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+
+        toml::from_str(&content).map_err(|e| crate::Error::Serialization(e.to_string()))
+    }
+
+    /// Get the credentials of a source
+    pub fn get(&self, source: &str) -> Option<&Credentials> {
+        self.sources.get(source)
+    }
+
+    /// Set the credentials of a source
+    pub fn set(&mut self, source: &str, credentials: Credentials) {
+        self.sources.insert(source.to_string(), credentials);
+    }
+}
+
+/// Refuse credential files that other users can read or replace
+fn check_permissions(path: &Path) -> crate::Result<()> {
+    let metadata = std::fs::metadata(path)?;
+
+    if metadata.mode() & 0o077 != 0 {
+        return Err(crate::Error::PermissionDenied(format!(
+            "{} must not be accessible by group or others (mode {:o})",
+            path.display(),
+            metadata.mode() & 0o777
+        )));
+    }
+
+    // SAFETY: geteuid has no preconditions and cannot fail
+    let euid = unsafe { libc::geteuid() };
+    if metadata.uid() != 0 && metadata.uid() != euid {
+        return Err(crate::Error::PermissionDenied(format!(
+            "{} must be owned by root",
+            path.display()
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_auth_config() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("auth.toml");
+
+        assert!(AuthConfig::load_from_path(&path)
+            .unwrap()
+            .sources
+            .is_empty());
+
+        std::fs::write(
+            &path,
+            "[internal]\nusername = \"deploy\"\npassword = \"secret\"\n\n\
+             [partner]\ntoken = \"abc\"\n",
+        )
+        .unwrap();
+
+        // World-readable credentials are refused
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(AuthConfig::load_from_path(&path).is_err());
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        let auth = AuthConfig::load_from_path(&path).unwrap();
+        assert_eq!(
+            auth.get("internal"),
+            Some(&Credentials::Basic {
+                username: "deploy".to_string(),
+                password: "secret".to_string(),
+            })
+        );
+        assert_eq!(
+            auth.get("partner"),
+            Some(&Credentials::Bearer {
+                token: "abc".to_string()
+            })
+        );
+        assert!(!format!("{:?}", auth).contains("secret"));
+    }
+}
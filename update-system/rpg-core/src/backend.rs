@@ -0,0 +1,401 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Fetch backends
+//!
+//! Every download goes through a [`FetchBackend`]. [`HttpBackend`] talks to
+//! real repositories, [`LocalBackend`] serves a mirror directory, and
+//! [`MemoryBackend`] serves canned files so package operations can be
+//! tested without a network.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::timeout;
+
+use crate::fetch::{FetchError, FetchOptions, RepositoryIndex};
+
+/// A boxed future returned by backend methods
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A way of fetching repository files by URL
+pub trait FetchBackend: std::fmt::Debug + Send + Sync {
+    /// Fetch and parse a repository index
+    fn fetch_index<'a>(
+        &'a self,
+        url: &'a str,
+        options: &'a FetchOptions,
+    ) -> BoxFuture<'a, Result<RepositoryIndex, FetchError>> {
+        Box::pin(async move {
+            let bytes = self.fetch_range(url, 0, None, options).await?;
+            serde_json::from_slice(&bytes)
+                .map_err(|e| FetchError::HttpError(format!("Invalid index {}: {}", url, e)))
+        })
+    }
+
+    /// Fetch `len` bytes of a file starting at `offset`, or everything
+    /// after `offset` if `len` is `None`
+    fn fetch_range<'a>(
+        &'a self,
+        url: &'a str,
+        offset: u64,
+        len: Option<u64>,
+        options: &'a FetchOptions,
+    ) -> BoxFuture<'a, Result<Vec<u8>, FetchError>>;
+
+    /// Get the size of a file without fetching it
+    fn head<'a>(
+        &'a self,
+        url: &'a str,
+        options: &'a FetchOptions,
+    ) -> BoxFuture<'a, Result<u64, FetchError>>;
+}
+
+/// Cut a range out of a whole file
+fn slice_range(bytes: Vec<u8>, offset: u64, len: Option<u64>) -> Vec<u8> {
+    let start = (offset as usize).min(bytes.len());
+    let end = match len {
+        Some(len) => (start + len as usize).min(bytes.len()),
+        None => bytes.len(),
+    };
+
+    if start == 0 && end == bytes.len() {
+        bytes
+    } else {
+        bytes[start..end].to_vec()
+    }
+}
+
+/// Fetches over HTTP(S) with reqwest
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpBackend;
+
+impl HttpBackend {
+    /// Send a request and check its status
+    async fn send(
+        request: reqwest::RequestBuilder,
+        url: &str,
+        options: &FetchOptions,
+    ) -> Result<reqwest::Response, FetchError> {
+        let response = timeout(Duration::from_secs(options.timeout_secs), request.send())
+            .await
+            .map_err(|_| FetchError::Timeout(options.timeout_secs))?
+            .map_err(FetchError::from)?;
+
+        if response.status() == 404 {
+            return Err(FetchError::NotFound(url.to_string()));
+        }
+
+        if !response.status().is_success() {
+            return Err(FetchError::HttpError(format!(
+                "HTTP {}: {}",
+                response.status().as_u16(),
+                response.status().canonical_reason().unwrap_or("Unknown")
+            )));
+        }
+
+        Ok(response)
+    }
+}
+
+impl FetchBackend for HttpBackend {
+    fn fetch_index<'a>(
+        &'a self,
+        url: &'a str,
+        options: &'a FetchOptions,
+    ) -> BoxFuture<'a, Result<RepositoryIndex, FetchError>> {
+        Box::pin(async move {
+            let client = options.client()?;
+            let request = options.request(&client, reqwest::Method::GET, url);
+            let response = Self::send(request, url, options).await?;
+
+            response
+                .json()
+                .await
+                .map_err(|e| FetchError::HttpError(e.to_string()))
+        })
+    }
+
+    fn fetch_range<'a>(
+        &'a self,
+        url: &'a str,
+        offset: u64,
+        len: Option<u64>,
+        options: &'a FetchOptions,
+    ) -> BoxFuture<'a, Result<Vec<u8>, FetchError>> {
+        Box::pin(async move {
+            let client = options.client()?;
+            let mut request = options.request(&client, reqwest::Method::GET, url);
+
+            let ranged = offset > 0 || len.is_some();
+            if ranged {
+                let range = match len {
+                    Some(0) => return Ok(Vec::new()),
+                    Some(len) => format!("bytes={}-{}", offset, offset + len - 1),
+                    None => format!("bytes={}-", offset),
+                };
+                request = request.header(reqwest::header::RANGE, range);
+            }
+
+            let response = Self::send(request, url, options).await?;
+            let partial = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+
+            let bytes = timeout(Duration::from_secs(options.timeout_secs), response.bytes())
+                .await
+                .map_err(|_| FetchError::Timeout(options.timeout_secs))?
+                .map_err(|e| FetchError::HttpError(e.to_string()))?
+                .to_vec();
+
+            // Servers without range support send the whole file
+            if ranged && !partial {
+                Ok(slice_range(bytes, offset, len))
+            } else {
+                Ok(bytes)
+            }
+        })
+    }
+
+    fn head<'a>(
+        &'a self,
+        url: &'a str,
+        options: &'a FetchOptions,
+    ) -> BoxFuture<'a, Result<u64, FetchError>> {
+        Box::pin(async move {
+            let client = options.client()?;
+            let request = options.request(&client, reqwest::Method::HEAD, url);
+            let response = Self::send(request, url, options).await?;
+
+            response
+                .headers()
+                .get(reqwest::header::CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| FetchError::HttpError("Missing Content-Length header".to_string()))
+        })
+    }
+}
+
+/// Serves repository files from a local directory
+///
+/// `file://` URLs are read directly. Other URLs map to `<root>/<host>/<path>`,
+/// so a copy of a repository tree can stand in for its remote source.
+#[derive(Debug, Clone)]
+pub struct LocalBackend {
+    root: PathBuf,
+}
+
+impl LocalBackend {
+    /// Create a backend serving files under `root`
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Get the local path a URL maps to
+    pub fn path_for(&self, url: &str) -> Result<PathBuf, FetchError> {
+        if let Some(path) = url.strip_prefix("file://") {
+            return Ok(PathBuf::from(path));
+        }
+
+        let rest = url
+            .split_once("://")
+            .map(|(_, rest)| rest)
+            .ok_or_else(|| FetchError::InvalidUrl(url.to_string()))?;
+        let relative = Path::new(rest.split(['?', '#']).next().unwrap_or_default());
+
+        if relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+        {
+            return Err(FetchError::InvalidUrl(url.to_string()));
+        }
+
+        Ok(self.root.join(relative))
+    }
+}
+
+impl FetchBackend for LocalBackend {
+    fn fetch_range<'a>(
+        &'a self,
+        url: &'a str,
+        offset: u64,
+        len: Option<u64>,
+        _options: &'a FetchOptions,
+    ) -> BoxFuture<'a, Result<Vec<u8>, FetchError>> {
+        Box::pin(async move {
+            let path = self.path_for(url)?;
+            match tokio::fs::read(&path).await {
+                Ok(bytes) => Ok(slice_range(bytes, offset, len)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    Err(FetchError::NotFound(url.to_string()))
+                }
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+
+    fn head<'a>(
+        &'a self,
+        url: &'a str,
+        _options: &'a FetchOptions,
+    ) -> BoxFuture<'a, Result<u64, FetchError>> {
+        Box::pin(async move {
+            let path = self.path_for(url)?;
+            match tokio::fs::metadata(&path).await {
+                Ok(metadata) if metadata.is_file() => Ok(metadata.len()),
+                Ok(_) => Err(FetchError::NotFound(url.to_string())),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    Err(FetchError::NotFound(url.to_string()))
+                }
+                Err(e) => Err(e.into()),
+            }
+        })
+    }
+}
+
+/// Serves files kept in memory, recording every request
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    files: Mutex<HashMap<String, Vec<u8>>>,
+    requests: Mutex<Vec<String>>,
+}
+
+impl MemoryBackend {
+    /// Create an empty backend
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve a file at a URL
+    pub fn insert(&self, url: impl Into<String>, contents: impl Into<Vec<u8>>) {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(url.into(), contents.into());
+    }
+
+    /// Serve a repository index at a URL
+    pub fn insert_index(&self, url: impl Into<String>, index: &RepositoryIndex) {
+        let json = serde_json::to_vec(index).expect("index serializes to JSON");
+        self.insert(url, json);
+    }
+
+    /// Stop serving a URL
+    pub fn remove(&self, url: &str) -> bool {
+        self.files.lock().unwrap().remove(url).is_some()
+    }
+
+    /// Get the URLs requested so far, in order
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Look up a file, recording the request
+    fn get(&self, url: &str) -> Result<Vec<u8>, FetchError> {
+        self.requests.lock().unwrap().push(url.to_string());
+        self.files
+            .lock()
+            .unwrap()
+            .get(url)
+            .cloned()
+            .ok_or_else(|| FetchError::NotFound(url.to_string()))
+    }
+}
+
+impl FetchBackend for MemoryBackend {
+    fn fetch_range<'a>(
+        &'a self,
+        url: &'a str,
+        offset: u64,
+        len: Option<u64>,
+        _options: &'a FetchOptions,
+    ) -> BoxFuture<'a, Result<Vec<u8>, FetchError>> {
+        Box::pin(async move { Ok(slice_range(self.get(url)?, offset, len)) })
+    }
+
+    fn head<'a>(
+        &'a self,
+        url: &'a str,
+        _options: &'a FetchOptions,
+    ) -> BoxFuture<'a, Result<u64, FetchError>> {
+        Box::pin(async move { Ok(self.get(url)?.len() as u64) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_backend() {
+        let backend = MemoryBackend::new();
+        let options = FetchOptions::default();
+        backend.insert("http://repo/file", b"hello world".to_vec());
+
+        assert_eq!(
+            backend.head("http://repo/file", &options).await.unwrap(),
+            11
+        );
+        assert_eq!(
+            backend
+                .fetch_range("http://repo/file", 6, None, &options)
+                .await
+                .unwrap(),
+            b"world"
+        );
+        assert_eq!(
+            backend
+                .fetch_range("http://repo/file", 0, Some(5), &options)
+                .await
+                .unwrap(),
+            b"hello"
+        );
+        assert!(matches!(
+            backend
+                .fetch_range("http://repo/missing", 0, None, &options)
+                .await,
+            Err(FetchError::NotFound(_))
+        ));
+        assert_eq!(backend.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_local_backend() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let backend = LocalBackend::new(temp_dir.path());
+        let options = FetchOptions::default();
+
+        let dir = temp_dir.path().join("rustux.com/apps");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("index.json"),
+            r#"{"name":"apps","version":"1","packages":[]}"#,
+        )
+        .unwrap();
+
+        let index = backend
+            .fetch_index("https://rustux.com/apps/index.json", &options)
+            .await
+            .unwrap();
+        assert_eq!(index.name, "apps");
+
+        let file_url = format!("file://{}", dir.join("index.json").display());
+        assert!(backend.head(&file_url, &options).await.unwrap() > 0);
+
+        assert!(matches!(
+            backend
+                .head("https://rustux.com/apps/missing", &options)
+                .await,
+            Err(FetchError::NotFound(_))
+        ));
+        assert!(matches!(
+            backend.path_for("https://rustux.com/../etc/shadow"),
+            Err(FetchError::InvalidUrl(_))
+        ));
+    }
+}
@@ -0,0 +1,371 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Configuration files (conffiles)
+//!
+//! Packages list the files they ship under `/etc` in the manifest's
+//! `conffiles`. Each one is taken from the package tree, so
+//! `/etc/editor/editor.toml` ships as `files/etc/editor/editor.toml`, and
+//! copied into place when the package is installed. The registry keeps the
+//! hash of the packaged copy, which lets an upgrade tell whether the file
+//! was edited locally:
+//!
+//! - untouched files are replaced by the new version;
+//! - edited files are kept, and the new version is written next to them as
+//!   `<file>.rpgnew` for review with `rpg config diff` and `rpg config merge`.
+//!
+//! A file that already exists when a package first ships it is treated as
+//! edited and never becomes the package's, so purging the package leaves it
+//! in place. Neither does a package take over another package's file.
+
+use serde::{Deserialize, Serialize};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+
+/// Suffix of new config file versions waiting for review
+pub const NEW_SUFFIX: &str = "rpgnew";
+
+/// Directory config files must live under
+const CONFIG_PREFIX: &str = "/etc/";
+
+/// Lines of context around each change in a diff
+const DIFF_CONTEXT: usize = 3;
+
+/// A config file installed by a package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfFile {
+    /// Package that owns the file
+    pub package: String,
+    /// SHA-256 of the version the package shipped
+    pub sha256: String,
+}
+
+/// What happened to a config file during an install
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfFileAction {
+    /// The file did not exist and was created
+    Installed,
+    /// The file was unmodified and was replaced by the new version
+    Replaced,
+    /// The file already matches the new version
+    Unchanged,
+    /// The file was modified locally; the new version was saved as
+    /// `<file>.rpgnew`
+    Preserved,
+}
+
+/// How to resolve a pending config file change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeChoice {
+    /// Keep the local file and discard the new version
+    KeepLocal,
+    /// Replace the local file with the new version
+    UseNew,
+}
+
+/// A config file with a new version waiting for review
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingConfFile {
+    /// Path of the installed file
+    pub path: PathBuf,
+    /// Package that shipped the new version
+    pub package: String,
+}
+
+impl PendingConfFile {
+    /// Get the path of the new version
+    pub fn new_path(&self) -> PathBuf {
+        new_path(&self.path)
+    }
+
+    /// Diff the local file against the new version
+    pub fn diff(&self) -> crate::Result<String> {
+        let local = std::fs::read_to_string(&self.path)?;
+        let new = std::fs::read_to_string(self.new_path())?;
+        Ok(diff(
+            &local,
+            &new,
+            &self.path.display().to_string(),
+            &self.new_path().display().to_string(),
+        ))
+    }
+
+    /// Resolve the pending change
+    pub fn merge(&self, choice: MergeChoice) -> crate::Result<()> {
+        match choice {
+            MergeChoice::KeepLocal => std::fs::remove_file(self.new_path())?,
+            MergeChoice::UseNew => std::fs::rename(self.new_path(), &self.path)?,
+        }
+        Ok(())
+    }
+}
+
+/// Check that a manifest's config file path is an absolute path under `/etc`
+pub fn validate_path(path: &str) -> crate::Result<()> {
+    let valid = path.starts_with(CONFIG_PREFIX)
+        && Path::new(path)
+            .components()
+            .skip(1)
+            .all(|c| matches!(c, Component::Normal(_)));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(crate::Error::Other(format!(
+            "Invalid config file path: {} (must be under {})",
+            path, CONFIG_PREFIX
+        )))
+    }
+}
+
+/// Get the path a new version of a config file is saved to
+pub fn new_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(NEW_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Install a config file shipped by a package
+///
+/// `pristine` is the hash of the version previously shipped, if any.
+/// Returns what was done and the hash of the shipped file, which becomes
+/// the new pristine hash.
+pub fn install(
+    source: &Path,
+    dest: &Path,
+    pristine: Option<&str>,
+) -> crate::Result<(ConfFileAction, String)> {
+    let contents = std::fs::read(source)?;
+    let sha256 = crate::fetch::checksum_bytes(&contents);
+    let mode = std::fs::metadata(source)?.permissions().mode();
+
+    let action = if !dest.exists() {
+        ConfFileAction::Installed
+    } else {
+        let current = crate::fetch::checksum_bytes(&std::fs::read(dest)?);
+        if current == sha256 {
+            ConfFileAction::Unchanged
+        } else if pristine == Some(current.as_str()) {
+            ConfFileAction::Replaced
+        } else {
+            // Edited locally, or not installed by us
+            ConfFileAction::Preserved
+        }
+    };
+
+    match action {
+        ConfFileAction::Installed | ConfFileAction::Replaced => {
+            write_file(dest, &contents, mode)?;
+            remove_stale(dest)?;
+        }
+        ConfFileAction::Unchanged => remove_stale(dest)?,
+        ConfFileAction::Preserved => write_file(&new_path(dest), &contents, mode)?,
+    }
+
+    Ok((action, sha256))
+}
+
+/// Write a file atomically with the given mode
+fn write_file(path: &Path, contents: &[u8], mode: u32) -> crate::Result<()> {
+    crate::lock::write_atomic(path, contents)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o7777))?;
+    Ok(())
+}
+
+/// Remove a `.rpgnew` file the installed version has caught up with
+fn remove_stale(path: &Path) -> crate::Result<()> {
+    match std::fs::remove_file(new_path(path)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Produce a unified diff between two texts
+pub fn diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let ops = diff_ops(&old, &new);
+
+    let mut out = String::new();
+    if ops.iter().all(|op| matches!(op, DiffOp::Same(..))) {
+        return out;
+    }
+    out.push_str(&format!("--- {}\n+++ {}\n", old_label, new_label));
+
+    // Group changes into hunks with surrounding context
+    let mut i = 0;
+    while i < ops.len() {
+        if matches!(ops[i], DiffOp::Same(..)) {
+            i += 1;
+            continue;
+        }
+
+        let start = i.saturating_sub(DIFF_CONTEXT);
+        let mut end = i;
+        let mut same_run = 0;
+        while end < ops.len() && same_run <= 2 * DIFF_CONTEXT {
+            match ops[end] {
+                DiffOp::Same(..) => same_run += 1,
+                _ => same_run = 0,
+            }
+            end += 1;
+        }
+        let end = end - same_run.saturating_sub(DIFF_CONTEXT);
+
+        let hunk = &ops[start..end];
+        let (old_start, new_start) = hunk_start(&ops[..start]);
+        let old_len = hunk
+            .iter()
+            .filter(|op| !matches!(op, DiffOp::Insert(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|op| !matches!(op, DiffOp::Delete(_)))
+            .count();
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start + usize::from(old_len > 0),
+            old_len,
+            new_start + usize::from(new_len > 0),
+            new_len
+        ));
+
+        for op in hunk {
+            match op {
+                DiffOp::Same(line) => out.push_str(&format!(" {}\n", old[*line])),
+                DiffOp::Delete(line) => out.push_str(&format!("-{}\n", old[*line])),
+                DiffOp::Insert(line) => out.push_str(&format!("+{}\n", new[*line])),
+            }
+        }
+
+        i = end;
+    }
+
+    out
+}
+
+/// A line-level edit, indexing into the old or new text
+#[derive(Debug, Clone, Copy)]
+enum DiffOp {
+    Same(usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Count the old and new lines before a hunk
+fn hunk_start(before: &[DiffOp]) -> (usize, usize) {
+    let old = before
+        .iter()
+        .filter(|op| !matches!(op, DiffOp::Insert(_)))
+        .count();
+    let new = before
+        .iter()
+        .filter(|op| !matches!(op, DiffOp::Delete(_)))
+        .count();
+    (old, new)
+}
+
+/// Compute the edits turning `old` into `new` from their longest common
+/// subsequence
+///
+/// Config files are small, so the quadratic table is fine.
+fn diff_ops(old: &[&str], new: &[&str]) -> Vec<DiffOp> {
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            ops.push(DiffOp::Same(i));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            ops.push(DiffOp::Delete(i));
+            i += 1;
+        } else {
+            ops.push(DiffOp::Insert(j));
+            j += 1;
+        }
+    }
+    ops.extend((i..old.len()).map(DiffOp::Delete));
+    ops.extend((j..new.len()).map(DiffOp::Insert));
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conffile_install() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let source = temp_dir.path().join("shipped.toml");
+        let dest = temp_dir.path().join("etc/editor.toml");
+
+        std::fs::write(&source, "theme = \"light\"\n").unwrap();
+        let (action, v1) = install(&source, &dest, None).unwrap();
+        assert_eq!(action, ConfFileAction::Installed);
+
+        // Untouched files follow the package
+        std::fs::write(&source, "theme = \"light\"\nfont = 12\n").unwrap();
+        let (action, v2) = install(&source, &dest, Some(&v1)).unwrap();
+        assert_eq!(action, ConfFileAction::Replaced);
+        assert!(std::fs::read_to_string(&dest).unwrap().contains("font"));
+
+        // Local edits are kept and the new version waits for review
+        std::fs::write(&dest, "theme = \"dark\"\nfont = 12\n").unwrap();
+        std::fs::write(&source, "theme = \"light\"\nfont = 14\n").unwrap();
+        let (action, v3) = install(&source, &dest, Some(&v2)).unwrap();
+        assert_eq!(action, ConfFileAction::Preserved);
+        assert!(std::fs::read_to_string(&dest).unwrap().contains("dark"));
+
+        let pending = PendingConfFile {
+            path: dest.clone(),
+            package: "editor".to_string(),
+        };
+        assert!(pending.new_path().exists());
+        assert!(pending.diff().unwrap().contains("+font = 14"));
+
+        pending.merge(MergeChoice::UseNew).unwrap();
+        assert!(!pending.new_path().exists());
+        let (action, _) = install(&source, &dest, Some(&v3)).unwrap();
+        assert_eq!(action, ConfFileAction::Unchanged);
+    }
+
+    #[test]
+    fn test_validate_path() {
+        assert!(validate_path("/etc/editor/editor.toml").is_ok());
+        assert!(validate_path("/usr/share/editor.toml").is_err());
+        assert!(validate_path("etc/editor.toml").is_err());
+        assert!(validate_path("/etc/../root/.ssh/authorized_keys").is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+
+        let diff = diff(old, new, "old", "new");
+        assert_eq!(
+            diff,
+            "--- old\n+++ new\n\
+             @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+             @@ -8,3 +8,4 @@\n h\n i\n j\n+k\n"
+        );
+
+        assert!(super::diff(old, old, "old", "new").is_empty());
+    }
+}
@@ -1,255 +1,410 @@
-// Copyright 2025 The Rustux Authors
-//
-// Use of this source code is governed by a MIT-style
-// license that can be found in the LICENSE file or at
-// https://opensource.org/licenses/MIT
-
-//! Configuration management for the package manager
-
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-
-/// Main configuration for the package manager
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Config {
-    /// Repository URLs
-    pub repositories: Vec<String>,
-
-    /// Whether to enable automatic background updates
-    pub auto_updates_enabled: bool,
-
-    /// Update check interval in seconds
-    pub update_check_interval: u64,
-
-    /// Maximum bandwidth for updates (bytes/sec, 0 = unlimited)
-    pub max_bandwidth: u64,
-
-    /// Whether to verify package signatures
-    pub verify_signatures: bool,
-
-    /// Embedded public key for signature verification (base64)
-    pub trust_key: Option<String>,
-
-    /// Cache directory
-    pub cache_dir: PathBuf,
-
-    /// Metadata directory
-    pub metadata_dir: PathBuf,
-
-    /// State directory
-    pub state_dir: PathBuf,
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            repositories: vec!["https://repo.rustica.os".to_string()],
-            auto_updates_enabled: false,
-            update_check_interval: 86400, // 24 hours
-            max_bandwidth: 0,
-            verify_signatures: true,
-            trust_key: None,
-            cache_dir: PathBuf::from("/var/cache/rpg"),
-            metadata_dir: PathBuf::from("/var/lib/rpg"),
-            state_dir: PathBuf::from("/var/run/rpg"),
-        }
-    }
-}
-
-impl Config {
-    /// Load configuration from the default path
-    pub fn load() -> crate::Result<Self> {
-        let config_path = "/etc/rpg/config.json";
-        Self::load_from_path(config_path)
-    }
-
-    /// Load configuration from a specific path
-    pub fn load_from_path(path: &str) -> crate::Result<Self> {
-        let content = std::fs::read_to_string(path).map_err(|_| {
-            crate::Error::Other(format!("Configuration file not found: {}", path))
-        })?;
-
-        serde_json::from_str(&content)
-            .map_err(|e| crate::Error::Serialization(e.to_string()))
-    }
-
-    /// Save configuration to the default path
-    pub fn save(&self) -> crate::Result<()> {
-        let config_path = "/etc/rpg/config.json";
-
-        // Ensure directory exists
-        if let Some(parent) = PathBuf::from(config_path).parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-
-        let content = serde_json::to_string_pretty(self)
-            .map_err(|e| crate::Error::Serialization(e.to_string()))?;
-
-        std::fs::write(config_path, content)?;
-        Ok(())
-    }
-
-    /// Get the repository URLs
-    pub fn repositories(&self) -> &[String] {
-        &self.repositories
-    }
-
-    /// Add a repository
-    pub fn add_repository(&mut self, url: String) {
-        if !self.repositories.contains(&url) {
-            self.repositories.push(url);
-        }
-    }
-
-    /// Remove a repository
-    pub fn remove_repository(&mut self, url: &str) {
-        self.repositories.retain(|r| r != url);
-    }
-
-    /// Check if signature verification is enabled
-    pub fn verify_signatures_enabled(&self) -> bool {
-        self.verify_signatures
-    }
-}
-
-/// Update-specific configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct UpdateConfig {
-    /// Whether to enable live background updates
-    pub live_updates_enabled: bool,
-
-    /// Whether to pause updates during high system load
-    pub pause_on_high_load: bool,
-
-    /// Maximum CPU usage percentage to allow updates
-    pub max_cpu_usage: u8,
-
-    /// Whether to notify user before installing updates
-    pub notify_before_install: bool,
-
-    /// Preferred update time (for scheduled updates)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub preferred_time: Option<String>,
-
-    /// Whether to auto-apply updates that don't require reboot
-    pub auto_apply_non_kernel: bool,
-}
-
-impl Default for UpdateConfig {
-    fn default() -> Self {
-        Self {
-            live_updates_enabled: true,
-            pause_on_high_load: true,
-            max_cpu_usage: 50,
-            notify_before_install: false,
-            preferred_time: None,
-            auto_apply_non_kernel: true,
-        }
-    }
-}
-
-impl UpdateConfig {
-    /// Load update configuration
-    pub fn load() -> crate::Result<Self> {
-        let config_path = "/etc/rpg/update-config.json";
-        let path = PathBuf::from(config_path);
-
-        if path.exists() {
-            let content = std::fs::read_to_string(config_path)?;
-            serde_json::from_str(&content)
-                .map_err(|e| crate::Error::Serialization(e.to_string()))
-        } else {
-            Ok(Self::default())
-        }
-    }
-
-    /// Save update configuration
-    pub fn save(&self) -> crate::Result<()> {
-        let config_path = "/etc/rpg/update-config.json";
-
-        // Ensure directory exists
-        if let Some(parent) = PathBuf::from(config_path).parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-
-        let content = serde_json::to_string_pretty(self)
-            .map_err(|e| crate::Error::Serialization(e.to_string()))?;
-
-        std::fs::write(config_path, content)?;
-        Ok(())
-    }
-
-    /// Check if live updates are enabled
-    pub fn live_updates_enabled(&self) -> bool {
-        self.live_updates_enabled
-    }
-
-    /// Check if updates should pause based on CPU usage
-    pub fn should_pause(&self, current_cpu_usage: u8) -> bool {
-        self.pause_on_high_load && current_cpu_usage > self.max_cpu_usage
-    }
-}
-
-/// User preferences
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct UserPreferences {
-    /// Whether the user has opted in to live updates
-    pub live_updates_opt_in: bool,
-
-    /// Whether to show update notifications
-    pub show_notifications: bool,
-
-    /// Maximum bandwidth to use for updates
-    pub max_bandwidth_mbps: Option<u32>,
-
-    /// Whether to download only on WiFi (if applicable)
-    pub wifi_only: bool,
-}
-
-impl Default for UserPreferences {
-    fn default() -> Self {
-        Self {
-            live_updates_opt_in: true,
-            show_notifications: true,
-            max_bandwidth_mbps: None,
-            wifi_only: false,
-        }
-    }
-}
-
-impl UserPreferences {
-    /// Load user preferences
-    pub fn load() -> crate::Result<Self> {
-        let config_path = "/etc/rpg/user-prefs.json";
-        let path = PathBuf::from(config_path);
-
-        if path.exists() {
-            let content = std::fs::read_to_string(config_path)?;
-            serde_json::from_str(&content)
-                .map_err(|e| crate::Error::Serialization(e.to_string()))
-        } else {
-            Ok(Self::default())
-        }
-    }
-
-    /// Save user preferences
-    pub fn save(&self) -> crate::Result<()> {
-        let config_path = "/etc/rpg/user-prefs.json";
-
-        // Ensure directory exists
-        if let Some(parent) = PathBuf::from(config_path).parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-
-        let content = serde_json::to_string_pretty(self)
-            .map_err(|e| crate::Error::Serialization(e.to_string()))?;
-
-        std::fs::write(config_path, content)?;
-        Ok(())
-    }
-
-    /// Check if user has opted in to live updates
-    pub fn live_updates_enabled(&self) -> bool {
-        self.live_updates_opt_in
-    }
-}
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Configuration management for the package manager
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::version::{Version, VersionConstraint};
+
+/// Default pins file path
+pub const PINS_PATH: &str = "/etc/rpg/pins.json";
+
+/// Main configuration for the package manager
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// Repository URLs
+    pub repositories: Vec<String>,
+
+    /// Whether to enable automatic background updates
+    pub auto_updates_enabled: bool,
+
+    /// Update check interval in seconds
+    pub update_check_interval: u64,
+
+    /// Maximum bandwidth for updates (bytes/sec, 0 = unlimited)
+    pub max_bandwidth: u64,
+
+    /// Whether to verify package signatures
+    pub verify_signatures: bool,
+
+    /// Embedded public key for signature verification (base64)
+    pub trust_key: Option<String>,
+
+    /// Cache directory
+    pub cache_dir: PathBuf,
+
+    /// Metadata directory
+    pub metadata_dir: PathBuf,
+
+    /// State directory
+    pub state_dir: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            repositories: vec!["https://repo.rustica.os".to_string()],
+            auto_updates_enabled: false,
+            update_check_interval: 86400, // 24 hours
+            max_bandwidth: 0,
+            verify_signatures: true,
+            trust_key: None,
+            cache_dir: PathBuf::from("/var/cache/rpg"),
+            metadata_dir: PathBuf::from("/var/lib/rpg"),
+            state_dir: PathBuf::from("/var/run/rpg"),
+        }
+    }
+}
+
+impl Config {
+    /// Load configuration from the default path
+    pub fn load() -> crate::Result<Self> {
+        let config_path = "/etc/rpg/config.json";
+        Self::load_from_path(config_path)
+    }
+
+    /// Load configuration from a specific path
+    pub fn load_from_path(path: &str) -> crate::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|_| crate::Error::Other(format!("Configuration file not found: {}", path)))?;
+
+        serde_json::from_str(&content).map_err(|e| crate::Error::Serialization(e.to_string()))
+    }
+
+    /// Save configuration to the default path
+    pub fn save(&self) -> crate::Result<()> {
+        let config_path = "/etc/rpg/config.json";
+
+        // Ensure directory exists
+        if let Some(parent) = PathBuf::from(config_path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| crate::Error::Serialization(e.to_string()))?;
+
+        std::fs::write(config_path, content)?;
+        Ok(())
+    }
+
+    /// Get the repository URLs
+    pub fn repositories(&self) -> &[String] {
+        &self.repositories
+    }
+
+    /// Add a repository
+    pub fn add_repository(&mut self, url: String) {
+        if !self.repositories.contains(&url) {
+            self.repositories.push(url);
+        }
+    }
+
+    /// Remove a repository
+    pub fn remove_repository(&mut self, url: &str) {
+        self.repositories.retain(|r| r != url);
+    }
+
+    /// Check if signature verification is enabled
+    pub fn verify_signatures_enabled(&self) -> bool {
+        self.verify_signatures
+    }
+}
+
+/// Update-specific configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateConfig {
+    /// Whether to enable live background updates
+    pub live_updates_enabled: bool,
+
+    /// Whether to pause updates during high system load
+    pub pause_on_high_load: bool,
+
+    /// Maximum CPU usage percentage to allow updates
+    pub max_cpu_usage: u8,
+
+    /// Whether to notify user before installing updates
+    pub notify_before_install: bool,
+
+    /// Preferred update time (for scheduled updates)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_time: Option<String>,
+
+    /// Whether to auto-apply updates that don't require reboot
+    pub auto_apply_non_kernel: bool,
+
+    /// Whether to install security fixes as soon as they are found,
+    /// without waiting for the preferred update time
+    #[serde(default = "default_security_updates_immediately")]
+    pub security_updates_immediately: bool,
+}
+
+fn default_security_updates_immediately() -> bool {
+    true
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            live_updates_enabled: true,
+            pause_on_high_load: true,
+            max_cpu_usage: 50,
+            notify_before_install: false,
+            preferred_time: None,
+            auto_apply_non_kernel: true,
+            security_updates_immediately: true,
+        }
+    }
+}
+
+impl UpdateConfig {
+    /// Load update configuration
+    pub fn load() -> crate::Result<Self> {
+        let config_path = "/etc/rpg/update-config.json";
+        let path = PathBuf::from(config_path);
+
+        if path.exists() {
+            let content = std::fs::read_to_string(config_path)?;
+            serde_json::from_str(&content).map_err(|e| crate::Error::Serialization(e.to_string()))
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Save update configuration
+    pub fn save(&self) -> crate::Result<()> {
+        let config_path = "/etc/rpg/update-config.json";
+
+        // Ensure directory exists
+        if let Some(parent) = PathBuf::from(config_path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| crate::Error::Serialization(e.to_string()))?;
+
+        std::fs::write(config_path, content)?;
+        Ok(())
+    }
+
+    /// Check if live updates are enabled
+    pub fn live_updates_enabled(&self) -> bool {
+        self.live_updates_enabled
+    }
+
+    /// Check if updates should pause based on CPU usage
+    pub fn should_pause(&self, current_cpu_usage: u8) -> bool {
+        self.pause_on_high_load && current_cpu_usage > self.max_cpu_usage
+    }
+}
+
+/// User preferences
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserPreferences {
+    /// Whether the user has opted in to live updates
+    pub live_updates_opt_in: bool,
+
+    /// Whether to show update notifications
+    pub show_notifications: bool,
+
+    /// Maximum bandwidth to use for updates
+    pub max_bandwidth_mbps: Option<u32>,
+
+    /// Whether to download only on WiFi (if applicable)
+    pub wifi_only: bool,
+}
+
+impl Default for UserPreferences {
+    fn default() -> Self {
+        Self {
+            live_updates_opt_in: true,
+            show_notifications: true,
+            max_bandwidth_mbps: None,
+            wifi_only: false,
+        }
+    }
+}
+
+impl UserPreferences {
+    /// Load user preferences
+    pub fn load() -> crate::Result<Self> {
+        let config_path = "/etc/rpg/user-prefs.json";
+        let path = PathBuf::from(config_path);
+
+        if path.exists() {
+            let content = std::fs::read_to_string(config_path)?;
+            serde_json::from_str(&content).map_err(|e| crate::Error::Serialization(e.to_string()))
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Save user preferences
+    pub fn save(&self) -> crate::Result<()> {
+        let config_path = "/etc/rpg/user-prefs.json";
+
+        // Ensure directory exists
+        if let Some(parent) = PathBuf::from(config_path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| crate::Error::Serialization(e.to_string()))?;
+
+        std::fs::write(config_path, content)?;
+        Ok(())
+    }
+
+    /// Check if user has opted in to live updates
+    pub fn live_updates_enabled(&self) -> bool {
+        self.live_updates_opt_in
+    }
+}
+
+/// A restriction placed on how a package may be updated
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Pin {
+    /// Keep the package at its installed version
+    Hold,
+    /// Only allow versions satisfying the constraint
+    Version {
+        /// The version constraint (e.g., "~1.4")
+        constraint: VersionConstraint,
+    },
+}
+
+impl Pin {
+    /// Check if this pin allows moving to the given version
+    pub fn allows(&self, version: &Version) -> bool {
+        match self {
+            Self::Hold => false,
+            Self::Version { constraint } => constraint.satisfies(version),
+        }
+    }
+}
+
+impl std::fmt::Display for Pin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Hold => write!(f, "hold"),
+            Self::Version { constraint } => write!(f, "pinned {}", constraint.requirement),
+        }
+    }
+}
+
+/// Package pins and holds
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PinConfig {
+    /// Pinned packages (name -> pin)
+    #[serde(default)]
+    pub pins: BTreeMap<String, Pin>,
+}
+
+impl PinConfig {
+    /// Load pins from the default path
+    pub fn load() -> crate::Result<Self> {
+        Self::load_from_path(PINS_PATH)
+    }
+
+    /// Load pins from a specific path
+    pub fn load_from_path(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+
+        if path.exists() {
+            let content = std::fs::read_to_string(path)?;
+            serde_json::from_str(&content).map_err(|e| crate::Error::Serialization(e.to_string()))
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Save pins to the default path
+    pub fn save(&self) -> crate::Result<()> {
+        self.save_to_path(PINS_PATH)
+    }
+
+    /// Save pins to a specific path
+    pub fn save_to_path(&self, path: impl AsRef<Path>) -> crate::Result<()> {
+        let path = path.as_ref();
+
+        // Ensure directory exists
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| crate::Error::Serialization(e.to_string()))?;
+
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Pin a package to a version constraint
+    pub fn pin(&mut self, name: &str, constraint: VersionConstraint) {
+        self.pins
+            .insert(name.to_string(), Pin::Version { constraint });
+    }
+
+    /// Hold a package at its installed version
+    pub fn hold(&mut self, name: &str) {
+        self.pins.insert(name.to_string(), Pin::Hold);
+    }
+
+    /// Remove any pin or hold from a package
+    pub fn unpin(&mut self, name: &str) -> bool {
+        self.pins.remove(name).is_some()
+    }
+
+    /// Get the pin for a package
+    pub fn get(&self, name: &str) -> Option<&Pin> {
+        self.pins.get(name)
+    }
+
+    /// Check if a package may be moved to the given version
+    pub fn allows(&self, name: &str, version: &Version) -> bool {
+        self.pins
+            .get(name)
+            .map(|pin| pin.allows(version))
+            .unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin_allows() {
+        let mut pins = PinConfig::default();
+        pins.pin("editor", VersionConstraint::new("~1.4").unwrap());
+        pins.hold("kernel");
+
+        assert!(pins.allows("editor", &Version::new(1, 4, 9)));
+        assert!(!pins.allows("editor", &Version::new(1, 5, 0)));
+        assert!(!pins.allows("kernel", &Version::new(9, 9, 9)));
+        assert!(pins.allows("other", &Version::new(2, 0, 0)));
+
+        assert!(pins.unpin("kernel"));
+        assert!(pins.allows("kernel", &Version::new(9, 9, 9)));
+    }
+
+    #[test]
+    fn test_pins_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("pins.json");
+
+        let mut pins = PinConfig::default();
+        pins.pin("editor", VersionConstraint::new("^2.0").unwrap());
+        pins.hold("kernel");
+        pins.save_to_path(&path).unwrap();
+
+        let loaded = PinConfig::load_from_path(&path).unwrap();
+        assert_eq!(loaded.pins, pins.pins);
+    }
+}
@@ -1,386 +1,667 @@
-// Copyright 2025 The Rustux Authors
-//
-// Use of this source code is governed by a MIT-style
-// license that can be found in the LICENSE file or at
-// https://opensource.org/licenses/MIT
-
-//! Filesystem layout for versioned packages
-//!
-//! This module defines the directory structure for storing versioned
-//! packages and systems.
-
-use std::path::{Path, PathBuf};
-
-/// Base system directory
-pub const SYSTEM_BASE: &str = "/system";
-
-/// Apps directory
-pub const APPS_BASE: &str = "/apps";
-
-/// Current system symlink
-pub const SYSTEM_CURRENT: &str = "/system/current";
-
-/// System versions directory
-pub const SYSTEM_VERSIONS: &str = "/system";
-
-/// Cache directory for downloads
-pub const CACHE_DIR: &str = "/var/cache/rpg";
-
-/// Metadata directory
-pub const META_DIR: &str = "/var/lib/rpg";
-
-/// State directory
-pub const STATE_DIR: &str = "/var/run/rpg";
-
-/// Configuration directory
-pub const CONFIG_DIR: &str = "/etc/rpg";
-
-/// System layout definition
-#[derive(Debug, Clone)]
-pub struct SystemLayout {
-    /// Base path for system versions
-    pub base: PathBuf,
-}
-
-impl SystemLayout {
-    /// Create a new system layout
-    pub fn new() -> Self {
-        Self {
-            base: PathBuf::from(SYSTEM_BASE),
-        }
-    }
-
-    /// Get the path to a specific version
-    pub fn version_path(&self, version: &str) -> PathBuf {
-        self.base.join(format!("v{}", version))
-    }
-
-    /// Get the current symlink path
-    pub fn current_path(&self) -> PathBuf {
-        PathBuf::from(SYSTEM_CURRENT)
-    }
-
-    /// Get the boot directory for a version
-    pub fn boot_path(&self, version: &str) -> PathBuf {
-        self.version_path(version).join("boot")
-    }
-
-    /// Get the kernel path for a version
-    pub fn kernel_path(&self, version: &str) -> PathBuf {
-        self.boot_path(version).join("kernel")
-    }
-
-    /// Get the initrd path for a version
-    pub fn initrd_path(&self, version: &str) -> PathBuf {
-        self.boot_path(version).join("initrd")
-    }
-
-    /// Get the userland binaries path for a version
-    pub fn bin_path(&self, version: &str) -> PathBuf {
-        self.version_path(version).join("bin")
-    }
-
-    /// Get the libraries path for a version
-    pub fn lib_path(&self, version: &str) -> PathBuf {
-        self.version_path(version).join("lib")
-    }
-
-    /// Get the metadata path for a version
-    pub fn metadata_path(&self, version: &str) -> PathBuf {
-        self.version_path(version).join("metadata.json")
-    }
-
-    /// List all installed versions
-    pub fn list_versions(&self) -> crate::Result<Vec<String>> {
-        let mut versions = Vec::new();
-
-        if !self.base.exists() {
-            return Ok(versions);
-        }
-
-        for entry in std::fs::read_dir(&self.base)? {
-            let entry = entry?;
-            let name = entry.file_name();
-            let name_str = name.to_string_lossy();
-
-            // Only count directories starting with 'v'
-            if entry.file_type()?.is_dir() && name_str.starts_with('v') {
-                versions.push(name_str[1..].to_string());
-            }
-        }
-
-        versions.sort();
-        Ok(versions)
-    }
-
-    /// Get the currently active version
-    pub fn current_version(&self) -> crate::Result<Option<String>> {
-        let current = self.current_path();
-
-        if !current.exists() {
-            return Ok(None);
-        }
-
-        let target = current.read_link()?;
-        let version_str = target
-            .file_name()
-            .and_then(|s| s.to_str())
-            .ok_or_else(|| crate::Error::Layout("Invalid current symlink".to_string()))?;
-
-        // Remove 'v' prefix if present
-        let version = if version_str.starts_with('v') {
-            version_str[1..].to_string()
-        } else {
-            version_str.to_string()
-        };
-
-        Ok(Some(version))
-    }
-
-    /// Check if a version exists
-    pub fn version_exists(&self, version: &str) -> bool {
-        self.version_path(version).exists()
-    }
-}
-
-impl Default for SystemLayout {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-/// Application layout definition
-#[derive(Debug, Clone)]
-pub struct AppLayout {
-    /// Base path for applications
-    pub base: PathBuf,
-}
-
-impl AppLayout {
-    /// Create a new app layout
-    pub fn new() -> Self {
-        Self {
-            base: PathBuf::from(APPS_BASE),
-        }
-    }
-
-    /// Get the path to an app directory
-    pub fn app_path(&self, app_name: &str) -> PathBuf {
-        self.base.join(app_name)
-    }
-
-    /// Get the path to a specific version of an app
-    pub fn version_path(&self, app_name: &str, version: &str) -> PathBuf {
-        self.app_path(app_name).join(version)
-    }
-
-    /// Get the current symlink path for an app
-    pub fn current_path(&self, app_name: &str) -> PathBuf {
-        self.app_path(app_name).join("current")
-    }
-
-    /// Get the metadata path for an app version
-    pub fn metadata_path(&self, app_name: &str, version: &str) -> PathBuf {
-        self.version_path(app_name, version).join("metadata.json")
-    }
-
-    /// Get the executable path for an app
-    pub fn executable_path(&self, app_name: &str) -> PathBuf {
-        self.current_path(app_name).join(app_name)
-    }
-
-    /// List all installed apps
-    pub fn list_apps(&self) -> crate::Result<Vec<String>> {
-        let mut apps = Vec::new();
-
-        if !self.base.exists() {
-            return Ok(apps);
-        }
-
-        for entry in std::fs::read_dir(&self.base)? {
-            let entry = entry?;
-            if entry.file_type()?.is_dir() {
-                if let Some(name) = entry.file_name().to_str() {
-                    apps.push(name.to_string());
-                }
-            }
-        }
-
-        apps.sort();
-        Ok(apps)
-    }
-
-    /// List versions of an app
-    pub fn list_versions(&self, app_name: &str) -> crate::Result<Vec<String>> {
-        let mut versions = Vec::new();
-        let app_path = self.app_path(app_name);
-
-        if !app_path.exists() {
-            return Ok(versions);
-        }
-
-        for entry in std::fs::read_dir(&app_path)? {
-            let entry = entry?;
-            let name = entry.file_name();
-            let name_str = name.to_string_lossy();
-
-            // Skip the current symlink
-            if name_str == "current" {
-                continue;
-            }
-
-            if entry.file_type()?.is_dir() {
-                versions.push(name_str.to_string());
-            }
-        }
-
-        versions.sort();
-        Ok(versions)
-    }
-
-    /// Get the currently active version of an app
-    pub fn current_version(&self, app_name: &str) -> crate::Result<Option<String>> {
-        let current = self.current_path(app_name);
-
-        if !current.exists() {
-            return Ok(None);
-        }
-
-        let target = current.read_link()?;
-        let version = target
-            .file_name()
-            .and_then(|s| s.to_str())
-            .ok_or_else(|| crate::Error::Layout("Invalid current symlink".to_string()))?;
-
-        Ok(Some(version.to_string()))
-    }
-
-    /// Check if an app exists
-    pub fn app_exists(&self, app_name: &str) -> bool {
-        self.app_path(app_name).exists()
-    }
-
-    /// Check if a specific version of an app exists
-    pub fn version_exists(&self, app_name: &str, version: &str) -> bool {
-        self.version_path(app_name, version).exists()
-    }
-}
-
-impl Default for AppLayout {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-/// Layout manager for managing system and app layouts
-#[derive(Debug, Clone)]
-pub struct LayoutManager {
-    /// System layout
-    pub system: SystemLayout,
-    /// App layout
-    pub apps: AppLayout,
-}
-
-impl LayoutManager {
-    /// Create a new layout manager
-    pub fn new() -> Self {
-        Self {
-            system: SystemLayout::new(),
-            apps: AppLayout::new(),
-        }
-    }
-
-    /// Initialize the layout directories
-    pub fn initialize(&self) -> crate::Result<()> {
-        // Create base directories
-        std::fs::create_dir_all(&self.system.base)?;
-        std::fs::create_dir_all(APPS_BASE)?;
-        std::fs::create_dir_all(CACHE_DIR)?;
-        std::fs::create_dir_all(META_DIR)?;
-        std::fs::create_dir_all(STATE_DIR)?;
-        std::fs::create_dir_all(CONFIG_DIR)?;
-
-        Ok(())
-    }
-
-    /// Get layout statistics
-    pub fn stats(&self) -> crate::Result<LayoutStats> {
-        Ok(LayoutStats {
-            system_versions: self.system.list_versions()?.len(),
-            installed_apps: self.apps.list_apps()?.len(),
-            cache_size: Self::dir_size(CACHE_DIR)?,
-            metadata_size: Self::dir_size(META_DIR)?,
-        })
-    }
-
-    /// Get the size of a directory
-    fn dir_size(path: &str) -> crate::Result<u64> {
-        let path = Path::new(path);
-        if !path.exists() {
-            return Ok(0);
-        }
-
-        let mut total = 0;
-        let mut stack = vec![path.to_path_buf()];
-
-        while let Some(current) = stack.pop() {
-            for entry in std::fs::read_dir(current)? {
-                let entry = entry?;
-                let ty = entry.file_type()?;
-
-                if ty.is_file() {
-                    total += entry.metadata()?.len();
-                } else if ty.is_dir() {
-                    stack.push(entry.path());
-                }
-            }
-        }
-
-        Ok(total)
-    }
-}
-
-impl Default for LayoutManager {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-/// Layout statistics
-#[derive(Debug, Clone)]
-pub struct LayoutStats {
-    /// Number of system versions installed
-    pub system_versions: usize,
-    /// Number of apps installed
-    pub installed_apps: usize,
-    /// Size of cache directory in bytes
-    pub cache_size: u64,
-    /// Size of metadata directory in bytes
-    pub metadata_size: u64,
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_system_layout_paths() {
-        let layout = SystemLayout::new();
-        assert_eq!(layout.version_path("1.0.0"), PathBuf::from("/system/v1.0.0"));
-        assert_eq!(layout.current_path(), PathBuf::from("/system/current"));
-    }
-
-    #[test]
-    fn test_app_layout_paths() {
-        let layout = AppLayout::new();
-        assert_eq!(layout.app_path("test"), PathBuf::from("/apps/test"));
-        assert_eq!(
-            layout.version_path("test", "1.0.0"),
-            PathBuf::from("/apps/test/1.0.0")
-        );
-        assert_eq!(
-            layout.current_path("test"),
-            PathBuf::from("/apps/test/current")
-        );
-    }
-}
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Filesystem layout for versioned packages
+//!
+//! This module defines the directory structure for storing versioned
+//! packages and systems.
+
+use std::path::{Path, PathBuf};
+
+/// Base system directory
+pub const SYSTEM_BASE: &str = "/system";
+
+/// Apps directory
+pub const APPS_BASE: &str = "/apps";
+
+/// App configuration directory
+pub const APPS_CONFIG_BASE: &str = "/etc/apps";
+
+/// App data directory
+pub const APPS_DATA_BASE: &str = "/var/lib/apps";
+
+/// Current system symlink
+pub const SYSTEM_CURRENT: &str = "/system/current";
+
+/// System versions directory
+pub const SYSTEM_VERSIONS: &str = "/system";
+
+/// Cache directory for downloads
+pub const CACHE_DIR: &str = "/var/cache/rpg";
+
+/// Metadata directory
+pub const META_DIR: &str = "/var/lib/rpg";
+
+/// State directory
+pub const STATE_DIR: &str = "/var/run/rpg";
+
+/// Configuration directory
+pub const CONFIG_DIR: &str = "/etc/rpg";
+
+/// Per-user data directory, relative to `$XDG_DATA_HOME`
+pub const USER_DATA_DIR: &str = "rustica";
+
+/// Per-user executables directory, relative to the home directory
+pub const USER_BIN_DIR: &str = ".local/bin";
+
+/// Where packages are installed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Scope {
+    /// System-wide under `/apps` and `/system` (requires root)
+    #[default]
+    System,
+    /// Apps for the current user under `~/.local/share/rustica/apps`
+    User,
+}
+
+impl Scope {
+    /// Get the app layout of this scope
+    pub fn app_layout(&self) -> crate::Result<AppLayout> {
+        match self {
+            Self::System => Ok(AppLayout::new()),
+            Self::User => Ok(AppLayout::with_base(user_data_dir()?.join("apps"))
+                .with_bin_dir(home_dir()?.join(USER_BIN_DIR))
+                .with_state_dirs(
+                    user_config_dir()?.join("apps"),
+                    user_data_dir()?.join("data"),
+                )),
+        }
+    }
+
+    /// Get the registry file path of this scope
+    pub fn registry_path(&self) -> crate::Result<PathBuf> {
+        Ok(self.meta_dir()?.join("registry.json"))
+    }
+
+    /// Get the pins file path of this scope
+    pub fn pins_path(&self) -> crate::Result<PathBuf> {
+        match self {
+            Self::System => Ok(PathBuf::from(crate::config::PINS_PATH)),
+            Self::User => Ok(self.meta_dir()?.join("pins.json")),
+        }
+    }
+
+    /// Get the registry lock path of this scope
+    pub fn lock_path(&self) -> crate::Result<PathBuf> {
+        match self {
+            Self::System => Ok(crate::lock::RegistryLock::default_path()),
+            Self::User => Ok(self.meta_dir()?.join(crate::lock::LOCK_FILE)),
+        }
+    }
+
+    /// Get the directory package config files are installed relative to
+    ///
+    /// System packages write `/etc/...` as is. Per-user apps get their own
+    /// copy below the user's configuration directory.
+    pub fn conffile_root(&self) -> crate::Result<PathBuf> {
+        match self {
+            Self::System => Ok(PathBuf::from("/")),
+            Self::User => user_config_dir(),
+        }
+    }
+
+    /// Get the download cache directory of this scope
+    pub fn cache_dir(&self) -> crate::Result<PathBuf> {
+        match self {
+            Self::System => Ok(PathBuf::from(CACHE_DIR)),
+            Self::User => {
+                let cache_home = match std::env::var_os("XDG_CACHE_HOME") {
+                    Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+                    _ => home_dir()?.join(".cache"),
+                };
+                Ok(cache_home.join(USER_DATA_DIR).join("rpg"))
+            }
+        }
+    }
+
+    /// Get the directory update state is published in
+    ///
+    /// See [`crate::state`]. The system scope uses `STATE_DIR`, which is
+    /// cleared on boot; the user scope keeps it with its metadata.
+    pub fn state_dir(&self) -> crate::Result<PathBuf> {
+        match self {
+            Self::System => Ok(PathBuf::from(STATE_DIR)),
+            Self::User => self.meta_dir(),
+        }
+    }
+
+    /// Get the package metadata directory of this scope
+    fn meta_dir(&self) -> crate::Result<PathBuf> {
+        match self {
+            Self::System => Ok(PathBuf::from(META_DIR)),
+            Self::User => Ok(user_data_dir()?.join("rpg")),
+        }
+    }
+
+    /// Check if packages of a kind can be installed in this scope
+    pub fn allows_kind(&self, kind: crate::package::PackageKind) -> bool {
+        match self {
+            Self::System => true,
+            Self::User => kind.is_app(),
+        }
+    }
+}
+
+/// Get the current user's home directory
+fn home_dir() -> crate::Result<PathBuf> {
+    match std::env::var_os("HOME") {
+        Some(home) if !home.is_empty() => Ok(PathBuf::from(home)),
+        _ => Err(crate::Error::Layout(
+            "HOME is not set, cannot locate the user scope".to_string(),
+        )),
+    }
+}
+
+/// Get the current user's Rustica data directory
+fn user_data_dir() -> crate::Result<PathBuf> {
+    let data_home = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir()?.join(".local/share"),
+    };
+    Ok(data_home.join(USER_DATA_DIR))
+}
+
+/// Get the current user's Rustica configuration directory
+fn user_config_dir() -> crate::Result<PathBuf> {
+    let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir()?.join(".config"),
+    };
+    Ok(config_home.join(USER_DATA_DIR))
+}
+
+/// System layout definition
+#[derive(Debug, Clone)]
+pub struct SystemLayout {
+    /// Base path for system versions
+    pub base: PathBuf,
+}
+
+impl SystemLayout {
+    /// Create a new system layout
+    pub fn new() -> Self {
+        Self::with_base(SYSTEM_BASE)
+    }
+
+    /// Create a system layout rooted at a different directory
+    pub fn with_base(base: impl Into<PathBuf>) -> Self {
+        Self { base: base.into() }
+    }
+
+    /// Get the path to a specific version
+    pub fn version_path(&self, version: &str) -> PathBuf {
+        self.base.join(format!("v{}", version))
+    }
+
+    /// Get the directory a system-level package version is installed to
+    ///
+    /// The `system` package is the system image the current link points
+    /// at; kernels, boot packages and system libraries each get their own
+    /// directory so packages sharing a version don't overwrite each other.
+    pub fn package_path(&self, name: &str, version: &str) -> PathBuf {
+        if name == "system" {
+            self.version_path(version)
+        } else {
+            self.base
+                .join("packages")
+                .join(name)
+                .join(format!("v{}", version))
+        }
+    }
+
+    /// Get the current symlink path
+    pub fn current_path(&self) -> PathBuf {
+        self.base.join("current")
+    }
+
+    /// Get the boot directory for a version
+    pub fn boot_path(&self, version: &str) -> PathBuf {
+        self.version_path(version).join("boot")
+    }
+
+    /// Get the kernel path for a version
+    pub fn kernel_path(&self, version: &str) -> PathBuf {
+        self.boot_path(version).join("kernel")
+    }
+
+    /// Get the initrd path for a version
+    pub fn initrd_path(&self, version: &str) -> PathBuf {
+        self.boot_path(version).join("initrd")
+    }
+
+    /// Get the userland binaries path for a version
+    pub fn bin_path(&self, version: &str) -> PathBuf {
+        self.version_path(version).join("bin")
+    }
+
+    /// Get the libraries path for a version
+    pub fn lib_path(&self, version: &str) -> PathBuf {
+        self.version_path(version).join("lib")
+    }
+
+    /// Get the metadata path for a version
+    pub fn metadata_path(&self, version: &str) -> PathBuf {
+        self.version_path(version).join("metadata.json")
+    }
+
+    /// List all installed versions
+    pub fn list_versions(&self) -> crate::Result<Vec<String>> {
+        let mut versions = Vec::new();
+
+        if !self.base.exists() {
+            return Ok(versions);
+        }
+
+        for entry in std::fs::read_dir(&self.base)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name_str = name.to_string_lossy();
+
+            // Only count directories starting with 'v'
+            if entry.file_type()?.is_dir() && name_str.starts_with('v') {
+                versions.push(name_str[1..].to_string());
+            }
+        }
+
+        versions.sort();
+        Ok(versions)
+    }
+
+    /// Get the currently active version
+    pub fn current_version(&self) -> crate::Result<Option<String>> {
+        let current = self.current_path();
+
+        if !current.exists() {
+            return Ok(None);
+        }
+
+        let target = current.read_link()?;
+        let version_str = target
+            .file_name()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| crate::Error::Layout("Invalid current symlink".to_string()))?;
+
+        // Remove 'v' prefix if present
+        let version = version_str
+            .strip_prefix('v')
+            .unwrap_or(version_str)
+            .to_string();
+
+        Ok(Some(version))
+    }
+
+    /// Check if a version exists
+    pub fn version_exists(&self, version: &str) -> bool {
+        self.version_path(version).exists()
+    }
+}
+
+impl Default for SystemLayout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Application layout definition
+#[derive(Debug, Clone)]
+pub struct AppLayout {
+    /// Base path for applications
+    pub base: PathBuf,
+    /// Directory on `PATH` that gets a link to each active app's executable
+    pub bin_dir: Option<PathBuf>,
+    /// Directory holding each app's configuration directory
+    pub config_base: Option<PathBuf>,
+    /// Directory holding each app's data directory
+    pub data_base: Option<PathBuf>,
+}
+
+impl AppLayout {
+    /// Create a new app layout
+    pub fn new() -> Self {
+        Self::with_base(APPS_BASE).with_state_dirs(APPS_CONFIG_BASE, APPS_DATA_BASE)
+    }
+
+    /// Create an app layout rooted at a different directory
+    pub fn with_base(base: impl Into<PathBuf>) -> Self {
+        Self {
+            base: base.into(),
+            bin_dir: None,
+            config_base: None,
+            data_base: None,
+        }
+    }
+
+    /// Keep app configuration and data in the given directories
+    pub fn with_state_dirs(
+        mut self,
+        config_base: impl Into<PathBuf>,
+        data_base: impl Into<PathBuf>,
+    ) -> Self {
+        self.config_base = Some(config_base.into());
+        self.data_base = Some(data_base.into());
+        self
+    }
+
+    /// Get the configuration and data directories of an app that exist
+    ///
+    /// These are left behind on removal and only deleted by a purge.
+    pub fn state_paths(&self, app_name: &str) -> Vec<PathBuf> {
+        [&self.config_base, &self.data_base]
+            .into_iter()
+            .flatten()
+            .map(|base| base.join(app_name))
+            .filter(|path| path.exists())
+            .collect()
+    }
+
+    /// Link app executables into a directory on `PATH`
+    pub fn with_bin_dir(mut self, bin_dir: impl Into<PathBuf>) -> Self {
+        self.bin_dir = Some(bin_dir.into());
+        self
+    }
+
+    /// Get the `PATH` link for an app, if this layout keeps a bin directory
+    pub fn bin_path(&self, app_name: &str) -> Option<PathBuf> {
+        self.bin_dir.as_ref().map(|dir| dir.join(app_name))
+    }
+
+    /// Link an app's executable into the bin directory
+    ///
+    /// The link points at [`AppLayout::executable_path`], which goes
+    /// through the `current` symlink, so it follows version switches.
+    pub fn link_executable(&self, app_name: &str) -> crate::Result<()> {
+        let Some(link) = self.bin_path(app_name) else {
+            return Ok(());
+        };
+
+        if let Some(parent) = link.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Apps without an executable named after them get no link
+        let target = self.executable_path(app_name);
+        if !target.exists() {
+            return Ok(());
+        }
+
+        match link.read_link() {
+            Ok(existing) if existing == target => Ok(()),
+            Err(_) if !link.is_symlink() && !link.exists() => {
+                std::os::unix::fs::symlink(&target, &link)?;
+                Ok(())
+            }
+            _ => Err(crate::Error::Layout(format!(
+                "{} already exists, not replacing it",
+                link.display()
+            ))),
+        }
+    }
+
+    /// Remove an app's link from the bin directory
+    pub fn unlink_executable(&self, app_name: &str) -> crate::Result<()> {
+        if let Some(link) = self.bin_path(app_name) {
+            // Only remove links this layout created
+            if link.read_link().ok() == Some(self.executable_path(app_name)) {
+                std::fs::remove_file(link)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the path to an app directory
+    pub fn app_path(&self, app_name: &str) -> PathBuf {
+        self.base.join(app_name)
+    }
+
+    /// Get the path to a specific version of an app
+    pub fn version_path(&self, app_name: &str, version: &str) -> PathBuf {
+        self.app_path(app_name).join(version)
+    }
+
+    /// Get the current symlink path for an app
+    pub fn current_path(&self, app_name: &str) -> PathBuf {
+        self.app_path(app_name).join("current")
+    }
+
+    /// Get the metadata path for an app version
+    pub fn metadata_path(&self, app_name: &str, version: &str) -> PathBuf {
+        self.version_path(app_name, version).join("metadata.json")
+    }
+
+    /// Get the executable path for an app
+    pub fn executable_path(&self, app_name: &str) -> PathBuf {
+        self.current_path(app_name).join(app_name)
+    }
+
+    /// List all installed apps
+    pub fn list_apps(&self) -> crate::Result<Vec<String>> {
+        let mut apps = Vec::new();
+
+        if !self.base.exists() {
+            return Ok(apps);
+        }
+
+        for entry in std::fs::read_dir(&self.base)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                if let Some(name) = entry.file_name().to_str() {
+                    apps.push(name.to_string());
+                }
+            }
+        }
+
+        apps.sort();
+        Ok(apps)
+    }
+
+    /// List versions of an app
+    pub fn list_versions(&self, app_name: &str) -> crate::Result<Vec<String>> {
+        let mut versions = Vec::new();
+        let app_path = self.app_path(app_name);
+
+        if !app_path.exists() {
+            return Ok(versions);
+        }
+
+        for entry in std::fs::read_dir(&app_path)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name_str = name.to_string_lossy();
+
+            // Skip the current symlink and extractions still being staged
+            if name_str == "current" || name_str.starts_with('.') {
+                continue;
+            }
+
+            if entry.file_type()?.is_dir() {
+                versions.push(name_str.to_string());
+            }
+        }
+
+        versions.sort();
+        Ok(versions)
+    }
+
+    /// Get the currently active version of an app
+    pub fn current_version(&self, app_name: &str) -> crate::Result<Option<String>> {
+        let current = self.current_path(app_name);
+
+        if !current.exists() {
+            return Ok(None);
+        }
+
+        let target = current.read_link()?;
+        let version = target
+            .file_name()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| crate::Error::Layout("Invalid current symlink".to_string()))?;
+
+        Ok(Some(version.to_string()))
+    }
+
+    /// Check if an app exists
+    pub fn app_exists(&self, app_name: &str) -> bool {
+        self.app_path(app_name).exists()
+    }
+
+    /// Check if a specific version of an app exists
+    pub fn version_exists(&self, app_name: &str, version: &str) -> bool {
+        self.version_path(app_name, version).exists()
+    }
+}
+
+impl Default for AppLayout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Layout manager for managing system and app layouts
+#[derive(Debug, Clone)]
+pub struct LayoutManager {
+    /// System layout
+    pub system: SystemLayout,
+    /// App layout
+    pub apps: AppLayout,
+}
+
+impl LayoutManager {
+    /// Create a new layout manager
+    pub fn new() -> Self {
+        Self {
+            system: SystemLayout::new(),
+            apps: AppLayout::new(),
+        }
+    }
+
+    /// Initialize the layout directories
+    pub fn initialize(&self) -> crate::Result<()> {
+        // Create base directories
+        std::fs::create_dir_all(&self.system.base)?;
+        std::fs::create_dir_all(APPS_BASE)?;
+        std::fs::create_dir_all(CACHE_DIR)?;
+        std::fs::create_dir_all(META_DIR)?;
+        std::fs::create_dir_all(STATE_DIR)?;
+        std::fs::create_dir_all(CONFIG_DIR)?;
+
+        Ok(())
+    }
+
+    /// Get layout statistics
+    pub fn stats(&self) -> crate::Result<LayoutStats> {
+        Ok(LayoutStats {
+            system_versions: self.system.list_versions()?.len(),
+            installed_apps: self.apps.list_apps()?.len(),
+            cache_size: Self::dir_size(CACHE_DIR)?,
+            metadata_size: Self::dir_size(META_DIR)?,
+        })
+    }
+
+    /// Get the size of a directory
+    fn dir_size(path: &str) -> crate::Result<u64> {
+        let path = Path::new(path);
+        if !path.exists() {
+            return Ok(0);
+        }
+
+        let mut total = 0;
+        let mut stack = vec![path.to_path_buf()];
+
+        while let Some(current) = stack.pop() {
+            for entry in std::fs::read_dir(current)? {
+                let entry = entry?;
+                let ty = entry.file_type()?;
+
+                if ty.is_file() {
+                    total += entry.metadata()?.len();
+                } else if ty.is_dir() {
+                    stack.push(entry.path());
+                }
+            }
+        }
+
+        Ok(total)
+    }
+}
+
+impl Default for LayoutManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Layout statistics
+#[derive(Debug, Clone)]
+pub struct LayoutStats {
+    /// Number of system versions installed
+    pub system_versions: usize,
+    /// Number of apps installed
+    pub installed_apps: usize,
+    /// Size of cache directory in bytes
+    pub cache_size: u64,
+    /// Size of metadata directory in bytes
+    pub metadata_size: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_layout_paths() {
+        let layout = SystemLayout::new();
+        assert_eq!(
+            layout.version_path("1.0.0"),
+            PathBuf::from("/system/v1.0.0")
+        );
+        assert_eq!(layout.current_path(), PathBuf::from("/system/current"));
+        assert_eq!(
+            layout.package_path("system", "1.0.0"),
+            PathBuf::from("/system/v1.0.0")
+        );
+        assert_eq!(
+            layout.package_path("libc", "1.0.0"),
+            PathBuf::from("/system/packages/libc/v1.0.0")
+        );
+    }
+
+    #[test]
+    fn test_user_layout_executables() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let layout = AppLayout::with_base(temp_dir.path().join("apps"))
+            .with_bin_dir(temp_dir.path().join("bin"));
+
+        let version_path = layout.version_path("hello", "1.0.0");
+        std::fs::create_dir_all(&version_path).unwrap();
+        std::fs::write(version_path.join("hello"), "#!/bin/sh\n").unwrap();
+        std::os::unix::fs::symlink(&version_path, layout.current_path("hello")).unwrap();
+
+        layout.link_executable("hello").unwrap();
+        let link = layout.bin_path("hello").unwrap();
+        assert_eq!(link.read_link().unwrap(), layout.executable_path("hello"));
+        assert!(link.exists());
+
+        layout.unlink_executable("hello").unwrap();
+        assert!(!link.is_symlink());
+
+        // System layouts keep no bin directory
+        assert!(AppLayout::new().bin_path("hello").is_none());
+    }
+
+    #[test]
+    fn test_app_layout_paths() {
+        let layout = AppLayout::new();
+        assert_eq!(layout.app_path("test"), PathBuf::from("/apps/test"));
+        assert_eq!(
+            layout.version_path("test", "1.0.0"),
+            PathBuf::from("/apps/test/1.0.0")
+        );
+        assert_eq!(
+            layout.current_path("test"),
+            PathBuf::from("/apps/test/current")
+        );
+    }
+}
@@ -0,0 +1,238 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Cross-process locking and atomic state writes
+//!
+//! Every process that modifies the registry holds an advisory `flock` on
+//! `STATE_DIR/lock` for as long as it works with a loaded registry. The
+//! lock file also records the holder's PID so contention can be reported
+//! and locks left behind by crashed processes can be recognized.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::layout::STATE_DIR;
+
+/// Lock file name inside the state directory
+pub const LOCK_FILE: &str = "lock";
+
+/// Mode of files [`write_atomic`] creates, readable by everyone
+pub const DEFAULT_FILE_MODE: u32 = 0o644;
+
+/// How often a waiting process retries the lock
+const WAIT_INTERVAL: Duration = Duration::from_millis(200);
+
+/// An exclusive lock on the package registry, released on drop
+#[derive(Debug)]
+pub struct RegistryLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl RegistryLock {
+    /// Get the default lock file path
+    pub fn default_path() -> PathBuf {
+        Path::new(STATE_DIR).join(LOCK_FILE)
+    }
+
+    /// Acquire the default lock, optionally waiting for other processes
+    pub fn acquire(wait: bool) -> crate::Result<Self> {
+        Self::acquire_at(Self::default_path(), wait)
+    }
+
+    /// Acquire a lock at a specific path
+    pub fn acquire_at(path: impl AsRef<Path>, wait: bool) -> crate::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        let mut announced = false;
+        while !try_flock(&file)? {
+            let holder = read_pid(&mut file);
+
+            if !wait {
+                return Err(crate::Error::Locked(match holder {
+                    Some(pid) => format!("another rpg process (pid {}) holds the lock", pid),
+                    None => "another rpg process holds the lock".to_string(),
+                }));
+            }
+
+            if !announced {
+                log::info!(
+                    "Waiting for another rpg process{} to release {}",
+                    holder
+                        .map(|pid| format!(" (pid {})", pid))
+                        .unwrap_or_default(),
+                    path.display()
+                );
+                announced = true;
+            }
+            std::thread::sleep(WAIT_INTERVAL);
+        }
+
+        // A PID left in an unlocked file belongs to a process that exited
+        // without cleaning up
+        if let Some(pid) = read_pid(&mut file) {
+            if pid != std::process::id() {
+                log::warn!("Recovered stale lock left by pid {}", pid);
+            }
+        }
+
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        writeln!(file, "{}", std::process::id())?;
+        file.sync_all()?;
+
+        Ok(Self { file, path })
+    }
+
+    /// Get the lock file path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for RegistryLock {
+    fn drop(&mut self) {
+        // Clear the PID; closing the file releases the flock
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// Try to take an exclusive flock without blocking
+fn try_flock(file: &File) -> crate::Result<bool> {
+    // SAFETY: flock only operates on the descriptor, which `file` keeps open
+    let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if ret == 0 {
+        return Ok(true);
+    }
+
+    let err = std::io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(err.into())
+    }
+}
+
+/// Read the PID recorded in a lock file
+fn read_pid(file: &mut File) -> Option<u32> {
+    let mut content = String::new();
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_string(&mut content).ok()?;
+    content.trim().parse().ok()
+}
+
+/// Write a file atomically: write a temporary file in the same directory,
+/// sync it, then rename it over the destination
+///
+/// The file keeps the mode of the destination it replaces, or gets
+/// [`DEFAULT_FILE_MODE`] if there was none.
+pub fn write_atomic(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> crate::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = path.as_ref();
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    std::fs::create_dir_all(dir)?;
+
+    let permissions = match std::fs::metadata(path) {
+        Ok(metadata) => metadata.permissions(),
+        Err(_) => std::fs::Permissions::from_mode(DEFAULT_FILE_MODE),
+    };
+
+    let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+    temp.as_file().set_permissions(permissions)?;
+    temp.write_all(contents.as_ref())?;
+    temp.as_file().sync_all()?;
+    temp.persist(path).map_err(|e| crate::Error::Io(e.error))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_contention() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("lock");
+
+        let lock = RegistryLock::acquire_at(&path, false).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.trim(), std::process::id().to_string());
+
+        // flock is per open file description, so a second open conflicts
+        let err = RegistryLock::acquire_at(&path, false).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains(&format!("pid {}", std::process::id())));
+
+        drop(lock);
+        assert!(std::fs::read_to_string(&path).unwrap().is_empty());
+        assert!(RegistryLock::acquire_at(&path, false).is_ok());
+    }
+
+    #[test]
+    fn test_stale_lock() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("lock");
+
+        // A crashed process leaves its PID behind but no flock
+        std::fs::write(&path, "999999\n").unwrap();
+
+        let _lock = RegistryLock::acquire_at(&path, false).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.trim(), std::process::id().to_string());
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("state").join("registry.json");
+
+        write_atomic(&path, "first").unwrap();
+        write_atomic(&path, "second").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_write_atomic_keeps_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("sources.toml");
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        write_atomic(&path, "first").unwrap();
+        assert_eq!(mode(&path), DEFAULT_FILE_MODE);
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        write_atomic(&path, "second").unwrap();
+        assert_eq!(mode(&path), 0o600);
+    }
+}
@@ -0,0 +1,491 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Repository mirrors
+//!
+//! A mirror is a directory holding a copy of a source's `index.json`, the
+//! packages it lists and its signed advisories, laid out like the source so
+//! it can be served as is. Syncing a mirror is safe while clients read from
+//! it:
+//!
+//! 1. new and changed packages are downloaded into a staging directory
+//!    inside the mirror and checked against the index's SHA-256 and, when
+//!    the source has a trusted key, its signature;
+//! 2. verified packages are renamed into place; a package rebuilt under an
+//!    existing path gets a new name, since the old index still refers to
+//!    the old file;
+//! 3. the new index replaces the old one atomically, followed by the
+//!    advisories;
+//! 4. only then are packages listed by the old index but not the new one
+//!    removed.
+//!
+//! A client therefore always sees an index whose packages are all present.
+//! A failed sync leaves the previous index in place.
+
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+
+use crate::advisory::{AdvisoryFeed, ADVISORIES_FILE, ADVISORIES_SIG_FILE};
+use crate::backend::FetchBackend;
+use crate::fetch::{self, FetchError, FetchOptions, PackageEntry, RepositoryIndex};
+use crate::lock::{write_atomic, RegistryLock};
+use crate::signature::SignatureVerifier;
+use crate::sources::Source;
+
+/// Index file name within a mirror
+pub const INDEX_FILE: &str = "index.json";
+
+/// Lock file held while a mirror is syncing
+const LOCK_FILE: &str = ".rpg-mirror.lock";
+
+/// Prefix of the staging directory used during a sync
+const STAGING_PREFIX: &str = ".rpg-mirror-";
+
+/// Result of syncing a mirror
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MirrorReport {
+    /// Packages downloaded, by path
+    pub downloaded: Vec<String>,
+    /// Number of packages already up to date
+    pub unchanged: usize,
+    /// Packages removed because the source no longer lists them
+    pub pruned: Vec<String>,
+    /// Bytes downloaded
+    pub bytes: u64,
+}
+
+/// Sync a mirror directory with a source
+pub async fn sync(
+    backend: &dyn FetchBackend,
+    source: &Source,
+    dir: &Path,
+    options: &FetchOptions,
+) -> crate::Result<MirrorReport> {
+    std::fs::create_dir_all(dir)?;
+    let _lock = RegistryLock::acquire_at(dir.join(LOCK_FILE), false)?;
+
+    let verifier = match &source.trusted_key {
+        Some(key) => Some(SignatureVerifier::from_base64(key)?),
+        None => {
+            log::warn!(
+                "Source {} has no trusted key, mirroring its packages unverified",
+                source.name
+            );
+            None
+        }
+    };
+
+    let mut index = fetch::fetch_index_with(backend, &[source], Some(options.clone())).await?;
+    for entry in &mut index.packages {
+        entry.path = mirror_path(entry)?;
+    }
+    let previous = read_index(dir);
+    let advisories = fetch_advisories(backend, source, options).await?;
+
+    let staging = tempfile::Builder::new()
+        .prefix(STAGING_PREFIX)
+        .tempdir_in(dir)?;
+    let mut report = MirrorReport::default();
+    let mut staged = Vec::new();
+
+    for entry in &mut index.packages {
+        let remote = entry.clone();
+        let mut dest = dir.join(&entry.path);
+        let rebuilt = rebuilt_path(&entry.path, &entry.sha256);
+        if (dest.is_file() && !has_checksum(&dest, &entry.sha256))
+            || has_checksum(&dir.join(&rebuilt), &entry.sha256)
+        {
+            entry.path = rebuilt;
+            dest = dir.join(&entry.path);
+        }
+        if has_checksum(&dest, &entry.sha256) {
+            report.unchanged += 1;
+            continue;
+        }
+
+        let staged_path = staging.path().join(&entry.path);
+        let result = fetch::fetch_package_with(
+            backend,
+            &[source],
+            &remote,
+            &staged_path,
+            Some(options.clone()),
+            None,
+        )
+        .await
+        .map_err(|e| {
+            crate::Error::Other(format!(
+                "Failed to download {} {}: {}",
+                entry.name, entry.version, e
+            ))
+        })?;
+
+        if let Some(verifier) = &verifier {
+            entry.verify_signature(verifier, &staged_path)?;
+        }
+
+        report.bytes += result.total_bytes;
+        staged.push((staged_path, dest, entry.path.clone()));
+    }
+
+    // Everything is verified; publish the packages, then the index that
+    // refers to them
+    for (staged_path, dest, path) in staged {
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(&staged_path, &dest)?;
+        report.downloaded.push(path);
+    }
+
+    let json = serde_json::to_vec_pretty(&index)
+        .map_err(|e| crate::Error::Serialization(e.to_string()))?;
+    write_atomic(dir.join(INDEX_FILE), json)?;
+    let (feed, signature) = advisories.unzip();
+    publish_file(dir, ADVISORIES_FILE, feed)?;
+    publish_file(dir, ADVISORIES_SIG_FILE, signature.flatten())?;
+
+    // Old packages go last, once no index refers to them
+    if let Some(previous) = previous {
+        let current: BTreeSet<&str> = index.packages.iter().map(|e| e.path.as_str()).collect();
+        for entry in &previous.packages {
+            if current.contains(entry.path.as_str()) || mirror_path(entry).is_err() {
+                continue;
+            }
+            let path = dir.join(&entry.path);
+            match std::fs::remove_file(&path) {
+                Ok(()) => {
+                    remove_empty_parents(dir, &path);
+                    report.pruned.push(entry.path.clone());
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Check that a file has the given SHA-256
+fn has_checksum(path: &Path, sha256: &str) -> bool {
+    fetch::compute_checksum(path).ok().as_deref() == Some(sha256)
+}
+
+/// Get a name for a package rebuilt under a path the mirror already serves
+///
+/// The checksum goes before the extension, so `editor/1.0.0.rpg` becomes
+/// `editor/1.0.0.<checksum>.rpg`.
+fn rebuilt_path(path: &str, sha256: &str) -> String {
+    let checksum = &sha256[..sha256.len().min(16)];
+    match path.rsplit_once('.') {
+        Some((stem, extension)) if !extension.contains('/') => {
+            format!("{}.{}.{}", stem, checksum, extension)
+        }
+        _ => format!("{}.{}", path, checksum),
+    }
+}
+
+/// Fetch a source's advisory feed and its signature, if it publishes one
+///
+/// A source with a trusted key must sign its feed, like its packages.
+async fn fetch_advisories(
+    backend: &dyn FetchBackend,
+    source: &Source,
+    options: &FetchOptions,
+) -> crate::Result<Option<(Vec<u8>, Option<Vec<u8>>)>> {
+    let options = options.for_source(source);
+    let feed_url = source.file_url(ADVISORIES_FILE);
+    let signature_url = source.file_url(ADVISORIES_SIG_FILE);
+
+    let feed = match backend.fetch_range(&feed_url, 0, None, &options).await {
+        Ok(feed) => feed,
+        Err(FetchError::NotFound(_)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let signature = match backend.fetch_range(&signature_url, 0, None, &options).await {
+        Ok(signature) => Some(signature),
+        Err(FetchError::NotFound(_)) => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    if let Some(key) = &source.trusted_key {
+        let failed = |reason: String| {
+            crate::Error::SignatureVerification(format!(
+                "advisories of {}: {}",
+                source.name, reason
+            ))
+        };
+        let signature = signature
+            .as_deref()
+            .ok_or_else(|| failed("not signed".to_string()))?;
+        AdvisoryFeed::parse_signed(&feed, &String::from_utf8_lossy(signature), key)
+            .map_err(|e| failed(e.to_string()))?;
+    }
+
+    Ok(Some((feed, signature)))
+}
+
+/// Write a file of the mirror, or remove it if the source has none
+fn publish_file(dir: &Path, name: &str, contents: Option<Vec<u8>>) -> crate::Result<()> {
+    let path = dir.join(name);
+    match contents {
+        Some(contents) => write_atomic(path, contents),
+        None => match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        },
+    }
+}
+
+/// Read the index currently published in a mirror
+pub fn read_index(dir: &Path) -> Option<RepositoryIndex> {
+    let bytes = std::fs::read(dir.join(INDEX_FILE)).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// Get the path of a package within the mirror
+///
+/// Entries without a path are stored where [`Source::package_url`] would
+/// look for them. Paths that could escape the mirror are refused.
+fn mirror_path(entry: &PackageEntry) -> crate::Result<String> {
+    let path = if entry.path.is_empty() {
+        format!("{}/{}.rpg", entry.name, entry.version)
+    } else {
+        entry.path.trim_start_matches('/').to_string()
+    };
+
+    let safe = !path.is_empty()
+        && path != INDEX_FILE
+        && path != ADVISORIES_FILE
+        && path != ADVISORIES_SIG_FILE
+        && !path.starts_with(STAGING_PREFIX)
+        && path != LOCK_FILE
+        && Path::new(&path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+
+    if safe {
+        Ok(path)
+    } else {
+        Err(crate::Error::Other(format!(
+            "Invalid package path in index: {} ({} {})",
+            entry.path, entry.name, entry.version
+        )))
+    }
+}
+
+/// Remove directories left empty by pruning, up to the mirror root
+fn remove_empty_parents(root: &Path, path: &Path) {
+    let mut dir: Option<PathBuf> = path.parent().map(Path::to_path_buf);
+    while let Some(current) = dir {
+        if current == root || !current.starts_with(root) || std::fs::remove_dir(&current).is_err() {
+            break;
+        }
+        dir = current.parent().map(Path::to_path_buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+    use crate::signature::KeyPair;
+
+    const REPO: &str = "https://rustux.com/apps";
+
+    fn entry(key: &KeyPair, name: &str, version: &str, contents: &[u8]) -> PackageEntry {
+        PackageEntry {
+            name: name.to_string(),
+            version: version.to_string(),
+            description: None,
+            kind: None,
+            arch: "noarch".to_string(),
+            size: contents.len() as u64,
+            installed_size: None,
+            sha256: fetch::checksum_bytes(contents),
+            signature: key.sign(contents).to_base64(),
+            dependencies: Vec::new(),
+            path: format!("{}/{}.rpg", name, version),
+            source: String::new(),
+        }
+    }
+
+    fn publish(backend: &MemoryBackend, packages: &[(&PackageEntry, &[u8])]) {
+        for (entry, contents) in packages {
+            backend.insert(format!("{}/{}", REPO, entry.path), contents.to_vec());
+        }
+        let index = RepositoryIndex {
+            name: "apps".to_string(),
+            version: "1".to_string(),
+            last_updated: None,
+            packages: packages.iter().map(|(e, _)| (*e).clone()).collect(),
+            source: String::new(),
+        };
+        backend.insert_index(format!("{}/index.json", REPO), &index);
+    }
+
+    #[tokio::test]
+    async fn test_mirror_sync() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().join("mirror");
+        let key = KeyPair::generate();
+        let source = Source::new("apps".to_string(), REPO.to_string(), "apps".to_string())
+            .with_trusted_key(key.export_public());
+        let backend = MemoryBackend::new();
+        let options = FetchOptions::default();
+
+        let editor = entry(&key, "editor", "1.0.0", b"editor 1");
+        let viewer = entry(&key, "viewer", "2.0.0", b"viewer 2");
+        publish(&backend, &[(&editor, b"editor 1"), (&viewer, b"viewer 2")]);
+
+        let report = sync(&backend, &source, &dir, &options).await.unwrap();
+        assert_eq!(
+            report.downloaded,
+            vec!["editor/1.0.0.rpg", "viewer/2.0.0.rpg"]
+        );
+        assert_eq!(read_index(&dir).unwrap().packages.len(), 2);
+
+        // Only the new version is fetched, and the dropped one is pruned
+        let editor2 = entry(&key, "editor", "1.1.0", b"editor 2");
+        publish(&backend, &[(&editor2, b"editor 2"), (&viewer, b"viewer 2")]);
+        let report = sync(&backend, &source, &dir, &options).await.unwrap();
+        assert_eq!(report.downloaded, vec!["editor/1.1.0.rpg"]);
+        assert_eq!(report.unchanged, 1);
+        assert_eq!(report.pruned, vec!["editor/1.0.0.rpg"]);
+        assert!(!dir.join("editor/1.0.0.rpg").exists());
+        assert_eq!(
+            std::fs::read(dir.join("editor/1.1.0.rpg")).unwrap(),
+            b"editor 2"
+        );
+
+        // A bad signature aborts the sync and keeps the published index
+        let mut forged = entry(&key, "editor", "1.2.0", b"editor 3");
+        forged.signature = KeyPair::generate().sign(b"editor 3").to_base64();
+        publish(&backend, &[(&forged, b"editor 3"), (&viewer, b"viewer 2")]);
+        assert!(matches!(
+            sync(&backend, &source, &dir, &options).await,
+            Err(crate::Error::SignatureVerification(_))
+        ));
+        let published = read_index(&dir).unwrap();
+        assert!(published.packages.iter().any(|e| e.version == "1.1.0"));
+        assert!(!dir.join("editor/1.2.0.rpg").exists());
+        assert!(dir.join("editor/1.1.0.rpg").exists());
+
+        // So does a package that does not match its checksum
+        let mut corrupt = entry(&key, "editor", "1.2.0", b"editor 3");
+        corrupt.sha256 = fetch::checksum_bytes(b"something else");
+        publish(&backend, &[(&corrupt, b"editor 3"), (&viewer, b"viewer 2")]);
+        assert!(sync(&backend, &source, &dir, &options).await.is_err());
+        assert!(read_index(&dir)
+            .unwrap()
+            .packages
+            .iter()
+            .any(|e| e.version == "1.1.0"));
+
+        // A package rebuilt under the same path does not replace the file
+        // the published index refers to before the index is swapped
+        let rebuilt = entry(&key, "viewer", "2.0.0", b"viewer 2, rebuilt");
+        publish(
+            &backend,
+            &[(&editor2, b"editor 2"), (&rebuilt, b"viewer 2, rebuilt")],
+        );
+        let report = sync(&backend, &source, &dir, &options).await.unwrap();
+        let path = rebuilt_path("viewer/2.0.0.rpg", &rebuilt.sha256);
+        assert_eq!(report.downloaded, vec![path.clone()]);
+        assert_eq!(report.pruned, vec!["viewer/2.0.0.rpg"]);
+        assert_eq!(
+            std::fs::read(dir.join(&path)).unwrap(),
+            b"viewer 2, rebuilt"
+        );
+        let published = read_index(&dir).unwrap();
+        assert!(published.packages.iter().any(|e| e.path == path));
+
+        // and is kept under that name by later syncs
+        let report = sync(&backend, &source, &dir, &options).await.unwrap();
+        assert!(report.downloaded.is_empty());
+        assert!(report.pruned.is_empty());
+
+        // Nothing is left behind in the staging area
+        let leftovers: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().starts_with(STAGING_PREFIX))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mirror_advisories() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().join("mirror");
+        let key = KeyPair::generate();
+        let source = Source::new("apps".to_string(), REPO.to_string(), "apps".to_string())
+            .with_trusted_key(key.export_public());
+        let backend = MemoryBackend::new();
+        let options = FetchOptions::default();
+
+        let editor = entry(&key, "editor", "1.0.0", b"editor 1");
+        publish(&backend, &[(&editor, b"editor 1")]);
+        let feed = br#"{"advisories": []}"#;
+        let signature = key.sign(feed).to_base64();
+        backend.insert(format!("{}/{}", REPO, ADVISORIES_FILE), feed.to_vec());
+        backend.insert(
+            format!("{}/{}", REPO, ADVISORIES_SIG_FILE),
+            signature.clone().into_bytes(),
+        );
+
+        sync(&backend, &source, &dir, &options).await.unwrap();
+        assert_eq!(std::fs::read(dir.join(ADVISORIES_FILE)).unwrap(), feed);
+        assert_eq!(
+            std::fs::read_to_string(dir.join(ADVISORIES_SIG_FILE)).unwrap(),
+            signature
+        );
+        let mode = std::fs::metadata(dir.join(INDEX_FILE))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o644);
+
+        // A feed that is not signed by the source's key is not mirrored
+        let forged = br#"{"advisories": [], "forged": true}"#;
+        backend.insert(format!("{}/{}", REPO, ADVISORIES_FILE), forged.to_vec());
+        assert!(matches!(
+            sync(&backend, &source, &dir, &options).await,
+            Err(crate::Error::SignatureVerification(_))
+        ));
+        assert_eq!(std::fs::read(dir.join(ADVISORIES_FILE)).unwrap(), feed);
+
+        // and a feed the source dropped is removed
+        backend.remove(&format!("{}/{}", REPO, ADVISORIES_FILE));
+        backend.remove(&format!("{}/{}", REPO, ADVISORIES_SIG_FILE));
+        sync(&backend, &source, &dir, &options).await.unwrap();
+        assert!(!dir.join(ADVISORIES_FILE).exists());
+        assert!(!dir.join(ADVISORIES_SIG_FILE).exists());
+    }
+
+    #[test]
+    fn test_mirror_path() {
+        let key = KeyPair::generate();
+        let mut e = entry(&key, "editor", "1.0.0", b"x");
+        assert_eq!(mirror_path(&e).unwrap(), "editor/1.0.0.rpg");
+
+        e.path = String::new();
+        assert_eq!(mirror_path(&e).unwrap(), "editor/1.0.0.rpg");
+
+        e.path = "../../etc/passwd".to_string();
+        assert!(mirror_path(&e).is_err());
+
+        e.path = "index.json".to_string();
+        assert!(mirror_path(&e).is_err());
+
+        e.path = ADVISORIES_FILE.to_string();
+        assert!(mirror_path(&e).is_err());
+    }
+}
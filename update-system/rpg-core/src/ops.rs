@@ -1,524 +1,4049 @@
-// Copyright 2025 The Rustux Authors
-//
-// Use of this source code is governed by a MIT-style
-// license that can be found in the LICENSE file or at
-// https://opensource.org/licenses/MIT
-
-//! High-level package operations
-
-use crate::archive::PackageArchive;
-use crate::fetch::{self, FetchError};
-use crate::package::{Package, PackageKind, PackageMetadata};
-use crate::registry::PackageRegistry;
-use crate::sources::{Source, SourcesConfig};
-use crate::transaction::{Transaction, TransactionKind, TransactionResult};
-use crate::version::Version;
-use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use tokio::sync::RwLock;
-
-/// Package manager for high-level operations
-#[derive(Debug, Clone)]
-pub struct PackageManager {
-    /// Sources configuration
-    sources: Arc<RwLock<SourcesConfig>>,
-    /// Package registry
-    registry: Arc<RwLock<PackageRegistry>>,
-    /// Download cache directory
-    cache_dir: PathBuf,
-    /// Temporary directory for downloads
-    temp_dir: PathBuf,
-}
-
-impl PackageManager {
-    /// Create a new package manager
-    pub fn new() -> crate::Result<Self> {
-        let cache_dir = PathBuf::from("/var/cache/rpg");
-        let temp_dir = PathBuf::from("/tmp/rpg");
-
-        // Create directories if they don't exist
-        std::fs::create_dir_all(&cache_dir)?;
-        std::fs::create_dir_all(&temp_dir)?;
-
-        Ok(Self {
-            sources: Arc::new(RwLock::new(SourcesConfig::load()?)),
-            registry: Arc::new(RwLock::new(PackageRegistry::load().unwrap_or_default())),
-            cache_dir,
-            temp_dir,
-        })
-    }
-
-    /// Check for updates
-    pub async fn check_updates(&self) -> crate::Result<UpdateInfo> {
-        let sources = self.sources.read().await;
-        let mut updates = Vec::new();
-        let mut errors = Vec::new();
-
-        // Fetch indices from all enabled sources
-        let enabled_sources = sources.enabled_sources();
-        if enabled_sources.is_empty() {
-            return Ok(UpdateInfo {
-                available: Vec::new(),
-                errors: vec!["No enabled sources found".to_string()],
-            });
-        }
-
-        // Fetch kernel updates
-        let kernel_sources: Vec<&Source> = sources.kernel_sources();
-        if !kernel_sources.is_empty() {
-            match fetch::fetch_index(&kernel_sources, None).await {
-                Ok(index) => {
-                    for entry in index.packages {
-                        if let Some(update) = self.check_package_update(&entry).await? {
-                            updates.push(update);
-                        }
-                    }
-                }
-                Err(e) => errors.push(format!("Failed to fetch kernel index: {}", e)),
-            }
-        }
-
-        // Fetch system updates
-        let system_sources: Vec<&Source> = sources.system_sources();
-        if !system_sources.is_empty() {
-            match fetch::fetch_index(&system_sources, None).await {
-                Ok(index) => {
-                    for entry in index.packages {
-                        if let Some(update) = self.check_package_update(&entry).await? {
-                            updates.push(update);
-                        }
-                    }
-                }
-                Err(e) => errors.push(format!("Failed to fetch system index: {}", e)),
-            }
-        }
-
-        // Fetch app updates
-        let app_sources: Vec<&Source> = sources.app_sources();
-        if !app_sources.is_empty() {
-            match fetch::fetch_index(&app_sources, None).await {
-                Ok(index) => {
-                    for entry in index.packages {
-                        if let Some(update) = self.check_package_update(&entry).await? {
-                            updates.push(update);
-                        }
-                    }
-                }
-                Err(e) => errors.push(format!("Failed to fetch app index: {}", e)),
-            }
-        }
-
-        Ok(UpdateInfo {
-            available: updates,
-            errors,
-        })
-    }
-
-    /// Check if a package has an update available
-    async fn check_package_update(&self, entry: &fetch::PackageEntry) -> crate::Result<Option<PackageUpdate>> {
-        let registry = self.registry.read().await;
-        let current_version = registry.get_active(&entry.name);
-
-        if let Some(current) = current_version {
-            let new_version = Version::parse(&entry.version)?;
-            if new_version > *current {
-                Ok(Some(PackageUpdate {
-                    name: entry.name.clone(),
-                    current_version: current.to_string(),
-                    new_version: entry.version.clone(),
-                    size: entry.size,
-                    kind: self.infer_package_kind(&entry.name),
-                }))
-            } else {
-                Ok(None)
-            }
-        } else {
-            // Package not installed, but available
-            Ok(Some(PackageUpdate {
-                name: entry.name.clone(),
-                current_version: "not installed".to_string(),
-                new_version: entry.version.clone(),
-                size: entry.size,
-                kind: self.infer_package_kind(&entry.name),
-            }))
-        }
-    }
-
-    /// Infer package kind from name
-    fn infer_package_kind(&self, name: &str) -> PackageKind {
-        if name == "kernel" {
-            PackageKind::Kernel
-        } else if name == "system" {
-            PackageKind::System
-        } else {
-            PackageKind::App
-        }
-    }
-
-    /// Download a package
-    pub async fn download_package(
-        &self,
-        name: &str,
-        version: &str,
-        kind: PackageKind,
-    ) -> crate::Result<PathBuf> {
-        let sources = self.sources.read().await;
-
-        let sources_for_type = match kind {
-            PackageKind::Kernel => sources.kernel_sources(),
-            PackageKind::System => sources.system_sources(),
-            PackageKind::App | PackageKind::Boot => sources.app_sources(),
-        };
-
-        if sources_for_type.is_empty() {
-            return Err(crate::Error::Other(format!(
-                "No sources configured for package type: {:?}",
-                kind
-            )));
-        }
-
-        // First fetch the index to get checksum
-        let index = fetch::fetch_index(&sources_for_type, None).await?;
-
-        let entry = index
-            .packages
-            .iter()
-            .find(|p| p.name == name && p.version == version)
-            .ok_or_else(|| crate::Error::PackageNotFound(format!("{}@{}", name, version)))?;
-
-        // Download package
-        let package_path = self.cache_dir.join(format!("{}-{}.rpg", name, version));
-
-        let result = fetch::fetch_package(
-            &sources_for_type,
-            name,
-            version,
-            &entry.sha256,
-            &package_path,
-            None,
-            None,
-        )
-        .await
-        .map_err(|e| match e {
-            FetchError::AllSourcesFailed => {
-                crate::Error::NetworkError("All sources failed".to_string())
-            }
-            FetchError::ChecksumMismatch { expected, actual } => {
-                crate::Error::Other(format!(
-                    "Checksum mismatch: expected {}, got {}",
-                    expected, actual
-                ))
-            }
-            _ => crate::Error::NetworkError(e.to_string()),
-        })
-        .map_err(crate::Error::from)?;
-
-        Ok(result.path)
-    }
-
-    /// Install a package
-    pub async fn install_package(
-        &self,
-        name: &str,
-        version: Option<&str>,
-        kind: PackageKind,
-    ) -> crate::Result<TransactionResult> {
-        // If version not specified, fetch latest
-        let version_to_install = if let Some(v) = version {
-            v.to_string()
-        } else {
-            self.get_latest_version(name, kind).await?
-        };
-
-        // Download package
-        let package_path = self
-            .download_package(name, &version_to_install, kind)
-            .await?;
-
-        // Open package archive
-        let archive = PackageArchive::open(&package_path)?;
-        let metadata = archive.metadata.clone();
-
-        // Extract package files to versioned directory
-        use crate::layout::{AppLayout, SystemLayout};
-
-        let version_str = metadata.version.as_str();
-        let extract_path = match kind {
-            PackageKind::App => {
-                let layout = AppLayout::new();
-                layout.version_path(name, &version_str)
-            }
-            PackageKind::Kernel | PackageKind::System | PackageKind::Boot => {
-                let layout = SystemLayout::new();
-                layout.version_path(&format!("v{}", version_str))
-            }
-        };
-
-        // Extract files
-        archive.extract_files(&extract_path)?;
-
-        // Create transaction
-        let package = Package::new(metadata.clone());
-        let mut transaction = Transaction::new(TransactionKind::Install, vec![package]);
-
-        // Execute transaction (handles symlink activation)
-        let result = transaction.execute().await;
-
-        // Update registry if successful
-        if matches!(result, TransactionResult::Success { .. }) {
-            let mut registry = self.registry.write().await;
-            registry.record_transaction(transaction.clone());
-            registry.add_package(name, &Version::parse(&version_to_install)?);
-            registry.set_active(name.to_string(), Version::parse(&version_to_install)?);
-            let _ = registry.save();
-        }
-
-        Ok(result)
-    }
-
-    /// Update all packages
-    pub async fn update_all(&self) -> crate::Result<UpdateResult> {
-        let update_info = self.check_updates().await?;
-
-        let mut succeeded = Vec::new();
-        let mut failed = Vec::new();
-        let mut requires_reboot = Vec::new();
-
-        for update in &update_info.available {
-            match self
-                .install_package(&update.name, Some(&update.new_version), update.kind.clone())
-                .await
-            {
-                Ok(TransactionResult::Success {
-                    activated,
-                    requires_reboot: reboot,
-                }) => {
-                    succeeded.push(update.name.clone());
-                    requires_reboot.extend(reboot);
-                    if activated.contains(&update.name) {
-                        println!("Updated {} to {}", update.name, update.new_version);
-                    }
-                }
-                Ok(TransactionResult::Failed { error, .. }) => {
-                    failed.push((update.name.clone(), error));
-                }
-                Ok(TransactionResult::RolledBack { reason, .. }) => {
-                    failed.push((update.name.clone(), reason));
-                }
-                Err(e) => {
-                    failed.push((update.name.clone(), e.to_string()));
-                }
-            }
-        }
-
-        Ok(UpdateResult {
-            succeeded,
-            failed,
-            requires_reboot,
-        })
-    }
-
-    /// Rollback to a previous version
-    pub async fn rollback(&self, package: &str, version: Option<&str>) -> crate::Result<TransactionResult> {
-        let registry = self.registry.read().await;
-
-        let rollback_version = if let Some(v) = version {
-            Version::parse(v)?
-        } else {
-            // Get previous version
-            let versions = registry.list_versions(package);
-            if versions.len() < 2 {
-                return Err(crate::Error::Other(
-                    "No previous version to rollback to".to_string(),
-                ));
-            }
-            versions[1].clone()
-        };
-
-        // Create rollback transaction
-        let mut transaction = Transaction::new(TransactionKind::Rollback, vec![]);
-        transaction.rollback_info.previous_app_versions.push((
-            package.to_string(),
-            rollback_version.clone(),
-        ));
-
-        let result = transaction.execute().await;
-
-        // Update registry if successful
-        if matches!(result, TransactionResult::Success { .. }) {
-            drop(registry);
-            let mut registry = self.registry.write().await;
-            registry.set_active(package.to_string(), rollback_version);
-            let _ = registry.save();
-        }
-
-        Ok(result)
-    }
-
-    /// Get latest version of a package
-    async fn get_latest_version(&self, name: &str, kind: PackageKind) -> crate::Result<String> {
-        let sources = self.sources.read().await;
-
-        let sources_for_type = match kind {
-            PackageKind::Kernel => sources.kernel_sources(),
-            PackageKind::System => sources.system_sources(),
-            PackageKind::App | PackageKind::Boot => sources.app_sources(),
-        };
-
-        let index = fetch::fetch_index(&sources_for_type, None).await?;
-
-        let entry = index
-            .packages
-            .iter()
-            .filter(|p| p.name == name)
-            .max_by_key(|p| &p.version)
-            .ok_or_else(|| crate::Error::PackageNotFound(name.to_string()))?;
-
-        Ok(entry.version.clone())
-    }
-
-    /// Get system status
-    pub async fn get_status(&self) -> crate::Result<SystemStatus> {
-        let registry = self.registry.read().await;
-        let sources = self.sources.read().await;
-
-        let stats = registry.stats();
-        let source_stats = sources.stats();
-
-        Ok(SystemStatus {
-            total_packages: stats.total_packages,
-            active_packages: stats.active_count,
-            pending_updates: stats.pending_count,
-            sources_total: source_stats.total,
-            sources_enabled: source_stats.enabled,
-        })
-    }
-
-    /// List installed packages
-    pub async fn list_installed(&self) -> crate::Result<Vec<InstalledPackage>> {
-        let registry = self.registry.read().await;
-
-        let mut packages = Vec::new();
-
-        for (name, versions) in &registry.packages {
-            if let Some(active) = registry.get_active(name) {
-                packages.push(InstalledPackage {
-                    name: name.clone(),
-                    version: active.to_string(),
-                    versions: versions.iter().map(|v| v.to_string()).collect(),
-                    kind: self.infer_package_kind(name),
-                });
-            }
-        }
-
-        packages.sort_by(|a, b| a.name.cmp(&b.name));
-
-        Ok(packages)
-    }
-
-    /// Remove a package
-    pub async fn remove_package(&self, name: &str) -> crate::Result<TransactionResult> {
-        // Get package metadata
-        let registry = self.registry.read().await;
-        let version = registry
-            .get_active(name)
-            .ok_or_else(|| crate::Error::PackageNotFound(name.to_string()))?;
-
-        let kind = self.infer_package_kind(name);
-
-        // Create metadata for removal
-        let metadata = PackageMetadata::new(
-            name.to_string(),
-            version.clone(),
-            kind,
-            0,
-            "0".repeat(64),
-            crate::signature::PackageSignature::new([0u8; 64]),
-            String::new(),
-        );
-
-        let package = Package::new(metadata);
-        let mut transaction = Transaction::new(TransactionKind::Remove, vec![package]);
-
-        let result = transaction.execute().await;
-
-        // Update registry if successful
-        if matches!(result, TransactionResult::Success { .. }) {
-            drop(registry);
-            let mut registry = self.registry.write().await;
-            registry.remove_active(name);
-            let _ = registry.save();
-        }
-
-        Ok(result)
-    }
-}
-
-impl Default for PackageManager {
-    fn default() -> Self {
-        Self::new().expect("Failed to create PackageManager")
-    }
-}
-
-/// Update information
-#[derive(Debug, Clone)]
-pub struct UpdateInfo {
-    /// Available updates
-    pub available: Vec<PackageUpdate>,
-    /// Errors encountered
-    pub errors: Vec<String>,
-}
-
-/// Package update
-#[derive(Debug, Clone)]
-pub struct PackageUpdate {
-    /// Package name
-    pub name: String,
-    /// Current version
-    pub current_version: String,
-    /// New version
-    pub new_version: String,
-    /// Package size in bytes
-    pub size: u64,
-    /// Package kind
-    pub kind: PackageKind,
-}
-
-/// Update result
-#[derive(Debug, Clone)]
-pub struct UpdateResult {
-    /// Packages that were successfully updated
-    pub succeeded: Vec<String>,
-    /// Packages that failed to update
-    pub failed: Vec<(String, String)>,
-    /// Packages requiring reboot
-    pub requires_reboot: Vec<String>,
-}
-
-/// System status
-#[derive(Debug, Clone)]
-pub struct SystemStatus {
-    /// Total number of packages
-    pub total_packages: usize,
-    /// Number of active packages
-    pub active_packages: usize,
-    /// Number of pending updates
-    pub pending_updates: usize,
-    /// Total number of sources
-    pub sources_total: usize,
-    /// Number of enabled sources
-    pub sources_enabled: usize,
-}
-
-/// Installed package information
-#[derive(Debug, Clone)]
-pub struct InstalledPackage {
-    /// Package name
-    pub name: String,
-    /// Active version
-    pub version: String,
-    /// All installed versions
-    pub versions: Vec<String>,
-    /// Package kind
-    pub kind: PackageKind,
-}
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! High-level package operations
+
+use crate::advisory::{self, AdvisoryFeed, AuditFinding, AuditReport, Severity};
+use crate::archive::PackageArchive;
+use crate::auth::{AuthConfig, AUTH_PATH};
+use crate::backend::{BoxFuture, FetchBackend, HttpBackend};
+use crate::conffile::{self, ConfFileAction, MergeChoice, PendingConfFile};
+use crate::config::PinConfig;
+use crate::fetch::{self, FetchError, FetchOptions};
+use crate::layout::{AppLayout, Scope, SystemLayout};
+use crate::mirror::{self, MirrorReport};
+use crate::package::{Dependency, InstallReason, Package, PackageKind, PackageMetadata};
+use crate::plan::{Plan, PlanStep};
+use crate::registry::PackageRegistry;
+use crate::sbom::{self, SbomFormat, SbomPackage, StoredManifest};
+use crate::snapshot::{Snapshot, SnapshotDiff};
+use crate::sources::{Source, SourcesConfig, SOURCES_PATH};
+use crate::state::{RebootRequired, UpdateSummary};
+use crate::transaction::{Transaction, TransactionKind, TransactionResult};
+use crate::trigger::{self, Registration};
+use crate::version::{Channel, Version};
+use crate::world::{WorldChange, WorldConfig, WorldPlan};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::RwLock;
+
+/// Package manager for high-level operations
+#[derive(Debug, Clone)]
+pub struct PackageManager {
+    /// Sources configuration
+    sources: Arc<RwLock<SourcesConfig>>,
+    /// Package registry
+    registry: Arc<RwLock<PackageRegistry>>,
+    /// Package pins and holds
+    pins: Arc<RwLock<PinConfig>>,
+    /// Download cache directory
+    cache_dir: PathBuf,
+    /// Target architecture for package selection
+    arch: String,
+    /// Where packages are installed
+    scope: Scope,
+    /// Layout apps are installed into
+    app_layout: AppLayout,
+    /// Layout system and kernel versions are installed into
+    system_layout: SystemLayout,
+    /// Registry file of the scope
+    registry_path: PathBuf,
+    /// Pins file of the scope
+    pins_path: PathBuf,
+    /// Sources file
+    sources_path: PathBuf,
+    /// Directory config files under `/etc` are installed relative to
+    conffile_root: PathBuf,
+    /// Directory update state is published in for the desktop and shell
+    state_dir: PathBuf,
+    /// HTTP options, including source credentials
+    fetch_options: FetchOptions,
+    /// How repository files are fetched
+    backend: Arc<dyn FetchBackend>,
+    /// Filesystems disk space is checked against instead of the mounted ones
+    mounts: Option<Vec<(PathBuf, u64)>>,
+    /// Paths changed by the batch being run, whose triggers run once it ends
+    trigger_batch: Arc<Mutex<Option<Vec<String>>>>,
+}
+
+impl PackageManager {
+    /// Create a new package manager
+    pub fn new() -> crate::Result<Self> {
+        Self::for_scope(Scope::System)
+    }
+
+    /// Create a package manager for a scope
+    ///
+    /// The user scope keeps its own registry, pins and cache under the
+    /// home directory and only installs apps, so it does not need root.
+    /// Sources are shared with the system.
+    pub fn for_scope(scope: Scope) -> crate::Result<Self> {
+        let cache_dir = scope.cache_dir()?;
+        let registry_path = scope.registry_path()?;
+        let pins_path = scope.pins_path()?;
+
+        // Create directories if they don't exist
+        std::fs::create_dir_all(&cache_dir)?;
+
+        Ok(Self {
+            sources: Arc::new(RwLock::new(SourcesConfig::load_from_path(SOURCES_PATH)?)),
+            registry: Arc::new(RwLock::new(
+                PackageRegistry::load_from_path(&registry_path).unwrap_or_default(),
+            )),
+            pins: Arc::new(RwLock::new(PinConfig::load_from_path(&pins_path)?)),
+            cache_dir,
+            arch: crate::arch::host_arch(),
+            scope,
+            app_layout: scope.app_layout()?,
+            system_layout: SystemLayout::new(),
+            registry_path,
+            pins_path,
+            sources_path: PathBuf::from(SOURCES_PATH),
+            conffile_root: scope.conffile_root()?,
+            state_dir: scope.state_dir()?,
+            fetch_options: FetchOptions::default().with_credentials(load_credentials(AUTH_PATH)),
+            backend: Arc::new(HttpBackend),
+            mounts: None,
+            trigger_batch: Arc::default(),
+        })
+    }
+
+    /// Create a package manager that keeps all of its state under `root`
+    ///
+    /// Sources, credentials, pins, registry, cache and apps live at their
+    /// usual paths below `root`, which lets tests and image builds run
+    /// without touching the host. So do system and kernel versions, which
+    /// go to `system/` below `root`.
+    pub fn at_root(root: impl AsRef<std::path::Path>) -> crate::Result<Self> {
+        let root = root.as_ref();
+        let under = |path: &str| root.join(path.trim_start_matches('/'));
+
+        let cache_dir = under(crate::layout::CACHE_DIR);
+        let registry_path = under(crate::layout::META_DIR).join("registry.json");
+        let pins_path = under(crate::config::PINS_PATH);
+        let sources_path = under(SOURCES_PATH);
+
+        std::fs::create_dir_all(&cache_dir)?;
+
+        Ok(Self {
+            sources: Arc::new(RwLock::new(SourcesConfig::load_from_path(&sources_path)?)),
+            registry: Arc::new(RwLock::new(
+                PackageRegistry::load_from_path(&registry_path).unwrap_or_default(),
+            )),
+            pins: Arc::new(RwLock::new(PinConfig::load_from_path(&pins_path)?)),
+            cache_dir,
+            arch: crate::arch::host_arch(),
+            scope: Scope::System,
+            app_layout: AppLayout::with_base(under(crate::layout::APPS_BASE)).with_state_dirs(
+                under(crate::layout::APPS_CONFIG_BASE),
+                under(crate::layout::APPS_DATA_BASE),
+            ),
+            system_layout: SystemLayout::with_base(under(crate::layout::SYSTEM_BASE)),
+            registry_path,
+            pins_path,
+            sources_path,
+            conffile_root: root.to_path_buf(),
+            state_dir: under(crate::layout::STATE_DIR),
+            fetch_options: FetchOptions::default()
+                .with_credentials(load_credentials(under(AUTH_PATH))),
+            backend: Arc::new(HttpBackend),
+            mounts: None,
+            trigger_batch: Arc::default(),
+        })
+    }
+
+    /// Get the scope packages are installed into
+    pub fn scope(&self) -> Scope {
+        self.scope
+    }
+
+    /// Get the layout apps are installed into
+    pub fn app_layout(&self) -> &AppLayout {
+        &self.app_layout
+    }
+
+    /// Get the directory update state is published in
+    pub fn state_dir(&self) -> &std::path::Path {
+        &self.state_dir
+    }
+
+    /// Select packages for a different architecture than the host
+    ///
+    /// Used when building images for another machine.
+    pub fn with_arch(mut self, arch: &str) -> crate::Result<Self> {
+        self.arch = crate::arch::validate(arch)?;
+        Ok(self)
+    }
+
+    /// Read sources from a different file, and save changes to them there
+    pub fn with_sources_file(mut self, path: impl Into<PathBuf>) -> crate::Result<Self> {
+        let path = path.into();
+        self.sources = Arc::new(RwLock::new(SourcesConfig::load_from_path(&path)?));
+        self.sources_path = path;
+        Ok(self)
+    }
+
+    /// Use different HTTP options for fetching
+    pub fn with_fetch_options(mut self, options: FetchOptions) -> Self {
+        self.fetch_options = options;
+        self
+    }
+
+    /// Fetch repository files through a different backend
+    pub fn with_backend(mut self, backend: Arc<dyn FetchBackend>) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Check disk space against the given mount points and available bytes
+    pub fn with_mounts(mut self, mounts: Vec<(PathBuf, u64)>) -> Self {
+        self.mounts = Some(mounts);
+        self
+    }
+
+    /// Fill in a plan's disk usage
+    fn with_disk_usage(&self, plan: Plan) -> Plan {
+        match &self.mounts {
+            Some(mounts) => plan.with_mounts(mounts),
+            None => plan.with_disk_usage(),
+        }
+    }
+
+    /// Get the target architecture
+    pub fn arch(&self) -> &str {
+        &self.arch
+    }
+
+    /// Get the configured sources, enabled or not
+    ///
+    /// A per-user manager only installs apps, so it only lists app sources.
+    pub async fn sources(&self) -> Vec<Source> {
+        self.sources
+            .read()
+            .await
+            .sources
+            .iter()
+            .filter(|s| self.scope == Scope::System || s.is_apps())
+            .cloned()
+            .collect()
+    }
+
+    /// Bring a mirror directory up to date with a configured source
+    ///
+    /// Fetches through the manager's backend with its credentials, so a
+    /// manager opened under a root uses the root's `auth.toml`.
+    pub async fn sync_mirror(
+        &self,
+        source: &str,
+        dir: &std::path::Path,
+    ) -> crate::Result<MirrorReport> {
+        let source = self
+            .sources
+            .read()
+            .await
+            .sources
+            .iter()
+            .find(|s| s.name == source)
+            .cloned()
+            .ok_or_else(|| crate::Error::Other(format!("Source not found: {}", source)))?;
+
+        mirror::sync(self.backend.as_ref(), &source, dir, &self.fetch_options).await
+    }
+
+    /// Check for updates
+    pub async fn check_updates(&self) -> crate::Result<UpdateInfo> {
+        let sources = self.sources.read().await;
+        let mut updates = Vec::new();
+        let mut errors = Vec::new();
+
+        // Fetch indices from all enabled sources
+        let enabled_sources = sources.enabled_sources();
+        if enabled_sources.is_empty() {
+            return Ok(UpdateInfo {
+                available: Vec::new(),
+                held: Vec::new(),
+                errors: vec!["No enabled sources found".to_string()],
+            });
+        }
+
+        // Fetch kernel updates
+        let kernel_sources: Vec<&Source> = sources.kernel_sources();
+        if !kernel_sources.is_empty() {
+            match fetch::fetch_index_with(
+                self.backend.as_ref(),
+                &kernel_sources,
+                Some(self.fetch_options.clone()),
+            )
+            .await
+            {
+                Ok(index) => {
+                    for entry in &index.packages {
+                        let channel = sources.channel_for(&entry.name, &index.source);
+                        match self
+                            .check_package_update(
+                                entry,
+                                entry.kind_or(PackageKind::Kernel),
+                                channel,
+                            )
+                            .await
+                        {
+                            Ok(Some(update)) => updates.push(update),
+                            Ok(None) => {}
+                            Err(e) => {
+                                errors.push(format!("{}@{}: {}", entry.name, entry.version, e))
+                            }
+                        }
+                    }
+                }
+                Err(e) => errors.push(format!("Failed to fetch kernel index: {}", e)),
+            }
+        }
+
+        // Fetch system updates
+        let system_sources: Vec<&Source> = sources.system_sources();
+        if !system_sources.is_empty() {
+            match fetch::fetch_index_with(
+                self.backend.as_ref(),
+                &system_sources,
+                Some(self.fetch_options.clone()),
+            )
+            .await
+            {
+                Ok(index) => {
+                    for entry in &index.packages {
+                        let channel = sources.channel_for(&entry.name, &index.source);
+                        match self
+                            .check_package_update(
+                                entry,
+                                entry.kind_or(PackageKind::System),
+                                channel,
+                            )
+                            .await
+                        {
+                            Ok(Some(update)) => updates.push(update),
+                            Ok(None) => {}
+                            Err(e) => {
+                                errors.push(format!("{}@{}: {}", entry.name, entry.version, e))
+                            }
+                        }
+                    }
+                }
+                Err(e) => errors.push(format!("Failed to fetch system index: {}", e)),
+            }
+        }
+
+        // Fetch app updates
+        let app_sources: Vec<&Source> = sources.app_sources();
+        if !app_sources.is_empty() {
+            match fetch::fetch_index_with(
+                self.backend.as_ref(),
+                &app_sources,
+                Some(self.fetch_options.clone()),
+            )
+            .await
+            {
+                Ok(index) => {
+                    for entry in &index.packages {
+                        let channel = sources.channel_for(&entry.name, &index.source);
+                        match self
+                            .check_package_update(entry, entry.kind_or(PackageKind::App), channel)
+                            .await
+                        {
+                            Ok(Some(update)) => updates.push(update),
+                            Ok(None) => {}
+                            Err(e) => {
+                                errors.push(format!("{}@{}: {}", entry.name, entry.version, e))
+                            }
+                        }
+                    }
+                }
+                Err(e) => errors.push(format!("Failed to fetch app index: {}", e)),
+            }
+        }
+
+        // Mark updates that fix known advisories and install those first
+        drop(sources);
+        let (advisories, advisory_errors) = self.fetch_advisories().await;
+        errors.extend(advisory_errors);
+        for update in &mut updates {
+            update.apply_advisories(&advisories);
+        }
+
+        // Offer the newest version of each package that its pin allows, and
+        // hold a package back only if no newer version is allowed at all
+        let mut candidates: Vec<Vec<(Version, PackageUpdate)>> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for update in updates {
+            match Version::parse(&update.new_version) {
+                Ok(version) => {
+                    let position = *positions.entry(update.name.clone()).or_insert_with(|| {
+                        candidates.push(Vec::new());
+                        candidates.len() - 1
+                    });
+                    candidates[position].push((version, update));
+                }
+                Err(e) => errors.push(format!("{}@{}: {}", update.name, update.new_version, e)),
+            }
+        }
+
+        let pins = self.pins.read().await;
+        let mut available = Vec::new();
+        let mut held = Vec::new();
+        for mut versions in candidates {
+            versions.sort_by(|a, b| b.0.cmp(&a.0));
+            match versions
+                .iter()
+                .position(|(version, update)| pins.allows(&update.name, version))
+            {
+                Some(newest_allowed) => available.push(versions.swap_remove(newest_allowed).1),
+                None => held.push(versions.swap_remove(0).1),
+            }
+        }
+        drop(pins);
+        available.sort_by_key(|update| std::cmp::Reverse(update.severity));
+        held.sort_by_key(|update| std::cmp::Reverse(update.severity));
+
+        Ok(UpdateInfo {
+            available,
+            held,
+            errors,
+        })
+    }
+
+    /// Publish the result of an update check in the update summary
+    ///
+    /// Callers that merely look at updates, like status output, a check or
+    /// a dry run, leave the summary alone. Failures are logged.
+    pub async fn publish_updates(&self, info: &UpdateInfo) {
+        let summary = UpdateSummary::new(info.available.iter(), info.held.iter());
+        if let Err(e) = summary.save(&self.state_dir) {
+            log::warn!("Failed to save the update summary: {}", e);
+        }
+    }
+
+    /// Check if a package has an update available on its channel
+    async fn check_package_update(
+        &self,
+        entry: &fetch::PackageEntry,
+        kind: PackageKind,
+        channel: Channel,
+    ) -> crate::Result<Option<PackageUpdate>> {
+        let registry = self.registry.read().await;
+        let current_version = registry.get_active(&entry.name);
+        let new_version = Version::parse(&entry.version)?;
+
+        if !channel.allows(&new_version) || !entry.supports_arch(&self.arch) {
+            return Ok(None);
+        }
+
+        // Packages that are not installed have nothing to update; installing
+        // them is left to an explicit install
+        match current_version {
+            Some(current) if new_version > *current => Ok(Some(PackageUpdate {
+                name: entry.name.clone(),
+                current_version: current.to_string(),
+                new_version: entry.version.clone(),
+                size: entry.size,
+                kind,
+                advisories: Vec::new(),
+                severity: None,
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    /// Fetch the advisory feeds of all enabled sources
+    ///
+    /// Sources that fail are reported as errors and skipped.
+    pub async fn fetch_advisories(&self) -> (AdvisoryFeed, Vec<String>) {
+        let sources = self.sources.read().await;
+        let mut feed = AdvisoryFeed::default();
+        let mut errors = Vec::new();
+
+        for source in sources.enabled_sources() {
+            match advisory::fetch_advisories(self.backend.as_ref(), source, &self.fetch_options)
+                .await
+            {
+                Ok(Some(advisories)) => feed.merge(advisories),
+                Ok(None) => {}
+                Err(e) => errors.push(format!(
+                    "Failed to fetch advisories from {}: {}",
+                    source.name, e
+                )),
+            }
+        }
+
+        (feed, errors)
+    }
+
+    /// Report installed packages whose active version has known advisories
+    pub async fn audit(&self) -> crate::Result<AuditReport> {
+        let (feed, errors) = self.fetch_advisories().await;
+        let registry = self.registry.read().await;
+
+        let mut findings = Vec::new();
+        for name in registry.list_packages() {
+            let Some(active) = registry.get_active(&name) else {
+                continue;
+            };
+            for advisory in feed.affecting(&name, active) {
+                findings.push(AuditFinding {
+                    package: name.clone(),
+                    installed: active.clone(),
+                    advisory: advisory.clone(),
+                });
+            }
+        }
+
+        findings.sort_by(|a, b| {
+            b.advisory
+                .severity
+                .cmp(&a.advisory.severity)
+                .then_with(|| a.package.cmp(&b.package))
+        });
+
+        Ok(AuditReport { findings, errors })
+    }
+
+    /// Determine the kind of a package
+    ///
+    /// Installed packages use the kind recorded in the registry; other
+    /// packages are looked up in the source indices, where the entry's
+    /// kind takes precedence over the kind its source serves.
+    pub async fn resolve_kind(&self, name: &str) -> crate::Result<PackageKind> {
+        if let Some(kind) = self.registry.read().await.get_kind(name) {
+            return Ok(kind);
+        }
+
+        let sources = self.sources.read().await;
+
+        for kind in [PackageKind::Kernel, PackageKind::System, PackageKind::App] {
+            let sources_for_kind = sources.sources_for_kind(kind);
+            if sources_for_kind.is_empty() {
+                continue;
+            }
+
+            match fetch::fetch_index_with(
+                self.backend.as_ref(),
+                &sources_for_kind,
+                Some(self.fetch_options.clone()),
+            )
+            .await
+            {
+                Ok(index) => {
+                    if let Some(entry) = index.packages.iter().find(|p| p.name == name) {
+                        return Ok(entry.kind_or(kind));
+                    }
+                }
+                Err(e) => log::warn!("Failed to fetch {} index: {}", kind, e),
+            }
+        }
+
+        Err(crate::Error::PackageNotFound(name.to_string()))
+    }
+
+    /// Get the recorded kind of an installed package
+    fn installed_kind(registry: &PackageRegistry, name: &str) -> PackageKind {
+        registry.get_kind(name).unwrap_or(PackageKind::App)
+    }
+
+    /// Find the index entry of a package version built for the target
+    /// architecture
+    async fn find_build(
+        &self,
+        name: &str,
+        version: &str,
+        kind: PackageKind,
+    ) -> crate::Result<fetch::PackageEntry> {
+        let sources = self.sources.read().await;
+        let sources_for_type = sources.sources_for_kind(kind);
+
+        if sources_for_type.is_empty() {
+            return Err(crate::Error::Other(format!(
+                "No sources configured for package type: {:?}",
+                kind
+            )));
+        }
+
+        let index = fetch::fetch_index_with(
+            self.backend.as_ref(),
+            &sources_for_type,
+            Some(self.fetch_options.clone()),
+        )
+        .await?;
+
+        let builds: Vec<&fetch::PackageEntry> = index
+            .packages
+            .iter()
+            .filter(|p| p.name == name && p.version == version)
+            .collect();
+
+        if builds.is_empty() {
+            return Err(crate::Error::PackageNotFound(format!(
+                "{}@{}",
+                name, version
+            )));
+        }
+
+        let entry = builds
+            .iter()
+            .find(|p| p.supports_arch(&self.arch))
+            .ok_or_else(|| {
+                crate::Error::ArchitectureMismatch(format!(
+                    "{}@{} is only available for {}, but the target architecture is {}",
+                    name,
+                    version,
+                    builds
+                        .iter()
+                        .map(|p| p.arch.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    self.arch
+                ))
+            })?;
+
+        Ok((*entry).clone())
+    }
+
+    /// Get the cache path of a package archive
+    fn cache_path(&self, entry: &fetch::PackageEntry) -> PathBuf {
+        self.cache_dir.join(format!(
+            "{}-{}-{}.rpg",
+            entry.name, entry.version, entry.arch
+        ))
+    }
+
+    /// Get the directory a package version is extracted to
+    fn extract_path(&self, name: &str, version: &str, kind: PackageKind) -> PathBuf {
+        match kind {
+            PackageKind::App => self.app_layout.version_path(name, version),
+            PackageKind::Kernel | PackageKind::System | PackageKind::Boot => {
+                self.system_layout.package_path(name, version)
+            }
+        }
+    }
+
+    /// Check that a package kind can be installed in the manager's scope
+    fn check_scope(&self, name: &str, kind: PackageKind) -> crate::Result<()> {
+        if self.scope.allows_kind(kind) {
+            Ok(())
+        } else {
+            Err(crate::Error::PermissionDenied(format!(
+                "{} is a {} package; only apps can be installed per user",
+                name, kind
+            )))
+        }
+    }
+
+    /// Download a package without installing it
+    ///
+    /// The version is picked like for an install, so pins and holds apply.
+    pub async fn download_package(
+        &self,
+        name: &str,
+        version: Option<&str>,
+        kind: PackageKind,
+    ) -> crate::Result<PathBuf> {
+        let version = self.select_version(name, version, kind).await?;
+
+        // The index entry carries the checksum and path of the build
+        let entry = self.find_build(name, &version, kind).await?;
+        self.download_build(&entry, kind).await
+    }
+
+    /// Download the archive of an index entry into the cache
+    async fn download_build(
+        &self,
+        entry: &fetch::PackageEntry,
+        kind: PackageKind,
+    ) -> crate::Result<PathBuf> {
+        let sources = self.sources.read().await;
+        let sources_for_type = sources.sources_for_kind(kind);
+
+        // Download package
+        let package_path = self.cache_path(entry);
+
+        let result = fetch::fetch_package_with(
+            self.backend.as_ref(),
+            &sources_for_type,
+            entry,
+            &package_path,
+            Some(self.fetch_options.clone()),
+            None,
+        )
+        .await
+        .map_err(|e| match e {
+            FetchError::AllSourcesFailed => {
+                crate::Error::NetworkError("All sources failed".to_string())
+            }
+            FetchError::ChecksumMismatch { expected, actual } => crate::Error::Other(format!(
+                "Checksum mismatch: expected {}, got {}",
+                expected, actual
+            )),
+            _ => crate::Error::NetworkError(e.to_string()),
+        })?;
+
+        // Archives from a source with a trusted key must carry its signature
+        let trusted_key = sources_for_type
+            .iter()
+            .find(|s| s.name == entry.source)
+            .and_then(|s| s.trusted_key.as_deref());
+        if let Some(key) = trusted_key {
+            let verifier = crate::signature::SignatureVerifier::from_base64(key)?;
+            if let Err(e) = entry.verify_signature(&verifier, &result.path) {
+                let _ = std::fs::remove_file(&result.path);
+                return Err(e);
+            }
+        }
+
+        Ok(result.path)
+    }
+
+    /// Install a package and any dependencies it is missing
+    pub async fn install_package(
+        &self,
+        name: &str,
+        version: Option<&str>,
+        kind: PackageKind,
+    ) -> crate::Result<TransactionResult> {
+        self.install_package_with(name, version, kind, InstallOptions::default())
+            .await
+    }
+
+    /// Install a package at the user's request
+    ///
+    /// Missing dependencies are installed in the same batch and recorded
+    /// as dependencies, so `autoremove` can clean them up later.
+    pub async fn install_package_with(
+        &self,
+        name: &str,
+        version: Option<&str>,
+        kind: PackageKind,
+        options: InstallOptions,
+    ) -> crate::Result<TransactionResult> {
+        let batch = ulid::Ulid::new().to_string();
+        self.install_package_in_batch(
+            name,
+            version,
+            kind,
+            Some(batch),
+            Some(InstallReason::Explicit),
+            options,
+        )
+        .await
+    }
+
+    /// Pick the version to install: the requested one if the package's pin
+    /// allows it, otherwise the latest available
+    async fn select_version(
+        &self,
+        name: &str,
+        version: Option<&str>,
+        kind: PackageKind,
+    ) -> crate::Result<String> {
+        let Some(v) = version else {
+            return self.get_latest_version(name, kind).await;
+        };
+
+        let pins = self.pins.read().await;
+        if let Some(pin) = pins.get(name) {
+            if !pin.allows(&Version::parse(v)?) {
+                return Err(crate::Error::Other(format!(
+                    "{} is {}, refusing to install version {} (use 'rpg unpin {}' first)",
+                    name, pin, v, name
+                )));
+            }
+        }
+
+        Ok(v.to_string())
+    }
+
+    /// Plan the installation of a package without changing anything
+    pub async fn plan_install(
+        &self,
+        name: &str,
+        version: Option<&str>,
+        kind: PackageKind,
+        options: InstallOptions,
+    ) -> crate::Result<Plan> {
+        let plan = self.plan_package(name, version, kind, options).await?;
+        Ok(self.with_disk_usage(plan))
+    }
+
+    /// Plan the steps installing a package and its missing dependencies
+    async fn plan_package(
+        &self,
+        name: &str,
+        version: Option<&str>,
+        kind: PackageKind,
+        options: InstallOptions,
+    ) -> crate::Result<Plan> {
+        let version = self.select_version(name, version, kind).await?;
+        let entry = self.find_build(name, &version, kind).await?;
+
+        let mut plan = Plan::new();
+        if !options.no_deps {
+            for (dependency, dependency_kind) in self.resolve_dependencies(&entry).await? {
+                plan.extend(self.plan_build(&dependency, dependency_kind)?);
+            }
+        }
+        plan.extend(self.plan_build(&entry, kind)?);
+
+        Ok(plan)
+    }
+
+    /// Plan the steps installing a single package build
+    fn plan_build(&self, entry: &fetch::PackageEntry, kind: PackageKind) -> crate::Result<Plan> {
+        let (name, version) = (entry.name.as_str(), entry.version.clone());
+        let kind = entry.kind_or(kind);
+        self.check_scope(name, kind)?;
+
+        let mut plan = Plan::new();
+        let cache_path = self.cache_path(entry);
+        let extract_path = self.extract_path(name, &version, kind);
+
+        plan.steps.push(PlanStep::Download {
+            name: name.to_string(),
+            version: version.clone(),
+            size: entry.size,
+            cached: cache_path.exists(),
+            path: cache_path,
+        });
+        plan.steps.push(PlanStep::Extract {
+            name: name.to_string(),
+            version: version.clone(),
+            path: extract_path.clone(),
+            size: entry.installed_size.unwrap_or(entry.size),
+        });
+
+        if kind.is_app() {
+            let link = self.app_layout.current_path(name);
+            plan.steps.push(PlanStep::Switch {
+                name: name.to_string(),
+                from: link.read_link().ok(),
+                link,
+                to: Some(extract_path),
+            });
+        } else {
+            plan.requires_reboot.push(name.to_string());
+        }
+
+        Ok(plan)
+    }
+
+    /// Find the dependencies of a build that are not satisfied by an
+    /// installed version, dependencies first
+    async fn resolve_dependencies(
+        &self,
+        entry: &fetch::PackageEntry,
+    ) -> crate::Result<Vec<(fetch::PackageEntry, PackageKind)>> {
+        let mut seen = HashSet::from([entry.name.clone()]);
+        let mut missing = Vec::new();
+        self.collect_dependencies(entry, &mut seen, &mut missing)
+            .await?;
+        Ok(missing)
+    }
+
+    /// Depth-first walk behind [`PackageManager::resolve_dependencies`]
+    fn collect_dependencies<'a>(
+        &'a self,
+        entry: &'a fetch::PackageEntry,
+        seen: &'a mut HashSet<String>,
+        missing: &'a mut Vec<(fetch::PackageEntry, PackageKind)>,
+    ) -> BoxFuture<'a, crate::Result<()>> {
+        Box::pin(async move {
+            for dependency in &entry.dependencies {
+                let dependency = Dependency::parse(dependency)?;
+
+                // Cycles and shared dependencies are resolved once
+                if !seen.insert(dependency.name.clone()) {
+                    continue;
+                }
+
+                let installed = self
+                    .registry
+                    .read()
+                    .await
+                    .get_active(&dependency.name)
+                    .cloned();
+                if installed.is_some_and(|v| dependency.constraint.satisfies(&v)) {
+                    continue;
+                }
+
+                let kind = self.resolve_kind(&dependency.name).await?;
+                let (candidates, channel) = self.available_versions(&dependency.name, kind).await?;
+                let pins = self.pins.read().await;
+                let version = candidates
+                    .into_iter()
+                    .filter(|v| {
+                        channel.allows(v)
+                            && pins.allows(&dependency.name, v)
+                            && dependency.constraint.satisfies(v)
+                    })
+                    .max()
+                    .ok_or_else(|| {
+                        crate::Error::VersionNotFound(format!(
+                            "no version of {} matching {} is available (required by {})",
+                            dependency.name, dependency.constraint.requirement, entry.name
+                        ))
+                    })?;
+                drop(pins);
+
+                let build = self
+                    .find_build(&dependency.name, &version.to_string(), kind)
+                    .await?;
+                self.collect_dependencies(&build, seen, missing).await?;
+                missing.push((build, kind));
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Install a package, tagging its transactions with a batch ID
+    ///
+    /// A `reason` of `None` keeps the reason already recorded, which is
+    /// what updates want. Triggers run once, after the package and its
+    /// dependencies are installed.
+    async fn install_package_in_batch(
+        &self,
+        name: &str,
+        version: Option<&str>,
+        kind: PackageKind,
+        batch: Option<String>,
+        reason: Option<InstallReason>,
+        options: InstallOptions,
+    ) -> crate::Result<TransactionResult> {
+        let started = self.start_trigger_batch();
+        let result = self
+            .install_with_dependencies(name, version, kind, batch, reason, options)
+            .await;
+        self.finish_trigger_batch(started).await;
+        result
+    }
+
+    /// Install a package and the dependencies it is missing
+    async fn install_with_dependencies(
+        &self,
+        name: &str,
+        version: Option<&str>,
+        kind: PackageKind,
+        batch: Option<String>,
+        reason: Option<InstallReason>,
+        options: InstallOptions,
+    ) -> crate::Result<TransactionResult> {
+        let version_to_install = self.select_version(name, version, kind).await?;
+
+        // Check every filesystem the install writes to before changing any
+        let plan = self
+            .plan_package(name, Some(&version_to_install), kind, options)
+            .await?;
+        self.with_disk_usage(plan).check_space()?;
+
+        let mut activated = Vec::new();
+        let mut requires_reboot = Vec::new();
+
+        // Dependencies installed so far, undone if a later install fails
+        let mut installed = Vec::new();
+
+        if !options.no_deps {
+            let entry = self.find_build(name, &version_to_install, kind).await?;
+            for (dependency, dependency_kind) in self.resolve_dependencies(&entry).await? {
+                let result = self
+                    .install_build(
+                        &dependency.name,
+                        &dependency.version,
+                        dependency_kind,
+                        batch.clone(),
+                        Some(InstallReason::Dependency),
+                    )
+                    .await;
+                match result {
+                    Ok(TransactionResult::Success {
+                        activated: a,
+                        requires_reboot: r,
+                    }) => {
+                        activated.extend(a);
+                        requires_reboot.extend(r);
+                        let registry = self.registry.read().await;
+                        installed.extend(registry.get_transactions().last().cloned());
+                    }
+                    other => {
+                        self.revert_installs(&installed).await;
+                        return other;
+                    }
+                }
+            }
+        }
+
+        match self
+            .install_build(name, &version_to_install, kind, batch, reason)
+            .await
+        {
+            Ok(TransactionResult::Success {
+                activated: a,
+                requires_reboot: r,
+            }) => {
+                activated.extend(a);
+                requires_reboot.extend(r);
+                Ok(TransactionResult::Success {
+                    activated,
+                    requires_reboot,
+                })
+            }
+            other => {
+                self.revert_installs(&installed).await;
+                other
+            }
+        }
+    }
+
+    /// Undo the installs of a batch that failed later on, newest first
+    ///
+    /// Failures are only logged, so the error that stopped the batch is the
+    /// one reported.
+    async fn revert_installs(&self, installed: &[Transaction]) {
+        for tx in installed.iter().rev() {
+            let error = match self.revert_activation(tx).await {
+                Ok(TransactionResult::Success { .. }) => continue,
+                Ok(TransactionResult::Failed { error, .. }) => error,
+                Ok(TransactionResult::RolledBack { reason }) => reason,
+                Err(e) => e.to_string(),
+            };
+            log::warn!("Failed to undo transaction {}: {}", tx.id, error);
+        }
+    }
+
+    /// Defer triggers until [`PackageManager::finish_trigger_batch`]
+    ///
+    /// Returns whether this call started the batch; inside an enclosing
+    /// batch the triggers wait for that one instead.
+    fn start_trigger_batch(&self) -> bool {
+        let mut batch = self.trigger_batch.lock().unwrap();
+        if batch.is_some() {
+            return false;
+        }
+        *batch = Some(Vec::new());
+        true
+    }
+
+    /// Check whether transactions leave their triggers to a batch
+    fn batching_triggers(&self) -> bool {
+        self.trigger_batch.lock().unwrap().is_some()
+    }
+
+    /// Add paths a transaction of the current batch changed
+    fn defer_trigger_paths(&self, paths: &[String]) {
+        if let Some(batch) = self.trigger_batch.lock().unwrap().as_mut() {
+            batch.extend(paths.iter().cloned());
+        }
+    }
+
+    /// End a batch this call started, running each matching trigger once
+    /// and recording the runs on the batch's last transaction
+    async fn finish_trigger_batch(&self, started: bool) {
+        if !started {
+            return;
+        }
+        let Some(mut changed) = self.trigger_batch.lock().unwrap().take() else {
+            return;
+        };
+        if changed.is_empty() {
+            return;
+        }
+        changed.sort();
+        changed.dedup();
+
+        let registered = self.registry.read().await.all_triggers();
+        let runs =
+            trigger::run_matching(&registered, &changed, &self.app_layout, &self.system_layout)
+                .await;
+        if runs.is_empty() {
+            return;
+        }
+
+        let mut registry = self.registry.write().await;
+        registry.record_trigger_runs(runs);
+        let _ = registry.save_to_path(&self.registry_path);
+    }
+
+    /// Download, extract and activate a single package version
+    async fn install_build(
+        &self,
+        name: &str,
+        version_to_install: &str,
+        kind: PackageKind,
+        batch: Option<String>,
+        reason: Option<InstallReason>,
+    ) -> crate::Result<TransactionResult> {
+        let entry = self.find_build(name, version_to_install, kind).await?;
+        self.install_entry(&entry, kind, batch, reason).await
+    }
+
+    /// Download, extract and activate the build of an index entry
+    async fn install_entry(
+        &self,
+        entry: &fetch::PackageEntry,
+        kind: PackageKind,
+        batch: Option<String>,
+        reason: Option<InstallReason>,
+    ) -> crate::Result<TransactionResult> {
+        let (name, version_to_install) = (entry.name.as_str(), entry.version.as_str());
+
+        // Download package
+        let package_path = self.download_build(entry, kind).await?;
+
+        // Open package archive
+        let archive = PackageArchive::open(&package_path)?;
+        archive.check_arch(&self.arch)?;
+        let metadata = archive.metadata.clone();
+
+        // The archive's manifest is authoritative for the package kind
+        let kind = metadata.kind;
+        self.check_scope(name, kind)?;
+
+        // Extract package files to versioned directory
+        let extract_path = self.extract_path(name, &metadata.version.as_str(), kind);
+
+        // Extract files
+        archive.extract_files(&extract_path)?;
+
+        // The package's own triggers replace those of its previous version
+        let mut triggers: Vec<_> = self
+            .registry
+            .read()
+            .await
+            .all_triggers()
+            .into_iter()
+            .filter(|registration| registration.owner != name)
+            .collect();
+        triggers.extend(metadata.triggers.iter().map(|t| Registration {
+            owner: name.to_string(),
+            kind,
+            trigger: t.clone(),
+        }));
+
+        // Create transaction
+        let package = Package::new(metadata.clone());
+        let mut transaction = Transaction::new(TransactionKind::Install, vec![package])
+            .with_batch(batch)
+            .with_app_layout(self.app_layout.clone())
+            .with_system_layout(self.system_layout.clone())
+            .with_triggers(triggers)
+            .with_deferred_triggers(self.batching_triggers());
+
+        // Execute transaction (handles symlink activation)
+        let result = transaction.execute().await;
+        self.defer_trigger_paths(transaction.deferred_paths());
+
+        // Update registry if successful
+        if matches!(result, TransactionResult::Success { .. }) {
+            let mut registry = self.registry.write().await;
+
+            // System-level packages have no link to remember, so undo
+            // needs the version that was active before
+            if !kind.is_app() {
+                if let Some(previous) = registry.get_active(name) {
+                    transaction
+                        .rollback_info
+                        .previous_package_versions
+                        .push((name.to_string(), previous.clone()));
+                }
+            }
+
+            registry.record_transaction(transaction.clone());
+            registry.add_package(name, &Version::parse(version_to_install)?);
+            registry.set_kind(name, kind);
+            registry.set_active(name.to_string(), Version::parse(version_to_install)?);
+            if let Some(reason) = reason {
+                registry.set_reason(name, reason);
+            }
+            let mut dependencies: Vec<String> = metadata.dependencies.keys().cloned().collect();
+            dependencies.sort();
+            registry.set_dependencies(name, dependencies);
+            registry.set_triggers(name, metadata.triggers.clone());
+            self.install_conffiles(&mut registry, name, &extract_path, &metadata.conffiles);
+            let _ = registry.save_to_path(&self.registry_path);
+            drop(registry);
+
+            // Keep the manifest for the software bill of materials
+            let stored = archive.manifest().and_then(|manifest| {
+                sbom::store_manifest(
+                    &self.manifests_dir(),
+                    &StoredManifest {
+                        manifest,
+                        sha256: entry.sha256.clone(),
+                        source: entry.source.clone(),
+                        signature: entry.signature.clone(),
+                    },
+                )
+            });
+            if let Err(e) = stored {
+                log::warn!("Failed to store the manifest of {}: {}", name, e);
+            }
+        }
+
+        self.publish_state(&result);
+        Ok(result)
+    }
+
+    /// Find the cached archive of a version that was installed before
+    ///
+    /// The stored manifest records the archive's checksum, so the archive
+    /// can be reinstalled without asking the sources for it again.
+    fn retained_build(&self, name: &str, version: &str) -> Option<fetch::PackageEntry> {
+        let stored = sbom::load_manifest(&self.manifests_dir(), name, version)?;
+        let entry = fetch::PackageEntry {
+            name: name.to_string(),
+            version: stored.manifest.version.clone(),
+            description: stored.manifest.description.clone(),
+            kind: stored.manifest.kind.parse().ok(),
+            arch: stored.manifest.arch.clone(),
+            size: stored.manifest.size,
+            installed_size: None,
+            sha256: stored.sha256,
+            signature: stored.signature,
+            dependencies: stored.manifest.dependencies.clone(),
+            path: String::new(),
+            source: stored.source,
+        };
+
+        let path = self.cache_path(&entry);
+        match fetch::compute_checksum(&path) {
+            Ok(checksum) if checksum == entry.sha256 => Some(entry),
+            _ => None,
+        }
+    }
+
+    /// Get where a config file is installed
+    fn conffile_path(&self, path: &str) -> PathBuf {
+        self.conffile_root.join(path.trim_start_matches('/'))
+    }
+
+    /// Copy a package's config files into place, keeping local edits
+    ///
+    /// The package is already active, so failures are logged rather than
+    /// failing the install.
+    fn install_conffiles(
+        &self,
+        registry: &mut PackageRegistry,
+        package: &str,
+        extract_path: &std::path::Path,
+        conffiles: &[String],
+    ) {
+        for path in conffiles {
+            let source = extract_path.join(path.trim_start_matches('/'));
+            let dest = self.conffile_path(path);
+
+            // Another package's file is left alone
+            let owned = match registry.conffiles.get(path) {
+                Some(existing) if existing.package != package => {
+                    log::warn!(
+                        "Not installing {}, it belongs to {}",
+                        dest.display(),
+                        existing.package
+                    );
+                    continue;
+                }
+                Some(existing) => Some(existing.sha256.clone()),
+                None => None,
+            };
+
+            match conffile::install(&source, &dest, owned.as_deref()) {
+                Ok((action, sha256)) => {
+                    if action == ConfFileAction::Preserved {
+                        log::warn!(
+                            "Keeping modified {}; the new version is {}",
+                            dest.display(),
+                            conffile::new_path(&dest).display()
+                        );
+                    }
+                    // A file that was there before the package stays the
+                    // admin's, so purging the package never deletes it
+                    let written =
+                        matches!(action, ConfFileAction::Installed | ConfFileAction::Replaced);
+                    if written || owned.is_some() {
+                        registry.record_conffile(path, package, sha256);
+                    }
+                }
+                Err(e) => log::warn!("Failed to install {}: {}", dest.display(), e),
+            }
+        }
+    }
+
+    /// List config files whose new version is waiting for review
+    pub async fn pending_conffiles(&self) -> Vec<PendingConfFile> {
+        self.registry
+            .read()
+            .await
+            .conffiles
+            .iter()
+            .map(|(path, conffile)| PendingConfFile {
+                path: self.conffile_path(path),
+                package: conffile.package.clone(),
+            })
+            .filter(|pending| pending.new_path().exists())
+            .collect()
+    }
+
+    /// Resolve the pending change of a config file
+    pub async fn merge_conffile(
+        &self,
+        path: &std::path::Path,
+        choice: MergeChoice,
+    ) -> crate::Result<()> {
+        let pending = self
+            .pending_conffiles()
+            .await
+            .into_iter()
+            .find(|pending| pending.path == path)
+            .ok_or_else(|| {
+                crate::Error::Other(format!(
+                    "No pending configuration change for {}",
+                    path.display()
+                ))
+            })?;
+
+        pending.merge(choice)
+    }
+
+    /// Update all packages
+    pub async fn update_all(&self) -> crate::Result<UpdateResult> {
+        let update_info = self.check_updates().await?;
+        self.publish_updates(&update_info).await;
+        self.install_updates(&update_info).await
+    }
+
+    /// Drop the cached archives of updates so installing them downloads
+    /// them again
+    pub async fn evict_cached(&self, update_info: &UpdateInfo) -> crate::Result<()> {
+        for update in &update_info.available {
+            let entry = self
+                .find_build(&update.name, &update.new_version, update.kind)
+                .await?;
+            match std::fs::remove_file(self.cache_path(&entry)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Plan the installation of the updates found by `check_updates`
+    pub async fn plan_updates(&self, update_info: &UpdateInfo) -> crate::Result<Plan> {
+        let mut plan = Plan::new();
+
+        for update in &update_info.available {
+            plan.extend(
+                self.plan_package(
+                    &update.name,
+                    Some(&update.new_version),
+                    update.kind,
+                    InstallOptions::default(),
+                )
+                .await?,
+            );
+        }
+
+        Ok(self.with_disk_usage(plan))
+    }
+
+    /// Install the updates found by `check_updates` as one batch
+    ///
+    /// Triggers run once, after the whole batch.
+    pub async fn install_updates(&self, update_info: &UpdateInfo) -> crate::Result<UpdateResult> {
+        let started = self.start_trigger_batch();
+        let result = self.install_update_batch(update_info).await;
+        self.finish_trigger_batch(started).await;
+        result
+    }
+
+    /// Install updates as one batch, leaving triggers to the caller
+    async fn install_update_batch(&self, update_info: &UpdateInfo) -> crate::Result<UpdateResult> {
+        // Refuse the whole batch rather than stop partway through it
+        self.plan_updates(update_info).await?.check_space()?;
+
+        // Tag every transaction so the whole batch can be undone at once
+        let batch = ulid::Ulid::new().to_string();
+
+        let mut succeeded = Vec::new();
+        let mut failed = Vec::new();
+        let mut requires_reboot = Vec::new();
+
+        for update in &update_info.available {
+            match self
+                .install_package_in_batch(
+                    &update.name,
+                    Some(&update.new_version),
+                    update.kind,
+                    Some(batch.clone()),
+                    None,
+                    InstallOptions::default(),
+                )
+                .await
+            {
+                Ok(TransactionResult::Success {
+                    activated,
+                    requires_reboot: reboot,
+                }) => {
+                    succeeded.push(update.name.clone());
+                    requires_reboot.extend(reboot);
+                    if activated.contains(&update.name) {
+                        println!("Updated {} to {}", update.name, update.new_version);
+                    }
+                }
+                Ok(TransactionResult::Failed { error, .. }) => {
+                    failed.push((update.name.clone(), error));
+                }
+                Ok(TransactionResult::RolledBack { reason, .. }) => {
+                    failed.push((update.name.clone(), reason));
+                }
+                Err(e) => {
+                    failed.push((update.name.clone(), e.to_string()));
+                }
+            }
+        }
+
+        Ok(UpdateResult {
+            succeeded,
+            failed,
+            held: update_info.held.iter().map(|u| u.name.clone()).collect(),
+            requires_reboot,
+        })
+    }
+
+    /// Rollback to a previous version
+    pub async fn rollback(
+        &self,
+        package: &str,
+        version: Option<&str>,
+    ) -> crate::Result<TransactionResult> {
+        let rollback_version = self.rollback_target(package, version).await?;
+        let versions = vec![(package.to_string(), rollback_version)];
+
+        if Self::installed_kind(&*self.registry.read().await, package).is_app() {
+            self.activate_versions(versions, None, Vec::new()).await
+        } else {
+            self.activate_versions(Vec::new(), None, versions).await
+        }
+    }
+
+    /// Plan a rollback without changing anything
+    pub async fn plan_rollback(&self, package: &str, version: Option<&str>) -> crate::Result<Plan> {
+        let version = self.rollback_target(package, version).await?;
+        let kind = Self::installed_kind(&*self.registry.read().await, package);
+
+        let mut plan = Plan::new();
+        if kind.is_app() {
+            let layout = &self.app_layout;
+            let link = layout.current_path(package);
+            plan.steps.push(PlanStep::Switch {
+                name: package.to_string(),
+                from: link.read_link().ok(),
+                link,
+                to: Some(layout.version_path(package, &version.as_str())),
+            });
+        } else {
+            plan.steps.push(PlanStep::Activate {
+                name: package.to_string(),
+                version: version.to_string(),
+                path: self.system_layout.package_path(package, &version.as_str()),
+            });
+            plan.requires_reboot.push(package.to_string());
+        }
+
+        Ok(plan)
+    }
+
+    /// Get the version a rollback activates: the requested one, or the
+    /// newest version older than the active one
+    async fn rollback_target(
+        &self,
+        package: &str,
+        version: Option<&str>,
+    ) -> crate::Result<Version> {
+        if let Some(v) = version {
+            Version::parse(v)
+        } else {
+            // Get the newest version older than the active one
+            let registry = self.registry.read().await;
+            let active = registry
+                .get_active(package)
+                .ok_or_else(|| crate::Error::PackageNotFound(package.to_string()))?;
+
+            registry
+                .list_versions(package)
+                .into_iter()
+                .filter(|v| v < active)
+                .max()
+                .ok_or_else(|| {
+                    crate::Error::Other("No previous version to rollback to".to_string())
+                })
+        }
+    }
+
+    /// Activate installed versions in a single rollback transaction
+    ///
+    /// The recorded transaction stores the versions that were active before
+    /// it ran, so it can itself be undone.
+    async fn activate_versions(
+        &self,
+        app_versions: Vec<(String, Version)>,
+        system_version: Option<Version>,
+        package_versions: Vec<(String, Version)>,
+    ) -> crate::Result<TransactionResult> {
+        let mut transaction = Transaction::new(TransactionKind::Rollback, vec![])
+            .with_app_layout(self.app_layout.clone())
+            .with_system_layout(self.system_layout.clone());
+        transaction.rollback_info.previous_app_versions = app_versions.clone();
+        transaction.rollback_info.previous_system_version = system_version.clone();
+        transaction.rollback_info.previous_package_versions = package_versions.clone();
+
+        let result = transaction.execute().await;
+
+        // Update registry if successful
+        if matches!(result, TransactionResult::Success { .. }) {
+            let mut registry = self.registry.write().await;
+
+            transaction.rollback_info.previous_app_versions = app_versions
+                .iter()
+                .filter_map(|(name, _)| {
+                    registry.get_active(name).map(|v| (name.clone(), v.clone()))
+                })
+                .collect();
+            transaction.rollback_info.previous_system_version = system_version
+                .as_ref()
+                .and_then(|_| registry.get_system_version().cloned());
+            transaction.rollback_info.previous_package_versions = package_versions
+                .iter()
+                .filter_map(|(name, _)| {
+                    registry.get_active(name).map(|v| (name.clone(), v.clone()))
+                })
+                .collect();
+
+            for (name, version) in app_versions.into_iter().chain(package_versions) {
+                registry.set_active(name, version);
+            }
+            if let Some(version) = system_version {
+                registry.set_system_version(version);
+            }
+
+            registry.record_transaction(transaction);
+            let _ = registry.save_to_path(&self.registry_path);
+        }
+
+        self.publish_state(&result);
+        Ok(result)
+    }
+
+    /// Get recent transactions, oldest first
+    pub async fn history(&self, count: usize) -> Vec<Transaction> {
+        self.registry
+            .read()
+            .await
+            .get_recent_transactions(count)
+            .to_vec()
+    }
+
+    /// Find the transactions named by an ID, batch ID, or unique prefix
+    pub async fn find_transactions(&self, id: &str) -> crate::Result<Vec<Transaction>> {
+        let registry = self.registry.read().await;
+        Ok(registry
+            .resolve_transactions(id)?
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+
+    /// Undo a transaction or a whole batch of transactions
+    ///
+    /// Transactions are reverted newest first: upgrades and rollbacks
+    /// re-activate the versions recorded in their `RollbackInfo`, fresh
+    /// installs are removed, and removals are reinstalled.
+    pub async fn undo(&self, id: &str) -> crate::Result<TransactionResult> {
+        let transactions = self.plan_undo(id).await?;
+
+        let mut activated = Vec::new();
+        let mut requires_reboot = Vec::new();
+
+        for tx in &transactions {
+            let result = match self.undo_transaction(tx).await {
+                Ok(result) => result,
+                Err(e) => TransactionResult::Failed {
+                    error: e.to_string(),
+                    partial: Vec::new(),
+                },
+            };
+            match result {
+                TransactionResult::Success {
+                    activated: a,
+                    requires_reboot: r,
+                } => {
+                    activated.extend(a);
+                    requires_reboot.extend(r);
+                }
+                TransactionResult::Failed { error, mut partial } => {
+                    partial.splice(0..0, activated);
+                    return Ok(TransactionResult::Failed {
+                        error: format!("Undo of {} failed: {}", tx.id, error),
+                        partial,
+                    });
+                }
+                // Transactions undone before this one stay undone
+                TransactionResult::RolledBack { reason } if activated.is_empty() => {
+                    return Ok(TransactionResult::RolledBack {
+                        reason: format!("Undo of {} rolled back: {}", tx.id, reason),
+                    });
+                }
+                TransactionResult::RolledBack { reason } => {
+                    return Ok(TransactionResult::Failed {
+                        error: format!("Undo of {} rolled back: {}", tx.id, reason),
+                        partial: activated,
+                    });
+                }
+            }
+        }
+
+        Ok(TransactionResult::Success {
+            activated,
+            requires_reboot,
+        })
+    }
+
+    /// Find the transactions [`PackageManager::undo`] would revert, newest
+    /// first
+    ///
+    /// The whole batch is checked, so undo fails before reverting any of it.
+    pub async fn plan_undo(&self, id: &str) -> crate::Result<Vec<Transaction>> {
+        let mut transactions = self.find_transactions(id).await?;
+        for tx in &transactions {
+            Self::check_undo(tx)?;
+        }
+        transactions.reverse();
+        Ok(transactions)
+    }
+
+    /// Check that a transaction can be undone without changing anything
+    fn check_undo(tx: &Transaction) -> crate::Result<()> {
+        if !tx.is_completed() {
+            return Err(crate::Error::TransactionFailed(format!(
+                "Transaction {} is {} and cannot be undone",
+                tx.id, tx.state
+            )));
+        }
+        if tx.kind == TransactionKind::Remove {
+            return Ok(());
+        }
+
+        let info = &tx.rollback_info;
+        let fresh = Self::fresh_packages(tx).count();
+        if info.previous_app_versions.is_empty()
+            && info.previous_system_version.is_none()
+            && info.previous_package_versions.is_empty()
+            && fresh == 0
+        {
+            return Err(crate::Error::Other(format!(
+                "Transaction {} ({}) has nothing to undo",
+                tx.id, tx.kind
+            )));
+        }
+        Ok(())
+    }
+
+    /// Packages a transaction installed for the first time, which have no
+    /// previous version to restore
+    fn fresh_packages(tx: &Transaction) -> impl Iterator<Item = &Package> {
+        let info = &tx.rollback_info;
+        tx.packages.iter().filter(move |p| {
+            !info
+                .previous_app_versions
+                .iter()
+                .chain(&info.previous_package_versions)
+                .any(|(n, _)| n == p.name())
+        })
+    }
+
+    /// Run the inverse of a single transaction
+    async fn undo_transaction(&self, tx: &Transaction) -> crate::Result<TransactionResult> {
+        Self::check_undo(tx)?;
+
+        if tx.kind == TransactionKind::Remove {
+            // Removed versions are gone from disk, so reinstall them from
+            // the archive kept in the cache when there is one
+            let mut activated = Vec::new();
+            let mut requires_reboot = Vec::new();
+
+            for package in &tx.packages {
+                let version = package.version().to_string();
+                let reason = tx
+                    .rollback_info
+                    .removed_reasons
+                    .iter()
+                    .find(|(name, _)| name == package.name())
+                    .map(|(_, reason)| *reason)
+                    .unwrap_or_default();
+                let batch = Some(ulid::Ulid::new().to_string());
+                let result = match self.retained_build(package.name(), &version) {
+                    Some(entry) => {
+                        self.install_entry(&entry, package.kind(), batch, Some(reason))
+                            .await?
+                    }
+                    None => {
+                        self.install_package_in_batch(
+                            package.name(),
+                            Some(&version),
+                            package.kind(),
+                            batch,
+                            Some(reason),
+                            InstallOptions::default(),
+                        )
+                        .await?
+                    }
+                };
+                match result {
+                    TransactionResult::Success {
+                        activated: a,
+                        requires_reboot: r,
+                    } => {
+                        activated.extend(a);
+                        requires_reboot.extend(r);
+                    }
+                    other => return Ok(other),
+                }
+            }
+
+            return Ok(TransactionResult::Success {
+                activated,
+                requires_reboot,
+            });
+        }
+
+        self.revert_activation(tx).await
+    }
+
+    /// Re-activate the versions a transaction replaced and remove the
+    /// packages it freshly installed
+    async fn revert_activation(&self, tx: &Transaction) -> crate::Result<TransactionResult> {
+        let info = &tx.rollback_info;
+        let fresh: Vec<&Package> = Self::fresh_packages(tx).collect();
+
+        let mut activated = Vec::new();
+        let mut requires_reboot = Vec::new();
+
+        if !info.previous_app_versions.is_empty()
+            || info.previous_system_version.is_some()
+            || !info.previous_package_versions.is_empty()
+        {
+            match self
+                .activate_versions(
+                    info.previous_app_versions.clone(),
+                    info.previous_system_version.clone(),
+                    info.previous_package_versions.clone(),
+                )
+                .await?
+            {
+                TransactionResult::Success {
+                    activated: a,
+                    requires_reboot: r,
+                } => {
+                    activated.extend(a);
+                    requires_reboot.extend(r);
+                }
+                other => return Ok(other),
+            }
+        }
+
+        for package in fresh {
+            if !package.kind().is_app() {
+                self.discard_system_package(package).await?;
+                activated.push(package.name().to_string());
+                continue;
+            }
+            match self.remove_package(package.name()).await? {
+                TransactionResult::Success { activated: a, .. } => activated.extend(a),
+                TransactionResult::Failed { error, mut partial } => {
+                    partial.splice(0..0, activated);
+                    return Ok(TransactionResult::Failed { error, partial });
+                }
+                other => return Ok(other),
+            }
+        }
+
+        Ok(TransactionResult::Success {
+            activated,
+            requires_reboot,
+        })
+    }
+
+    /// Drop a system-level package version that an undone transaction
+    /// installed for the first time
+    ///
+    /// Such versions only take effect on the next boot, so their directory
+    /// is deleted unless the system image currently points at it.
+    async fn discard_system_package(&self, package: &Package) -> crate::Result<()> {
+        let name = package.name();
+        let version = package.version().as_str();
+        if name == "system" && self.system_layout.current_version()?.as_deref() == Some(&version) {
+            return Err(crate::Error::Other(format!(
+                "system {} is the current system and cannot be discarded",
+                version
+            )));
+        }
+
+        let path = self.system_layout.package_path(name, &version);
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+
+        let mut registry = self.registry.write().await;
+        registry.unregister_package(name, package.version());
+        registry.save_to_path(&self.registry_path)
+    }
+
+    /// Get the versions of a package available for the target architecture,
+    /// together with the channel the package follows
+    async fn available_versions(
+        &self,
+        name: &str,
+        kind: PackageKind,
+    ) -> crate::Result<(Vec<Version>, Channel)> {
+        let sources = self.sources.read().await;
+        let sources_for_type = sources.sources_for_kind(kind);
+
+        let index = fetch::fetch_index_with(
+            self.backend.as_ref(),
+            &sources_for_type,
+            Some(self.fetch_options.clone()),
+        )
+        .await?;
+        let channel = sources.channel_for(name, &index.source);
+
+        let candidates: Vec<Version> = index
+            .packages
+            .iter()
+            .filter(|p| p.name == name && p.supports_arch(&self.arch))
+            .filter_map(|p| Version::parse(&p.version).ok())
+            .collect();
+
+        if candidates.is_empty() {
+            return Err(crate::Error::PackageNotFound(format!(
+                "{} (for {})",
+                name, self.arch
+            )));
+        }
+
+        Ok((candidates, channel))
+    }
+
+    /// Get latest version of a package
+    async fn get_latest_version(&self, name: &str, kind: PackageKind) -> crate::Result<String> {
+        let (candidates, channel) = self.available_versions(name, kind).await?;
+        let pins = self.pins.read().await;
+
+        // Pick the newest version the package's channel and pin allow
+        let latest = candidates
+            .into_iter()
+            .filter(|v| channel.allows(v) && pins.allows(name, v))
+            .max()
+            .ok_or_else(|| {
+                let pin = pins
+                    .get(name)
+                    .map(|p| format!(", {}", p))
+                    .unwrap_or_default();
+                crate::Error::VersionNotFound(format!(
+                    "no version of {} is available on channel {}{}",
+                    name, channel, pin
+                ))
+            })?;
+
+        Ok(latest.to_string())
+    }
+
+    /// Compute the changes needed to reach a desired world
+    ///
+    /// The world's sources and channel overrides are applied to the
+    /// in-memory sources first, so versions resolve the way they will once
+    /// the plan is applied. Nothing is written to disk.
+    pub async fn plan_world(&self, world: &WorldConfig) -> crate::Result<WorldPlan> {
+        world.validate()?;
+
+        let mut plan = WorldPlan::default();
+
+        // Resolve versions with the world's sources, leaving the manager's
+        // own sources alone until the plan is applied
+        let mut sources = self.sources.read().await.clone();
+        plan.sources = world.apply_sources(&mut sources);
+        plan.channels = world.apply_channels(&mut sources);
+        let planner = Self {
+            sources: Arc::new(RwLock::new(sources.clone())),
+            ..self.clone()
+        };
+        plan.sources_config = sources;
+        plan.pins = world.pin_changes(&*self.pins.read().await)?;
+
+        for (name, package) in &world.packages {
+            let current = self.registry.read().await.get_active(name).cloned();
+
+            // Installed versions that satisfy the world are left alone
+            if current.as_ref().is_some_and(|v| package.allows(v)) {
+                continue;
+            }
+
+            let kind = planner.resolve_kind(name).await?;
+            let (candidates, channel) = planner.available_versions(name, kind).await?;
+            let version = candidates
+                .into_iter()
+                .filter(|v| channel.allows(v) && package.allows(v))
+                .max()
+                .ok_or_else(|| {
+                    crate::Error::VersionNotFound(format!(
+                        "no version of {} matching {} is available on channel {}",
+                        name, package.version, channel
+                    ))
+                })?;
+
+            let name = name.clone();
+            plan.changes.push(match current {
+                None => WorldChange::Install {
+                    name,
+                    version,
+                    kind,
+                },
+                Some(from) if version > from => WorldChange::Upgrade {
+                    name,
+                    from,
+                    to: version,
+                    kind,
+                },
+                Some(from) => WorldChange::Downgrade {
+                    name,
+                    from,
+                    to: version,
+                    kind,
+                },
+            });
+        }
+
+        // Apps that are not part of the world are removed, unless a pin or
+        // hold says to keep them; dependencies are left to autoremove
+        let registry = self.registry.read().await;
+        let pins = self.pins.read().await;
+        let mut removals = Vec::new();
+        for (name, version) in &registry.active {
+            if world.packages.contains_key(name)
+                || name == "system"
+                || !Self::installed_kind(&registry, name).is_app()
+                || registry.get_reason(name) != InstallReason::Explicit
+            {
+                continue;
+            }
+            if pins.get(name).is_some() {
+                plan.kept.push(name.clone());
+                continue;
+            }
+            removals.push(WorldChange::Remove {
+                name: name.clone(),
+                version: version.clone(),
+            });
+        }
+        removals.sort_by(|a, b| a.name().cmp(b.name()));
+        plan.changes.extend(removals);
+        plan.kept.sort();
+
+        Ok(plan)
+    }
+
+    /// Apply a plan computed by [`PackageManager::plan_world`]
+    ///
+    /// All package changes share one batch. If any of them fails, the
+    /// changes already made are undone and nothing else is written, so the
+    /// system either reaches the world or stays as it was. Triggers run
+    /// once, after the whole batch.
+    pub async fn apply_world(&self, plan: &WorldPlan) -> crate::Result<TransactionResult> {
+        let started = self.start_trigger_batch();
+        let result = self.apply_world_batch(plan).await;
+        self.finish_trigger_batch(started).await;
+        result
+    }
+
+    /// Apply a world plan as one batch, leaving triggers to the caller
+    async fn apply_world_batch(&self, plan: &WorldPlan) -> crate::Result<TransactionResult> {
+        let batch = ulid::Ulid::new().to_string();
+        let previous_pins = self.pins.read().await.clone();
+
+        // The plan already honors the world's pins, so old pins must not
+        // block the packages it changes
+        {
+            let mut pins = self.pins.write().await;
+            for change in &plan.changes {
+                pins.unpin(change.name());
+            }
+        }
+
+        // Packages are installed from the world's sources
+        let sources_changed = !plan.sources.is_empty() || !plan.channels.is_empty();
+        let previous_sources = if sources_changed {
+            let mut sources = self.sources.write().await;
+            Some(std::mem::replace(
+                &mut *sources,
+                plan.sources_config.clone(),
+            ))
+        } else {
+            None
+        };
+
+        let mut activated = Vec::new();
+        let mut requires_reboot = Vec::new();
+        let mut applied = 0;
+        let mut failure = None;
+
+        for change in &plan.changes {
+            let result = match change {
+                WorldChange::Install {
+                    name,
+                    version,
+                    kind,
+                }
+                | WorldChange::Upgrade {
+                    name,
+                    to: version,
+                    kind,
+                    ..
+                }
+                | WorldChange::Downgrade {
+                    name,
+                    to: version,
+                    kind,
+                    ..
+                } => {
+                    self.install_package_in_batch(
+                        name,
+                        Some(&version.to_string()),
+                        *kind,
+                        Some(batch.clone()),
+                        Some(InstallReason::Explicit),
+                        InstallOptions::default(),
+                    )
+                    .await
+                }
+                WorldChange::Remove { name, .. } => {
+                    self.remove_package_in_batch(
+                        name,
+                        Some(batch.clone()),
+                        RemoveOptions::default(),
+                    )
+                    .await
+                }
+            };
+
+            match result {
+                Ok(TransactionResult::Success {
+                    activated: a,
+                    requires_reboot: r,
+                }) => {
+                    activated.extend(a);
+                    requires_reboot.extend(r);
+                    applied += 1;
+                }
+                Ok(TransactionResult::Failed { error, .. }) => failure = Some((change, error)),
+                Ok(TransactionResult::RolledBack { reason }) => failure = Some((change, reason)),
+                Err(e) => failure = Some((change, e.to_string())),
+            }
+
+            if failure.is_some() {
+                break;
+            }
+        }
+
+        let mut pins = previous_pins;
+
+        if let Some((change, error)) = failure {
+            *self.pins.write().await = pins;
+            let reason = format!("failed to {}: {}", change, error);
+            if let Some(sources) = previous_sources {
+                *self.sources.write().await = sources;
+            }
+
+            if applied > 0 {
+                return Ok(self.undo_failed_batch(&batch, reason, activated).await);
+            }
+
+            return Ok(TransactionResult::RolledBack { reason });
+        }
+
+        // Record configuration only once the packages are in place
+        for (name, pin) in &plan.pins {
+            match pin {
+                Some(pin) => {
+                    pins.pins.insert(name.clone(), pin.clone());
+                }
+                None => {
+                    pins.unpin(name);
+                }
+            }
+        }
+        pins.save_to_path(&self.pins_path)?;
+        *self.pins.write().await = pins;
+
+        if sources_changed {
+            self.sources.read().await.save_to_path(&self.sources_path)?;
+        }
+
+        Ok(TransactionResult::Success {
+            activated,
+            requires_reboot,
+        })
+    }
+
+    /// Undo what a batch applied before one of its steps failed
+    ///
+    /// The batch only counts as rolled back if the undo succeeds; otherwise
+    /// it failed, listing the packages the undo reverted before it stopped.
+    async fn undo_failed_batch(
+        &self,
+        batch: &str,
+        reason: String,
+        activated: Vec<String>,
+    ) -> TransactionResult {
+        let (error, partial) = match self.undo(batch).await {
+            Ok(TransactionResult::Success { .. }) => {
+                return TransactionResult::RolledBack { reason };
+            }
+            Ok(TransactionResult::Failed { error, partial }) => (error, partial),
+            Ok(TransactionResult::RolledBack { reason }) => (reason, activated),
+            Err(e) => (e.to_string(), activated),
+        };
+
+        TransactionResult::Failed {
+            error: format!("{}; undoing batch {} failed: {}", reason, batch, error),
+            partial,
+        }
+    }
+
+    /// Record the active versions of all packages as a named snapshot
+    pub async fn create_snapshot(
+        &self,
+        name: &str,
+        description: Option<String>,
+    ) -> crate::Result<Snapshot> {
+        let mut registry = self.registry.write().await;
+        let snapshot = registry.create_snapshot(name, description)?.clone();
+        registry.save_to_path(&self.registry_path)?;
+        Ok(snapshot)
+    }
+
+    /// Build the snapshot [`PackageManager::create_snapshot`] would record,
+    /// without recording it
+    pub async fn plan_snapshot(
+        &self,
+        name: &str,
+        description: Option<String>,
+    ) -> crate::Result<Snapshot> {
+        let mut registry = self.registry.read().await.clone();
+        Ok(registry.create_snapshot(name, description)?.clone())
+    }
+
+    /// List snapshots, oldest first
+    pub async fn list_snapshots(&self) -> Vec<Snapshot> {
+        self.registry.read().await.list_snapshots().to_vec()
+    }
+
+    /// Delete a snapshot
+    pub async fn delete_snapshot(&self, name: &str) -> crate::Result<()> {
+        let mut registry = self.registry.write().await;
+        if !registry.delete_snapshot(name) {
+            return Err(crate::Error::Other(format!("Snapshot not found: {}", name)));
+        }
+        registry.save_to_path(&self.registry_path)
+    }
+
+    /// Compare a snapshot against the active versions
+    pub async fn snapshot_diff(&self, name: &str) -> crate::Result<SnapshotDiff> {
+        let registry = self.registry.read().await;
+        let snapshot = registry
+            .get_snapshot(name)
+            .ok_or_else(|| crate::Error::Other(format!("Snapshot not found: {}", name)))?;
+        Ok(snapshot.diff(&registry.active))
+    }
+
+    /// Restore the versions recorded in a snapshot
+    ///
+    /// Apps, the system version, kernels and other system-level packages
+    /// are switched in one rollback transaction; system-level packages take
+    /// effect on the next boot. Packages installed after the snapshot was
+    /// taken are left untouched.
+    pub async fn restore_snapshot(&self, name: &str) -> crate::Result<TransactionResult> {
+        let registry = self.registry.read().await;
+        let snapshot = registry
+            .get_snapshot(name)
+            .ok_or_else(|| crate::Error::Other(format!("Snapshot not found: {}", name)))?
+            .clone();
+        let diff = snapshot.diff(&registry.active);
+
+        // Every recorded version must still be on disk
+        for (package, _, version) in &diff.changed {
+            if !registry.is_installed(package, version) {
+                return Err(crate::Error::VersionNotFound(format!(
+                    "{} {} is no longer installed",
+                    package, version
+                )));
+            }
+        }
+        drop(registry);
+
+        let mut app_versions = Vec::new();
+        let mut system_version = None;
+        let mut package_versions = Vec::new();
+
+        for (package, _, version) in diff.changed {
+            if package == "system" {
+                system_version = Some(version);
+            } else if snapshot.kind(&package) == PackageKind::App {
+                app_versions.push((package, version));
+            } else {
+                package_versions.push((package, version));
+            }
+        }
+
+        if app_versions.is_empty() && system_version.is_none() && package_versions.is_empty() {
+            return Ok(TransactionResult::Success {
+                activated: Vec::new(),
+                requires_reboot: Vec::new(),
+            });
+        }
+
+        // One transaction switches every version or none of them
+        self.activate_versions(app_versions, system_version, package_versions)
+            .await
+    }
+
+    /// Get system status
+    pub async fn get_status(&self) -> crate::Result<SystemStatus> {
+        let registry = self.registry.read().await;
+        let sources = self.sources.read().await;
+
+        let stats = registry.stats();
+        let source_stats = sources.stats();
+
+        Ok(SystemStatus {
+            total_packages: stats.total_packages,
+            active_packages: stats.active_count,
+            pending_updates: stats.pending_count,
+            sources_total: source_stats.total,
+            sources_enabled: source_stats.enabled,
+        })
+    }
+
+    /// List installed packages
+    pub async fn list_installed(&self) -> crate::Result<Vec<InstalledPackage>> {
+        let registry = self.registry.read().await;
+
+        let mut packages = Vec::new();
+
+        for (name, versions) in &registry.packages {
+            if let Some(active) = registry.get_active(name) {
+                packages.push(InstalledPackage {
+                    name: name.clone(),
+                    version: active.to_string(),
+                    versions: versions.iter().map(|v| v.to_string()).collect(),
+                    kind: Self::installed_kind(&registry, name),
+                    reason: registry.get_reason(name),
+                });
+            }
+        }
+
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(packages)
+    }
+
+    /// List the packages the enabled sources offer for the target
+    /// architecture
+    ///
+    /// Each package appears once, at the newest version its channel allows.
+    /// Sources that cannot be reached are skipped.
+    pub async fn available_packages(&self) -> crate::Result<Vec<AvailablePackage>> {
+        let sources = self.sources.read().await;
+        let mut packages: BTreeMap<String, AvailablePackage> = BTreeMap::new();
+
+        for kind in [PackageKind::Kernel, PackageKind::System, PackageKind::App] {
+            let sources_for_kind = sources.sources_for_kind(kind);
+            if sources_for_kind.is_empty() {
+                continue;
+            }
+
+            let index = match fetch::fetch_index_with(
+                self.backend.as_ref(),
+                &sources_for_kind,
+                Some(self.fetch_options.clone()),
+            )
+            .await
+            {
+                Ok(index) => index,
+                Err(e) => {
+                    log::warn!("Failed to fetch {} index: {}", kind, e);
+                    continue;
+                }
+            };
+
+            for entry in &index.packages {
+                let Ok(version) = Version::parse(&entry.version) else {
+                    continue;
+                };
+                if !entry.supports_arch(&self.arch)
+                    || !sources
+                        .channel_for(&entry.name, &index.source)
+                        .allows(&version)
+                    || packages
+                        .get(&entry.name)
+                        .is_some_and(|p| p.version >= version)
+                {
+                    continue;
+                }
+
+                packages.insert(
+                    entry.name.clone(),
+                    AvailablePackage {
+                        name: entry.name.clone(),
+                        version,
+                        kind: entry.kind_or(kind),
+                        description: entry.description.clone(),
+                        size: entry.size,
+                        installed_size: entry.installed_size,
+                        dependencies: entry.dependencies.clone(),
+                        source: index.source.clone(),
+                        installed: None,
+                    },
+                );
+            }
+        }
+
+        let registry = self.registry.read().await;
+        Ok(packages
+            .into_values()
+            .map(|mut package| {
+                package.installed = registry.get_active(&package.name).cloned();
+                package
+            })
+            .collect())
+    }
+
+    /// Publish what a successful transaction changed in the update state
+    ///
+    /// The transaction has already happened, so failures are logged rather
+    /// than returned.
+    fn publish_state(&self, result: &TransactionResult) {
+        let TransactionResult::Success {
+            activated,
+            requires_reboot,
+        } = result
+        else {
+            return;
+        };
+
+        if !requires_reboot.is_empty() {
+            if let Err(e) = RebootRequired::record(&self.state_dir, requires_reboot) {
+                log::warn!("Failed to record that a reboot is required: {}", e);
+            }
+        }
+
+        if let Some(mut summary) = UpdateSummary::load(&self.state_dir) {
+            if summary.remove(activated) {
+                if let Err(e) = summary.save(&self.state_dir) {
+                    log::warn!("Failed to save the update summary: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Get the directory installed packages' manifests are kept in
+    fn manifests_dir(&self) -> PathBuf {
+        self.registry_path.with_file_name(sbom::MANIFESTS_DIR)
+    }
+
+    /// Describe the active packages for a software bill of materials
+    pub async fn sbom_packages(&self) -> Vec<SbomPackage> {
+        sbom::installed_packages(&*self.registry.read().await, &self.manifests_dir())
+    }
+
+    /// Produce a software bill of materials of the active packages
+    pub async fn sbom(&self, format: SbomFormat, name: &str) -> serde_json::Value {
+        sbom::document(format, name, &self.sbom_packages().await)
+    }
+
+    /// Remove a package
+    pub async fn remove_package(&self, name: &str) -> crate::Result<TransactionResult> {
+        self.remove_package_with(name, RemoveOptions::default())
+            .await
+    }
+
+    /// Remove a package with options
+    ///
+    /// Packages that other installed packages depend on are refused unless
+    /// `force` is set.
+    pub async fn remove_package_with(
+        &self,
+        name: &str,
+        options: RemoveOptions,
+    ) -> crate::Result<TransactionResult> {
+        self.remove_package_in_batch(name, None, options).await
+    }
+
+    /// Plan the removal of a package without changing anything
+    pub async fn plan_remove(&self, name: &str, options: RemoveOptions) -> crate::Result<Plan> {
+        let registry = self.registry.read().await;
+        let version = registry
+            .get_active(name)
+            .ok_or_else(|| crate::Error::PackageNotFound(name.to_string()))?;
+
+        if !Self::installed_kind(&registry, name).is_app() {
+            return Err(crate::Error::Other(format!(
+                "Cannot remove system package: {}",
+                name
+            )));
+        }
+        if !options.force {
+            Self::check_dependents(&registry, name)?;
+        }
+
+        let layout = &self.app_layout;
+        let link = layout.current_path(name);
+
+        let mut plan = Plan::new();
+        plan.steps.push(PlanStep::Switch {
+            name: name.to_string(),
+            from: link.read_link().ok(),
+            link,
+            to: None,
+        });
+        plan.steps.push(PlanStep::Remove {
+            name: name.to_string(),
+            version: version.to_string(),
+            path: layout.version_path(name, &version.as_str()),
+        });
+        if options.purge {
+            for path in self.purge_paths(&registry, name) {
+                plan.steps.push(PlanStep::Purge {
+                    name: name.to_string(),
+                    path,
+                });
+            }
+        }
+
+        Ok(plan)
+    }
+
+    /// Get the config files and directories a purge deletes
+    fn purge_paths(&self, registry: &PackageRegistry, name: &str) -> Vec<PathBuf> {
+        let conffiles = registry
+            .conffiles_of(name)
+            .into_iter()
+            .map(|path| self.conffile_path(&path))
+            .flat_map(|path| [conffile::new_path(&path), path])
+            .filter(|path| path.exists());
+
+        conffiles.chain(self.app_layout.state_paths(name)).collect()
+    }
+
+    /// Refuse to remove a package that installed packages depend on
+    fn check_dependents(registry: &PackageRegistry, name: &str) -> crate::Result<()> {
+        let dependents = registry.reverse_dependencies(name);
+        if dependents.is_empty() {
+            return Ok(());
+        }
+
+        Err(crate::Error::Other(format!(
+            "{} is required by {} (use --force to remove it anyway)",
+            name,
+            dependents.join(", ")
+        )))
+    }
+
+    /// Remove a package, tagging its transaction with a batch ID
+    async fn remove_package_in_batch(
+        &self,
+        name: &str,
+        batch: Option<String>,
+        options: RemoveOptions,
+    ) -> crate::Result<TransactionResult> {
+        // Get package metadata
+        let registry = self.registry.read().await;
+        let version = registry
+            .get_active(name)
+            .ok_or_else(|| crate::Error::PackageNotFound(name.to_string()))?;
+
+        if !options.force {
+            Self::check_dependents(&registry, name)?;
+        }
+
+        let kind = Self::installed_kind(&registry, name);
+
+        // Create metadata for removal
+        let metadata = PackageMetadata::new(
+            name.to_string(),
+            version.clone(),
+            kind,
+            0,
+            "0".repeat(64),
+            crate::signature::PackageSignature::new([0u8; 64]),
+            String::new(),
+        );
+
+        // A package being removed can't handle its own triggers
+        let triggers = registry
+            .all_triggers()
+            .into_iter()
+            .filter(|registration| registration.owner != name)
+            .collect();
+
+        let package = Package::new(metadata);
+        let mut transaction = Transaction::new(TransactionKind::Remove, vec![package])
+            .with_batch(batch)
+            .with_app_layout(self.app_layout.clone())
+            .with_system_layout(self.system_layout.clone())
+            .with_triggers(triggers)
+            .with_deferred_triggers(self.batching_triggers());
+
+        let result = transaction.execute().await;
+        self.defer_trigger_paths(transaction.deferred_paths());
+
+        // Update registry if successful
+        if matches!(result, TransactionResult::Success { .. }) {
+            let version = version.clone();
+            drop(registry);
+            let mut registry = self.registry.write().await;
+            let purge = if options.purge {
+                self.purge_paths(&registry, name)
+            } else {
+                Vec::new()
+            };
+            transaction
+                .rollback_info
+                .removed_reasons
+                .push((name.to_string(), registry.get_reason(name)));
+            registry.unregister_package(name, &version);
+            if options.purge {
+                registry.forget_conffiles(name);
+            }
+            registry.record_transaction(transaction);
+            let _ = registry.save_to_path(&self.registry_path);
+            drop(registry);
+
+            // Configuration and data cannot be restored, so they go last
+            for path in purge {
+                if path.is_dir() {
+                    std::fs::remove_dir_all(&path)?;
+                } else {
+                    std::fs::remove_file(&path)?;
+                }
+            }
+        }
+
+        self.publish_state(&result);
+        Ok(result)
+    }
+
+    /// Plan the removal of orphaned dependencies without changing anything
+    pub async fn plan_autoremove(&self) -> crate::Result<Plan> {
+        let orphans = self.registry.read().await.orphans();
+
+        let mut plan = Plan::new();
+        for name in orphans {
+            // Planning leaves the registry alone, so earlier orphans would
+            // still count as dependents of later ones
+            plan.extend(
+                self.plan_remove(
+                    &name,
+                    RemoveOptions {
+                        force: true,
+                        purge: false,
+                    },
+                )
+                .await?,
+            );
+        }
+
+        Ok(plan)
+    }
+
+    /// Remove dependencies that no installed package needs anymore
+    ///
+    /// All removals share one batch; if one fails, the rest are undone.
+    pub async fn autoremove(&self) -> crate::Result<TransactionResult> {
+        let orphans = self.registry.read().await.orphans();
+        let batch = ulid::Ulid::new().to_string();
+
+        let mut activated = Vec::new();
+        let mut applied = 0;
+        let mut failure = None;
+
+        for name in &orphans {
+            match self
+                .remove_package_in_batch(name, Some(batch.clone()), RemoveOptions::default())
+                .await
+            {
+                Ok(TransactionResult::Success { activated: a, .. }) => {
+                    activated.extend(a);
+                    applied += 1;
+                }
+                Ok(TransactionResult::Failed { error, .. }) => failure = Some((name, error)),
+                Ok(TransactionResult::RolledBack { reason }) => failure = Some((name, reason)),
+                Err(e) => failure = Some((name, e.to_string())),
+            }
+
+            if failure.is_some() {
+                break;
+            }
+        }
+
+        if let Some((name, error)) = failure {
+            let reason = format!("failed to remove {}: {}", name, error);
+            if applied > 0 {
+                return Ok(self.undo_failed_batch(&batch, reason, activated).await);
+            }
+            return Ok(TransactionResult::RolledBack { reason });
+        }
+
+        Ok(TransactionResult::Success {
+            activated,
+            requires_reboot: Vec::new(),
+        })
+    }
+
+    /// Plan a garbage collection without changing anything
+    pub async fn plan_gc(&self, keep: usize) -> crate::Result<Plan> {
+        self.gc_plan(&*self.registry.read().await, keep)
+    }
+
+    /// Delete old app versions and cached archives nothing uses anymore
+    ///
+    /// Each app keeps its active version, the `keep` newest of its other
+    /// versions for rollbacks, and any version a snapshot refers to.
+    /// Cached archives are kept for the installed versions only, so undoing
+    /// an older removal downloads the package again. System and kernel
+    /// versions are left alone, since the boot menu may still offer them.
+    pub async fn gc(&self, keep: usize) -> crate::Result<Plan> {
+        let mut registry = self.registry.write().await;
+        let plan = self.gc_plan(&registry, keep)?;
+
+        let mut result = Ok(());
+        for step in &plan.steps {
+            result = self.collect(&mut registry, step);
+            if result.is_err() {
+                break;
+            }
+        }
+
+        // Keep the registry in line with what was deleted before failing
+        registry.save_to_path(&self.registry_path)?;
+        result.map(|()| plan)
+    }
+
+    /// List the app versions and cached archives a garbage collection deletes
+    fn gc_plan(&self, registry: &PackageRegistry, keep: usize) -> crate::Result<Plan> {
+        let mut plan = Plan::new();
+        let mut kept = Vec::new();
+
+        let mut names: Vec<&String> = registry.packages.keys().collect();
+        names.sort();
+        for name in names {
+            let mut versions = registry.list_versions(name);
+            versions.sort_by(|a, b| b.cmp(a));
+
+            if !Self::installed_kind(registry, name).is_app() {
+                kept.extend(versions.into_iter().map(|v| (name.clone(), v)));
+                continue;
+            }
+
+            let active = registry.get_active(name);
+            let in_snapshot = |version: &Version| {
+                registry
+                    .list_snapshots()
+                    .iter()
+                    .any(|s| s.versions.get(name) == Some(version))
+            };
+            let mut inactive = 0;
+            for version in versions {
+                let needed = active == Some(&version) || in_snapshot(&version);
+                if !needed {
+                    inactive += 1;
+                }
+                if needed || inactive <= keep {
+                    kept.push((name.clone(), version));
+                    continue;
+                }
+
+                plan.steps.push(PlanStep::Remove {
+                    path: self.app_layout.version_path(name, &version.as_str()),
+                    name: name.clone(),
+                    version: version.to_string(),
+                });
+            }
+        }
+
+        let prefixes: Vec<String> = kept
+            .iter()
+            .map(|(name, version)| format!("{}-{}-", name, version))
+            .collect();
+        let mut archives = Vec::new();
+        if self.cache_dir.is_dir() {
+            for entry in std::fs::read_dir(&self.cache_dir)? {
+                let path = entry?.path();
+                let file_name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                if path.is_file()
+                    && file_name.ends_with(".rpg")
+                    && !prefixes.iter().any(|p| file_name.starts_with(p))
+                {
+                    archives.push(path);
+                }
+            }
+        }
+        archives.sort();
+        plan.steps
+            .extend(archives.into_iter().map(|path| PlanStep::Clean { path }));
+
+        Ok(plan)
+    }
+
+    /// Carry out one step of a garbage collection
+    fn collect(&self, registry: &mut PackageRegistry, step: &PlanStep) -> crate::Result<()> {
+        match step {
+            PlanStep::Remove {
+                name,
+                version,
+                path,
+            } => {
+                if path.exists() {
+                    std::fs::remove_dir_all(path)?;
+                }
+                registry.unregister_package(name, &Version::parse(version)?);
+
+                // The archive goes too, so the manifest is of no more use
+                let manifest = sbom::manifest_path(&self.manifests_dir(), name, version);
+                if let Err(e) = std::fs::remove_file(manifest) {
+                    if e.kind() != std::io::ErrorKind::NotFound {
+                        return Err(e.into());
+                    }
+                }
+            }
+            PlanStep::Clean { path } => std::fs::remove_file(path)?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for PackageManager {
+    fn default() -> Self {
+        Self::new().expect("Failed to create PackageManager")
+    }
+}
+
+/// Load source credentials, falling back to none
+///
+/// Unprivileged users cannot read the root-only credentials file, and
+/// public sources work without it.
+fn load_credentials(path: impl AsRef<std::path::Path>) -> AuthConfig {
+    AuthConfig::load_from_path(path).unwrap_or_else(|e| {
+        log::warn!("Not using source credentials: {}", e);
+        AuthConfig::default()
+    })
+}
+
+/// Options for installing a package
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InstallOptions {
+    /// Don't install missing dependencies
+    pub no_deps: bool,
+}
+
+/// Options for removing a package
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RemoveOptions {
+    /// Remove the package even if installed packages depend on it
+    pub force: bool,
+    /// Also delete the app's configuration and data directories
+    pub purge: bool,
+}
+
+/// Update information
+#[derive(Debug, Clone)]
+pub struct UpdateInfo {
+    /// Available updates
+    pub available: Vec<PackageUpdate>,
+    /// Updates held back by a pin or hold
+    pub held: Vec<PackageUpdate>,
+    /// Errors encountered
+    pub errors: Vec<String>,
+}
+
+impl UpdateInfo {
+    /// Keep only the updates that fix known advisories
+    pub fn security_only(mut self) -> Self {
+        self.available.retain(PackageUpdate::is_security);
+        self.held.retain(PackageUpdate::is_security);
+        self
+    }
+
+    /// Keep only the updates of one package
+    pub fn for_package(mut self, name: &str) -> Self {
+        self.available.retain(|update| update.name == name);
+        self.held.retain(|update| update.name == name);
+        self
+    }
+}
+
+/// Package update
+#[derive(Debug, Clone)]
+pub struct PackageUpdate {
+    /// Package name
+    pub name: String,
+    /// Current version
+    pub current_version: String,
+    /// New version
+    pub new_version: String,
+    /// Package size in bytes
+    pub size: u64,
+    /// Package kind
+    pub kind: PackageKind,
+    /// Advisories the update fixes
+    pub advisories: Vec<String>,
+    /// Highest severity among the fixed advisories
+    pub severity: Option<Severity>,
+}
+
+impl PackageUpdate {
+    /// Check if the update fixes a known advisory
+    pub fn is_security(&self) -> bool {
+        !self.advisories.is_empty()
+    }
+
+    /// Record the advisories this update fixes
+    fn apply_advisories(&mut self, feed: &AdvisoryFeed) {
+        let (Ok(from), Ok(to)) = (
+            Version::parse(&self.current_version),
+            Version::parse(&self.new_version),
+        ) else {
+            // Not installed yet, so nothing to fix
+            return;
+        };
+
+        for advisory in &feed.advisories {
+            if advisory.package == self.name && advisory.fixed_by(&from, &to) {
+                self.advisories.push(advisory.id.clone());
+                self.severity = self.severity.max(Some(advisory.severity));
+            }
+        }
+    }
+}
+
+/// Update result
+#[derive(Debug, Clone)]
+pub struct UpdateResult {
+    /// Packages that were successfully updated
+    pub succeeded: Vec<String>,
+    /// Packages that failed to update
+    pub failed: Vec<(String, String)>,
+    /// Packages skipped because of a pin or hold
+    pub held: Vec<String>,
+    /// Packages requiring reboot
+    pub requires_reboot: Vec<String>,
+}
+
+/// System status
+#[derive(Debug, Clone)]
+pub struct SystemStatus {
+    /// Total number of packages
+    pub total_packages: usize,
+    /// Number of active packages
+    pub active_packages: usize,
+    /// Number of pending updates
+    pub pending_updates: usize,
+    /// Total number of sources
+    pub sources_total: usize,
+    /// Number of enabled sources
+    pub sources_enabled: usize,
+}
+
+/// Installed package information
+#[derive(Debug, Clone)]
+pub struct InstalledPackage {
+    /// Package name
+    pub name: String,
+    /// Active version
+    pub version: String,
+    /// All installed versions
+    pub versions: Vec<String>,
+    /// Package kind
+    pub kind: PackageKind,
+    /// Why the package is installed
+    pub reason: InstallReason,
+}
+
+/// A package offered by the configured sources
+#[derive(Debug, Clone)]
+pub struct AvailablePackage {
+    /// Package name
+    pub name: String,
+    /// Newest version allowed by the package's channel
+    pub version: Version,
+    /// Package kind
+    pub kind: PackageKind,
+    /// Package description
+    pub description: Option<String>,
+    /// Download size in bytes
+    pub size: u64,
+    /// Size of the extracted package in bytes
+    pub installed_size: Option<u64>,
+    /// Dependencies, as listed in the index
+    pub dependencies: Vec<String>,
+    /// Name of the source that serves it
+    pub source: String,
+    /// Active installed version, if any
+    pub installed: Option<Version>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{create_package, PackageManifest};
+    use crate::backend::MemoryBackend;
+    use crate::fetch::{PackageEntry, RepositoryIndex};
+    use crate::version::VersionConstraint;
+    use tempfile::TempDir;
+
+    const REPO: &str = "http://repo.test/apps";
+    const REPO_KEY: &str = "BwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwc=";
+
+    /// A package manager rooted in a temporary directory, fetching from an
+    /// in-memory repository with a single app source
+    fn test_manager(root: &TempDir) -> (PackageManager, Arc<MemoryBackend>) {
+        let source = Source::new("apps".to_string(), REPO.to_string(), "apps".to_string())
+            .with_trusted_key(repo_key().export_public());
+        test_manager_with_source(root, source)
+    }
+
+    /// Key the test repository signs packages and advisories with
+    fn repo_key() -> crate::signature::KeyPair {
+        crate::signature::KeyPair::import_secret(REPO_KEY).unwrap()
+    }
+
+    /// Like [`test_manager`], with the repository added as the given source
+    fn test_manager_with_source(
+        root: &TempDir,
+        source: Source,
+    ) -> (PackageManager, Arc<MemoryBackend>) {
+        test_manager_with_sources(root, vec![source])
+    }
+
+    /// Like [`test_manager`], with the repository added as several sources
+    fn test_manager_with_sources(
+        root: &TempDir,
+        sources: Vec<Source>,
+    ) -> (PackageManager, Arc<MemoryBackend>) {
+        let sources = SourcesConfig {
+            sources,
+            ..SourcesConfig::default()
+        };
+        sources
+            .save_to_path(root.path().join("etc/rpg/sources.toml"))
+            .unwrap();
+
+        let backend = Arc::new(MemoryBackend::new());
+        let manager = PackageManager::at_root(root.path())
+            .unwrap()
+            .with_backend(backend.clone());
+        (manager, backend)
+    }
+
+    /// Build an app archive and serve it, returning its index entry
+    fn publish(backend: &MemoryBackend, root: &TempDir, name: &str, version: &str) -> PackageEntry {
+        publish_with(backend, root, name, version, |_, _| {})
+    }
+
+    /// Like [`publish`], for an app with dependencies
+    fn publish_with_deps(
+        backend: &MemoryBackend,
+        root: &TempDir,
+        name: &str,
+        version: &str,
+        dependencies: &[&str],
+    ) -> PackageEntry {
+        publish_with(backend, root, name, version, |_, manifest| {
+            manifest.dependencies = dependencies.iter().map(|d| d.to_string()).collect();
+        })
+    }
+
+    /// Like [`publish`], letting the caller add files and edit the manifest
+    /// before the archive is built
+    fn publish_with(
+        backend: &MemoryBackend,
+        root: &TempDir,
+        name: &str,
+        version: &str,
+        customize: impl FnOnce(&std::path::Path, &mut PackageManifest),
+    ) -> PackageEntry {
+        let build_dir = root.path().join("build").join(name).join(version);
+        std::fs::create_dir_all(build_dir.join("files")).unwrap();
+        std::fs::write(build_dir.join("files").join(name), version).unwrap();
+
+        let mut manifest = PackageManifest::new(
+            name.to_string(),
+            version.to_string(),
+            PackageKind::App,
+            crate::arch::host_arch(),
+            0,
+            "0".repeat(64),
+            format!("{}/{}/{}.rpg", REPO, name, version),
+            crate::signature::KeyPair::generate().sign(name.as_bytes()),
+        );
+        customize(&build_dir.join("files"), &mut manifest);
+        let archive_path = build_dir.join("package.rpg");
+        let dependencies = manifest.dependencies.clone();
+        create_package(build_dir.join("files"), &archive_path, manifest).unwrap();
+
+        let bytes = std::fs::read(&archive_path).unwrap();
+        let entry = PackageEntry {
+            name: name.to_string(),
+            version: version.to_string(),
+            description: None,
+            kind: Some(PackageKind::App),
+            arch: crate::arch::host_arch(),
+            size: bytes.len() as u64,
+            installed_size: None,
+            sha256: fetch::compute_checksum(&archive_path).unwrap(),
+            signature: repo_key().sign(&bytes).to_base64(),
+            dependencies,
+            path: format!("{}/{}.rpg", name, version),
+            source: String::new(),
+        };
+        backend.insert(format!("{}/{}", REPO, entry.path), bytes);
+        entry
+    }
+
+    fn serve_index(backend: &MemoryBackend, packages: Vec<PackageEntry>) {
+        let index = RepositoryIndex {
+            name: "apps".to_string(),
+            version: "1".to_string(),
+            last_updated: None,
+            packages,
+            source: String::new(),
+        };
+        backend.insert_index(format!("{}/index.json", REPO), &index);
+    }
+
+    #[tokio::test]
+    async fn test_install_and_update_without_network() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+
+        let v1 = publish(&backend, &root, "editor", "1.0.0");
+        serve_index(&backend, vec![v1.clone()]);
+
+        // Packages that are not installed are not updates
+        let updates = manager.check_updates().await.unwrap();
+        assert!(updates.errors.is_empty());
+        assert!(updates.available.is_empty());
+
+        let result = manager
+            .install_package("editor", None, PackageKind::App)
+            .await
+            .unwrap();
+        assert!(matches!(result, TransactionResult::Success { .. }));
+
+        let current = manager.app_layout().current_path("editor");
+        assert!(current.read_link().unwrap().ends_with("1.0.0"));
+        assert!(manager.check_updates().await.unwrap().available.is_empty());
+
+        // Publish a new version and update everything
+        let v2 = publish(&backend, &root, "editor", "1.1.0");
+        serve_index(&backend, vec![v1, v2]);
+
+        let result = manager.update_all().await.unwrap();
+        assert_eq!(result.succeeded, vec!["editor".to_string()]);
+        assert!(result.failed.is_empty());
+        assert!(current.read_link().unwrap().ends_with("1.1.0"));
+
+        // Reinstalling the active version keeps its directory
+        manager
+            .install_package("editor", Some("1.1.0"), PackageKind::App)
+            .await
+            .unwrap();
+        assert!(current.read_link().unwrap().ends_with("1.1.0"));
+
+        // The registry was saved under the root
+        let registry = PackageRegistry::load_from_path(&manager.registry_path).unwrap();
+        assert_eq!(registry.get_active("editor"), Some(&Version::new(1, 1, 0)));
+        assert!(backend
+            .requests()
+            .contains(&format!("{}/editor/1.1.0.rpg", REPO)));
+    }
+
+    #[tokio::test]
+    async fn test_updates_offer_only_the_newest_allowed_version() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+
+        let v1 = publish(&backend, &root, "editor", "1.0.0");
+        let v11 = publish(&backend, &root, "editor", "1.1.0");
+        let v2 = publish(&backend, &root, "editor", "2.0.0");
+        serve_index(&backend, vec![v1, v11, v2]);
+        manager
+            .install_package("editor", Some("1.0.0"), PackageKind::App)
+            .await
+            .unwrap();
+
+        let updates = manager.check_updates().await.unwrap();
+        assert_eq!(updates.available.len(), 1);
+        assert_eq!(updates.available[0].new_version, "2.0.0");
+        assert!(updates.held.is_empty());
+
+        // A pin picks the newest version it allows, without holding the package
+        manager
+            .pins
+            .write()
+            .await
+            .pin("editor", VersionConstraint::new("~1.0").unwrap());
+        let updates = manager.check_updates().await.unwrap();
+        assert_eq!(updates.available.len(), 0);
+        assert_eq!(updates.held.len(), 1);
+        assert_eq!(updates.held[0].new_version, "2.0.0");
+
+        manager
+            .pins
+            .write()
+            .await
+            .pin("editor", VersionConstraint::new("^1").unwrap());
+        let updates = manager.check_updates().await.unwrap();
+        assert_eq!(updates.available.len(), 1);
+        assert_eq!(updates.available[0].new_version, "1.1.0");
+        assert!(updates.held.is_empty());
+
+        let result = manager.update_all().await.unwrap();
+        assert_eq!(result.succeeded, vec!["editor".to_string()]);
+        let current = manager.app_layout().current_path("editor");
+        assert!(current.read_link().unwrap().ends_with("1.1.0"));
+    }
+
+    #[tokio::test]
+    async fn test_install_verifies_signatures_of_trusted_sources() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+
+        let signed = publish(&backend, &root, "editor", "1.0.0");
+        let mut forged = publish(&backend, &root, "viewer", "1.0.0");
+        let archive = root.path().join("build/viewer/1.0.0/package.rpg");
+        forged.signature = crate::signature::KeyPair::generate()
+            .sign(&std::fs::read(archive).unwrap())
+            .to_base64();
+        let mut unsigned = publish(&backend, &root, "pager", "1.0.0");
+        unsigned.signature.clear();
+        serve_index(&backend, vec![signed, forged, unsigned]);
+
+        let result = manager
+            .install_package("editor", None, PackageKind::App)
+            .await
+            .unwrap();
+        assert!(matches!(result, TransactionResult::Success { .. }));
+
+        for name in ["viewer", "pager"] {
+            let result = manager.install_package(name, None, PackageKind::App).await;
+            assert!(matches!(
+                result,
+                Err(crate::Error::SignatureVerification(_))
+            ));
+            assert!(!manager.app_layout().version_path(name, "1.0.0").exists());
+            assert!(manager.registry.read().await.get_active(name).is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unparsable_versions_are_reported() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+
+        let viewer = publish(&backend, &root, "viewer", "1.0.0");
+        serve_index(&backend, vec![viewer.clone()]);
+        manager
+            .install_package("viewer", None, PackageKind::App)
+            .await
+            .unwrap();
+
+        let mut broken = publish(&backend, &root, "editor", "1.0.0");
+        broken.version = "one".to_string();
+        let viewer2 = publish(&backend, &root, "viewer", "1.1.0");
+        serve_index(&backend, vec![broken, viewer, viewer2]);
+
+        let updates = manager.check_updates().await.unwrap();
+        assert_eq!(updates.available.len(), 1);
+        assert!(updates.held.is_empty());
+        assert_eq!(updates.errors.len(), 1);
+        assert!(updates.errors[0].starts_with("editor@one"));
+    }
+
+    #[tokio::test]
+    async fn test_update_summary_follows_installs() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+
+        let editor = publish(&backend, &root, "editor", "1.0.0");
+        let viewer = publish(&backend, &root, "viewer", "0.3.0");
+        serve_index(&backend, vec![editor.clone(), viewer.clone()]);
+        manager
+            .install_package("editor", None, PackageKind::App)
+            .await
+            .unwrap();
+
+        let editor2 = publish(&backend, &root, "editor", "1.1.0");
+        let editor3 = publish(&backend, &root, "editor", "1.2.0");
+        serve_index(&backend, vec![editor, editor2, editor3, viewer]);
+        let info = manager.check_updates().await.unwrap();
+        assert!(UpdateSummary::load(&manager.state_dir).is_none());
+        manager.publish_updates(&info).await;
+        let summary = UpdateSummary::load(&manager.state_dir).unwrap();
+
+        // Packages that are not installed are not updates
+        assert_eq!(summary.available, 1);
+        assert_eq!(summary.packages.len(), 1);
+        assert_eq!(summary.packages[0].name, "editor");
+
+        // Of two newer versions, only the newest is counted
+        assert_eq!(summary.packages[0].new_version, "1.2.0");
+        assert!(summary.held.is_empty());
+
+        manager.update_all().await.unwrap();
+        let summary = UpdateSummary::load(&manager.state_dir).unwrap();
+        assert_eq!(summary.available, 0);
+        assert!(RebootRequired::load(&manager.state_dir).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_available_packages() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+
+        let editor = publish(&backend, &root, "editor", "1.0.0");
+        let mut viewer = publish(&backend, &root, "viewer", "0.3.0");
+        viewer.description = Some("Image viewer".to_string());
+        serve_index(&backend, vec![editor.clone(), viewer.clone()]);
+        manager
+            .install_package("editor", None, PackageKind::App)
+            .await
+            .unwrap();
+
+        let editor2 = publish(&backend, &root, "editor", "1.1.0");
+        serve_index(&backend, vec![editor, editor2, viewer]);
+
+        let available = manager.available_packages().await.unwrap();
+        assert_eq!(available.len(), 2);
+        assert_eq!(available[0].name, "editor");
+        assert_eq!(available[0].version, Version::new(1, 1, 0));
+        assert_eq!(available[0].installed, Some(Version::new(1, 0, 0)));
+        assert_eq!(available[1].description.as_deref(), Some("Image viewer"));
+        assert_eq!(available[1].installed, None);
+
+        // Downloads pick the version an install would, honoring pins
+        manager.pins.write().await.pin(
+            "editor",
+            crate::version::VersionConstraint::new("<1.1.0").unwrap(),
+        );
+        let path = manager
+            .download_package("editor", None, PackageKind::App)
+            .await
+            .unwrap();
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("editor-1.0.0-"));
+        assert!(manager
+            .download_package("editor", Some("1.1.0"), PackageKind::App)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sbom_uses_installed_manifests() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+
+        let editor = publish_with(&backend, &root, "editor", "1.0.0", |_, manifest| {
+            manifest.license = Some("MIT".to_string());
+            manifest.homepage = Some("https://rustux.com/editor".to_string());
+        });
+        serve_index(&backend, vec![editor.clone()]);
+        manager
+            .install_package("editor", None, PackageKind::App)
+            .await
+            .unwrap();
+
+        let packages = manager.sbom_packages().await;
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].license.as_deref(), Some("MIT"));
+        assert_eq!(packages[0].sha256.as_deref(), Some(editor.sha256.as_str()));
+        assert_eq!(packages[0].source.as_deref(), Some("apps"));
+
+        let doc = manager.sbom(SbomFormat::Spdx, "test").await;
+        assert_eq!(doc["packages"][0]["homepage"], "https://rustux.com/editor");
+    }
+
+    #[tokio::test]
+    async fn test_install_rejects_corrupt_download() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+
+        let mut entry = publish(&backend, &root, "editor", "1.0.0");
+        entry.sha256 = "0".repeat(64);
+        serve_index(&backend, vec![entry]);
+
+        assert!(manager
+            .install_package("editor", Some("1.0.0"), PackageKind::App)
+            .await
+            .is_err());
+        assert!(manager.list_installed().await.unwrap().is_empty());
+        assert!(!manager.app_layout().current_path("editor").exists());
+    }
+
+    #[tokio::test]
+    async fn test_install_checks_disk_space_first() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+        let manager = manager.with_mounts(vec![(root.path().to_path_buf(), 1 << 20)]);
+
+        let mut entry = publish(&backend, &root, "editor", "1.0.0");
+        entry.installed_size = Some(1 << 30);
+        serve_index(&backend, vec![entry]);
+
+        let plan = manager
+            .plan_install("editor", None, PackageKind::App, InstallOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(plan.disk.len(), 1);
+        assert!(!plan.disk[0].is_sufficient());
+
+        let result = manager
+            .install_package("editor", None, PackageKind::App)
+            .await;
+        assert!(matches!(result, Err(crate::Error::InsufficientSpace(_))));
+        assert!(!backend
+            .requests()
+            .contains(&format!("{}/editor/1.0.0.rpg", REPO)));
+        assert!(!manager.app_layout().app_path("editor").exists());
+    }
+
+    #[tokio::test]
+    async fn test_audit_and_security_updates() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+
+        let editor = publish(&backend, &root, "editor", "1.0.0");
+        let viewer = publish(&backend, &root, "viewer", "1.0.0");
+        serve_index(&backend, vec![editor.clone(), viewer.clone()]);
+        for name in ["editor", "viewer"] {
+            manager
+                .install_package(name, None, PackageKind::App)
+                .await
+                .unwrap();
+        }
+        assert!(manager.audit().await.unwrap().findings.is_empty());
+
+        let feed = r#"{"advisories": [{"id": "RSA-1", "package": "editor",
+            "affected": "<1.1.0", "severity": "critical", "fixed_in": "1.1.0"}]}"#;
+        backend.insert(format!("{}/advisories.json", REPO), feed);
+        backend.insert(
+            format!("{}/advisories.json.sig", REPO),
+            repo_key().sign(feed.as_bytes()).to_base64(),
+        );
+        let report = manager.audit().await.unwrap();
+        assert_eq!(report.findings.len(), 1);
+        assert_eq!(report.findings[0].package, "editor");
+        assert_eq!(report.max_severity(), Some(Severity::Critical));
+
+        let editor_fix = publish(&backend, &root, "editor", "1.1.0");
+        let viewer_update = publish(&backend, &root, "viewer", "1.2.0");
+        serve_index(&backend, vec![editor, viewer, viewer_update, editor_fix]);
+
+        let updates = manager.check_updates().await.unwrap();
+        assert_eq!(updates.available.len(), 2);
+        assert_eq!(updates.available[0].name, "editor");
+        assert_eq!(updates.available[0].advisories, vec!["RSA-1".to_string()]);
+
+        let security = updates.security_only();
+        assert_eq!(security.available.len(), 1);
+        manager.install_updates(&security).await.unwrap();
+        assert!(manager.audit().await.unwrap().findings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_advisories_need_a_trusted_key() {
+        let root = TempDir::new().unwrap();
+        let source = Source::new("apps".to_string(), REPO.to_string(), "apps".to_string());
+        let (manager, backend) = test_manager_with_source(&root, source);
+        backend.insert(
+            format!("{}/advisories.json", REPO),
+            r#"{"advisories": [{"id": "RSA-1", "package": "editor",
+                "affected": "<9", "severity": "critical"}]}"#,
+        );
+
+        let (feed, errors) = manager.fetch_advisories().await;
+        assert!(feed.advisories.is_empty());
+        assert!(errors.is_empty());
+        assert!(backend.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_install_removes_its_dependencies() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+
+        let editor = publish_with_deps(&backend, &root, "editor", "1.0.0", &["libui"]);
+        let libui = publish(&backend, &root, "libui", "1.2.0");
+        assert!(backend.remove(&format!("{}/{}", REPO, editor.path)));
+        serve_index(&backend, vec![editor, libui]);
+
+        assert!(manager
+            .install_package("editor", None, PackageKind::App)
+            .await
+            .is_err());
+        assert!(manager.registry.read().await.get_active("libui").is_none());
+        assert!(!manager.app_layout().current_path("libui").exists());
+    }
+
+    #[tokio::test]
+    async fn test_dependencies_and_autoremove() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+
+        let editor = publish_with_deps(&backend, &root, "editor", "1.0.0", &["libui >=1.0, <2"]);
+        let libui = publish_with_deps(&backend, &root, "libui", "1.2.0", &["libfont"]);
+        let libfont = publish(&backend, &root, "libfont", "0.3.0");
+        serve_index(&backend, vec![editor, libui, libfont]);
+
+        let plan = manager
+            .plan_install("editor", None, PackageKind::App, InstallOptions::default())
+            .await
+            .unwrap();
+        let extracted: Vec<&str> = plan
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                PlanStep::Extract { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(extracted, vec!["libfont", "libui", "editor"]);
+
+        manager
+            .install_package("editor", None, PackageKind::App)
+            .await
+            .unwrap();
+        let installed = manager.list_installed().await.unwrap();
+        assert_eq!(installed.len(), 3);
+        assert_eq!(installed[0].name, "editor");
+        assert_eq!(installed[0].reason, InstallReason::Explicit);
+        assert_eq!(installed[1].reason, InstallReason::Dependency);
+
+        // Depended-on packages are only removed by force
+        assert!(manager.remove_package("libui").await.is_err());
+        assert!(matches!(
+            manager.autoremove().await.unwrap(),
+            TransactionResult::Success { .. }
+        ));
+        assert_eq!(manager.list_installed().await.unwrap().len(), 3);
+
+        // Purging removes config and data along with the app
+        let config = root.path().join("etc/apps/editor");
+        std::fs::create_dir_all(&config).unwrap();
+        std::fs::write(config.join("editor.toml"), "theme = \"dark\"\n").unwrap();
+        let options = RemoveOptions {
+            purge: true,
+            ..RemoveOptions::default()
+        };
+        let plan = manager.plan_remove("editor", options).await.unwrap();
+        assert!(plan.steps.contains(&PlanStep::Purge {
+            name: "editor".to_string(),
+            path: config.clone(),
+        }));
+        manager
+            .remove_package_with("editor", options)
+            .await
+            .unwrap();
+        assert!(!config.exists());
+
+        // Its dependencies are now orphans, removed in one batch
+        assert_eq!(manager.plan_autoremove().await.unwrap().steps.len(), 4);
+        assert!(matches!(
+            manager.autoremove().await.unwrap(),
+            TransactionResult::Success { .. }
+        ));
+        assert!(manager.list_installed().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_autoremove_keeps_system_packages() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+
+        let editor = publish_with_deps(&backend, &root, "editor", "1.0.0", &["libui"]);
+        let libui = publish_with_deps(&backend, &root, "libui", "1.2.0", &["libc"]);
+        let libc = publish(&backend, &root, "libc", "2.38.0");
+        serve_index(&backend, vec![editor, libui, libc]);
+        manager
+            .install_package("editor", None, PackageKind::App)
+            .await
+            .unwrap();
+        manager
+            .registry
+            .write()
+            .await
+            .set_kind("libc", PackageKind::System);
+        manager.remove_package("editor").await.unwrap();
+
+        // The orphaned system package is left alone rather than failing
+        // the batch
+        assert_eq!(manager.plan_autoremove().await.unwrap().steps.len(), 2);
+        assert!(matches!(
+            manager.autoremove().await.unwrap(),
+            TransactionResult::Success { .. }
+        ));
+        let installed = manager.list_installed().await.unwrap();
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].name, "libc");
+    }
+
+    #[tokio::test]
+    async fn test_rollback_system_package() {
+        let root = TempDir::new().unwrap();
+        let (manager, _backend) = test_manager(&root);
+        for version in [Version::new(6, 1, 0), Version::new(6, 2, 0)] {
+            let path = manager
+                .system_layout
+                .package_path("linux", &version.as_str());
+            std::fs::create_dir_all(path).unwrap();
+            let mut registry = manager.registry.write().await;
+            registry.add_package("linux", &version);
+            registry.set_kind("linux", PackageKind::Kernel);
+            registry.set_active("linux".to_string(), version);
+        }
+
+        let plan = manager.plan_rollback("linux", None).await.unwrap();
+        assert_eq!(
+            plan.steps,
+            vec![PlanStep::Activate {
+                name: "linux".to_string(),
+                version: "6.1.0".to_string(),
+                path: manager.system_layout.package_path("linux", "6.1.0"),
+            }]
+        );
+        assert_eq!(plan.requires_reboot, vec!["linux"]);
+
+        // There is no link to switch, the older kernel boots next time
+        let result = manager.rollback("linux", None).await.unwrap();
+        assert!(matches!(
+            result,
+            TransactionResult::Success { ref requires_reboot, .. } if requires_reboot == &["linux"]
+        ));
+        let registry = manager.registry.read().await;
+        assert_eq!(registry.get_active("linux"), Some(&Version::new(6, 1, 0)));
+        assert!(!manager.app_layout().app_path("linux").exists());
+    }
+
+    #[tokio::test]
+    async fn test_restore_snapshot_switches_kernels() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+        let v1 = publish(&backend, &root, "editor", "1.0.0");
+        let v2 = publish(&backend, &root, "editor", "1.1.0");
+        serve_index(&backend, vec![v1]);
+        manager
+            .install_package("editor", None, PackageKind::App)
+            .await
+            .unwrap();
+
+        let set_kernel = |version: Version| async {
+            let path = manager
+                .system_layout
+                .package_path("linux", &version.as_str());
+            std::fs::create_dir_all(path).unwrap();
+            let mut registry = manager.registry.write().await;
+            registry.add_package("linux", &version);
+            registry.set_kind("linux", PackageKind::Kernel);
+            registry.set_active("linux".to_string(), version);
+        };
+        set_kernel(Version::new(6, 1, 0)).await;
+        manager.create_snapshot("known-good", None).await.unwrap();
+
+        serve_index(&backend, vec![v2]);
+        manager.update_all().await.unwrap();
+        set_kernel(Version::new(6, 2, 0)).await;
+
+        // A kernel that is gone from disk stops the restore before anything
+        // is switched
+        let old_kernel = manager.system_layout.package_path("linux", "6.1.0");
+        std::fs::rename(&old_kernel, root.path().join("linux-6.1.0")).unwrap();
+        let result = manager.restore_snapshot("known-good").await.unwrap();
+        assert!(matches!(result, TransactionResult::RolledBack { .. }));
+        let current = manager.app_layout().current_path("editor");
+        assert!(current.read_link().unwrap().ends_with("1.1.0"));
+        std::fs::rename(root.path().join("linux-6.1.0"), &old_kernel).unwrap();
+
+        // The app and the kernel go back together
+        let result = manager.restore_snapshot("known-good").await.unwrap();
+        let TransactionResult::Success {
+            activated,
+            requires_reboot,
+        } = result
+        else {
+            panic!("restore failed: {:?}", result);
+        };
+        assert_eq!(activated, vec!["editor".to_string()]);
+        assert_eq!(requires_reboot, vec!["linux".to_string()]);
+        assert!(current.read_link().unwrap().ends_with("1.0.0"));
+        {
+            let registry = manager.registry.read().await;
+            assert_eq!(registry.get_active("linux"), Some(&Version::new(6, 1, 0)));
+            assert_eq!(registry.get_active("editor"), Some(&Version::new(1, 0, 0)));
+        }
+
+        // Undoing the restore brings the new kernel back
+        let restore = manager.history(1).await.pop().unwrap();
+        manager.undo(&restore.id).await.unwrap();
+        let registry = manager.registry.read().await;
+        assert_eq!(registry.get_active("linux"), Some(&Version::new(6, 2, 0)));
+        assert!(current.read_link().unwrap().ends_with("1.1.0"));
+    }
+
+    #[tokio::test]
+    async fn test_system_packages_sharing_a_version() {
+        let root = TempDir::new().unwrap();
+        let source = Source::new("system".to_string(), REPO.to_string(), "system".to_string())
+            .with_trusted_key(repo_key().export_public());
+        let (manager, backend) = test_manager_with_source(&root, source);
+        let library = |name: &str| {
+            let mut entry = publish_with(&backend, &root, name, "1.0.0", |files, manifest| {
+                manifest.kind = PackageKind::System.to_string();
+                std::fs::create_dir_all(files.join("lib")).unwrap();
+                std::fs::write(files.join("lib").join(name), name).unwrap();
+            });
+            entry.kind = Some(PackageKind::System);
+            entry
+        };
+        serve_index(&backend, vec![library("libc"), library("libssl")]);
+
+        for name in ["libc", "libssl"] {
+            let result = manager
+                .install_package(name, None, PackageKind::System)
+                .await
+                .unwrap();
+            assert!(matches!(result, TransactionResult::Success { .. }));
+        }
+
+        // Each package keeps its own files and metadata
+        let layout = &manager.system_layout;
+        for name in ["libc", "libssl"] {
+            let path = layout.package_path(name, "1.0.0");
+            assert_eq!(
+                std::fs::read_to_string(path.join("lib").join(name)).unwrap(),
+                name
+            );
+            let metadata = std::fs::read_to_string(path.join("metadata.json")).unwrap();
+            assert!(metadata.contains(&format!("\"{}\"", name)));
+        }
+        assert!(layout.list_versions().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_undo_batch_with_system_packages() {
+        let root = TempDir::new().unwrap();
+        let key = repo_key().export_public();
+        let (manager, backend) = test_manager_with_sources(
+            &root,
+            vec![
+                Source::new("apps".to_string(), REPO.to_string(), "apps".to_string())
+                    .with_trusted_key(key.clone()),
+                Source::new("system".to_string(), REPO.to_string(), "system".to_string())
+                    .with_trusted_key(key),
+            ],
+        );
+        let system_package = |name: &str, version: &str| {
+            let mut entry = publish_with(&backend, &root, name, version, |_, manifest| {
+                manifest.kind = PackageKind::System.to_string();
+            });
+            entry.kind = Some(PackageKind::System);
+            entry
+        };
+
+        let editor = publish(&backend, &root, "editor", "1.0.0");
+        let libc = system_package("libc", "1.0.0");
+        serve_index(&backend, vec![editor.clone(), libc.clone()]);
+        manager
+            .install_package("editor", None, PackageKind::App)
+            .await
+            .unwrap();
+        manager
+            .install_package("libc", None, PackageKind::System)
+            .await
+            .unwrap();
+
+        // An update batch of an app and a system package is undone whole
+        let editor2 = publish(&backend, &root, "editor", "1.1.0");
+        let libc2 = system_package("libc", "1.1.0");
+        serve_index(&backend, vec![editor, editor2, libc, libc2]);
+        let result = manager.update_all().await.unwrap();
+        assert_eq!(result.succeeded.len(), 2);
+        let batch = manager.history(1).await.pop().unwrap().batch.unwrap();
+        let result = manager.undo(&batch).await.unwrap();
+        assert!(matches!(result, TransactionResult::Success { .. }));
+        let current = manager.app_layout().current_path("editor");
+        assert!(current.read_link().unwrap().ends_with("1.0.0"));
+        assert_eq!(
+            manager.registry.read().await.get_active("libc"),
+            Some(&Version::new(1, 0, 0))
+        );
+
+        // A system package installed as a dependency goes with its dependent
+        let libssl = system_package("libssl", "1.0.0");
+        let viewer = publish_with_deps(&backend, &root, "viewer", "1.0.0", &["libssl"]);
+        serve_index(&backend, vec![libssl, viewer]);
+        manager
+            .install_package("viewer", None, PackageKind::App)
+            .await
+            .unwrap();
+        assert!(manager.registry.read().await.get_active("libssl").is_some());
+        let batch = manager.history(1).await.pop().unwrap().batch.unwrap();
+        let result = manager.undo(&batch).await.unwrap();
+        assert!(matches!(result, TransactionResult::Success { .. }));
+        let registry = manager.registry.read().await;
+        assert!(registry.get_active("viewer").is_none());
+        assert!(registry.get_active("libssl").is_none());
+        assert!(!manager
+            .system_layout
+            .package_path("libssl", "1.0.0")
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn test_world_plan_keeps_sources_and_held_apps() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+        let editor = publish(&backend, &root, "editor", "1.0.0");
+        let viewer = publish(&backend, &root, "viewer", "1.0.0");
+        let notes = publish(&backend, &root, "notes", "1.0.0");
+        serve_index(&backend, vec![editor, viewer, notes]);
+        for name in ["editor", "viewer", "notes"] {
+            manager
+                .install_package(name, None, PackageKind::App)
+                .await
+                .unwrap();
+        }
+        manager.pins.write().await.hold("viewer");
+
+        let world = WorldConfig::parse(
+            r#"
+            [[sources]]
+            name = "extra"
+            url = "http://repo.test/extra"
+            type = "apps"
+
+            [packages]
+            editor = "*"
+            "#,
+        )
+        .unwrap();
+        let plan = manager.plan_world(&world).await.unwrap();
+
+        // Planning does not touch the sources in use
+        assert_eq!(plan.sources, vec!["extra".to_string()]);
+        let sources = manager.sources.read().await;
+        assert!(!sources.sources.iter().any(|source| source.name == "extra"));
+
+        // The held app is kept and reported, the other one removed
+        assert_eq!(plan.kept, vec!["viewer".to_string()]);
+        let removed: Vec<&str> = plan.changes.iter().map(|c| c.name()).collect();
+        assert_eq!(removed, vec!["notes"]);
+    }
+
+    #[tokio::test]
+    async fn test_undo_checks_the_whole_batch_first() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+        let v1 = publish(&backend, &root, "editor", "1.0.0");
+        serve_index(&backend, vec![v1]);
+        manager
+            .install_package("editor", None, PackageKind::App)
+            .await
+            .unwrap();
+
+        // A transaction in the same batch that cannot be undone
+        let batch = manager.history(1).await[0].batch.clone().unwrap();
+        let mut stuck =
+            Transaction::new(TransactionKind::Install, vec![]).with_batch(Some(batch.clone()));
+        stuck.state = crate::transaction::TransactionState::Completed;
+        manager.registry.write().await.record_transaction(stuck);
+
+        let error = manager.undo(&batch).await.unwrap_err();
+        assert!(error.to_string().contains("nothing to undo"));
+        let current = manager.app_layout().current_path("editor");
+        assert!(current.read_link().unwrap().ends_with("1.0.0"));
+    }
+
+    #[tokio::test]
+    async fn test_undo_remove_reinstalls_the_cached_archive() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+        let v1 = publish(&backend, &root, "editor", "1.0.0");
+        serve_index(&backend, vec![v1.clone()]);
+        manager
+            .install_package("editor", None, PackageKind::App)
+            .await
+            .unwrap();
+        manager.remove_package("editor").await.unwrap();
+
+        // The repository no longer serves the removed version
+        assert!(backend.remove(&format!("{}/{}", REPO, v1.path)));
+        serve_index(&backend, vec![]);
+
+        let remove = manager.history(1).await[0].id.clone();
+        let result = manager.undo(&remove).await.unwrap();
+        assert!(matches!(result, TransactionResult::Success { .. }));
+        let current = manager.app_layout().current_path("editor");
+        assert!(current.read_link().unwrap().ends_with("1.0.0"));
+    }
+
+    #[tokio::test]
+    async fn test_undo_remove_keeps_the_install_reason() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+        let libui = publish(&backend, &root, "libui", "1.0.0");
+        let viewer = publish_with_deps(&backend, &root, "viewer", "1.0.0", &["libui"]);
+        serve_index(&backend, vec![libui, viewer]);
+        manager
+            .install_package("viewer", None, PackageKind::App)
+            .await
+            .unwrap();
+        manager.remove_package("viewer").await.unwrap();
+        manager.autoremove().await.unwrap();
+
+        // Brought back, the dependency is still offered for autoremove
+        let remove = manager.history(1).await[0].id.clone();
+        let result = manager.undo(&remove).await.unwrap();
+        assert!(matches!(result, TransactionResult::Success { .. }));
+        let registry = manager.registry.read().await;
+        assert_eq!(registry.get_reason("libui"), InstallReason::Dependency);
+        assert_eq!(registry.orphans(), vec!["libui".to_string()]);
+    }
+
+    /// Publish an editor version shipping a config file
+    fn publish_editor_config(
+        backend: &MemoryBackend,
+        root: &TempDir,
+        version: &str,
+        config: &str,
+    ) -> PackageEntry {
+        publish_with(backend, root, "editor", version, |files, manifest| {
+            std::fs::create_dir_all(files.join("etc/editor")).unwrap();
+            std::fs::write(files.join("etc/editor/editor.toml"), config).unwrap();
+            manifest.conffiles = vec!["/etc/editor/editor.toml".to_string()];
+        })
+    }
+
+    #[tokio::test]
+    async fn test_conffiles_survive_upgrades() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+        let config = root.path().join("etc/editor/editor.toml");
+
+        let v1 = publish_editor_config(&backend, &root, "1.0.0", "theme = \"light\"\n");
+        serve_index(&backend, vec![v1.clone()]);
+        manager
+            .install_package("editor", None, PackageKind::App)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&config).unwrap(),
+            "theme = \"light\"\n"
+        );
+
+        // Untouched config follows the package
+        let v2 = publish_editor_config(&backend, &root, "1.1.0", "theme = \"solar\"\n");
+        serve_index(&backend, vec![v1.clone(), v2.clone()]);
+        manager.update_all().await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&config).unwrap(),
+            "theme = \"solar\"\n"
+        );
+        assert!(manager.pending_conffiles().await.is_empty());
+
+        // Local edits are kept and the new version waits for review
+        std::fs::write(&config, "theme = \"dark\"\n").unwrap();
+        let v3 = publish_editor_config(&backend, &root, "1.2.0", "theme = \"solar\"\nfont = 14\n");
+        serve_index(&backend, vec![v1, v2, v3]);
+        manager.update_all().await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&config).unwrap(),
+            "theme = \"dark\"\n"
+        );
+
+        let pending = manager.pending_conffiles().await;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].path, config);
+        assert!(pending[0].diff().unwrap().contains("+font = 14"));
+
+        manager
+            .merge_conffile(&config, MergeChoice::UseNew)
+            .await
+            .unwrap();
+        assert!(std::fs::read_to_string(&config)
+            .unwrap()
+            .contains("font = 14"));
+        assert!(manager.pending_conffiles().await.is_empty());
+
+        // Removal keeps config files; purging deletes them
+        manager.remove_package("editor").await.unwrap();
+        assert!(config.exists());
+        manager
+            .install_package("editor", None, PackageKind::App)
+            .await
+            .unwrap();
+        let options = RemoveOptions {
+            purge: true,
+            ..RemoveOptions::default()
+        };
+        manager
+            .remove_package_with("editor", options)
+            .await
+            .unwrap();
+        assert!(!config.exists());
+    }
+
+    #[tokio::test]
+    async fn test_purge_keeps_preexisting_conffiles() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+        let config = root.path().join("etc/editor/editor.toml");
+        std::fs::create_dir_all(config.parent().unwrap()).unwrap();
+        std::fs::write(&config, "theme = \"dark\"\n").unwrap();
+
+        let v1 = publish_editor_config(&backend, &root, "1.0.0", "theme = \"light\"\n");
+        serve_index(&backend, vec![v1]);
+        manager
+            .install_package("editor", None, PackageKind::App)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&config).unwrap(),
+            "theme = \"dark\"\n"
+        );
+
+        let options = RemoveOptions {
+            purge: true,
+            ..RemoveOptions::default()
+        };
+        manager
+            .remove_package_with("editor", options)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&config).unwrap(),
+            "theme = \"dark\"\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_triggers_run_once_per_transaction() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+        let log = root.path().join("trigger.log");
+
+        let command = format!("echo \"$RPG_TRIGGER_PATHS\" >> {}", log.display());
+        let fontconfig = publish_with(&backend, &root, "fontconfig", "1.0.0", |_, manifest| {
+            manifest.triggers = vec![crate::trigger::Trigger {
+                name: "font-cache".to_string(),
+                paths: vec!["share/fonts/**".to_string()],
+                command,
+            }];
+        });
+        let fonts = publish_with(&backend, &root, "dejavu", "2.37.0", |files, _| {
+            std::fs::create_dir_all(files.join("share/fonts")).unwrap();
+            std::fs::write(files.join("share/fonts/sans.ttf"), "font").unwrap();
+            std::fs::write(files.join("share/fonts/serif.ttf"), "font").unwrap();
+        });
+        let editor = publish(&backend, &root, "editor", "1.0.0");
+        serve_index(&backend, vec![fontconfig, fonts, editor]);
+
+        for name in ["fontconfig", "editor", "dejavu"] {
+            manager
+                .install_package(name, None, PackageKind::App)
+                .await
+                .unwrap();
+        }
+
+        // Two fonts, one run
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            "share/fonts/sans.ttf\nshare/fonts/serif.ttf\n"
+        );
+        let history = manager.history(10).await;
+        let runs: Vec<&crate::trigger::TriggerRun> =
+            history.iter().flat_map(|tx| &tx.triggers).collect();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].owner, "fontconfig");
+        assert_eq!(runs[0].matched, 2);
+        assert!(runs[0].succeeded());
+
+        // Removing the fonts fires the trigger again
+        manager.remove_package("dejavu").await.unwrap();
+        let last = manager.history(1).await;
+        assert_eq!(last[0].triggers.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_triggers_run_once_per_batch() {
+        let root = TempDir::new().unwrap();
+        let (manager, backend) = test_manager(&root);
+        let log = root.path().join("trigger.log");
+
+        let command = format!("echo run >> {}", log.display());
+        let fontconfig = publish_with(&backend, &root, "fontconfig", "1.0.0", |_, manifest| {
+            manifest.triggers = vec![crate::trigger::Trigger {
+                name: "font-cache".to_string(),
+                paths: vec!["share/fonts/**".to_string()],
+                command,
+            }];
+        });
+        let font = |name: &str| {
+            publish_with(&backend, &root, name, "1.0.0", |files, _| {
+                std::fs::create_dir_all(files.join("share/fonts")).unwrap();
+                std::fs::write(files.join("share/fonts").join(name), "font").unwrap();
+            })
+        };
+        let (dejavu, noto) = (font("dejavu"), font("noto"));
+        let office = publish_with_deps(&backend, &root, "office", "1.0.0", &["dejavu", "noto"]);
+        serve_index(&backend, vec![fontconfig, dejavu, noto, office]);
+
+        manager
+            .install_package("fontconfig", None, PackageKind::App)
+            .await
+            .unwrap();
+        manager
+            .install_package("office", None, PackageKind::App)
+            .await
+            .unwrap();
+
+        // Both fonts came in one batch, so the cache was refreshed once
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "run\n");
+        let history = manager.history(10).await;
+        let runs: Vec<&crate::trigger::TriggerRun> =
+            history.iter().flat_map(|tx| &tx.triggers).collect();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].matched, 2);
+    }
+}
@@ -0,0 +1,444 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Operation plans
+//!
+//! A plan lists what an operation would do (downloads, extractions,
+//! symlink switches and removals) without touching disk. Plans back
+//! `--dry-run` and the confirmation prompt before updates, and serialize
+//! to JSON for other front ends.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// A single step of a plan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum PlanStep {
+    /// Download a package archive into the cache
+    Download {
+        name: String,
+        version: String,
+        /// Archive size in bytes
+        size: u64,
+        /// Cache path of the archive
+        path: PathBuf,
+        /// The archive is already cached and will not be fetched
+        cached: bool,
+    },
+    /// Extract a package into its versioned directory
+    Extract {
+        name: String,
+        version: String,
+        /// Destination directory
+        path: PathBuf,
+        /// Estimated size on disk in bytes
+        size: u64,
+    },
+    /// Point a `current` symlink at a new target, or remove it
+    Switch {
+        name: String,
+        /// The symlink
+        link: PathBuf,
+        /// Current target, if the link exists
+        #[serde(skip_serializing_if = "Option::is_none")]
+        from: Option<PathBuf>,
+        /// New target, or `None` to remove the link
+        #[serde(skip_serializing_if = "Option::is_none")]
+        to: Option<PathBuf>,
+    },
+    /// Make an installed system-level version active at the next boot
+    Activate {
+        name: String,
+        version: String,
+        /// Installed version directory
+        path: PathBuf,
+    },
+    /// Delete an installed version directory
+    Remove {
+        name: String,
+        version: String,
+        path: PathBuf,
+    },
+    /// Delete an app's configuration or data directory
+    Purge { name: String, path: PathBuf },
+    /// Delete a cached package archive
+    Clean { path: PathBuf },
+}
+
+impl std::fmt::Display for PlanStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Download {
+                name,
+                version,
+                size,
+                cached,
+                ..
+            } => {
+                if *cached {
+                    write!(f, "download {} {} (cached)", name, version)
+                } else {
+                    write!(f, "download {} {} ({})", name, version, format_size(*size))
+                }
+            }
+            Self::Extract {
+                name,
+                version,
+                path,
+                size,
+            } => write!(
+                f,
+                "extract  {} {} to {} ({})",
+                name,
+                version,
+                path.display(),
+                format_size(*size)
+            ),
+            Self::Switch { link, from, to, .. } => {
+                match to {
+                    Some(to) => write!(f, "switch   {} -> {}", link.display(), to.display())?,
+                    None => write!(f, "unlink   {}", link.display())?,
+                }
+                if let Some(from) = from {
+                    write!(f, " (was {})", from.display())?;
+                }
+                Ok(())
+            }
+            Self::Activate {
+                name,
+                version,
+                path,
+            } => write!(f, "activate {} {} ({})", name, version, path.display()),
+            Self::Remove {
+                name,
+                version,
+                path,
+            } => write!(f, "remove   {} {} ({})", name, version, path.display()),
+            Self::Purge { name, path } => write!(f, "purge    {} ({})", name, path.display()),
+            Self::Clean { path } => write!(f, "clean    {}", path.display()),
+        }
+    }
+}
+
+/// Disk space needed on one filesystem
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    /// Mount point of the filesystem
+    pub mount_point: PathBuf,
+    /// Bytes the plan writes to it
+    pub required: u64,
+    /// Bytes currently available
+    pub available: u64,
+}
+
+impl DiskUsage {
+    /// Check if the filesystem has room for the plan
+    pub fn is_sufficient(&self) -> bool {
+        self.required <= self.available
+    }
+}
+
+/// What an operation would do
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plan {
+    /// Steps in execution order
+    pub steps: Vec<PlanStep>,
+    /// Packages that need a reboot to take effect
+    #[serde(default)]
+    pub requires_reboot: Vec<String>,
+    /// Disk space needed per filesystem
+    #[serde(default)]
+    pub disk: Vec<DiskUsage>,
+}
+
+impl Plan {
+    /// Create an empty plan
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check if the plan does nothing
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Append the steps of another plan
+    pub fn extend(&mut self, other: Plan) {
+        self.steps.extend(other.steps);
+        self.requires_reboot.extend(other.requires_reboot);
+    }
+
+    /// Total bytes to download
+    pub fn download_size(&self) -> u64 {
+        self.steps
+            .iter()
+            .map(|step| match step {
+                PlanStep::Download {
+                    size,
+                    cached: false,
+                    ..
+                } => *size,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Total bytes written to disk by downloads and extractions
+    pub fn disk_required(&self) -> u64 {
+        self.writes().map(|(_, size)| size).sum()
+    }
+
+    /// Check if every filesystem has room for the plan
+    pub fn has_enough_space(&self) -> bool {
+        self.disk.iter().all(DiskUsage::is_sufficient)
+    }
+
+    /// Check that every filesystem has room for the plan
+    ///
+    /// The error names each filesystem that is short, so it can be reported
+    /// before anything is downloaded or extracted.
+    pub fn check_space(&self) -> crate::Result<()> {
+        let short: Vec<String> = self
+            .disk
+            .iter()
+            .filter(|disk| !disk.is_sufficient())
+            .map(|disk| {
+                format!(
+                    "{} needs {} but has {} available",
+                    disk.mount_point.display(),
+                    format_size(disk.required),
+                    format_size(disk.available)
+                )
+            })
+            .collect();
+
+        if short.is_empty() {
+            Ok(())
+        } else {
+            Err(crate::Error::InsufficientSpace(short.join("; ")))
+        }
+    }
+
+    /// Fill in disk usage from the mounted filesystems
+    pub fn with_disk_usage(self) -> Self {
+        let disks = sysinfo::Disks::new_with_refreshed_list();
+        let mounts: Vec<(PathBuf, u64)> = disks
+            .list()
+            .iter()
+            .map(|d| (d.mount_point().to_path_buf(), d.available_space()))
+            .collect();
+
+        self.with_mounts(&mounts)
+    }
+
+    /// Fill in disk usage from the given mount points and available bytes
+    pub fn with_mounts(mut self, mounts: &[(PathBuf, u64)]) -> Self {
+        self.disk = self.disk_usage(mounts);
+        self
+    }
+
+    /// Group the plan's writes by the filesystem they land on
+    fn disk_usage(&self, mounts: &[(PathBuf, u64)]) -> Vec<DiskUsage> {
+        let mut usage: BTreeMap<PathBuf, DiskUsage> = BTreeMap::new();
+
+        for (path, size) in self.writes() {
+            let Some((mount_point, available)) = mount_for(path, mounts) else {
+                continue;
+            };
+
+            usage
+                .entry(mount_point.to_path_buf())
+                .or_insert_with(|| DiskUsage {
+                    mount_point: mount_point.to_path_buf(),
+                    required: 0,
+                    available,
+                })
+                .required += size;
+        }
+
+        usage.into_values().collect()
+    }
+
+    /// Paths written by the plan with the bytes written to each
+    fn writes(&self) -> impl Iterator<Item = (&Path, u64)> {
+        self.steps.iter().filter_map(|step| match step {
+            PlanStep::Download {
+                path,
+                size,
+                cached: false,
+                ..
+            }
+            | PlanStep::Extract { path, size, .. } => Some((path.as_path(), *size)),
+            _ => None,
+        })
+    }
+}
+
+impl std::fmt::Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.steps.is_empty() {
+            return writeln!(f, "Nothing to do.");
+        }
+
+        for step in &self.steps {
+            writeln!(f, "  {}", step)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "Download size: {}", format_size(self.download_size()))?;
+        for disk in &self.disk {
+            writeln!(
+                f,
+                "Disk space on {}: {} needed, {} available{}",
+                disk.mount_point.display(),
+                format_size(disk.required),
+                format_size(disk.available),
+                if disk.is_sufficient() {
+                    ""
+                } else {
+                    " (not enough)"
+                }
+            )?;
+        }
+        if !self.requires_reboot.is_empty() {
+            writeln!(
+                f,
+                "Reboot required for: {}",
+                self.requires_reboot.join(", ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Find the mount point a path lives on (the longest matching prefix)
+fn mount_for<'a>(path: &Path, mounts: &'a [(PathBuf, u64)]) -> Option<(&'a Path, u64)> {
+    mounts
+        .iter()
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+        .map(|(mount_point, available)| (mount_point.as_path(), *available))
+}
+
+/// Format a byte count for display
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Format a Unix timestamp as a UTC date and time
+pub fn format_timestamp(secs: i64) -> String {
+    let days = secs.div_euclid(86400);
+    let rem = secs.rem_euclid(86400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install_plan() -> Plan {
+        let mut plan = Plan::new();
+        plan.steps.push(PlanStep::Download {
+            name: "editor".to_string(),
+            version: "1.2.0".to_string(),
+            size: 1000,
+            path: PathBuf::from("/var/cache/rpg/editor-1.2.0-noarch.rpg"),
+            cached: false,
+        });
+        plan.steps.push(PlanStep::Extract {
+            name: "editor".to_string(),
+            version: "1.2.0".to_string(),
+            path: PathBuf::from("/apps/editor/1.2.0"),
+            size: 4000,
+        });
+        plan.steps.push(PlanStep::Switch {
+            name: "editor".to_string(),
+            link: PathBuf::from("/apps/editor/current"),
+            from: Some(PathBuf::from("/apps/editor/1.0.0")),
+            to: Some(PathBuf::from("/apps/editor/1.2.0")),
+        });
+        plan
+    }
+
+    #[test]
+    fn test_plan_disk_usage() {
+        let plan = install_plan();
+        assert_eq!(plan.download_size(), 1000);
+        assert_eq!(plan.disk_required(), 5000);
+
+        let mounts = vec![(PathBuf::from("/"), 10_000), (PathBuf::from("/apps"), 3000)];
+        let usage = plan.disk_usage(&mounts);
+
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage[0].mount_point, PathBuf::from("/"));
+        assert_eq!(usage[0].required, 1000);
+        assert!(usage[0].is_sufficient());
+        assert_eq!(usage[1].mount_point, PathBuf::from("/apps"));
+        assert!(!usage[1].is_sufficient());
+
+        let plan = Plan {
+            disk: usage,
+            ..plan
+        };
+        assert!(!plan.has_enough_space());
+        let error = plan.check_space().unwrap_err().to_string();
+        assert!(error.contains("/apps needs 3.9 KiB but has 2.9 KiB available"));
+        assert!(!error.contains("/ needs"));
+    }
+
+    #[test]
+    fn test_plan_serialization() {
+        let plan = install_plan();
+        let json = serde_json::to_string(&plan).unwrap();
+        assert!(json.contains("\"action\":\"switch\""));
+
+        let parsed: Plan = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, plan);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
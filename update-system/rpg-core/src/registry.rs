@@ -1,365 +1,748 @@
-// Copyright 2025 The Rustux Authors
-//
-// Use of this source code is governed by a MIT-style
-// license that can be found in the LICENSE file or at
-// https://opensource.org/licenses/MIT
-
-//! Package registry for tracking installed packages
-
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
-
-use crate::package::{PackageMetadata, PackageRef};
-use crate::transaction::Transaction;
-use crate::version::Version;
-
-/// Package registry
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PackageRegistry {
-    /// Installed packages (name -> versions)
-    #[serde(default)]
-    pub packages: HashMap<String, Vec<Version>>,
-
-    /// Active versions (name -> version)
-    #[serde(default)]
-    pub active: HashMap<String, Version>,
-
-    /// Pending updates (packages downloaded but not activated)
-    #[serde(default)]
-    pub pending: Vec<PackageRef>,
-
-    /// Transaction history
-    #[serde(default)]
-    pub transactions: Vec<Transaction>,
-}
-
-impl PackageRegistry {
-    /// Create a new package registry
-    pub fn new() -> Self {
-        Self {
-            packages: HashMap::new(),
-            active: HashMap::new(),
-            pending: Vec::new(),
-            transactions: Vec::new(),
-        }
-    }
-
-    /// Load the registry from disk
-    pub fn load() -> crate::Result<Self> {
-        let path = Self::registry_path();
-
-        if path.exists() {
-            let content = std::fs::read_to_string(&path)?;
-            serde_json::from_str(&content)
-                .map_err(|e| crate::Error::Serialization(e.to_string()))
-        } else {
-            Ok(Self::new())
-        }
-    }
-
-    /// Save the registry to disk
-    pub fn save(&self) -> crate::Result<()> {
-        let path = Self::registry_path();
-
-        // Ensure directory exists
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-
-        let content = serde_json::to_string_pretty(self)
-            .map_err(|e| crate::Error::Serialization(e.to_string()))?;
-
-        std::fs::write(&path, content)?;
-        Ok(())
-    }
-
-    /// Get the registry file path
-    fn registry_path() -> PathBuf {
-        PathBuf::from("/var/lib/rpg/registry.json")
-    }
-
-    /// Register a package
-    pub fn register_package(&mut self, name: String, version: Version) {
-        self.packages
-            .entry(name.clone())
-            .or_insert_with(Vec::new)
-            .push(version.clone());
-
-        // Sort versions
-        if let Some(versions) = self.packages.get_mut(&name) {
-            versions.sort();
-            versions.dedup();
-        }
-    }
-
-    /// Unregister a package
-    pub fn unregister_package(&mut self, name: &str, version: &Version) {
-        if let Some(versions) = self.packages.get_mut(name) {
-            versions.retain(|v| v != version);
-        }
-
-        // Remove from active if it was the active version
-        if let Some(active) = self.active.get(name) {
-            if active == version {
-                self.active.remove(name);
-            }
-        }
-    }
-
-    /// Set the active version of a package
-    pub fn set_active(&mut self, name: String, version: Version) {
-        self.active.insert(name, version);
-    }
-
-    /// Get the active version of a package
-    pub fn get_active(&self, name: &str) -> Option<&Version> {
-        self.active.get(name)
-    }
-
-    /// Get all versions of a package
-    pub fn get_versions(&self, name: &str) -> Option<&[Version]> {
-        self.packages.get(name).map(|v| v.as_slice())
-    }
-
-    /// Check if a package is installed
-    pub fn is_installed(&self, name: &str, version: &Version) -> bool {
-        self.packages
-            .get(name)
-            .map(|versions| versions.contains(version))
-            .unwrap_or(false)
-    }
-
-    /// Get all installed packages
-    pub fn list_packages(&self) -> Vec<String> {
-        self.packages.keys().cloned().collect()
-    }
-
-    /// Get system version
-    pub fn get_system_version(&self) -> Option<&Version> {
-        self.get_active("system")
-    }
-
-    /// Set system version
-    pub fn set_system_version(&mut self, version: Version) {
-        self.set_active("system".to_string(), version);
-    }
-
-    /// Add a pending update
-    pub fn add_pending(&mut self, package_ref: PackageRef) {
-        if !self.pending.contains(&package_ref) {
-            self.pending.push(package_ref);
-        }
-    }
-
-    /// Remove a pending update
-    pub fn remove_pending(&mut self, package_ref: &PackageRef) {
-        self.pending.retain(|p| p != package_ref);
-    }
-
-    /// Get all pending updates
-    pub fn get_pending(&self) -> &[PackageRef] {
-        &self.pending
-    }
-
-    /// Clear all pending updates
-    pub fn clear_pending(&mut self) {
-        self.pending.clear();
-    }
-
-    /// Add a transaction to history
-    pub fn add_transaction(&mut self, transaction: Transaction) {
-        self.transactions.push(transaction);
-
-        // Keep only last 100 transactions
-        if self.transactions.len() > 100 {
-            self.transactions = self.transactions
-                .split_off(self.transactions.len() - 100);
-        }
-    }
-
-    /// Get transaction history
-    pub fn get_transactions(&self) -> &[Transaction] {
-        &self.transactions
-    }
-
-    /// Get recent transactions
-    pub fn get_recent_transactions(&self, count: usize) -> &[Transaction] {
-        let start = if self.transactions.len() > count {
-            self.transactions.len() - count
-        } else {
-            0
-        };
-        &self.transactions[start..]
-    }
-
-    /// Get the latest transaction
-    pub fn get_latest_transaction(&self) -> Option<&Transaction> {
-        self.transactions.last()
-    }
-
-    /// Find a transaction by ID
-    pub fn find_transaction(&self, id: &str) -> Option<&Transaction> {
-        self.transactions.iter().find(|t| t.id() == id)
-    }
-
-    /// Check if a kernel update is pending
-    pub fn has_pending_kernel_update(&self) -> bool {
-        self.pending.iter().any(|p| {
-            self.packages.get(&p.name)
-                .map(|versions| {
-                    versions.iter().any(|v| {
-                        // Check if this is a newer kernel version
-                        p.name == "kernel" && v > &p.version
-                    })
-                })
-                .unwrap_or(false)
-        })
-    }
-
-    /// Get packages that need updates
-    pub fn get_available_updates(&self, repo_metadata: &HashMap<String, Vec<PackageMetadata>>) -> Vec<PackageRef> {
-        let mut updates = Vec::new();
-
-        for (name, _versions) in &self.packages {
-            if let Some(repo_versions) = repo_metadata.get(name) {
-                let current = self.get_active(name);
-
-                for metadata in repo_versions {
-                    if let Some(current_version) = current {
-                        if metadata.version > *current_version {
-                            updates.push(PackageRef::new(
-                                name.clone(),
-                                metadata.version.clone(),
-                            ));
-                        }
-                    }
-                }
-            }
-        }
-
-        updates
-    }
-
-    /// Get registry statistics
-    pub fn stats(&self) -> RegistryStats {
-        let total_packages = self.packages.len();
-        let total_versions: usize = self.packages.values().map(|v| v.len()).sum();
-        let active_count = self.active.len();
-        let pending_count = self.pending.len();
-        let transaction_count = self.transactions.len();
-
-        RegistryStats {
-            total_packages,
-            total_versions,
-            active_count,
-            pending_count,
-            transaction_count,
-        }
-    }
-
-    /// Record a transaction (alias for add_transaction)
-    pub fn record_transaction(&mut self, transaction: Transaction) {
-        self.add_transaction(transaction);
-    }
-
-    /// Add a package version to the registry
-    pub fn add_package(&mut self, name: &str, version: &Version) {
-        self.packages
-            .entry(name.to_string())
-            .or_insert_with(Vec::new)
-            .push(version.clone());
-
-        // Sort and dedupe versions
-        if let Some(versions) = self.packages.get_mut(name) {
-            versions.sort();
-            versions.dedup();
-        }
-    }
-
-    /// List all versions of a package
-    pub fn list_versions(&self, name: &str) -> Vec<Version> {
-        self.packages
-            .get(name)
-            .cloned()
-            .unwrap_or_default()
-    }
-
-    /// Remove the active version of a package
-    pub fn remove_active(&mut self, name: &str) {
-        self.active.remove(name);
-    }
-}
-
-impl Default for PackageRegistry {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-/// Registry statistics
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RegistryStats {
-    /// Total number of unique packages
-    pub total_packages: usize,
-
-    /// Total number of installed package versions
-    pub total_versions: usize,
-
-    /// Number of active packages
-    pub active_count: usize,
-
-    /// Number of pending updates
-    pub pending_count: usize,
-
-    /// Number of transactions in history
-    pub transaction_count: usize,
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_registry_basic() {
-        let mut registry = PackageRegistry::new();
-
-        registry.register_package("test".to_string(), Version::new(1, 0, 0));
-        registry.set_active("test".to_string(), Version::new(1, 0, 0));
-
-        assert!(registry.is_installed("test", &Version::new(1, 0, 0)));
-        assert_eq!(
-            registry.get_active("test"),
-            Some(&Version::new(1, 0, 0))
-        );
-    }
-
-    #[test]
-    fn test_registry_versions() {
-        let mut registry = PackageRegistry::new();
-
-        registry.register_package("test".to_string(), Version::new(1, 0, 0));
-        registry.register_package("test".to_string(), Version::new(1, 1, 0));
-        registry.register_package("test".to_string(), Version::new(2, 0, 0));
-
-        let versions = registry.get_versions("test").unwrap();
-        assert_eq!(versions.len(), 3);
-        assert_eq!(versions[0], Version::new(1, 0, 0));
-        assert_eq!(versions[1], Version::new(1, 1, 0));
-        assert_eq!(versions[2], Version::new(2, 0, 0));
-    }
-
-    #[test]
-    fn test_pending_updates() {
-        let mut registry = PackageRegistry::new();
-
-        let pkg_ref = PackageRef::new("test".to_string(), Version::new(2, 0, 0));
-        registry.add_pending(pkg_ref.clone());
-
-        assert_eq!(registry.get_pending().len(), 1);
-        assert_eq!(registry.get_pending()[0], pkg_ref);
-
-        registry.remove_pending(&pkg_ref);
-        assert_eq!(registry.get_pending().len(), 0);
-    }
-}
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Package registry for tracking installed packages
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::conffile::ConfFile;
+use crate::package::{InstallReason, PackageKind, PackageMetadata, PackageRef};
+use crate::snapshot::Snapshot;
+use crate::transaction::Transaction;
+use crate::trigger::{Registration, Trigger, TriggerRun};
+use crate::version::Version;
+
+/// Package registry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageRegistry {
+    /// Installed packages (name -> versions)
+    #[serde(default)]
+    pub packages: HashMap<String, Vec<Version>>,
+
+    /// Active versions (name -> version)
+    #[serde(default)]
+    pub active: HashMap<String, Version>,
+
+    /// Package kinds (name -> kind)
+    #[serde(default)]
+    pub kinds: HashMap<String, PackageKind>,
+
+    /// Why packages were installed (name -> reason); missing means explicit
+    #[serde(default)]
+    pub reasons: HashMap<String, InstallReason>,
+
+    /// Dependencies of the active versions (name -> package names)
+    #[serde(default)]
+    pub dependencies: HashMap<String, Vec<String>>,
+
+    /// Installed config files (path -> owner and pristine hash)
+    #[serde(default)]
+    pub conffiles: BTreeMap<String, ConfFile>,
+
+    /// Triggers registered by active packages (name -> triggers)
+    #[serde(default)]
+    pub triggers: BTreeMap<String, Vec<Trigger>>,
+
+    /// Pending updates (packages downloaded but not activated)
+    #[serde(default)]
+    pub pending: Vec<PackageRef>,
+
+    /// Transaction history
+    #[serde(default)]
+    pub transactions: Vec<Transaction>,
+
+    /// Named snapshots (restore points)
+    #[serde(default)]
+    pub snapshots: Vec<Snapshot>,
+}
+
+impl PackageRegistry {
+    /// Create a new package registry
+    pub fn new() -> Self {
+        Self {
+            packages: HashMap::new(),
+            active: HashMap::new(),
+            kinds: HashMap::new(),
+            reasons: HashMap::new(),
+            dependencies: HashMap::new(),
+            conffiles: BTreeMap::new(),
+            triggers: BTreeMap::new(),
+            pending: Vec::new(),
+            transactions: Vec::new(),
+            snapshots: Vec::new(),
+        }
+    }
+
+    /// Load the registry from disk
+    pub fn load() -> crate::Result<Self> {
+        Self::load_from_path(Self::registry_path())
+    }
+
+    /// Load the registry from a specific path
+    pub fn load_from_path(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+
+        if path.exists() {
+            let content = std::fs::read_to_string(path)?;
+            let mut registry: Self = serde_json::from_str(&content)
+                .map_err(|e| crate::Error::Serialization(e.to_string()))?;
+            registry.migrate_kinds();
+            Ok(registry)
+        } else {
+            Ok(Self::new())
+        }
+    }
+
+    /// Record kinds for packages installed before kinds were recorded
+    ///
+    /// Older registries only knew the kernel and system packages by name,
+    /// so the kind is inferred the same way.
+    fn migrate_kinds(&mut self) {
+        for name in self.packages.keys().chain(self.active.keys()) {
+            if !self.kinds.contains_key(name) {
+                let kind = match name.as_str() {
+                    "kernel" => PackageKind::Kernel,
+                    "system" => PackageKind::System,
+                    _ => PackageKind::App,
+                };
+                self.kinds.insert(name.clone(), kind);
+            }
+        }
+    }
+
+    /// Save the registry to disk
+    pub fn save(&self) -> crate::Result<()> {
+        self.save_to_path(Self::registry_path())
+    }
+
+    /// Save the registry to a specific path
+    pub fn save_to_path(&self, path: impl AsRef<Path>) -> crate::Result<()> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| crate::Error::Serialization(e.to_string()))?;
+
+        // Readers never see a half-written registry
+        crate::lock::write_atomic(path, content)
+    }
+
+    /// Get the registry file path
+    fn registry_path() -> PathBuf {
+        PathBuf::from("/var/lib/rpg/registry.json")
+    }
+
+    /// Register a package
+    pub fn register_package(&mut self, name: String, version: Version) {
+        self.packages
+            .entry(name.clone())
+            .or_default()
+            .push(version.clone());
+
+        // Sort versions
+        if let Some(versions) = self.packages.get_mut(&name) {
+            versions.sort();
+            versions.dedup();
+        }
+    }
+
+    /// Unregister a package
+    pub fn unregister_package(&mut self, name: &str, version: &Version) {
+        if let Some(versions) = self.packages.get_mut(name) {
+            versions.retain(|v| v != version);
+        }
+
+        // Remove from active if it was the active version
+        if let Some(active) = self.active.get(name) {
+            if active == version {
+                self.active.remove(name);
+                self.reasons.remove(name);
+                self.dependencies.remove(name);
+                self.triggers.remove(name);
+            }
+        }
+    }
+
+    /// Set the active version of a package
+    pub fn set_active(&mut self, name: String, version: Version) {
+        self.active.insert(name, version);
+    }
+
+    /// Get the active version of a package
+    pub fn get_active(&self, name: &str) -> Option<&Version> {
+        self.active.get(name)
+    }
+
+    /// Record the kind of a package
+    pub fn set_kind(&mut self, name: &str, kind: PackageKind) {
+        self.kinds.insert(name.to_string(), kind);
+    }
+
+    /// Get the recorded kind of a package
+    pub fn get_kind(&self, name: &str) -> Option<PackageKind> {
+        self.kinds.get(name).copied()
+    }
+
+    /// Get all versions of a package
+    pub fn get_versions(&self, name: &str) -> Option<&[Version]> {
+        self.packages.get(name).map(|v| v.as_slice())
+    }
+
+    /// Check if a package is installed
+    pub fn is_installed(&self, name: &str, version: &Version) -> bool {
+        self.packages
+            .get(name)
+            .map(|versions| versions.contains(version))
+            .unwrap_or(false)
+    }
+
+    /// Get all installed packages
+    pub fn list_packages(&self) -> Vec<String> {
+        self.packages.keys().cloned().collect()
+    }
+
+    /// Get system version
+    pub fn get_system_version(&self) -> Option<&Version> {
+        self.get_active("system")
+    }
+
+    /// Set system version
+    pub fn set_system_version(&mut self, version: Version) {
+        self.set_active("system".to_string(), version);
+    }
+
+    /// Add a pending update
+    pub fn add_pending(&mut self, package_ref: PackageRef) {
+        if !self.pending.contains(&package_ref) {
+            self.pending.push(package_ref);
+        }
+    }
+
+    /// Remove a pending update
+    pub fn remove_pending(&mut self, package_ref: &PackageRef) {
+        self.pending.retain(|p| p != package_ref);
+    }
+
+    /// Get all pending updates
+    pub fn get_pending(&self) -> &[PackageRef] {
+        &self.pending
+    }
+
+    /// Clear all pending updates
+    pub fn clear_pending(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Add a transaction to history
+    pub fn add_transaction(&mut self, transaction: Transaction) {
+        self.transactions.push(transaction);
+
+        // Keep only last 100 transactions
+        if self.transactions.len() > 100 {
+            self.transactions = self.transactions.split_off(self.transactions.len() - 100);
+        }
+    }
+
+    /// Record trigger runs of a batch on its most recent transaction
+    pub fn record_trigger_runs(&mut self, runs: Vec<TriggerRun>) {
+        if let Some(last) = self.transactions.last_mut() {
+            last.triggers.extend(runs);
+        }
+    }
+
+    /// Get transaction history
+    pub fn get_transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    /// Get recent transactions
+    pub fn get_recent_transactions(&self, count: usize) -> &[Transaction] {
+        let start = if self.transactions.len() > count {
+            self.transactions.len() - count
+        } else {
+            0
+        };
+        &self.transactions[start..]
+    }
+
+    /// Get the latest transaction
+    pub fn get_latest_transaction(&self) -> Option<&Transaction> {
+        self.transactions.last()
+    }
+
+    /// Find a transaction by ID
+    pub fn find_transaction(&self, id: &str) -> Option<&Transaction> {
+        self.transactions.iter().find(|t| t.id() == id)
+    }
+
+    /// Find the transactions named by an ID, batch ID, or unique prefix of either
+    ///
+    /// A batch matches all of its transactions, oldest first.
+    pub fn resolve_transactions(&self, id: &str) -> crate::Result<Vec<&Transaction>> {
+        if id.is_empty() {
+            return Err(crate::Error::Other("Empty transaction ID".to_string()));
+        }
+
+        let id = id.to_uppercase();
+
+        let mut by_id: Vec<&Transaction> = self
+            .transactions
+            .iter()
+            .filter(|t| t.id.starts_with(&id))
+            .collect();
+
+        let mut batches: Vec<&str> = self
+            .transactions
+            .iter()
+            .filter_map(|t| t.batch.as_deref())
+            .filter(|b| b.starts_with(&id))
+            .collect();
+        batches.sort_unstable();
+        batches.dedup();
+
+        match (by_id.len(), batches.len()) {
+            (1, 0) => Ok(vec![by_id.remove(0)]),
+            (0, 1) => Ok(self
+                .transactions
+                .iter()
+                .filter(|t| t.batch.as_deref() == Some(batches[0]))
+                .collect()),
+            (0, 0) => Err(crate::Error::Other(format!(
+                "Transaction not found: {}",
+                id
+            ))),
+            _ => Err(crate::Error::Other(format!(
+                "Transaction ID is ambiguous: {}",
+                id
+            ))),
+        }
+    }
+
+    /// Check if a kernel update is pending
+    pub fn has_pending_kernel_update(&self) -> bool {
+        self.pending.iter().any(|p| {
+            self.packages
+                .get(&p.name)
+                .map(|versions| {
+                    versions.iter().any(|v| {
+                        // Check if this is a newer kernel version
+                        p.name == "kernel" && v > &p.version
+                    })
+                })
+                .unwrap_or(false)
+        })
+    }
+
+    /// Get packages that need updates
+    pub fn get_available_updates(
+        &self,
+        repo_metadata: &HashMap<String, Vec<PackageMetadata>>,
+    ) -> Vec<PackageRef> {
+        let mut updates = Vec::new();
+
+        for name in self.packages.keys() {
+            if let Some(repo_versions) = repo_metadata.get(name) {
+                let current = self.get_active(name);
+
+                for metadata in repo_versions {
+                    if let Some(current_version) = current {
+                        if metadata.version > *current_version {
+                            updates.push(PackageRef::new(name.clone(), metadata.version.clone()));
+                        }
+                    }
+                }
+            }
+        }
+
+        updates
+    }
+
+    /// Get registry statistics
+    pub fn stats(&self) -> RegistryStats {
+        let total_packages = self.packages.len();
+        let total_versions: usize = self.packages.values().map(|v| v.len()).sum();
+        let active_count = self.active.len();
+        let pending_count = self.pending.len();
+        let transaction_count = self.transactions.len();
+
+        RegistryStats {
+            total_packages,
+            total_versions,
+            active_count,
+            pending_count,
+            transaction_count,
+        }
+    }
+
+    /// Record a transaction (alias for add_transaction)
+    pub fn record_transaction(&mut self, transaction: Transaction) {
+        self.add_transaction(transaction);
+    }
+
+    /// Add a package version to the registry
+    pub fn add_package(&mut self, name: &str, version: &Version) {
+        self.packages
+            .entry(name.to_string())
+            .or_default()
+            .push(version.clone());
+
+        // Sort and dedupe versions
+        if let Some(versions) = self.packages.get_mut(name) {
+            versions.sort();
+            versions.dedup();
+        }
+    }
+
+    /// List all versions of a package
+    pub fn list_versions(&self, name: &str) -> Vec<Version> {
+        self.packages.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Remove the active version of a package
+    pub fn remove_active(&mut self, name: &str) {
+        self.active.remove(name);
+    }
+
+    /// Get why a package is installed
+    pub fn get_reason(&self, name: &str) -> InstallReason {
+        self.reasons.get(name).copied().unwrap_or_default()
+    }
+
+    /// Record why a package is installed
+    ///
+    /// Explicitly installed packages stay explicit when something else
+    /// later depends on them.
+    pub fn set_reason(&mut self, name: &str, reason: InstallReason) {
+        let current = self.reasons.get(name).copied();
+        if reason == InstallReason::Dependency && current == Some(InstallReason::Explicit) {
+            return;
+        }
+        self.reasons.insert(name.to_string(), reason);
+    }
+
+    /// Record the dependencies of a package's active version
+    pub fn set_dependencies(&mut self, name: &str, dependencies: Vec<String>) {
+        if dependencies.is_empty() {
+            self.dependencies.remove(name);
+        } else {
+            self.dependencies.insert(name.to_string(), dependencies);
+        }
+    }
+
+    /// Get the active packages that depend on a package, sorted by name
+    pub fn reverse_dependencies(&self, name: &str) -> Vec<String> {
+        let mut dependents: Vec<String> = self
+            .dependencies
+            .iter()
+            .filter(|(dependent, deps)| {
+                self.active.contains_key(*dependent) && deps.iter().any(|d| d == name)
+            })
+            .map(|(dependent, _)| dependent.clone())
+            .collect();
+        dependents.sort();
+        dependents
+    }
+
+    /// Get dependencies that nothing needs anymore
+    ///
+    /// Removing an orphan can orphan its own dependencies, so they are
+    /// included too. Dependents come before their dependencies. Only apps
+    /// are offered, since system-level packages cannot be removed.
+    pub fn orphans(&self) -> Vec<String> {
+        let mut orphans: Vec<String> = Vec::new();
+
+        loop {
+            let mut found: Vec<String> = self
+                .active
+                .keys()
+                .filter(|name| {
+                    self.get_reason(name) == InstallReason::Dependency
+                        && self.get_kind(name).is_none_or(|kind| kind.is_app())
+                        && !orphans.contains(*name)
+                        && self
+                            .reverse_dependencies(name)
+                            .iter()
+                            .all(|dependent| orphans.contains(dependent))
+                })
+                .cloned()
+                .collect();
+
+            if found.is_empty() {
+                return orphans;
+            }
+
+            found.sort();
+            orphans.extend(found);
+        }
+    }
+
+    /// Record the triggers a package's active version registers
+    pub fn set_triggers(&mut self, name: &str, triggers: Vec<Trigger>) {
+        if triggers.is_empty() {
+            self.triggers.remove(name);
+        } else {
+            self.triggers.insert(name.to_string(), triggers);
+        }
+    }
+
+    /// Get all registered triggers with the package that owns them
+    pub fn all_triggers(&self) -> Vec<Registration> {
+        self.triggers
+            .iter()
+            .flat_map(|(owner, triggers)| {
+                let kind = self.get_kind(owner).unwrap_or(PackageKind::App);
+                triggers.iter().map(move |t| Registration {
+                    owner: owner.clone(),
+                    kind,
+                    trigger: t.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Get the config files owned by a package
+    pub fn conffiles_of(&self, package: &str) -> Vec<String> {
+        self.conffiles
+            .iter()
+            .filter(|(_, conffile)| conffile.package == package)
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// Record the pristine hash of an installed config file
+    pub fn record_conffile(&mut self, path: &str, package: &str, sha256: String) {
+        self.conffiles.insert(
+            path.to_string(),
+            ConfFile {
+                package: package.to_string(),
+                sha256,
+            },
+        );
+    }
+
+    /// Forget the config files owned by a package
+    pub fn forget_conffiles(&mut self, package: &str) {
+        self.conffiles
+            .retain(|_, conffile| conffile.package != package);
+    }
+
+    /// Record the current active versions as a named snapshot
+    pub fn create_snapshot(
+        &mut self,
+        name: &str,
+        description: Option<String>,
+    ) -> crate::Result<&Snapshot> {
+        if self.get_snapshot(name).is_some() {
+            return Err(crate::Error::Other(format!(
+                "Snapshot already exists: {}",
+                name
+            )));
+        }
+
+        let snapshot = Snapshot::new(name, description, &self.active, &self.kinds)?;
+        self.snapshots.push(snapshot);
+        Ok(self.snapshots.last().unwrap())
+    }
+
+    /// Get a snapshot by name
+    pub fn get_snapshot(&self, name: &str) -> Option<&Snapshot> {
+        self.snapshots.iter().find(|s| s.name == name)
+    }
+
+    /// List all snapshots, oldest first
+    pub fn list_snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
+    }
+
+    /// Delete a snapshot by name
+    pub fn delete_snapshot(&mut self, name: &str) -> bool {
+        let before = self.snapshots.len();
+        self.snapshots.retain(|s| s.name != name);
+        self.snapshots.len() != before
+    }
+}
+
+impl Default for PackageRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Registry statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryStats {
+    /// Total number of unique packages
+    pub total_packages: usize,
+
+    /// Total number of installed package versions
+    pub total_versions: usize,
+
+    /// Number of active packages
+    pub active_count: usize,
+
+    /// Number of pending updates
+    pub pending_count: usize,
+
+    /// Number of transactions in history
+    pub transaction_count: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::TransactionKind;
+
+    #[test]
+    fn test_registry_basic() {
+        let mut registry = PackageRegistry::new();
+
+        registry.register_package("test".to_string(), Version::new(1, 0, 0));
+        registry.set_active("test".to_string(), Version::new(1, 0, 0));
+
+        assert!(registry.is_installed("test", &Version::new(1, 0, 0)));
+        assert_eq!(registry.get_active("test"), Some(&Version::new(1, 0, 0)));
+    }
+
+    #[test]
+    fn test_registry_versions() {
+        let mut registry = PackageRegistry::new();
+
+        registry.register_package("test".to_string(), Version::new(1, 0, 0));
+        registry.register_package("test".to_string(), Version::new(1, 1, 0));
+        registry.register_package("test".to_string(), Version::new(2, 0, 0));
+
+        let versions = registry.get_versions("test").unwrap();
+        assert_eq!(versions.len(), 3);
+        assert_eq!(versions[0], Version::new(1, 0, 0));
+        assert_eq!(versions[1], Version::new(1, 1, 0));
+        assert_eq!(versions[2], Version::new(2, 0, 0));
+    }
+
+    #[test]
+    fn test_pending_updates() {
+        let mut registry = PackageRegistry::new();
+
+        let pkg_ref = PackageRef::new("test".to_string(), Version::new(2, 0, 0));
+        registry.add_pending(pkg_ref.clone());
+
+        assert_eq!(registry.get_pending().len(), 1);
+        assert_eq!(registry.get_pending()[0], pkg_ref);
+
+        registry.remove_pending(&pkg_ref);
+        assert_eq!(registry.get_pending().len(), 0);
+    }
+
+    #[test]
+    fn test_resolve_transactions() {
+        let mut registry = PackageRegistry::new();
+
+        let single = Transaction::new(TransactionKind::Install, vec![]);
+        let batch = "01BATCH".to_string();
+        let first =
+            Transaction::new(TransactionKind::Install, vec![]).with_batch(Some(batch.clone()));
+        let second =
+            Transaction::new(TransactionKind::Install, vec![]).with_batch(Some(batch.clone()));
+
+        registry.add_transaction(single.clone());
+        registry.add_transaction(first.clone());
+        registry.add_transaction(second.clone());
+
+        let found = registry.resolve_transactions(&single.id).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, single.id);
+
+        let found = registry.resolve_transactions("01batch").unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].id, first.id);
+        assert_eq!(found[1].id, second.id);
+
+        assert!(registry.resolve_transactions("ZZZZ").is_err());
+        assert!(registry.resolve_transactions("").is_err());
+    }
+
+    #[test]
+    fn test_registry_snapshots() {
+        let mut registry = PackageRegistry::new();
+        registry.register_package("editor".to_string(), Version::new(1, 0, 0));
+        registry.set_active("editor".to_string(), Version::new(1, 0, 0));
+
+        registry.create_snapshot("known-good", None).unwrap();
+        assert!(registry.create_snapshot("known-good", None).is_err());
+
+        registry.set_active("editor".to_string(), Version::new(2, 0, 0));
+        let snapshot = registry.get_snapshot("known-good").unwrap();
+        assert_eq!(
+            snapshot.versions.get("editor"),
+            Some(&Version::new(1, 0, 0))
+        );
+
+        assert!(registry.delete_snapshot("known-good"));
+        assert!(!registry.delete_snapshot("known-good"));
+        assert!(registry.list_snapshots().is_empty());
+    }
+
+    #[test]
+    fn test_registry_kinds() {
+        let mut registry = PackageRegistry::new();
+
+        registry.add_package("libfoo", &Version::new(1, 0, 0));
+        registry.set_kind("libfoo", PackageKind::System);
+
+        assert_eq!(registry.get_kind("libfoo"), Some(PackageKind::System));
+        assert_eq!(registry.get_kind("missing"), None);
+
+        let json = serde_json::to_string(&registry).unwrap();
+        let loaded: PackageRegistry = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.get_kind("libfoo"), Some(PackageKind::System));
+    }
+
+    #[test]
+    fn test_registry_infers_missing_kinds() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("registry.json");
+        let mut registry = PackageRegistry::new();
+        for name in ["kernel", "system", "editor"] {
+            registry.register_package(name.to_string(), Version::new(1, 0, 0));
+            registry.set_active(name.to_string(), Version::new(1, 0, 0));
+        }
+        registry.save_to_path(&path).unwrap();
+
+        let loaded = PackageRegistry::load_from_path(&path).unwrap();
+        assert_eq!(loaded.get_kind("kernel"), Some(PackageKind::Kernel));
+        assert_eq!(loaded.get_kind("system"), Some(PackageKind::System));
+        assert_eq!(loaded.get_kind("editor"), Some(PackageKind::App));
+    }
+
+    #[test]
+    fn test_registry_orphans() {
+        let mut registry = PackageRegistry::new();
+        for name in ["editor", "libui", "libfont", "viewer"] {
+            registry.register_package(name.to_string(), Version::new(1, 0, 0));
+            registry.set_active(name.to_string(), Version::new(1, 0, 0));
+        }
+
+        registry.set_reason("editor", InstallReason::Explicit);
+        registry.set_reason("viewer", InstallReason::Explicit);
+        registry.set_reason("libui", InstallReason::Dependency);
+        registry.set_reason("libfont", InstallReason::Dependency);
+        registry.set_dependencies("editor", vec!["libui".to_string()]);
+        registry.set_dependencies("libui", vec!["libfont".to_string()]);
+
+        // Explicit installs stay explicit
+        registry.set_reason("viewer", InstallReason::Dependency);
+        assert_eq!(registry.get_reason("viewer"), InstallReason::Explicit);
+
+        assert_eq!(registry.reverse_dependencies("libui"), vec!["editor"]);
+        assert!(registry.orphans().is_empty());
+
+        registry.unregister_package("editor", &Version::new(1, 0, 0));
+        assert!(registry.reverse_dependencies("libui").is_empty());
+        assert_eq!(registry.orphans(), vec!["libui", "libfont"]);
+
+        // System-level dependencies are never offered for removal
+        registry.set_kind("libfont", PackageKind::System);
+        assert_eq!(registry.orphans(), vec!["libui"]);
+    }
+}
@@ -0,0 +1,549 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Software bill of materials
+//!
+//! When a package is installed, its manifest is kept next to the registry
+//! in `manifests/<name>/<version>.json`, together with the SHA-256 of the
+//! archive and the source that served it. An SBOM lists every active
+//! package with what the registry and its manifest know about it, as an
+//! SPDX 2.3 or CycloneDX 1.5 JSON document.
+//!
+//! Packages installed before manifests were kept still appear, with the
+//! fields only the manifest carries left out.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use crate::archive::PackageManifest;
+use crate::package::PackageKind;
+use crate::registry::PackageRegistry;
+use crate::version::Version;
+
+/// Directory of stored manifests, next to the registry
+pub const MANIFESTS_DIR: &str = "manifests";
+
+/// Placeholder for unknown SPDX fields
+const NOASSERTION: &str = "NOASSERTION";
+
+/// SBOM document format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SbomFormat {
+    /// SPDX 2.3 JSON
+    #[default]
+    Spdx,
+    /// CycloneDX 1.5 JSON
+    CycloneDx,
+}
+
+impl std::fmt::Display for SbomFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Spdx => "spdx",
+            Self::CycloneDx => "cyclonedx",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for SbomFormat {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "spdx" => Ok(Self::Spdx),
+            "cyclonedx" => Ok(Self::CycloneDx),
+            _ => Err(crate::Error::Other(format!(
+                "Unknown SBOM format: {} (expected spdx or cyclonedx)",
+                s
+            ))),
+        }
+    }
+}
+
+/// The manifest of an installed package version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredManifest {
+    /// Manifest from the package archive
+    pub manifest: PackageManifest,
+    /// SHA-256 of the package archive
+    pub sha256: String,
+    /// Name of the source that served the package
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub source: String,
+    /// Signature of the package archive from the source's index
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub signature: String,
+}
+
+/// Get the path of a stored manifest
+pub fn manifest_path(dir: &Path, name: &str, version: &str) -> PathBuf {
+    dir.join(name).join(format!("{}.json", version))
+}
+
+/// Keep the manifest of an installed package version
+pub fn store_manifest(dir: &Path, stored: &StoredManifest) -> crate::Result<()> {
+    // Stored under the version as the registry spells it
+    let version = Version::parse(&stored.manifest.version)?;
+    let json = serde_json::to_vec_pretty(stored)
+        .map_err(|e| crate::Error::Serialization(e.to_string()))?;
+    crate::lock::write_atomic(
+        manifest_path(dir, &stored.manifest.name, &version.to_string()),
+        json,
+    )
+}
+
+/// Load the stored manifest of a package version, if there is one
+pub fn load_manifest(dir: &Path, name: &str, version: &str) -> Option<StoredManifest> {
+    let bytes = std::fs::read(manifest_path(dir, name, version)).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// An installed package as described in the SBOM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SbomPackage {
+    /// Package name
+    pub name: String,
+    /// Active version
+    pub version: Version,
+    /// Package kind
+    pub kind: PackageKind,
+    /// Architecture the package was built for
+    pub arch: Option<String>,
+    /// Package description
+    pub description: Option<String>,
+    /// Maintainer
+    pub author: Option<String>,
+    /// Homepage
+    pub homepage: Option<String>,
+    /// License, as an SPDX expression
+    pub license: Option<String>,
+    /// Download URL
+    pub url: Option<String>,
+    /// SHA-256 of the package archive
+    pub sha256: Option<String>,
+    /// Name of the source that served the package
+    pub source: Option<String>,
+    /// Names of the packages it depends on
+    pub dependencies: Vec<String>,
+}
+
+impl SbomPackage {
+    /// Describe the active version of a package
+    fn new(
+        registry: &PackageRegistry,
+        manifests_dir: &Path,
+        name: &str,
+        version: &Version,
+    ) -> Self {
+        let stored = load_manifest(manifests_dir, name, &version.to_string());
+        let manifest = stored.as_ref().map(|s| &s.manifest);
+        let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+
+        Self {
+            name: name.to_string(),
+            version: version.clone(),
+            kind: registry.get_kind(name).unwrap_or(PackageKind::App),
+            arch: manifest.map(|m| m.arch.clone()),
+            description: manifest.and_then(|m| m.description.clone()),
+            author: manifest.and_then(|m| m.maintainer.clone()),
+            homepage: manifest.and_then(|m| m.homepage.clone()),
+            license: manifest.and_then(|m| m.license.clone()),
+            url: manifest.and_then(|m| non_empty(&m.url)),
+            sha256: stored.as_ref().and_then(|s| non_empty(&s.sha256)),
+            source: stored.as_ref().and_then(|s| non_empty(&s.source)),
+            dependencies: registry.dependencies.get(name).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Get the package URL (purl) identifying the package
+    fn purl(&self) -> String {
+        format!("pkg:generic/{}@{}", self.name, self.version)
+    }
+
+    /// Get the SPDX identifier of the package
+    ///
+    /// Characters SPDX does not allow become `-`, followed by a hash of the
+    /// name so that, say, `foo_bar` and `foo-bar` get different IDs.
+    fn spdx_id(&self) -> String {
+        let allowed = |c: char| c.is_ascii_alphanumeric() || c == '.' || c == '-';
+        if self.name.chars().all(allowed) {
+            return format!("SPDXRef-Package-{}", self.name);
+        }
+
+        let name: String = self
+            .name
+            .chars()
+            .map(|c| if allowed(c) { c } else { '-' })
+            .collect();
+        let hash = crate::fetch::checksum_bytes(self.name.as_bytes());
+        format!("SPDXRef-Package-{}-{}", name, &hash[..8])
+    }
+}
+
+/// List the active packages of a registry
+pub fn installed_packages(registry: &PackageRegistry, manifests_dir: &Path) -> Vec<SbomPackage> {
+    let mut packages: Vec<SbomPackage> = registry
+        .active
+        .iter()
+        .map(|(name, version)| SbomPackage::new(registry, manifests_dir, name, version))
+        .collect();
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+    packages
+}
+
+/// Produce an SBOM document for a set of packages
+pub fn document(format: SbomFormat, name: &str, packages: &[SbomPackage]) -> Value {
+    let created = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let id = random_uuid();
+
+    match format {
+        SbomFormat::Spdx => spdx(name, packages, created, &id),
+        SbomFormat::CycloneDx => cyclonedx(name, packages, created, &id),
+    }
+}
+
+/// Build an SPDX 2.3 document
+fn spdx(name: &str, packages: &[SbomPackage], created: i64, id: &str) -> Value {
+    let or_noassertion =
+        |value: &Option<String>| value.clone().unwrap_or_else(|| NOASSERTION.to_string());
+
+    let spdx_packages: Vec<Value> = packages
+        .iter()
+        .map(|package| {
+            let mut entry = json!({
+                "name": package.name,
+                "SPDXID": package.spdx_id(),
+                "versionInfo": package.version.to_string(),
+                "downloadLocation": or_noassertion(&package.url),
+                "filesAnalyzed": false,
+                "licenseConcluded": NOASSERTION,
+                "licenseDeclared": or_noassertion(&package.license),
+                "copyrightText": NOASSERTION,
+                "primaryPackagePurpose": spdx_purpose(package.kind),
+                "externalRefs": [{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": package.purl(),
+                }],
+            });
+            if let Some(homepage) = &package.homepage {
+                entry["homepage"] = json!(homepage);
+            }
+            if let Some(author) = &package.author {
+                entry["supplier"] = json!(format!("Person: {}", author));
+            }
+            if let Some(description) = &package.description {
+                entry["summary"] = json!(description);
+            }
+            if let Some(sha256) = &package.sha256 {
+                entry["checksums"] = json!([{ "algorithm": "SHA256", "checksumValue": sha256 }]);
+            }
+            if let Some(source) = &package.source {
+                entry["sourceInfo"] = json!(format!("served by source {}", source));
+            }
+            entry
+        })
+        .collect();
+
+    let mut relationships: Vec<Value> = packages
+        .iter()
+        .map(|package| {
+            json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": package.spdx_id(),
+            })
+        })
+        .collect();
+    for (package, dependency) in installed_dependencies(packages) {
+        relationships.push(json!({
+            "spdxElementId": package.spdx_id(),
+            "relationshipType": "DEPENDS_ON",
+            "relatedSpdxElement": dependency.spdx_id(),
+        }));
+    }
+
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": name,
+        "documentNamespace": format!("https://rustux.com/spdx/{}-{}", name, id),
+        "creationInfo": {
+            "created": rfc3339(created),
+            "creators": [format!("Tool: rpg-{}", env!("CARGO_PKG_VERSION"))],
+        },
+        "packages": spdx_packages,
+        "relationships": relationships,
+    })
+}
+
+/// Build a CycloneDX 1.5 document
+fn cyclonedx(name: &str, packages: &[SbomPackage], created: i64, id: &str) -> Value {
+    let components: Vec<Value> = packages
+        .iter()
+        .map(|package| {
+            let mut component = json!({
+                "type": purpose(package.kind),
+                "bom-ref": package.purl(),
+                "name": package.name,
+                "version": package.version.to_string(),
+                "purl": package.purl(),
+            });
+            if let Some(description) = &package.description {
+                component["description"] = json!(description);
+            }
+            if let Some(author) = &package.author {
+                component["author"] = json!(author);
+            }
+            if let Some(license) = &package.license {
+                component["licenses"] = json!([{ "expression": license }]);
+            }
+            if let Some(sha256) = &package.sha256 {
+                component["hashes"] = json!([{ "alg": "SHA-256", "content": sha256 }]);
+            }
+
+            let mut references = Vec::new();
+            if let Some(homepage) = &package.homepage {
+                references.push(json!({ "type": "website", "url": homepage }));
+            }
+            if let Some(url) = &package.url {
+                references.push(json!({ "type": "distribution", "url": url }));
+            }
+            if !references.is_empty() {
+                component["externalReferences"] = json!(references);
+            }
+
+            let mut properties =
+                vec![json!({ "name": "rpg:kind", "value": package.kind.as_str() })];
+            if let Some(arch) = &package.arch {
+                properties.push(json!({ "name": "rpg:arch", "value": arch }));
+            }
+            if let Some(source) = &package.source {
+                properties.push(json!({ "name": "rpg:source", "value": source }));
+            }
+            component["properties"] = json!(properties);
+            component
+        })
+        .collect();
+
+    let edges = installed_dependencies(packages);
+    let dependencies: Vec<Value> = packages
+        .iter()
+        .map(|package| {
+            let depends_on: Vec<String> = edges
+                .iter()
+                .filter(|(p, _)| p.name == package.name)
+                .map(|(_, dependency)| dependency.purl())
+                .collect();
+            json!({ "ref": package.purl(), "dependsOn": depends_on })
+        })
+        .collect();
+
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "serialNumber": format!("urn:uuid:{}", id),
+        "version": 1,
+        "metadata": {
+            "timestamp": rfc3339(created),
+            "tools": [{
+                "vendor": "Rustux",
+                "name": "rpg",
+                "version": env!("CARGO_PKG_VERSION"),
+            }],
+            "component": { "type": "operating-system", "name": name },
+        },
+        "components": components,
+        "dependencies": dependencies,
+    })
+}
+
+/// Get the dependencies between packages of the set
+fn installed_dependencies(packages: &[SbomPackage]) -> Vec<(&SbomPackage, &SbomPackage)> {
+    packages
+        .iter()
+        .flat_map(|package| {
+            package.dependencies.iter().filter_map(move |name| {
+                packages
+                    .iter()
+                    .find(|p| p.name == *name)
+                    .map(|dependency| (package, dependency))
+            })
+        })
+        .collect()
+}
+
+/// Map a package kind to an SPDX primary package purpose
+fn spdx_purpose(kind: PackageKind) -> &'static str {
+    match kind {
+        PackageKind::App => "APPLICATION",
+        PackageKind::System | PackageKind::Kernel => "OPERATING_SYSTEM",
+        PackageKind::Boot => "FIRMWARE",
+    }
+}
+
+/// Map a package kind to a CycloneDX component type
+fn purpose(kind: PackageKind) -> &'static str {
+    match kind {
+        PackageKind::App => "application",
+        PackageKind::System | PackageKind::Kernel => "operating-system",
+        PackageKind::Boot => "firmware",
+    }
+}
+
+/// Format a Unix timestamp as an RFC 3339 UTC time
+fn rfc3339(secs: i64) -> String {
+    format!(
+        "{}Z",
+        crate::plan::format_timestamp(secs).replacen(' ', "T", 1)
+    )
+}
+
+/// Generate a random (version 4) UUID
+fn random_uuid() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signature::PackageSignature;
+
+    fn registry_with_manifests(dir: &Path) -> PackageRegistry {
+        let mut registry = PackageRegistry::new();
+        for (name, version) in [
+            ("editor", "1.2.0"),
+            ("libtext", "0.4.1"),
+            ("legacy", "0.1.0"),
+        ] {
+            let version = Version::parse(version).unwrap();
+            registry.add_package(name, &version);
+            registry.set_active(name.to_string(), version);
+        }
+        registry.set_dependencies("editor", vec!["libtext".to_string()]);
+
+        let mut manifest = PackageManifest::new(
+            "editor".to_string(),
+            "1.2.0".to_string(),
+            PackageKind::App,
+            "x86_64".to_string(),
+            0,
+            String::new(),
+            "https://rustux.com/apps/editor/1.2.0.rpg".to_string(),
+            PackageSignature::new([0u8; 64]),
+        );
+        manifest.license = Some("MIT OR Apache-2.0".to_string());
+        manifest.homepage = Some("https://rustux.com/editor".to_string());
+        manifest.maintainer = Some("Rustux Authors".to_string());
+        store_manifest(
+            dir,
+            &StoredManifest {
+                manifest,
+                sha256: "ab".repeat(32),
+                source: "apps".to_string(),
+                signature: String::new(),
+            },
+        )
+        .unwrap();
+
+        registry
+    }
+
+    #[test]
+    fn test_spdx_document() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let registry = registry_with_manifests(temp_dir.path());
+        let packages = installed_packages(&registry, temp_dir.path());
+
+        assert_eq!(packages.len(), 3);
+        assert_eq!(packages[0].license.as_deref(), Some("MIT OR Apache-2.0"));
+        assert_eq!(packages[0].source.as_deref(), Some("apps"));
+        // Packages without a stored manifest are still listed
+        assert_eq!(packages[1].name, "legacy");
+        assert_eq!(packages[1].license, None);
+
+        let doc = spdx("rustica", &packages, 0, "id");
+        assert_eq!(doc["spdxVersion"], "SPDX-2.3");
+        assert_eq!(doc["creationInfo"]["created"], "1970-01-01T00:00:00Z");
+        let editor = &doc["packages"][0];
+        assert_eq!(editor["SPDXID"], "SPDXRef-Package-editor");
+        assert_eq!(editor["licenseDeclared"], "MIT OR Apache-2.0");
+        assert_eq!(editor["checksums"][0]["checksumValue"], "ab".repeat(32));
+        assert_eq!(editor["primaryPackagePurpose"], "APPLICATION");
+        assert_eq!(doc["packages"][1]["licenseDeclared"], NOASSERTION);
+
+        let relationships = doc["relationships"].as_array().unwrap();
+        assert!(relationships.contains(&json!({
+            "spdxElementId": "SPDXRef-Package-editor",
+            "relationshipType": "DEPENDS_ON",
+            "relatedSpdxElement": "SPDXRef-Package-libtext",
+        })));
+
+        // Names that only differ in characters SPDX does not allow keep
+        // distinct IDs
+        let mut dashed = packages[0].clone();
+        dashed.name = "foo-bar".to_string();
+        let mut underscored = dashed.clone();
+        underscored.name = "foo_bar".to_string();
+        assert_eq!(dashed.spdx_id(), "SPDXRef-Package-foo-bar");
+        assert_ne!(underscored.spdx_id(), dashed.spdx_id());
+        assert!(underscored
+            .spdx_id()
+            .starts_with("SPDXRef-Package-foo-bar-"));
+
+        let mut kernel = packages[0].clone();
+        kernel.kind = PackageKind::Kernel;
+        let doc = spdx("rustica", &[kernel], 0, "id");
+        assert_eq!(
+            doc["packages"][0]["primaryPackagePurpose"],
+            "OPERATING_SYSTEM"
+        );
+    }
+
+    #[test]
+    fn test_cyclonedx_document() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let registry = registry_with_manifests(temp_dir.path());
+        let packages = installed_packages(&registry, temp_dir.path());
+
+        let doc = cyclonedx("rustica", &packages, 0, "id");
+        assert_eq!(doc["bomFormat"], "CycloneDX");
+        assert_eq!(doc["serialNumber"], "urn:uuid:id");
+        let editor = &doc["components"][0];
+        assert_eq!(editor["purl"], "pkg:generic/editor@1.2.0");
+        assert_eq!(editor["licenses"][0]["expression"], "MIT OR Apache-2.0");
+        assert_eq!(editor["hashes"][0]["alg"], "SHA-256");
+        assert_eq!(
+            doc["dependencies"][0],
+            json!({ "ref": "pkg:generic/editor@1.2.0", "dependsOn": ["pkg:generic/libtext@0.4.1"] })
+        );
+
+        assert_eq!(
+            "CycloneDX".parse::<SbomFormat>().unwrap(),
+            SbomFormat::CycloneDx
+        );
+        assert!("swid".parse::<SbomFormat>().is_err());
+        assert_eq!(random_uuid().len(), 36);
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Named system snapshots (restore points)
+//!
+//! A snapshot records the active version of every installed package
+//! (apps, system and kernel) so the whole set can be re-activated later
+//! in a single transaction.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::package::PackageKind;
+use crate::version::Version;
+
+/// A named restore point
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Snapshot name
+    pub name: String,
+
+    /// Optional description
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Creation timestamp
+    pub created_at: i64,
+
+    /// Active versions at creation time (name -> version)
+    pub versions: BTreeMap<String, Version>,
+
+    /// Package kinds at creation time (name -> kind)
+    #[serde(default)]
+    pub kinds: BTreeMap<String, PackageKind>,
+}
+
+/// Differences between a snapshot and the currently active versions
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotDiff {
+    /// Packages whose active version differs (name, current, snapshot)
+    pub changed: Vec<(String, Option<Version>, Version)>,
+    /// Active packages that are not part of the snapshot
+    pub extra: Vec<String>,
+}
+
+impl SnapshotDiff {
+    /// Check if the snapshot matches the active versions
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.extra.is_empty()
+    }
+}
+
+impl Snapshot {
+    /// Create a snapshot of the given active versions
+    pub fn new(
+        name: &str,
+        description: Option<String>,
+        active: &HashMap<String, Version>,
+        kinds: &HashMap<String, PackageKind>,
+    ) -> crate::Result<Self> {
+        Self::validate_name(name)?;
+
+        Ok(Self {
+            name: name.to_string(),
+            description,
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64,
+            versions: active.iter().map(|(n, v)| (n.clone(), v.clone())).collect(),
+            kinds: kinds
+                .iter()
+                .filter(|(n, _)| active.contains_key(*n))
+                .map(|(n, k)| (n.clone(), *k))
+                .collect(),
+        })
+    }
+
+    /// Validate a snapshot name
+    pub fn validate_name(name: &str) -> crate::Result<()> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            return Err(crate::Error::Other(format!(
+                "Invalid snapshot name: '{}' (use letters, digits, '-', '_' or '.')",
+                name
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Get the recorded kind of a package
+    pub fn kind(&self, name: &str) -> PackageKind {
+        self.kinds.get(name).copied().unwrap_or(PackageKind::App)
+    }
+
+    /// Compare the snapshot against the active versions
+    pub fn diff(&self, active: &HashMap<String, Version>) -> SnapshotDiff {
+        let changed = self
+            .versions
+            .iter()
+            .filter(|(name, version)| active.get(*name) != Some(*version))
+            .map(|(name, version)| (name.clone(), active.get(name).cloned(), version.clone()))
+            .collect();
+
+        let mut extra: Vec<String> = active
+            .keys()
+            .filter(|name| !self.versions.contains_key(*name))
+            .cloned()
+            .collect();
+        extra.sort();
+
+        SnapshotDiff { changed, extra }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_diff() {
+        let mut active = HashMap::new();
+        active.insert("editor".to_string(), Version::new(1, 0, 0));
+        active.insert("kernel".to_string(), Version::new(6, 1, 0));
+
+        let mut kinds = HashMap::new();
+        kinds.insert("kernel".to_string(), PackageKind::Kernel);
+
+        let snapshot = Snapshot::new("known-good", None, &active, &kinds).unwrap();
+        assert!(snapshot.diff(&active).is_empty());
+        assert_eq!(snapshot.kind("kernel"), PackageKind::Kernel);
+        assert_eq!(snapshot.kind("editor"), PackageKind::App);
+
+        active.insert("editor".to_string(), Version::new(2, 0, 0));
+        active.insert("viewer".to_string(), Version::new(0, 1, 0));
+
+        let diff = snapshot.diff(&active);
+        assert_eq!(
+            diff.changed,
+            vec![(
+                "editor".to_string(),
+                Some(Version::new(2, 0, 0)),
+                Version::new(1, 0, 0)
+            )]
+        );
+        assert_eq!(diff.extra, vec!["viewer".to_string()]);
+    }
+
+    #[test]
+    fn test_snapshot_name_validation() {
+        assert!(Snapshot::validate_name("pre-upgrade_2025.1").is_ok());
+        assert!(Snapshot::validate_name("").is_err());
+        assert!(Snapshot::validate_name("has space").is_err());
+        assert!(Snapshot::validate_name("../escape").is_err());
+    }
+}
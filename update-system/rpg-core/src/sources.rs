@@ -1,418 +1,831 @@
-// Copyright 2025 The Rustux Authors
-//
-// Use of this source code is governed by a MIT-style
-// license that can be found in the LICENSE file or at
-// https://opensource.org/licenses/MIT
-
-//! Repository sources management
-
-use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::Path;
-
-/// Default sources list file path
-pub const SOURCES_LIST_PATH: &str = "/etc/rpg/sources.list";
-
-/// Default sources
-pub const DEFAULT_SOURCES: &[(&str, &str)] = &[
-    ("kernel", "http://rustux.com/kernel"),
-    ("system", "http://rustux.com/rustica"),
-    ("apps", "http://rustux.com/apps"),
-];
-
-/// Repository source
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Source {
-    /// Source name/identifier
-    pub name: String,
-    /// Source URL
-    pub url: String,
-    /// Source type (kernel, system, apps)
-    #[serde(rename = "type")]
-    pub source_type: String,
-    /// Whether this source is enabled
-    #[serde(default = "default_enabled")]
-    pub enabled: bool,
-    /// Source priority (lower = higher priority)
-    #[serde(default = "default_priority")]
-    pub priority: u32,
-}
-
-fn default_enabled() -> bool {
-    true
-}
-
-fn default_priority() -> u32 {
-    100
-}
-
-impl Source {
-    /// Create a new source
-    pub fn new(name: String, url: String, source_type: String) -> Self {
-        Self {
-            name,
-            url,
-            source_type,
-            enabled: true,
-            priority: 100,
-        }
-    }
-
-    /// Create a source with priority
-    pub fn with_priority(name: String, url: String, source_type: String, priority: u32) -> Self {
-        Self {
-            name,
-            url,
-            source_type,
-            enabled: true,
-            priority,
-        }
-    }
-
-    /// Check if this source is for kernels
-    pub fn is_kernel(&self) -> bool {
-        self.source_type == "kernel"
-    }
-
-    /// Check if this source is for system packages
-    pub fn is_system(&self) -> bool {
-        self.source_type == "system"
-    }
-
-    /// Check if this source is for apps
-    pub fn is_apps(&self) -> bool {
-        self.source_type == "apps"
-    }
-
-    /// Get the package index URL for this source
-    pub fn index_url(&self) -> String {
-        format!("{}/index.json", self.url.trim_end_matches('/'))
-    }
-
-    /// Get the package URL for a specific package
-    pub fn package_url(&self, package_name: &str, version: &str) -> String {
-        format!(
-            "{}/{}/{}.rpg",
-            self.url.trim_end_matches('/'),
-            package_name,
-            version
-        )
-    }
-
-    /// Check if the source is reachable
-    pub async fn check_reachable(&self) -> bool {
-        // In production, would perform an HTTP HEAD request
-        // For now, just return true
-        true
-    }
-}
-
-/// Sources configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SourcesConfig {
-    /// List of sources
-    #[serde(default)]
-    pub sources: Vec<Source>,
-}
-
-impl Default for SourcesConfig {
-    fn default() -> Self {
-        Self {
-            sources: DEFAULT_SOURCES
-                .iter()
-                .map(|(name, url)| Source::new(name.to_string(), url.to_string(), name.to_string()))
-                .collect(),
-        }
-    }
-}
-
-impl SourcesConfig {
-    /// Load sources from the default path
-    pub fn load() -> crate::Result<Self> {
-        Self::load_from_path(SOURCES_LIST_PATH)
-    }
-
-    /// Load sources from a specific path
-    pub fn load_from_path(path: &str) -> crate::Result<Self> {
-        if !Path::new(path).exists() {
-            // Return default config if file doesn't exist
-            return Ok(Self::default());
-        }
-
-        let content = fs::read_to_string(path).map_err(|e| {
-            crate::Error::Other(format!("Failed to read sources list: {}", e))
-        })?;
-
-        // Parse the file (line by line format)
-        let mut sources = Vec::new();
-
-        for line in content.lines() {
-            let line = line.trim();
-
-            // Skip comments and empty lines
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-
-            // Parse source line
-            // Format: type url [priority]
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() < 2 {
-                continue;
-            }
-
-            let source_type = parts[0];
-            let url = parts[1];
-            let priority = if parts.len() > 2 {
-                parts[2].parse().unwrap_or(100)
-            } else {
-                100
-            };
-
-            sources.push(Source::with_priority(
-                format!("{}-{}", source_type, url),
-                url.to_string(),
-                source_type.to_string(),
-                priority,
-            ));
-        }
-
-        Ok(Self { sources })
-    }
-
-    /// Save sources to the default path
-    pub fn save(&self) -> crate::Result<()> {
-        self.save_to_path(SOURCES_LIST_PATH)
-    }
-
-    /// Save sources to a specific path
-    pub fn save_to_path(&self, path: &str) -> crate::Result<()> {
-        // Ensure directory exists
-        if let Some(parent) = Path::new(path).parent() {
-            fs::create_dir_all(parent).map_err(|e| {
-                crate::Error::Other(format!("Failed to create directory: {}", e))
-            })?;
-        }
-
-        // Write sources file
-        let mut content = String::from("# Rustica Package Sources\n");
-        content.push_str("# Format: type url [priority]\n");
-        content.push_str("# Types: kernel, system, apps\n\n");
-
-        // Sort by priority
-        let mut sorted_sources = self.sources.clone();
-        sorted_sources.sort_by_key(|s| s.priority);
-
-        for source in &sorted_sources {
-            if !source.enabled {
-                content.push_str("# ");
-            }
-            content.push_str(&format!("{} {}\n", source.source_type, source.url));
-        }
-
-        fs::write(path, content).map_err(|e| {
-            crate::Error::Other(format!("Failed to write sources list: {}", e))
-        })?;
-
-        Ok(())
-    }
-
-    /// Get sources for a specific type
-    pub fn get_sources_for_type(&self, source_type: &str) -> Vec<&Source> {
-        self.sources
-            .iter()
-            .filter(|s| s.enabled && s.source_type == source_type)
-            .collect()
-    }
-
-    /// Get all kernel sources
-    pub fn kernel_sources(&self) -> Vec<&Source> {
-        self.get_sources_for_type("kernel")
-    }
-
-    /// Get all system sources
-    pub fn system_sources(&self) -> Vec<&Source> {
-        self.get_sources_for_type("system")
-    }
-
-    /// Get all app sources
-    pub fn app_sources(&self) -> Vec<&Source> {
-        self.get_sources_for_type("apps")
-    }
-
-    /// Add a source
-    pub fn add_source(&mut self, source: Source) {
-        // Remove existing source with same name
-        self.sources.retain(|s| s.name != source.name);
-        self.sources.push(source);
-    }
-
-    /// Remove a source by name
-    pub fn remove_source(&mut self, name: &str) {
-        self.sources.retain(|s| s.name != name);
-    }
-
-    /// Enable a source
-    pub fn enable_source(&mut self, name: &str) -> bool {
-        if let Some(source) = self.sources.iter_mut().find(|s| s.name == name) {
-            source.enabled = true;
-            true
-        } else {
-            false
-        }
-    }
-
-    /// Disable a source
-    pub fn disable_source(&mut self, name: &str) -> bool {
-        if let Some(source) = self.sources.iter_mut().find(|s| s.name == name) {
-            source.enabled = false;
-            true
-        } else {
-            false
-        }
-    }
-
-    /// Get all enabled sources
-    pub fn enabled_sources(&self) -> Vec<&Source> {
-        self.sources.iter().filter(|s| s.enabled).collect()
-    }
-
-    /// Validate sources configuration
-    pub fn validate(&self) -> crate::Result<()> {
-        for source in &self.sources {
-            if source.url.is_empty() {
-                return Err(crate::Error::Other(format!(
-                    "Source '{}' has empty URL",
-                    source.name
-                )));
-            }
-
-            // Validate URL format
-            if !source.url.starts_with("http://") && !source.url.starts_with("https://") {
-                return Err(crate::Error::Other(format!(
-                    "Source '{}' has invalid URL: {}",
-                    source.name, source.url
-                )));
-            }
-
-            // Validate source type
-            if !["kernel", "system", "apps"].contains(&source.source_type.as_str()) {
-                return Err(crate::Error::Other(format!(
-                    "Source '{}' has invalid type: {}",
-                    source.name, source.source_type
-                )));
-            }
-        }
-
-        Ok(())
-    }
-
-    /// Get statistics
-    pub fn stats(&self) -> SourcesStats {
-        let total = self.sources.len();
-        let enabled = self.sources.iter().filter(|s| s.enabled).count();
-        let kernel_count = self.kernel_sources().len();
-        let system_count = self.system_sources().len();
-        let apps_count = self.app_sources().len();
-
-        SourcesStats {
-            total,
-            enabled,
-            disabled: total - enabled,
-            kernel_count,
-            system_count,
-            apps_count,
-        }
-    }
-}
-
-/// Sources statistics
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SourcesStats {
-    /// Total number of sources
-    pub total: usize,
-    /// Number of enabled sources
-    pub enabled: usize,
-    /// Number of disabled sources
-    pub disabled: usize,
-    /// Number of kernel sources
-    pub kernel_count: usize,
-    /// Number of system sources
-    pub system_count: usize,
-    /// Number of app sources
-    pub apps_count: usize,
-}
-
-/// Repository index
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RepositoryIndex {
-    /// Repository name
-    pub name: String,
-    /// Repository URL
-    pub url: String,
-    /// Last update timestamp
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_updated: Option<i64>,
-    /// Available packages
-    pub packages: Vec<String>,
-}
-
-/// Package manifest from repository
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PackageManifest {
-    /// Package name
-    pub name: String,
-    /// Package version
-    pub version: String,
-    /// Package description
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub description: Option<String>,
-    /// Package size in bytes
-    pub size: u64,
-    /// SHA-256 checksum
-    pub sha256: String,
-    /// Package signature (base64)
-    pub signature: String,
-    /// Dependencies
-    #[serde(default)]
-    pub dependencies: Vec<String>,
-    /// Download URL
-    pub url: String,
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_source_creation() {
-        let source = Source::new(
-            "test".to_string(),
-            "http://example.com".to_string(),
-            "kernel".to_string(),
-        );
-
-        assert_eq!(source.name, "test");
-        assert!(source.is_kernel());
-        assert!(!source.is_apps());
-    }
-
-    #[test]
-    fn test_sources_config() {
-        let config = SourcesConfig::default();
-        assert_eq!(config.sources.len(), 3);
-        assert!(config.kernel_sources().len() > 0);
-    }
-
-    #[test]
-    fn test_source_urls() {
-        let source = Source::new(
-            "test".to_string(),
-            "http://example.com/".to_string(),
-            "apps".to_string(),
-        );
-
-        assert_eq!(source.index_url(), "http://example.com/index.json");
-        assert_eq!(source.package_url("foo", "1.0.0"), "http://example.com/foo/1.0.0.rpg");
-    }
-}
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Repository sources management
+//!
+//! Sources live in `/etc/rpg/sources.toml`:
+//!
+//! ```toml
+//! [[sources]]
+//! name = "apps"
+//! url = "https://rustux.com/apps"
+//! type = "apps"
+//! priority = 10
+//! channel = "beta"
+//! trusted_key = "<base64 Ed25519 public key>"
+//! proxy = "http://proxy.example.com:3128"
+//! ca_bundle = "/etc/rpg/certs/internal.pem"
+//!
+//! [package_channels]
+//! editor = "nightly"
+//! ```
+//!
+//! Basic and bearer credentials belong in the root-only
+//! `/etc/rpg/auth.toml` (see [`crate::auth`]) rather than in this file.
+//!
+//! The older line-based `sources.list` is still read, and is migrated to
+//! the TOML format the first time the sources are loaded.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::package::PackageKind;
+use crate::version::Channel;
+
+/// Default sources file path
+pub const SOURCES_PATH: &str = "/etc/rpg/sources.toml";
+
+/// Legacy line-based sources list, migrated to [`SOURCES_PATH`]
+pub const SOURCES_LIST_PATH: &str = "/etc/rpg/sources.list";
+
+/// Suffix given to a legacy sources list once it has been migrated
+const MIGRATED_SUFFIX: &str = "migrated";
+
+/// Default sources
+pub const DEFAULT_SOURCES: &[(&str, &str)] = &[
+    ("kernel", "http://rustux.com/kernel"),
+    ("system", "http://rustux.com/rustica"),
+    ("apps", "http://rustux.com/apps"),
+];
+
+/// Repository source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Source {
+    /// Source name/identifier
+    pub name: String,
+    /// Source URL
+    pub url: String,
+    /// Source type (kernel, system, apps)
+    #[serde(rename = "type")]
+    pub source_type: String,
+    /// Whether this source is enabled
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Source priority (lower = higher priority)
+    #[serde(default = "default_priority")]
+    pub priority: u32,
+    /// Update channel offered to packages from this source
+    #[serde(default)]
+    pub channel: Channel,
+    /// Base64 Ed25519 public key that signs this source's packages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trusted_key: Option<String>,
+    /// Extra HTTP header sent with every request (`Name: value`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_header: Option<String>,
+    /// Proxy URL used to reach this source
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// PEM bundle of CA certificates trusted for this source
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<PathBuf>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_priority() -> u32 {
+    100
+}
+
+impl Source {
+    /// Create a new source
+    pub fn new(name: String, url: String, source_type: String) -> Self {
+        Self {
+            name,
+            url,
+            source_type,
+            enabled: true,
+            priority: 100,
+            channel: Channel::Stable,
+            trusted_key: None,
+            auth_header: None,
+            proxy: None,
+            ca_bundle: None,
+        }
+    }
+
+    /// Create a source with priority
+    pub fn with_priority(name: String, url: String, source_type: String, priority: u32) -> Self {
+        Self {
+            name,
+            url,
+            source_type,
+            enabled: true,
+            priority,
+            channel: Channel::Stable,
+            trusted_key: None,
+            auth_header: None,
+            proxy: None,
+            ca_bundle: None,
+        }
+    }
+
+    /// Set the update channel for this source
+    pub fn with_channel(mut self, channel: Channel) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// Set the key this source's packages must be signed with
+    pub fn with_trusted_key(mut self, key: impl Into<String>) -> Self {
+        self.trusted_key = Some(key.into());
+        self
+    }
+
+    /// Set an HTTP header sent with every request to this source
+    pub fn with_auth_header(mut self, header: impl Into<String>) -> Self {
+        self.auth_header = Some(header.into());
+        self
+    }
+
+    /// Set the proxy used to reach this source
+    pub fn with_proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    /// Split the auth header into its name and value
+    pub fn auth_header_parts(&self) -> Option<(&str, &str)> {
+        let (name, value) = self.auth_header.as_deref()?.split_once(':')?;
+        Some((name.trim(), value.trim()))
+    }
+
+    /// Check if this source is for kernels
+    pub fn is_kernel(&self) -> bool {
+        self.source_type == "kernel"
+    }
+
+    /// Check if this source is for system packages
+    pub fn is_system(&self) -> bool {
+        self.source_type == "system"
+    }
+
+    /// Check if this source is for apps
+    pub fn is_apps(&self) -> bool {
+        self.source_type == "apps"
+    }
+
+    /// Get the package index URL for this source
+    pub fn index_url(&self) -> String {
+        format!("{}/index.json", self.url.trim_end_matches('/'))
+    }
+
+    /// Get the package URL for a specific package
+    pub fn package_url(&self, package_name: &str, version: &str) -> String {
+        format!(
+            "{}/{}/{}.rpg",
+            self.url.trim_end_matches('/'),
+            package_name,
+            version
+        )
+    }
+
+    /// Get the URL of a file given its path relative to the source
+    pub fn file_url(&self, path: &str) -> String {
+        format!(
+            "{}/{}",
+            self.url.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    }
+
+    /// Check if the source is reachable
+    pub async fn check_reachable(&self) -> bool {
+        // In production, would perform an HTTP HEAD request
+        // For now, just return true
+        true
+    }
+}
+
+/// Sources configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourcesConfig {
+    /// List of sources
+    #[serde(default)]
+    pub sources: Vec<Source>,
+    /// Per-package channel overrides (name -> channel)
+    #[serde(default)]
+    pub package_channels: BTreeMap<String, Channel>,
+}
+
+impl Default for SourcesConfig {
+    fn default() -> Self {
+        Self {
+            sources: DEFAULT_SOURCES
+                .iter()
+                .map(|(name, url)| Source::new(name.to_string(), url.to_string(), name.to_string()))
+                .collect(),
+            package_channels: BTreeMap::new(),
+        }
+    }
+}
+
+impl SourcesConfig {
+    /// Load sources from the default path
+    pub fn load() -> crate::Result<Self> {
+        Self::load_from_path(SOURCES_PATH)
+    }
+
+    /// Load sources from a specific path
+    ///
+    /// If a TOML sources file does not exist yet but a legacy `sources.list`
+    /// sits next to it, the legacy file is converted, written to `path` and
+    /// renamed to `sources.list.migrated`. When that fails, e.g. for an
+    /// unprivileged user, the legacy file is used as is. A file still in the
+    /// legacy format is read as such and rewritten as TOML on the next save.
+    pub fn load_from_path(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+
+        if !path.exists() {
+            let legacy = path.with_file_name("sources.list");
+            if legacy != path && legacy.exists() {
+                let content = fs::read_to_string(&legacy).map_err(|e| {
+                    crate::Error::Other(format!("Failed to read {}: {}", legacy.display(), e))
+                })?;
+                let config = Self::parse_legacy(&content)?;
+
+                if let Err(e) = config.migrate(&legacy, path) {
+                    log::warn!("Failed to migrate {}: {}", legacy.display(), e);
+                }
+                return Ok(config);
+            }
+
+            // Return default config if file doesn't exist
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path).map_err(|e| {
+            crate::Error::Other(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+
+        if is_legacy_format(&content) {
+            return Self::parse_legacy(&content);
+        }
+
+        Self::parse(&content)
+    }
+
+    /// Parse the TOML sources format
+    pub fn parse(content: &str) -> crate::Result<Self> {
+        let config: Self =
+            toml::from_str(content).map_err(|e| crate::Error::Serialization(e.to_string()))?;
+
+        let mut names = std::collections::HashSet::new();
+        for source in &config.sources {
+            if !names.insert(source.name.as_str()) {
+                return Err(crate::Error::Other(format!(
+                    "Duplicate source name: {}",
+                    source.name
+                )));
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Parse the legacy line-based `sources.list` format
+    ///
+    /// Lines are `type url [priority] [channel=name]` or
+    /// `channel package name`. Sources commented out with `# ` are kept as
+    /// disabled. Names are derived from the type, numbered when a type has
+    /// more than one source.
+    pub fn parse_legacy(content: &str) -> crate::Result<Self> {
+        let mut sources: Vec<Source> = Vec::new();
+        let mut package_channels = BTreeMap::new();
+
+        for line in content.lines() {
+            let mut line = line.trim();
+            let mut enabled = true;
+
+            if let Some(rest) = line.strip_prefix('#') {
+                // Disabled sources are written as commented-out source lines
+                line = rest.trim();
+                enabled = false;
+            }
+
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 2 {
+                continue;
+            }
+
+            if parts[0] == "channel" {
+                if enabled && parts.len() > 2 {
+                    package_channels.insert(parts[1].to_string(), parts[2].parse()?);
+                }
+                continue;
+            }
+
+            let source_type = parts[0];
+            let url = parts[1];
+            if !["kernel", "system", "apps"].contains(&source_type)
+                || !(url.starts_with("http://") || url.starts_with("https://"))
+            {
+                if enabled {
+                    log::warn!("Ignoring malformed sources.list line: {}", line);
+                }
+                continue;
+            }
+
+            let mut priority = 100;
+            let mut channel = Channel::Stable;
+
+            for option in &parts[2..] {
+                if let Some(value) = option.strip_prefix("channel=") {
+                    channel = value.parse()?;
+                } else {
+                    priority = option.parse().unwrap_or(100);
+                }
+            }
+
+            let mut source = Source::with_priority(
+                source_type.to_string(),
+                url.to_string(),
+                source_type.to_string(),
+                priority,
+            )
+            .with_channel(channel);
+            source.enabled = enabled;
+            sources.push(source);
+        }
+
+        // Number the names of types with several sources
+        for source_type in ["kernel", "system", "apps"] {
+            let count = sources
+                .iter()
+                .filter(|s| s.source_type == source_type)
+                .count();
+            if count < 2 {
+                continue;
+            }
+            for (i, source) in sources
+                .iter_mut()
+                .filter(|s| s.source_type == source_type)
+                .enumerate()
+            {
+                source.name = format!("{}-{}", source_type, i + 1);
+            }
+        }
+
+        Ok(Self {
+            sources,
+            package_channels,
+        })
+    }
+
+    /// Convert a legacy sources list into a TOML sources file
+    fn migrate(&self, legacy: &Path, path: &Path) -> crate::Result<()> {
+        self.save_to_path(path)?;
+
+        let mut migrated = legacy.as_os_str().to_owned();
+        migrated.push(".");
+        migrated.push(MIGRATED_SUFFIX);
+        fs::rename(legacy, &migrated)?;
+
+        log::info!(
+            "Migrated {} to {} ({} sources)",
+            legacy.display(),
+            path.display(),
+            self.sources.len()
+        );
+
+        Ok(())
+    }
+
+    /// Save sources to the default path
+    pub fn save(&self) -> crate::Result<()> {
+        self.save_to_path(SOURCES_PATH)
+    }
+
+    /// Save sources to a specific path
+    ///
+    /// Always writes the TOML format, even when `path` names a legacy
+    /// `sources.list`.
+    pub fn save_to_path(&self, path: impl AsRef<Path>) -> crate::Result<()> {
+        let body =
+            toml::to_string_pretty(self).map_err(|e| crate::Error::Serialization(e.to_string()))?;
+
+        let mut content = String::from("# Rustica Package Sources\n");
+        content.push_str("# Types: kernel, system, apps\n");
+        content.push_str("# Channels: stable, beta, nightly\n\n");
+        content.push_str(&body);
+
+        crate::lock::write_atomic(path, content)
+    }
+
+    /// Get sources for a specific type
+    pub fn get_sources_for_type(&self, source_type: &str) -> Vec<&Source> {
+        self.sources
+            .iter()
+            .filter(|s| s.enabled && s.source_type == source_type)
+            .collect()
+    }
+
+    /// Get all kernel sources
+    pub fn kernel_sources(&self) -> Vec<&Source> {
+        self.get_sources_for_type("kernel")
+    }
+
+    /// Get all system sources
+    pub fn system_sources(&self) -> Vec<&Source> {
+        self.get_sources_for_type("system")
+    }
+
+    /// Get all app sources
+    pub fn app_sources(&self) -> Vec<&Source> {
+        self.get_sources_for_type("apps")
+    }
+
+    /// Get the sources that serve packages of a given kind
+    pub fn sources_for_kind(&self, kind: PackageKind) -> Vec<&Source> {
+        match kind {
+            PackageKind::Kernel => self.kernel_sources(),
+            PackageKind::System => self.system_sources(),
+            PackageKind::App | PackageKind::Boot => self.app_sources(),
+        }
+    }
+
+    /// Add a source
+    pub fn add_source(&mut self, source: Source) {
+        // Remove existing source with same name
+        self.sources.retain(|s| s.name != source.name);
+        self.sources.push(source);
+    }
+
+    /// Remove a source by name
+    pub fn remove_source(&mut self, name: &str) {
+        self.sources.retain(|s| s.name != name);
+    }
+
+    /// Enable a source
+    pub fn enable_source(&mut self, name: &str) -> bool {
+        if let Some(source) = self.sources.iter_mut().find(|s| s.name == name) {
+            source.enabled = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Disable a source
+    pub fn disable_source(&mut self, name: &str) -> bool {
+        if let Some(source) = self.sources.iter_mut().find(|s| s.name == name) {
+            source.enabled = false;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Set the update channel of a source
+    pub fn set_source_channel(&mut self, name: &str, channel: Channel) -> bool {
+        if let Some(source) = self.sources.iter_mut().find(|s| s.name == name) {
+            source.channel = channel;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Subscribe a package to a channel, overriding its source's channel
+    pub fn set_package_channel(&mut self, package: &str, channel: Channel) {
+        self.package_channels.insert(package.to_string(), channel);
+    }
+
+    /// Remove a package's channel override
+    pub fn clear_package_channel(&mut self, package: &str) -> bool {
+        self.package_channels.remove(package).is_some()
+    }
+
+    /// Get the channel a package follows when served by the named source
+    pub fn channel_for(&self, package: &str, source_name: &str) -> Channel {
+        if let Some(channel) = self.package_channels.get(package) {
+            return *channel;
+        }
+
+        self.sources
+            .iter()
+            .find(|s| s.name == source_name)
+            .map(|s| s.channel)
+            .unwrap_or_default()
+    }
+
+    /// Get all enabled sources
+    pub fn enabled_sources(&self) -> Vec<&Source> {
+        self.sources.iter().filter(|s| s.enabled).collect()
+    }
+
+    /// Validate sources configuration
+    pub fn validate(&self) -> crate::Result<()> {
+        for source in &self.sources {
+            if source.url.is_empty() {
+                return Err(crate::Error::Other(format!(
+                    "Source '{}' has empty URL",
+                    source.name
+                )));
+            }
+
+            // Validate URL format
+            if !source.url.starts_with("http://") && !source.url.starts_with("https://") {
+                return Err(crate::Error::Other(format!(
+                    "Source '{}' has invalid URL: {}",
+                    source.name, source.url
+                )));
+            }
+
+            // Validate source type
+            if !["kernel", "system", "apps"].contains(&source.source_type.as_str()) {
+                return Err(crate::Error::Other(format!(
+                    "Source '{}' has invalid type: {}",
+                    source.name, source.source_type
+                )));
+            }
+
+            if let Some(key) = &source.trusted_key {
+                crate::signature::KeyPair::import_public(key).map_err(|_| {
+                    crate::Error::Other(format!("Source '{}' has invalid trusted key", source.name))
+                })?;
+            }
+
+            if source.auth_header.is_some() && source.auth_header_parts().is_none() {
+                return Err(crate::Error::Other(format!(
+                    "Source '{}' has invalid auth header (expected 'Name: value')",
+                    source.name
+                )));
+            }
+
+            if let Some(proxy) = &source.proxy {
+                if !proxy.contains("://") {
+                    return Err(crate::Error::Other(format!(
+                        "Source '{}' has invalid proxy URL: {}",
+                        source.name, proxy
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get statistics
+    pub fn stats(&self) -> SourcesStats {
+        let total = self.sources.len();
+        let enabled = self.sources.iter().filter(|s| s.enabled).count();
+        let kernel_count = self.kernel_sources().len();
+        let system_count = self.system_sources().len();
+        let apps_count = self.app_sources().len();
+
+        SourcesStats {
+            total,
+            enabled,
+            disabled: total - enabled,
+            kernel_count,
+            system_count,
+            apps_count,
+        }
+    }
+}
+
+/// Check if content is in the legacy line-based format
+///
+/// Legacy files start with a `type url` or `channel package name` line,
+/// which is never valid TOML.
+fn is_legacy_format(content: &str) -> bool {
+    content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut parts = line.split_whitespace();
+            let first = parts.next().unwrap_or_default();
+            let second = parts.next().unwrap_or_default();
+            ["kernel", "system", "apps", "channel"].contains(&first)
+                && !second.is_empty()
+                && !second.starts_with('=')
+        })
+        .unwrap_or(false)
+}
+
+/// Sources statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourcesStats {
+    /// Total number of sources
+    pub total: usize,
+    /// Number of enabled sources
+    pub enabled: usize,
+    /// Number of disabled sources
+    pub disabled: usize,
+    /// Number of kernel sources
+    pub kernel_count: usize,
+    /// Number of system sources
+    pub system_count: usize,
+    /// Number of app sources
+    pub apps_count: usize,
+}
+
+/// Repository index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepositoryIndex {
+    /// Repository name
+    pub name: String,
+    /// Repository URL
+    pub url: String,
+    /// Last update timestamp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_updated: Option<i64>,
+    /// Available packages
+    pub packages: Vec<String>,
+}
+
+/// Package manifest from repository
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageManifest {
+    /// Package name
+    pub name: String,
+    /// Package version
+    pub version: String,
+    /// Package description
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Package size in bytes
+    pub size: u64,
+    /// SHA-256 checksum
+    pub sha256: String,
+    /// Package signature (base64)
+    pub signature: String,
+    /// Dependencies
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    /// Download URL
+    pub url: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_creation() {
+        let source = Source::new(
+            "test".to_string(),
+            "http://example.com".to_string(),
+            "kernel".to_string(),
+        );
+
+        assert_eq!(source.name, "test");
+        assert!(source.is_kernel());
+        assert!(!source.is_apps());
+    }
+
+    #[test]
+    fn test_sources_config() {
+        let config = SourcesConfig::default();
+        assert_eq!(config.sources.len(), 3);
+        assert!(!config.kernel_sources().is_empty());
+    }
+
+    #[test]
+    fn test_source_urls() {
+        let source = Source::new(
+            "test".to_string(),
+            "http://example.com/".to_string(),
+            "apps".to_string(),
+        );
+
+        assert_eq!(source.index_url(), "http://example.com/index.json");
+        assert_eq!(
+            source.package_url("foo", "1.0.0"),
+            "http://example.com/foo/1.0.0.rpg"
+        );
+        assert_eq!(
+            source.file_url("/foo/1.0.0-aarch64.rpg"),
+            "http://example.com/foo/1.0.0-aarch64.rpg"
+        );
+    }
+
+    #[test]
+    fn test_channels_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("sources.toml");
+
+        let mut config = SourcesConfig::default();
+        let apps = config.app_sources()[0].name.clone();
+        config.set_source_channel(&apps, Channel::Beta);
+        config.set_package_channel("editor", Channel::Nightly);
+        config.save_to_path(&path).unwrap();
+
+        let loaded = SourcesConfig::load_from_path(&path).unwrap();
+        let apps = loaded.app_sources()[0].name.clone();
+        assert_eq!(loaded.app_sources()[0].channel, Channel::Beta);
+        assert_eq!(loaded.kernel_sources()[0].channel, Channel::Stable);
+        assert_eq!(loaded.channel_for("editor", &apps), Channel::Nightly);
+        assert_eq!(loaded.channel_for("viewer", &apps), Channel::Beta);
+    }
+
+    #[test]
+    fn test_sources_toml_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("sources.toml");
+        let key = crate::signature::KeyPair::generate().export_public();
+
+        let mut config = SourcesConfig::default();
+        config.add_source(
+            Source::with_priority(
+                "internal".to_string(),
+                "https://pkg.example.com/apps".to_string(),
+                "apps".to_string(),
+                10,
+            )
+            .with_trusted_key(key.clone())
+            .with_auth_header("Authorization: Bearer secret")
+            .with_proxy("http://proxy.example.com:3128"),
+        );
+        config.disable_source("kernel");
+        config.validate().unwrap();
+        config.save_to_path(&path).unwrap();
+
+        let loaded = SourcesConfig::load_from_path(&path).unwrap();
+        assert_eq!(loaded.sources, config.sources);
+
+        let internal = loaded
+            .sources
+            .iter()
+            .find(|s| s.name == "internal")
+            .unwrap();
+        assert_eq!(internal.trusted_key.as_deref(), Some(key.as_str()));
+        assert_eq!(
+            internal.auth_header_parts(),
+            Some(("Authorization", "Bearer secret"))
+        );
+        assert!(
+            !loaded
+                .sources
+                .iter()
+                .find(|s| s.name == "kernel")
+                .unwrap()
+                .enabled
+        );
+
+        let mut invalid = loaded.clone();
+        invalid.sources[3].auth_header = Some("no separator".to_string());
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_sources_list_migration() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let legacy = temp_dir.path().join("sources.list");
+        let path = temp_dir.path().join("sources.toml");
+
+        std::fs::write(
+            &legacy,
+            "# Rustica Package Sources\n\
+             kernel http://rustux.com/kernel 100\n\
+             apps https://rustux.com/apps 10 channel=beta\n\
+             # apps https://mirror.example.com/apps 50\n\
+             channel editor nightly\n",
+        )
+        .unwrap();
+
+        let config = SourcesConfig::load_from_path(&path).unwrap();
+        let names: Vec<_> = config.sources.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["kernel", "apps-1", "apps-2"]);
+        assert_eq!(config.sources[1].channel, Channel::Beta);
+        assert!(!config.sources[2].enabled);
+        assert_eq!(config.package_channels["editor"], Channel::Nightly);
+
+        // The legacy file is retired and the TOML file is used from now on
+        assert!(path.exists());
+        assert!(!legacy.exists());
+        assert!(temp_dir.path().join("sources.list.migrated").exists());
+        assert_eq!(
+            SourcesConfig::load_from_path(&path).unwrap().sources,
+            config.sources
+        );
+    }
+
+    #[test]
+    fn test_sources_list_migration_failure() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let legacy = temp_dir.path().join("sources.list");
+        let path = temp_dir.path().join("sources.toml");
+
+        std::fs::write(&legacy, "apps https://rustux.com/apps 10\n").unwrap();
+        // Nothing can be renamed onto a non-empty directory
+        let migrated = temp_dir.path().join("sources.list.migrated");
+        std::fs::create_dir(&migrated).unwrap();
+        std::fs::write(migrated.join("keep"), "").unwrap();
+
+        // A migration that cannot finish still loads the legacy sources
+        let config = SourcesConfig::load_from_path(&path).unwrap();
+        assert_eq!(config.sources.len(), 1);
+        assert_eq!(config.sources[0].url, "https://rustux.com/apps");
+        assert!(legacy.exists());
+    }
+}
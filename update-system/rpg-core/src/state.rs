@@ -0,0 +1,357 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Update state for the desktop and shell
+//!
+//! rpg keeps two small JSON files in the scope's state directory
+//! (`STATE_DIR` for the system) so the shell prompt, `system-check` and the
+//! Aurora panel can show update state without loading the registry or
+//! touching the network:
+//!
+//! - `reboot-required.json` exists while packages activated since the last
+//!   boot need a reboot to take effect, and names them;
+//! - `updates.json` summarizes the updates found by the last update or
+//!   refresh: which installed packages have updates, how many fix
+//!   advisories, and the highest severity among them. Merely looking for
+//!   updates, as `rpg update --check-only` and dry runs do, leaves it
+//!   alone.
+//!
+//! Both are replaced atomically and are world-readable. A reboot marker
+//! written during an earlier boot is ignored, so a stale one left on a
+//! persistent directory never shows.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::advisory::Severity;
+use crate::lock::write_atomic;
+use crate::ops::PackageUpdate;
+use crate::version::Version;
+
+/// Reboot marker file name within the state directory
+pub const REBOOT_REQUIRED_FILE: &str = "reboot-required.json";
+
+/// Update summary file name within the state directory
+pub const UPDATES_FILE: &str = "updates.json";
+
+/// Where the kernel publishes the ID of the current boot
+const BOOT_ID_PATH: &str = "/proc/sys/kernel/random/boot_id";
+
+/// Packages waiting for a reboot to take effect
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RebootRequired {
+    /// Packages that caused the reboot requirement, sorted
+    pub packages: Vec<String>,
+    /// When a reboot first became necessary (Unix time)
+    pub since: i64,
+    /// Boot the marker was written during
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_id: Option<String>,
+}
+
+impl RebootRequired {
+    /// Get the marker path within a state directory
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(REBOOT_REQUIRED_FILE)
+    }
+
+    /// Load the marker of the current boot, if any
+    pub fn load(dir: &Path) -> Option<Self> {
+        let marker: Self = read_json(&Self::path(dir))?;
+        match (&marker.boot_id, boot_id()) {
+            (Some(written), Some(current)) if *written != current => None,
+            _ => Some(marker),
+        }
+    }
+
+    /// Record packages that need a reboot, keeping those already recorded
+    pub fn record(dir: &Path, packages: &[String]) -> crate::Result<Self> {
+        let mut marker = Self::load(dir).unwrap_or_else(|| Self {
+            packages: Vec::new(),
+            since: now(),
+            boot_id: boot_id(),
+        });
+
+        marker.packages.extend(packages.iter().cloned());
+        marker.packages.sort();
+        marker.packages.dedup();
+
+        write_json(&Self::path(dir), &marker)?;
+        Ok(marker)
+    }
+
+    /// Remove the marker
+    pub fn clear(dir: &Path) -> crate::Result<()> {
+        match std::fs::remove_file(Self::path(dir)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// An update waiting to be installed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingUpdate {
+    /// Package name
+    pub name: String,
+    /// Installed version
+    pub current_version: String,
+    /// Version the update installs
+    pub new_version: String,
+    /// Advisories the update fixes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub advisories: Vec<String>,
+    /// Highest severity among the fixed advisories
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+}
+
+impl From<&PackageUpdate> for PendingUpdate {
+    fn from(update: &PackageUpdate) -> Self {
+        Self {
+            name: update.name.clone(),
+            current_version: update.current_version.clone(),
+            new_version: update.new_version.clone(),
+            advisories: update.advisories.clone(),
+            severity: update.severity,
+        }
+    }
+}
+
+/// Result of the last update check
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdateSummary {
+    /// When the check ran (Unix time)
+    pub checked_at: i64,
+    /// Number of updates available
+    pub available: usize,
+    /// Number of available updates that fix advisories
+    pub security: usize,
+    /// Highest severity among the available updates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    /// Available updates, most severe first
+    pub packages: Vec<PendingUpdate>,
+    /// Packages whose update is held back by a pin
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub held: Vec<String>,
+}
+
+impl UpdateSummary {
+    /// Summarize the updates of a check that just ran
+    ///
+    /// Each package is listed once, with its newest update, most severe
+    /// first and then by name. A package with an available update is not
+    /// also listed as held.
+    pub fn new<'a>(
+        available: impl IntoIterator<Item = &'a PackageUpdate>,
+        held: impl IntoIterator<Item = &'a PackageUpdate>,
+    ) -> Self {
+        let mut packages: Vec<PendingUpdate> = Vec::new();
+        for update in available {
+            match packages.iter_mut().find(|p| p.name == update.name) {
+                Some(pending) if is_newer(&update.new_version, &pending.new_version) => {
+                    *pending = PendingUpdate::from(update)
+                }
+                Some(_) => {}
+                None => packages.push(PendingUpdate::from(update)),
+            }
+        }
+        packages.sort_by(|a, b| {
+            b.severity
+                .cmp(&a.severity)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        let mut held_names: Vec<String> = Vec::new();
+        for update in held {
+            if !packages.iter().any(|p| p.name == update.name) && !held_names.contains(&update.name)
+            {
+                held_names.push(update.name.clone());
+            }
+        }
+
+        let mut summary = Self {
+            checked_at: now(),
+            packages,
+            held: held_names,
+            ..Self::default()
+        };
+        summary.count();
+        summary
+    }
+
+    /// Get the summary path within a state directory
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(UPDATES_FILE)
+    }
+
+    /// Load the summary of the last check, if any
+    pub fn load(dir: &Path) -> Option<Self> {
+        read_json(&Self::path(dir))
+    }
+
+    /// Save the summary
+    pub fn save(&self, dir: &Path) -> crate::Result<()> {
+        write_json(&Self::path(dir), self)
+    }
+
+    /// Drop updates that have been installed since the check
+    ///
+    /// Returns whether anything changed.
+    pub fn remove(&mut self, names: &[String]) -> bool {
+        let before = self.packages.len();
+        self.packages.retain(|p| !names.contains(&p.name));
+        self.count();
+        self.packages.len() != before
+    }
+
+    /// Recompute the counters from the package list
+    fn count(&mut self) {
+        self.available = self.packages.len();
+        self.security = self
+            .packages
+            .iter()
+            .filter(|p| !p.advisories.is_empty())
+            .count();
+        self.severity = self.packages.iter().filter_map(|p| p.severity).max();
+    }
+}
+
+/// Check if one version string is newer than another
+///
+/// Unparsable versions never replace a listed update.
+fn is_newer(version: &str, than: &str) -> bool {
+    match (Version::parse(version), Version::parse(than)) {
+        (Ok(version), Ok(than)) => version > than,
+        _ => false,
+    }
+}
+
+/// Read a state file, treating a missing or unreadable one as absent
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Option<T> {
+    let bytes = std::fs::read(path).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// Write a state file, readable by unprivileged users
+fn write_json<T: Serialize>(path: &Path, value: &T) -> crate::Result<()> {
+    let json =
+        serde_json::to_vec_pretty(value).map_err(|e| crate::Error::Serialization(e.to_string()))?;
+    write_atomic(path, json)
+}
+
+/// Get the ID of the current boot
+fn boot_id() -> Option<String> {
+    std::fs::read_to_string(BOOT_ID_PATH)
+        .ok()
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+}
+
+/// Get the current Unix time
+fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package::PackageKind;
+
+    fn update(name: &str, advisories: &[&str], severity: Option<Severity>) -> PackageUpdate {
+        PackageUpdate {
+            name: name.to_string(),
+            current_version: "1.0.0".to_string(),
+            new_version: "1.1.0".to_string(),
+            size: 0,
+            kind: PackageKind::App,
+            advisories: advisories.iter().map(|a| a.to_string()).collect(),
+            severity,
+        }
+    }
+
+    #[test]
+    fn test_reboot_required() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        assert!(RebootRequired::load(dir).is_none());
+
+        RebootRequired::record(dir, &["kernel".to_string()]).unwrap();
+        let marker =
+            RebootRequired::record(dir, &["system".to_string(), "kernel".to_string()]).unwrap();
+        assert_eq!(marker.packages, vec!["kernel", "system"]);
+        assert_eq!(RebootRequired::load(dir), Some(marker.clone()));
+
+        // A marker from another boot no longer applies
+        if boot_id().is_some() {
+            let stale = RebootRequired {
+                boot_id: Some("another-boot".to_string()),
+                ..marker
+            };
+            write_json(&RebootRequired::path(dir), &stale).unwrap();
+            assert!(RebootRequired::load(dir).is_none());
+        }
+
+        RebootRequired::clear(dir).unwrap();
+        RebootRequired::clear(dir).unwrap();
+        assert!(RebootRequired::load(dir).is_none());
+    }
+
+    #[test]
+    fn test_update_summary() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+
+        let available = [
+            update("editor", &[], None),
+            update("zlib", &["RSA-2025-0002"], Some(Severity::Medium)),
+            update("openssl", &["RSA-2025-0001"], Some(Severity::Critical)),
+            update("browser", &[], None),
+        ];
+        let held = [update("viewer", &[], None)];
+        let mut summary = UpdateSummary::new(&available, &held);
+        let names: Vec<&str> = summary.packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["openssl", "zlib", "browser", "editor"]);
+        assert_eq!(summary.available, 4);
+        assert_eq!(summary.security, 2);
+        assert_eq!(summary.severity, Some(Severity::Critical));
+        assert_eq!(summary.held, vec!["viewer"]);
+
+        summary.save(dir).unwrap();
+        assert_eq!(UpdateSummary::load(dir), Some(summary.clone()));
+
+        assert!(summary.remove(&["openssl".to_string()]));
+        assert!(!summary.remove(&["openssl".to_string()]));
+        assert_eq!(summary.available, 3);
+        assert_eq!(summary.security, 1);
+        assert_eq!(summary.severity, Some(Severity::Medium));
+    }
+
+    #[test]
+    fn test_update_summary_lists_each_package_once() {
+        let mut newer = update("openssl", &["RSA-2025-0001"], Some(Severity::High));
+        newer.new_version = "1.2.0".to_string();
+        let available = [
+            update("openssl", &["RSA-2025-0001"], Some(Severity::High)),
+            newer,
+        ];
+        let held = [
+            update("openssl", &[], None),
+            update("browser", &[], None),
+            update("browser", &[], None),
+        ];
+        let summary = UpdateSummary::new(&available, &held);
+        assert_eq!(summary.available, 1);
+        assert_eq!(summary.security, 1);
+        assert_eq!(summary.packages[0].new_version, "1.2.0");
+        assert_eq!(summary.held, vec!["browser"]);
+    }
+}
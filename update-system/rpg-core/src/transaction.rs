@@ -1,465 +1,659 @@
-// Copyright 2025 The Rustux Authors
-//
-// Use of this source code is governed by a MIT-style
-// license that can be found in the LICENSE file or at
-// https://opensource.org/licenses/MIT
-
-//! Transaction management for atomic package operations
-
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-
-use crate::package::{Package, PackageKind};
-use crate::symlink::atomic_symlink_swap_with_rollback;
-use crate::version::Version;
-
-/// Transaction kind
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum TransactionKind {
-    /// Install a package
-    Install,
-    /// Remove a package
-    Remove,
-    /// Upgrade a package
-    Upgrade,
-    /// Rollback a package
-    Rollback,
-    /// Switch system version
-    SwitchSystem,
-}
-
-/// Transaction state
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum TransactionState {
-    /// Transaction is prepared but not started
-    Prepared,
-    /// Transaction is in progress
-    InProgress,
-    /// Transaction completed successfully
-    Completed,
-    /// Transaction failed
-    Failed,
-    /// Transaction was rolled back
-    RolledBack,
-}
-
-/// A transaction for package operations
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Transaction {
-    /// Unique transaction ID
-    pub id: String,
-
-    /// Transaction kind
-    pub kind: TransactionKind,
-
-    /// Transaction state
-    pub state: TransactionState,
-
-    /// Packages affected by this transaction
-    pub packages: Vec<Package>,
-
-    /// Rollback information
-    #[serde(default)]
-    pub rollback_info: RollbackInfo,
-
-    /// Transaction timestamp
-    pub created_at: i64,
-
-    /// Error message if transaction failed
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
-}
-
-/// Rollback information
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct RollbackInfo {
-    /// Previous system version (for system switches)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub previous_system_version: Option<Version>,
-
-    /// Previous app versions (name -> version)
-    #[serde(default)]
-    pub previous_app_versions: Vec<(String, Version)>,
-
-    /// Symlink targets before transaction
-    #[serde(default)]
-    pub previous_symlinks: Vec<(PathBuf, PathBuf)>,
-}
-
-/// Result of a transaction operation
-#[derive(Debug, Clone)]
-pub enum TransactionResult {
-    /// Transaction succeeded
-    Success {
-        /// Activated packages
-        activated: Vec<String>,
-        /// Packages requiring reboot
-        requires_reboot: Vec<String>,
-    },
-    /// Transaction failed
-    Failed {
-        /// Error message
-        error: String,
-        /// Packages that were partially installed
-        partial: Vec<String>,
-    },
-    /// Transaction was rolled back
-    RolledBack {
-        /// Reason for rollback
-        reason: String,
-    },
-}
-
-impl Transaction {
-    /// Create a new transaction
-    pub fn new(kind: TransactionKind, packages: Vec<Package>) -> Self {
-        Self {
-            id: ulid::Ulid::new().to_string(),
-            kind,
-            state: TransactionState::Prepared,
-            packages,
-            rollback_info: RollbackInfo::default(),
-            created_at: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs() as i64,
-            error: None,
-        }
-    }
-
-    /// Execute the transaction
-    pub async fn execute(&mut self) -> TransactionResult {
-        self.state = TransactionState::InProgress;
-
-        match self.kind {
-            TransactionKind::Install => self.install(),
-            TransactionKind::Remove => self.remove(),
-            TransactionKind::Upgrade => self.upgrade(),
-            TransactionKind::Rollback => self.rollback(),
-            TransactionKind::SwitchSystem => self.switch_system(),
-        }
-    }
-
-    /// Install packages
-    fn install(&mut self) -> TransactionResult {
-        let mut activated = Vec::new();
-        let mut requires_reboot = Vec::new();
-        let mut partial = Vec::new();
-
-        // Collect package names first to avoid borrow issues
-        let package_names: Vec<String> = self.packages.iter().map(|p| p.name().to_string()).collect();
-
-        for (idx, name) in package_names.iter().enumerate() {
-            match self.install_package(idx) {
-                Ok(Some(reboot)) => {
-                    if reboot {
-                        requires_reboot.push(name.clone());
-                    } else {
-                        activated.push(name.clone());
-                    }
-                }
-                Ok(None) => {
-                    // Package already installed
-                }
-                Err(e) => {
-                    partial.push(name.clone());
-                    self.state = TransactionState::Failed;
-                    self.error = Some(e.to_string());
-                }
-            }
-        }
-
-        if partial.is_empty() {
-            self.state = TransactionState::Completed;
-            TransactionResult::Success {
-                activated,
-                requires_reboot,
-            }
-        } else {
-            TransactionResult::Failed {
-                error: self.error.clone().unwrap_or_default(),
-                partial,
-            }
-        }
-    }
-
-    /// Install a single package
-    fn install_package(&mut self, idx: usize) -> crate::Result<Option<bool>> {
-        use crate::layout::{AppLayout, SystemLayout};
-
-        let requires_reboot = self.packages[idx].kind().requires_reboot();
-
-        match self.packages[idx].kind() {
-            PackageKind::App => {
-                let layout = AppLayout::new();
-                let version_str = self.packages[idx].version().as_str();
-                let app_path = layout.version_path(self.packages[idx].name(), &version_str);
-
-                // Create version directory
-                std::fs::create_dir_all(&app_path)?;
-
-                // Extract package (stub for now)
-                // In production, would extract from archive
-
-                // Update metadata
-                let metadata_path = layout.metadata_path(self.packages[idx].name(), &version_str);
-                let metadata_json = serde_json::to_string_pretty(&self.packages[idx].metadata)
-                    .map_err(|e| crate::Error::Serialization(e.to_string()))?;
-                std::fs::write(&metadata_path, metadata_json)?;
-
-                // Activate if not requiring reboot
-                if !requires_reboot {
-                    let current_path = layout.current_path(self.packages[idx].name());
-                    let old_target = atomic_symlink_swap_with_rollback(&current_path, &app_path)?;
-
-                    if let Some(old) = old_target {
-                        if let Some(old_version) = old.file_name().and_then(|s| s.to_str()) {
-                            self.rollback_info.previous_app_versions.push((
-                                self.packages[idx].name().to_string(),
-                                Version::parse(old_version)?,
-                            ));
-                        }
-                    }
-                }
-
-                self.packages[idx].set_state(crate::package::PackageState::Active);
-                Ok(Some(requires_reboot))
-            }
-            PackageKind::Kernel | PackageKind::System => {
-                let layout = SystemLayout::new();
-                let version_str = format!("v{}", self.packages[idx].version().as_str());
-                let version_path = layout.version_path(&version_str);
-
-                // Create version directory
-                std::fs::create_dir_all(&version_path)?;
-
-                // Update metadata
-                let metadata_path = layout.metadata_path(&version_str);
-                let metadata_json = serde_json::to_string_pretty(&self.packages[idx].metadata)
-                    .map_err(|e| crate::Error::Serialization(e.to_string()))?;
-                std::fs::write(&metadata_path, metadata_json)?;
-
-                // Mark as pending (requires reboot)
-                self.packages[idx].set_state(crate::package::PackageState::Pending);
-
-                Ok(Some(true))
-            }
-            _ => Ok(Some(false)),
-        }
-    }
-
-    /// Remove packages
-    fn remove(&mut self) -> TransactionResult {
-        let mut activated = Vec::new();
-        let packages_to_remove: Vec<_> = self.packages.iter().map(|p| p.clone()).collect();
-
-        for package in &packages_to_remove {
-            match self.remove_package(package) {
-                Ok(_) => {
-                    activated.push(package.name().to_string());
-                }
-                Err(e) => {
-                    self.state = TransactionState::Failed;
-                    self.error = Some(e.to_string());
-                    return TransactionResult::Failed {
-                        error: e.to_string(),
-                        partial: activated,
-                    };
-                }
-            }
-        }
-
-        self.state = TransactionState::Completed;
-        TransactionResult::Success {
-            activated,
-            requires_reboot: Vec::new(),
-        }
-    }
-
-    /// Remove a single package
-    fn remove_package(&mut self, package: &Package) -> crate::Result<()> {
-        use crate::layout::AppLayout;
-
-        match package.kind() {
-            PackageKind::App => {
-                let layout = AppLayout::new();
-
-                // Don't remove the active version
-                if let Some(current) = layout.current_version(package.name())? {
-                    if current == package.version().as_str() {
-                        // Switch to another version first
-                        let versions = layout.list_versions(package.name())?;
-                        if versions.len() <= 1 {
-                            return Err(crate::Error::Other(
-                                "Cannot remove only version of app".into(),
-                            ));
-                        }
-                    }
-                }
-
-                // Remove the version directory
-                let version_path = layout.version_path(package.name(), &package.version().as_str());
-                if version_path.exists() {
-                    std::fs::remove_dir_all(&version_path)?;
-                }
-
-                Ok(())
-            }
-            _ => Err(crate::Error::Other(
-                "Cannot remove system packages".into(),
-            )),
-        }
-    }
-
-    /// Upgrade packages
-    fn upgrade(&mut self) -> TransactionResult {
-        // For now, upgrade is implemented as install + switch
-        self.install()
-    }
-
-    /// Rollback to previous version
-    fn rollback(&mut self) -> TransactionResult {
-        let mut activated = Vec::new();
-        let mut requires_reboot = Vec::new();
-
-        // Rollback each package
-        for (name, version) in &self.rollback_info.previous_app_versions {
-            use crate::layout::AppLayout;
-
-            let layout = AppLayout::new();
-            let version_path = layout.version_path(name, &version.as_str());
-            let current_path = layout.current_path(name);
-
-            if let Err(e) = atomic_symlink_swap_with_rollback(&current_path, &version_path) {
-                self.state = TransactionState::Failed;
-                self.error = Some(e.to_string());
-                return TransactionResult::Failed {
-                    error: e.to_string(),
-                    partial: activated,
-                };
-            }
-
-            activated.push(name.clone());
-        }
-
-        // Rollback system version if needed
-        if let Some(ref version) = self.rollback_info.previous_system_version {
-            use crate::layout::SystemLayout;
-
-            let layout = SystemLayout::new();
-            let version_path = layout.version_path(&format!("v{}", version.as_str()));
-            let current_path = layout.current_path();
-
-            if let Err(e) = atomic_symlink_swap_with_rollback(&current_path, &version_path) {
-                self.state = TransactionState::Failed;
-                self.error = Some(e.to_string());
-                return TransactionResult::Failed {
-                    error: e.to_string(),
-                    partial: activated,
-                };
-            }
-
-            requires_reboot.push("system".to_string());
-        }
-
-        self.state = TransactionState::Completed;
-        TransactionResult::Success {
-            activated,
-            requires_reboot,
-        }
-    }
-
-    /// Switch to a new system version
-    fn switch_system(&mut self) -> TransactionResult {
-        use crate::layout::SystemLayout;
-
-        if self.packages.is_empty() {
-            self.state = TransactionState::Failed;
-            self.error = Some("No system version specified".into());
-            return TransactionResult::Failed {
-                error: "No system version specified".into(),
-                partial: Vec::new(),
-            };
-        }
-
-        let package = &self.packages[0];
-        let layout = SystemLayout::new();
-        let version_str = format!("v{}", package.version().as_str());
-        let version_path = layout.version_path(&version_str);
-        let current_path = layout.current_path();
-
-        // Store current version for rollback
-        if let Ok(Some(current)) = layout.current_version() {
-            match Version::parse(&current) {
-                Ok(version) => {
-                    self.rollback_info.previous_system_version = Some(version);
-                }
-                Err(_) => {
-                    // Continue anyway if we can't parse the version
-                }
-            }
-        }
-
-        match atomic_symlink_swap_with_rollback(&current_path, &version_path) {
-            Ok(_) => {
-                self.state = TransactionState::Completed;
-                TransactionResult::Success {
-                    activated: vec!["system".to_string()],
-                    requires_reboot: vec!["system".to_string()],
-                }
-            }
-            Err(e) => {
-                self.state = TransactionState::Failed;
-                self.error = Some(e.to_string());
-                TransactionResult::Failed {
-                    error: e.to_string(),
-                    partial: Vec::new(),
-                }
-            }
-        }
-    }
-
-    /// Check if the transaction is reversible
-    pub fn can_rollback(&self) -> bool {
-        !self.rollback_info.previous_app_versions.is_empty() ||
-            self.rollback_info.previous_system_version.is_some()
-    }
-
-    /// Get the transaction ID
-    pub fn id(&self) -> &str {
-        &self.id
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::package::{Package, PackageKind};
-    use crate::signature::SigningKey;
-    use crate::version::Version;
-
-    #[test]
-    fn test_transaction_creation() {
-        let key = SigningKey::generate();
-        let signature = key.sign(b"test");
-        let version = Version::new(1, 0, 0);
-
-        let metadata = crate::package::PackageMetadata::new(
-            "test".to_string(),
-            version.clone(),
-            PackageKind::App,
-            1024,
-            "0".repeat(64),
-            signature,
-            "https://example.com/test.rpg".to_string(),
-        );
-
-        let package = Package::new(metadata);
-        let tx = Transaction::new(TransactionKind::Install, vec![package]);
-
-        assert_eq!(tx.state, TransactionState::Prepared);
-        assert!(!tx.id.is_empty());
-    }
-}
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Transaction management for atomic package operations
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::layout::{AppLayout, SystemLayout};
+use crate::package::{InstallReason, Package, PackageKind};
+use crate::symlink::{atomic_symlink_swap, atomic_symlink_swap_with_rollback};
+use crate::trigger::{self, Registration, TriggerRun};
+use crate::version::Version;
+
+/// Transaction kind
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionKind {
+    /// Install a package
+    Install,
+    /// Remove a package
+    Remove,
+    /// Upgrade a package
+    Upgrade,
+    /// Rollback a package
+    Rollback,
+    /// Switch system version
+    SwitchSystem,
+}
+
+/// Transaction state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionState {
+    /// Transaction is prepared but not started
+    Prepared,
+    /// Transaction is in progress
+    InProgress,
+    /// Transaction completed successfully
+    Completed,
+    /// Transaction failed
+    Failed,
+    /// Transaction was rolled back
+    RolledBack,
+}
+
+/// A transaction for package operations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transaction {
+    /// Unique transaction ID
+    pub id: String,
+
+    /// Transaction kind
+    pub kind: TransactionKind,
+
+    /// Transaction state
+    pub state: TransactionState,
+
+    /// Packages affected by this transaction
+    pub packages: Vec<Package>,
+
+    /// Rollback information
+    #[serde(default)]
+    pub rollback_info: RollbackInfo,
+
+    /// Transaction timestamp
+    pub created_at: i64,
+
+    /// Batch identifier shared by transactions run as one operation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch: Option<String>,
+
+    /// Error message if transaction failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Triggers run after the transaction completed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<TriggerRun>,
+
+    /// Layout apps are installed into
+    #[serde(skip)]
+    app_layout: AppLayout,
+
+    /// Layout system and kernel versions are installed into
+    #[serde(skip)]
+    system_layout: SystemLayout,
+
+    /// Registered triggers
+    #[serde(skip)]
+    registered_triggers: Vec<Registration>,
+
+    /// Leave triggers to the batch, only collecting the changed paths
+    #[serde(skip)]
+    defer_triggers: bool,
+
+    /// Paths changed by the transaction when its triggers are deferred
+    #[serde(skip)]
+    deferred_paths: Vec<String>,
+}
+
+/// Rollback information
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RollbackInfo {
+    /// Previous system version (for system switches)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_system_version: Option<Version>,
+
+    /// Previous app versions (name -> version)
+    #[serde(default)]
+    pub previous_app_versions: Vec<(String, Version)>,
+
+    /// Previous versions of kernels and other system-level packages
+    /// (name -> version), which take effect on the next boot
+    #[serde(default)]
+    pub previous_package_versions: Vec<(String, Version)>,
+
+    /// Symlink targets before transaction
+    #[serde(default)]
+    pub previous_symlinks: Vec<(PathBuf, PathBuf)>,
+
+    /// Why removed packages were installed (name -> reason), so undoing
+    /// the removal keeps auto-installed dependencies removable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed_reasons: Vec<(String, InstallReason)>,
+}
+
+/// Result of a transaction operation
+#[derive(Debug, Clone)]
+pub enum TransactionResult {
+    /// Transaction succeeded
+    Success {
+        /// Activated packages
+        activated: Vec<String>,
+        /// Packages requiring reboot
+        requires_reboot: Vec<String>,
+    },
+    /// Transaction failed
+    Failed {
+        /// Error message
+        error: String,
+        /// Packages that were partially installed
+        partial: Vec<String>,
+    },
+    /// Transaction was rolled back
+    RolledBack {
+        /// Reason for rollback
+        reason: String,
+    },
+}
+
+impl Transaction {
+    /// Create a new transaction
+    pub fn new(kind: TransactionKind, packages: Vec<Package>) -> Self {
+        Self {
+            id: ulid::Ulid::new().to_string(),
+            kind,
+            state: TransactionState::Prepared,
+            packages,
+            rollback_info: RollbackInfo::default(),
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64,
+            batch: None,
+            error: None,
+            triggers: Vec::new(),
+            app_layout: AppLayout::new(),
+            system_layout: SystemLayout::new(),
+            registered_triggers: Vec::new(),
+            defer_triggers: false,
+            deferred_paths: Vec::new(),
+        }
+    }
+
+    /// Install apps into a different layout (e.g. a user's home)
+    pub fn with_app_layout(mut self, app_layout: AppLayout) -> Self {
+        self.app_layout = app_layout;
+        self
+    }
+
+    /// Install system and kernel versions into a different layout
+    pub fn with_system_layout(mut self, system_layout: SystemLayout) -> Self {
+        self.system_layout = system_layout;
+        self
+    }
+
+    /// Tag the transaction as part of a batch
+    pub fn with_batch(mut self, batch: Option<String>) -> Self {
+        self.batch = batch;
+        self
+    }
+
+    /// Run matching triggers once the transaction completes
+    pub fn with_triggers(mut self, triggers: Vec<Registration>) -> Self {
+        self.registered_triggers = triggers;
+        self
+    }
+
+    /// Leave running triggers to the batch the transaction is part of
+    ///
+    /// The changed paths are kept in [`Transaction::deferred_paths`]
+    /// instead, so the batch can run each trigger once for all of them.
+    pub fn with_deferred_triggers(mut self, defer: bool) -> Self {
+        self.defer_triggers = defer;
+        self
+    }
+
+    /// Get the paths changed by a transaction whose triggers were deferred
+    pub fn deferred_paths(&self) -> &[String] {
+        &self.deferred_paths
+    }
+
+    /// Execute the transaction
+    pub async fn execute(&mut self) -> TransactionResult {
+        self.state = TransactionState::InProgress;
+
+        // Removed files are gone afterwards, so look before acting
+        let changed = self.changed_paths();
+
+        let result = match self.kind {
+            TransactionKind::Install => self.install(),
+            TransactionKind::Remove => self.remove(),
+            TransactionKind::Upgrade => self.upgrade(),
+            TransactionKind::Rollback => self.rollback(),
+            TransactionKind::SwitchSystem => self.switch_system(),
+        };
+
+        if matches!(result, TransactionResult::Success { .. }) {
+            if self.defer_triggers {
+                self.deferred_paths = changed;
+            } else {
+                self.triggers = trigger::run_matching(
+                    &self.registered_triggers,
+                    &changed,
+                    &self.app_layout,
+                    &self.system_layout,
+                )
+                .await;
+            }
+        }
+
+        result
+    }
+
+    /// List the package paths this transaction installs or removes
+    fn changed_paths(&self) -> Vec<String> {
+        if self.registered_triggers.is_empty() && !self.defer_triggers {
+            return Vec::new();
+        }
+
+        self.packages
+            .iter()
+            .flat_map(|package| {
+                let version = package.version().as_str();
+                let root = match package.kind() {
+                    PackageKind::App => self.app_layout.version_path(package.name(), &version),
+                    _ => self.system_layout.package_path(package.name(), &version),
+                };
+                trigger::package_paths(&root)
+            })
+            .collect()
+    }
+
+    /// Install packages
+    fn install(&mut self) -> TransactionResult {
+        let mut activated = Vec::new();
+        let mut requires_reboot = Vec::new();
+        let mut partial = Vec::new();
+
+        // Collect package names first to avoid borrow issues
+        let package_names: Vec<String> =
+            self.packages.iter().map(|p| p.name().to_string()).collect();
+
+        for (idx, name) in package_names.iter().enumerate() {
+            match self.install_package(idx) {
+                Ok(Some(reboot)) => {
+                    if reboot {
+                        requires_reboot.push(name.clone());
+                    } else {
+                        activated.push(name.clone());
+                    }
+                }
+                Ok(None) => {
+                    // Package already installed
+                }
+                Err(e) => {
+                    partial.push(name.clone());
+                    self.state = TransactionState::Failed;
+                    self.error = Some(e.to_string());
+                }
+            }
+        }
+
+        if partial.is_empty() {
+            self.state = TransactionState::Completed;
+            TransactionResult::Success {
+                activated,
+                requires_reboot,
+            }
+        } else {
+            TransactionResult::Failed {
+                error: self.error.clone().unwrap_or_default(),
+                partial,
+            }
+        }
+    }
+
+    /// Install a single package
+    fn install_package(&mut self, idx: usize) -> crate::Result<Option<bool>> {
+        let requires_reboot = self.packages[idx].kind().requires_reboot();
+
+        match self.packages[idx].kind() {
+            PackageKind::App => {
+                let layout = self.app_layout.clone();
+                let version_str = self.packages[idx].version().as_str();
+                let app_path = layout.version_path(self.packages[idx].name(), &version_str);
+
+                // Create version directory
+                std::fs::create_dir_all(&app_path)?;
+
+                // Extract package (stub for now)
+                // In production, would extract from archive
+
+                // Update metadata
+                let metadata_path = layout.metadata_path(self.packages[idx].name(), &version_str);
+                let metadata_json = serde_json::to_string_pretty(&self.packages[idx].metadata)
+                    .map_err(|e| crate::Error::Serialization(e.to_string()))?;
+                std::fs::write(&metadata_path, metadata_json)?;
+
+                // Activate if not requiring reboot
+                if !requires_reboot {
+                    let current_path = layout.current_path(self.packages[idx].name());
+                    let old_target = atomic_symlink_swap_with_rollback(&current_path, &app_path)?;
+
+                    if let Some(old) = old_target {
+                        if let Some(old_version) = old.file_name().and_then(|s| s.to_str()) {
+                            self.rollback_info.previous_app_versions.push((
+                                self.packages[idx].name().to_string(),
+                                Version::parse(old_version)?,
+                            ));
+                        }
+                    }
+
+                    // A missing PATH link doesn't make the app unusable
+                    if let Err(e) = layout.link_executable(self.packages[idx].name()) {
+                        log::warn!("Failed to link {}: {}", self.packages[idx].name(), e);
+                    }
+                }
+
+                self.packages[idx].set_state(crate::package::PackageState::Active);
+                Ok(Some(requires_reboot))
+            }
+            PackageKind::Kernel | PackageKind::System => {
+                let layout = self.system_layout.clone();
+                let version_str = self.packages[idx].version().as_str();
+                let version_path = layout.package_path(self.packages[idx].name(), &version_str);
+
+                // Create version directory
+                std::fs::create_dir_all(&version_path)?;
+
+                // Update metadata
+                let metadata_path = version_path.join("metadata.json");
+                let metadata_json = serde_json::to_string_pretty(&self.packages[idx].metadata)
+                    .map_err(|e| crate::Error::Serialization(e.to_string()))?;
+                std::fs::write(&metadata_path, metadata_json)?;
+
+                // Mark as pending (requires reboot)
+                self.packages[idx].set_state(crate::package::PackageState::Pending);
+
+                Ok(Some(true))
+            }
+            _ => Ok(Some(false)),
+        }
+    }
+
+    /// Remove packages
+    fn remove(&mut self) -> TransactionResult {
+        let mut activated = Vec::new();
+        let packages_to_remove: Vec<_> = self.packages.to_vec();
+
+        for package in &packages_to_remove {
+            match self.remove_package(package) {
+                Ok(_) => {
+                    activated.push(package.name().to_string());
+                }
+                Err(e) => {
+                    self.state = TransactionState::Failed;
+                    self.error = Some(e.to_string());
+                    return TransactionResult::Failed {
+                        error: e.to_string(),
+                        partial: activated,
+                    };
+                }
+            }
+        }
+
+        self.state = TransactionState::Completed;
+        TransactionResult::Success {
+            activated,
+            requires_reboot: Vec::new(),
+        }
+    }
+
+    /// Remove a single package
+    fn remove_package(&mut self, package: &Package) -> crate::Result<()> {
+        match package.kind() {
+            PackageKind::App => {
+                let layout = self.app_layout.clone();
+
+                // Removing the active version deactivates the app
+                if let Some(current) = layout.current_version(package.name())? {
+                    if current == package.version().as_str() {
+                        let current_path = layout.current_path(package.name());
+                        let old_target = current_path.read_link()?;
+                        layout.unlink_executable(package.name())?;
+                        std::fs::remove_file(&current_path)?;
+                        self.rollback_info
+                            .previous_symlinks
+                            .push((current_path, old_target));
+                    }
+                }
+
+                // Remove the version directory
+                let version_path = layout.version_path(package.name(), &package.version().as_str());
+                if version_path.exists() {
+                    std::fs::remove_dir_all(&version_path)?;
+                }
+
+                Ok(())
+            }
+            _ => Err(crate::Error::Other("Cannot remove system packages".into())),
+        }
+    }
+
+    /// Upgrade packages
+    fn upgrade(&mut self) -> TransactionResult {
+        // For now, upgrade is implemented as install + switch
+        self.install()
+    }
+
+    /// Rollback to previous version
+    ///
+    /// Either every symlink is switched or none is: if one switch fails,
+    /// the links already switched are restored. System-level packages are
+    /// checked to be on disk before anything is switched.
+    fn rollback(&mut self) -> TransactionResult {
+        let mut activated = Vec::new();
+        let mut requires_reboot = Vec::new();
+        let mut switches = Vec::new();
+
+        for (name, version) in &self.rollback_info.previous_package_versions {
+            let path = self.system_layout.package_path(name, &version.as_str());
+            if !path.is_dir() {
+                let reason = format!("{} {} is not on disk", name, version);
+                self.state = TransactionState::RolledBack;
+                self.error = Some(reason.clone());
+                return TransactionResult::RolledBack { reason };
+            }
+            requires_reboot.push(name.clone());
+        }
+
+        // Rollback each package
+        let app_layout = &self.app_layout;
+        for (name, version) in &self.rollback_info.previous_app_versions {
+            switches.push((
+                app_layout.current_path(name),
+                app_layout.version_path(name, &version.as_str()),
+            ));
+            activated.push(name.clone());
+        }
+
+        // Rollback system version if needed
+        if let Some(ref version) = self.rollback_info.previous_system_version {
+            let layout = &self.system_layout;
+            switches.push((
+                layout.current_path(),
+                layout.version_path(&version.as_str()),
+            ));
+            requires_reboot.push("system".to_string());
+        }
+
+        for (current_path, version_path) in switches {
+            match atomic_symlink_swap_with_rollback(&current_path, &version_path) {
+                Ok(old_target) => {
+                    // An empty target marks a link that did not exist before
+                    self.rollback_info
+                        .previous_symlinks
+                        .push((current_path, old_target.unwrap_or_default()));
+                }
+                Err(e) => {
+                    self.restore_symlinks();
+                    self.state = TransactionState::RolledBack;
+                    self.error = Some(e.to_string());
+                    return TransactionResult::RolledBack {
+                        reason: e.to_string(),
+                    };
+                }
+            }
+        }
+
+        self.state = TransactionState::Completed;
+        TransactionResult::Success {
+            activated,
+            requires_reboot,
+        }
+    }
+
+    /// Restore symlinks switched by this transaction, newest first
+    fn restore_symlinks(&mut self) {
+        for (link, old_target) in self.rollback_info.previous_symlinks.drain(..).rev() {
+            let restored = if old_target.as_os_str().is_empty() {
+                std::fs::remove_file(&link).map_err(crate::Error::from)
+            } else {
+                atomic_symlink_swap(&link, &old_target)
+            };
+
+            if let Err(e) = restored {
+                log::error!("Failed to restore {}: {}", link.display(), e);
+            }
+        }
+    }
+
+    /// Switch to a new system version
+    fn switch_system(&mut self) -> TransactionResult {
+        if self.packages.is_empty() {
+            self.state = TransactionState::Failed;
+            self.error = Some("No system version specified".into());
+            return TransactionResult::Failed {
+                error: "No system version specified".into(),
+                partial: Vec::new(),
+            };
+        }
+
+        let package = &self.packages[0];
+        let layout = &self.system_layout;
+        let version_path = layout.version_path(&package.version().as_str());
+        let current_path = layout.current_path();
+
+        // Store current version for rollback
+        if let Ok(Some(current)) = layout.current_version() {
+            match Version::parse(&current) {
+                Ok(version) => {
+                    self.rollback_info.previous_system_version = Some(version);
+                }
+                Err(_) => {
+                    // Continue anyway if we can't parse the version
+                }
+            }
+        }
+
+        match atomic_symlink_swap_with_rollback(&current_path, &version_path) {
+            Ok(_) => {
+                self.state = TransactionState::Completed;
+                TransactionResult::Success {
+                    activated: vec!["system".to_string()],
+                    requires_reboot: vec!["system".to_string()],
+                }
+            }
+            Err(e) => {
+                self.state = TransactionState::Failed;
+                self.error = Some(e.to_string());
+                TransactionResult::Failed {
+                    error: e.to_string(),
+                    partial: Vec::new(),
+                }
+            }
+        }
+    }
+
+    /// Check if the transaction is reversible
+    pub fn can_rollback(&self) -> bool {
+        !self.rollback_info.previous_app_versions.is_empty()
+            || self.rollback_info.previous_system_version.is_some()
+            || !self.rollback_info.previous_package_versions.is_empty()
+    }
+
+    /// Get the transaction ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Check if the transaction completed successfully
+    pub fn is_completed(&self) -> bool {
+        self.state == TransactionState::Completed
+    }
+}
+
+impl TransactionKind {
+    /// Convert to string
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Install => "install",
+            Self::Remove => "remove",
+            Self::Upgrade => "upgrade",
+            Self::Rollback => "rollback",
+            Self::SwitchSystem => "switch-system",
+        }
+    }
+}
+
+impl std::fmt::Display for TransactionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl TransactionState {
+    /// Convert to string
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Prepared => "prepared",
+            Self::InProgress => "in progress",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+            Self::RolledBack => "rolled back",
+        }
+    }
+}
+
+impl std::fmt::Display for TransactionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::package::{Package, PackageKind};
+    use crate::signature::SigningKey;
+    use crate::version::Version;
+
+    #[test]
+    fn test_transaction_creation() {
+        let key = SigningKey::generate();
+        let signature = key.sign(b"test");
+        let version = Version::new(1, 0, 0);
+
+        let metadata = crate::package::PackageMetadata::new(
+            "test".to_string(),
+            version.clone(),
+            PackageKind::App,
+            1024,
+            "0".repeat(64),
+            signature,
+            "https://example.com/test.rpg".to_string(),
+        );
+
+        let package = Package::new(metadata);
+        let tx = Transaction::new(TransactionKind::Install, vec![package]);
+
+        assert_eq!(tx.state, TransactionState::Prepared);
+        assert!(!tx.id.is_empty());
+    }
+}
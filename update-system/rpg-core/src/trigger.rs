@@ -0,0 +1,287 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Package triggers
+//!
+//! A package can ask to be told when files it cares about change, for
+//! example to refresh a font cache or reload `svc` units. Its manifest
+//! lists triggers with path globs, matched against the paths inside the
+//! packages a transaction installs or removes:
+//!
+//! ```json
+//! "triggers": [
+//!   {
+//!     "name": "font-cache",
+//!     "paths": ["share/fonts/**"],
+//!     "command": "bin/fc-cache --system"
+//!   }
+//! ]
+//! ```
+//!
+//! Each matching trigger runs once after the transaction completes, or once
+//! after a whole batch of transactions such as `rpg update`, through
+//! `/bin/sh -c` in the owning package's active directory: the app's
+//! `current` directory, or the active system for system packages. The
+//! matched paths are passed newline-separated in `RPG_TRIGGER_PATHS`. Globs
+//! support `?`, `*` (within a path component) and `**` (across components).
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::layout::{AppLayout, SystemLayout};
+use crate::package::PackageKind;
+
+/// How long a trigger handler may run
+pub const TRIGGER_TIMEOUT_SECS: u64 = 300;
+
+/// Most output kept per run, in bytes
+const MAX_OUTPUT: usize = 16 * 1024;
+
+/// A handler registered by a package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trigger {
+    /// Trigger name, unique within the package
+    pub name: String,
+    /// Globs of package paths the trigger is interested in
+    pub paths: Vec<String>,
+    /// Shell command to run
+    pub command: String,
+}
+
+impl Trigger {
+    /// Check that the trigger can run
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.name.is_empty() || self.command.trim().is_empty() || self.paths.is_empty() {
+            return Err(crate::Error::Other(format!(
+                "Trigger '{}' needs a name, a command and at least one path",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Get the paths matching any of the trigger's globs
+    pub fn matching<'a>(&self, paths: &'a [String]) -> Vec<&'a String> {
+        paths
+            .iter()
+            .filter(|path| self.paths.iter().any(|glob| glob_match(glob, path)))
+            .collect()
+    }
+}
+
+/// A trigger and the package that registered it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registration {
+    /// Package that registered the trigger
+    pub owner: String,
+    /// Kind of the owning package, which decides where the handler runs
+    pub kind: PackageKind,
+    /// The trigger
+    pub trigger: Trigger,
+}
+
+/// The outcome of running a trigger
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TriggerRun {
+    /// Package that registered the trigger
+    pub owner: String,
+    /// Trigger name
+    pub name: String,
+    /// Number of changed paths that matched
+    pub matched: usize,
+    /// Exit code, or `None` if the handler could not run or was killed
+    pub status: Option<i32>,
+    /// Combined stdout and stderr, truncated
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub output: String,
+}
+
+impl TriggerRun {
+    /// Check if the handler succeeded
+    pub fn succeeded(&self) -> bool {
+        self.status == Some(0)
+    }
+}
+
+/// Run a trigger for the paths that matched it
+pub async fn run(
+    owner: &str,
+    trigger: &Trigger,
+    cwd: Option<&Path>,
+    paths: &[&String],
+) -> TriggerRun {
+    let mut command = tokio::process::Command::new("/bin/sh");
+    command
+        .arg("-c")
+        .arg(&trigger.command)
+        .env("RPG_TRIGGER", &trigger.name)
+        .env(
+            "RPG_TRIGGER_PATHS",
+            paths
+                .iter()
+                .map(|p| p.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true);
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+
+    let (status, output) =
+        match tokio::time::timeout(Duration::from_secs(TRIGGER_TIMEOUT_SECS), command.output())
+            .await
+        {
+            Ok(Ok(output)) => {
+                let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+                text.push_str(&String::from_utf8_lossy(&output.stderr));
+                (output.status.code(), text)
+            }
+            Ok(Err(e)) => (None, format!("failed to run: {}", e)),
+            Err(_) => (None, format!("timed out after {}s", TRIGGER_TIMEOUT_SECS)),
+        };
+
+    TriggerRun {
+        owner: owner.to_string(),
+        name: trigger.name.clone(),
+        matched: paths.len(),
+        status,
+        output: truncate(output),
+    }
+}
+
+/// Run each registered trigger matching the changed paths, once
+pub async fn run_matching(
+    registered: &[Registration],
+    changed: &[String],
+    app_layout: &AppLayout,
+    system_layout: &SystemLayout,
+) -> Vec<TriggerRun> {
+    let mut runs = Vec::new();
+    for registration in registered {
+        let matched = registration.trigger.matching(changed);
+        if matched.is_empty() {
+            continue;
+        }
+
+        let active = match registration.kind {
+            PackageKind::App => app_layout.current_path(&registration.owner),
+            _ => system_layout.current_path(),
+        };
+        let cwd = active.is_dir().then_some(active.as_path());
+        let run = run(&registration.owner, &registration.trigger, cwd, &matched).await;
+
+        if !run.succeeded() {
+            log::warn!(
+                "Trigger {} of {} failed: {}",
+                run.name,
+                run.owner,
+                run.output.lines().last().unwrap_or("no output")
+            );
+        }
+        runs.push(run);
+    }
+    runs
+}
+
+/// Keep the end of long output, where errors usually are
+fn truncate(output: String) -> String {
+    if output.len() <= MAX_OUTPUT {
+        return output;
+    }
+
+    let mut start = output.len() - MAX_OUTPUT;
+    while !output.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("[...]\n{}", &output[start..])
+}
+
+/// List the files in a package tree as paths relative to it
+pub fn package_paths(root: &Path) -> Vec<String> {
+    let mut paths: Vec<String> = walkdir::WalkDir::new(root)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.file_type().is_dir())
+        .filter_map(|e| {
+            e.path()
+                .strip_prefix(root)
+                .ok()
+                .map(|p| p.to_string_lossy().into_owned())
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Match a path against a glob
+pub fn glob_match(glob: &str, path: &str) -> bool {
+    match_bytes(glob.as_bytes(), path.as_bytes())
+}
+
+fn match_bytes(glob: &[u8], path: &[u8]) -> bool {
+    match glob {
+        [] => path.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            // `**/` also matches no directories at all
+            let rest = rest.strip_prefix(b"/").unwrap_or(rest);
+            (0..=path.len()).any(|i| match_bytes(rest, &path[i..]))
+        }
+        [b'*', rest @ ..] => (0..=path.len())
+            .take_while(|&i| i == 0 || path[i - 1] != b'/')
+            .any(|i| match_bytes(rest, &path[i..])),
+        [b'?', rest @ ..] => {
+            matches!(path, [c, tail @ ..] if *c != b'/' && match_bytes(rest, tail))
+        }
+        [c, rest @ ..] => matches!(path, [p, tail @ ..] if p == c && match_bytes(rest, tail)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("share/fonts/**", "share/fonts/dejavu/sans.ttf"));
+        assert!(glob_match("lib/*.so*", "lib/libfoo.so.1"));
+        assert!(!glob_match("lib/*.so*", "lib/nested/libfoo.so"));
+        assert!(glob_match("**/*.service", "etc/svc/net.service"));
+        assert!(glob_match("**/*.service", "net.service"));
+        assert!(glob_match("etc/svc/?.toml", "etc/svc/a.toml"));
+        assert!(!glob_match("etc/svc/?.toml", "etc/svc/ab.toml"));
+        assert!(!glob_match("share/fonts/**", "share/icons/a.png"));
+    }
+
+    #[tokio::test]
+    async fn test_run_trigger() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let trigger = Trigger {
+            name: "font-cache".to_string(),
+            paths: vec!["share/fonts/**".to_string()],
+            command: "echo \"$RPG_TRIGGER: $RPG_TRIGGER_PATHS\"; pwd; exit 3".to_string(),
+        };
+
+        let changed = vec!["bin/app".to_string(), "share/fonts/a.ttf".to_string()];
+        let matched = trigger.matching(&changed);
+        assert_eq!(matched, vec!["share/fonts/a.ttf"]);
+
+        let run = run("fontconfig", &trigger, Some(temp_dir.path()), &matched).await;
+        assert_eq!(run.status, Some(3));
+        assert!(!run.succeeded());
+        assert!(run.output.starts_with("font-cache: share/fonts/a.ttf\n"));
+        assert!(run.output.contains(
+            &temp_dir
+                .path()
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        ));
+    }
+}
@@ -0,0 +1,414 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Declarative desired-state configuration
+//!
+//! The world file describes the packages a machine should have, the
+//! version constraints and pins they follow, and the sources they come
+//! from:
+//!
+//! ```toml
+//! [[sources]]
+//! name = "apps"
+//! url = "https://rustux.com/apps"
+//! type = "apps"
+//!
+//! [packages]
+//! editor = "^1.4"
+//! browser = { version = "*", channel = "beta" }
+//! kernel = { version = "~6.1", hold = true }
+//! ```
+//!
+//! `rpg apply` compares the world against the registry and turns the
+//! differences into a [`WorldPlan`].
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::{Pin, PinConfig};
+use crate::package::PackageKind;
+use crate::sources::{Source, SourcesConfig};
+use crate::version::{Channel, Version, VersionConstraint};
+
+/// Default world file path
+pub const WORLD_PATH: &str = "/etc/rpg/world.toml";
+
+/// Desired state of the system
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorldConfig {
+    /// Sources that must be configured
+    #[serde(default)]
+    pub sources: Vec<Source>,
+
+    /// Packages that must be installed (name -> spec)
+    #[serde(default)]
+    pub packages: BTreeMap<String, WorldPackage>,
+}
+
+/// Desired state of a single package
+///
+/// Written either as a bare version constraint (`editor = "^1.4"`) or as a
+/// table with the optional `pin`, `hold` and `channel` keys.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "WorldPackageSpec")]
+pub struct WorldPackage {
+    /// Version constraint the installed version must satisfy
+    pub version: String,
+
+    /// Pin to record for the package
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pin: Option<String>,
+
+    /// Hold the package at its installed version
+    #[serde(default)]
+    pub hold: bool,
+
+    /// Channel the package follows, overriding its source's channel
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<Channel>,
+}
+
+impl Default for WorldPackage {
+    fn default() -> Self {
+        Self {
+            version: "*".to_string(),
+            pin: None,
+            hold: false,
+            channel: None,
+        }
+    }
+}
+
+/// On-disk forms of a package entry
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WorldPackageSpec {
+    Constraint(String),
+    Table {
+        #[serde(default)]
+        version: Option<String>,
+        #[serde(default)]
+        pin: Option<String>,
+        #[serde(default)]
+        hold: bool,
+        #[serde(default)]
+        channel: Option<Channel>,
+    },
+}
+
+impl From<WorldPackageSpec> for WorldPackage {
+    fn from(spec: WorldPackageSpec) -> Self {
+        match spec {
+            WorldPackageSpec::Constraint(version) => Self {
+                version,
+                ..Self::default()
+            },
+            WorldPackageSpec::Table {
+                version,
+                pin,
+                hold,
+                channel,
+            } => Self {
+                version: version.unwrap_or_else(|| "*".to_string()),
+                pin,
+                hold,
+                channel,
+            },
+        }
+    }
+}
+
+impl WorldPackage {
+    /// Get the version constraint
+    pub fn constraint(&self) -> crate::Result<VersionConstraint> {
+        VersionConstraint::new(&self.version)
+    }
+
+    /// Get the pin this entry asks for
+    pub fn desired_pin(&self) -> crate::Result<Option<Pin>> {
+        if self.hold {
+            return Ok(Some(Pin::Hold));
+        }
+
+        self.pin
+            .as_deref()
+            .map(|req| {
+                Ok(Pin::Version {
+                    constraint: VersionConstraint::new(req)?,
+                })
+            })
+            .transpose()
+    }
+
+    /// Check if a version satisfies both the constraint and the pin
+    ///
+    /// Holds only restrict later updates, so they allow any version here.
+    /// Invalid constraints match nothing; see [`WorldConfig::validate`].
+    pub fn allows(&self, version: &Version) -> bool {
+        let pinned = match self.desired_pin() {
+            Ok(Some(pin @ Pin::Version { .. })) => pin.allows(version),
+            Ok(_) => true,
+            Err(_) => false,
+        };
+
+        pinned
+            && self
+                .constraint()
+                .map(|c| c.satisfies(version))
+                .unwrap_or(false)
+    }
+}
+
+impl WorldConfig {
+    /// Load the world from the default path
+    pub fn load() -> crate::Result<Self> {
+        Self::load_from_path(WORLD_PATH)
+    }
+
+    /// Load the world from a specific path
+    pub fn load_from_path(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            crate::Error::Other(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+
+        Self::parse(&content)
+    }
+
+    /// Parse and validate a world file
+    pub fn parse(content: &str) -> crate::Result<Self> {
+        let world: Self =
+            toml::from_str(content).map_err(|e| crate::Error::Serialization(e.to_string()))?;
+        world.validate()?;
+        Ok(world)
+    }
+
+    /// Validate constraints, pins and sources
+    pub fn validate(&self) -> crate::Result<()> {
+        for (name, package) in &self.packages {
+            package.constraint().map_err(|_| {
+                crate::Error::Other(format!(
+                    "Invalid version constraint for {}: {}",
+                    name, package.version
+                ))
+            })?;
+            package.desired_pin().map_err(|_| {
+                crate::Error::Other(format!(
+                    "Invalid pin for {}: {}",
+                    name,
+                    package.pin.as_deref().unwrap_or_default()
+                ))
+            })?;
+        }
+
+        let sources = SourcesConfig {
+            sources: self.sources.clone(),
+            ..SourcesConfig::default()
+        };
+        sources.validate()
+    }
+
+    /// Add or update the world's sources, returning the names that changed
+    pub fn apply_sources(&self, sources: &mut SourcesConfig) -> Vec<String> {
+        let mut changed = Vec::new();
+
+        for source in &self.sources {
+            if sources.sources.iter().any(|s| s == source) {
+                continue;
+            }
+            sources.add_source(source.clone());
+            changed.push(source.name.clone());
+        }
+
+        changed
+    }
+
+    /// Set the channel overrides of listed packages, returning the changes
+    pub fn apply_channels(&self, sources: &mut SourcesConfig) -> Vec<(String, Option<Channel>)> {
+        let mut changed = Vec::new();
+
+        for (name, package) in &self.packages {
+            if sources.package_channels.get(name) == package.channel.as_ref() {
+                continue;
+            }
+            match package.channel {
+                Some(channel) => sources.set_package_channel(name, channel),
+                None => {
+                    sources.clear_package_channel(name);
+                }
+            }
+            changed.push((name.clone(), package.channel));
+        }
+
+        changed
+    }
+
+    /// Compute the pin changes for listed packages
+    pub fn pin_changes(&self, pins: &PinConfig) -> crate::Result<Vec<(String, Option<Pin>)>> {
+        let mut changes = Vec::new();
+
+        for (name, package) in &self.packages {
+            let desired = package.desired_pin()?;
+            if pins.get(name) != desired.as_ref() {
+                changes.push((name.clone(), desired));
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+/// A package change needed to reach the world
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum WorldChange {
+    /// Install a package that is not installed
+    Install {
+        name: String,
+        version: Version,
+        kind: PackageKind,
+    },
+    /// Move an installed package to a newer version
+    Upgrade {
+        name: String,
+        from: Version,
+        to: Version,
+        kind: PackageKind,
+    },
+    /// Move an installed package to an older version
+    Downgrade {
+        name: String,
+        from: Version,
+        to: Version,
+        kind: PackageKind,
+    },
+    /// Remove a package that is not part of the world
+    Remove { name: String, version: Version },
+}
+
+impl WorldChange {
+    /// Get the package name
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Install { name, .. }
+            | Self::Upgrade { name, .. }
+            | Self::Downgrade { name, .. }
+            | Self::Remove { name, .. } => name,
+        }
+    }
+}
+
+impl std::fmt::Display for WorldChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Install { name, version, .. } => write!(f, "install {} {}", name, version),
+            Self::Upgrade { name, from, to, .. } => {
+                write!(f, "upgrade {} {} -> {}", name, from, to)
+            }
+            Self::Downgrade { name, from, to, .. } => {
+                write!(f, "downgrade {} {} -> {}", name, from, to)
+            }
+            Self::Remove { name, version } => write!(f, "remove {} {}", name, version),
+        }
+    }
+}
+
+/// Everything `rpg apply` would change
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WorldPlan {
+    /// Package changes, in execution order
+    pub changes: Vec<WorldChange>,
+    /// Pin changes (name, new pin or `None` to unpin)
+    pub pins: Vec<(String, Option<Pin>)>,
+    /// Sources added or updated
+    pub sources: Vec<String>,
+    /// Channel override changes (name, new channel or `None` to clear)
+    pub channels: Vec<(String, Option<Channel>)>,
+    /// Sources configuration with the world's sources and channels applied
+    #[serde(skip)]
+    pub sources_config: SourcesConfig,
+    /// Unlisted apps that are kept because they are pinned or held
+    pub kept: Vec<String>,
+}
+
+impl WorldPlan {
+    /// Check if the system already matches the world
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+            && self.pins.is_empty()
+            && self.sources.is_empty()
+            && self.channels.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_world_parse() {
+        let world = WorldConfig::parse(
+            r#"
+            [[sources]]
+            name = "apps"
+            url = "https://rustux.com/apps"
+            type = "apps"
+            priority = 10
+
+            [packages]
+            editor = "^1.4"
+            browser = { channel = "beta", pin = "~2.0" }
+            kernel = { version = "~6.1", hold = true }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(world.sources[0].priority, 10);
+        assert_eq!(world.packages["editor"].version, "^1.4");
+        assert_eq!(world.packages["browser"].version, "*");
+        assert_eq!(world.packages["browser"].channel, Some(Channel::Beta));
+        assert_eq!(
+            world.packages["kernel"].desired_pin().unwrap(),
+            Some(Pin::Hold)
+        );
+
+        let browser = &world.packages["browser"];
+        assert!(browser.allows(&Version::new(2, 0, 5)));
+        assert!(!browser.allows(&Version::new(2, 1, 0)));
+
+        assert!(WorldConfig::parse("[packages]\neditor = \"not a version\"").is_err());
+    }
+
+    #[test]
+    fn test_world_pins_and_channels() {
+        let world = WorldConfig::parse(
+            r#"
+            [packages]
+            editor = { pin = "~1.4" }
+            viewer = "*"
+            "#,
+        )
+        .unwrap();
+
+        let mut pins = PinConfig::default();
+        pins.hold("viewer");
+        pins.hold("kernel");
+
+        let changes = world.pin_changes(&pins).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].0, "editor");
+        assert_eq!(changes[1], ("viewer".to_string(), None));
+
+        let mut sources = SourcesConfig::default();
+        sources.set_package_channel("viewer", Channel::Nightly);
+        assert_eq!(
+            world.apply_channels(&mut sources),
+            vec![("viewer".to_string(), None)]
+        );
+        assert!(sources.package_channels.is_empty());
+    }
+}
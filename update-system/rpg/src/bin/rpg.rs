@@ -1,675 +1,2089 @@
-// Copyright 2025 The Rustux Authors
-//
-// Use of this source code is governed by a MIT-style
-// license that can be found in the LICENSE file or at
-// https://opensource.org/licenses/MIT
-
-//! RPG (Rustica Package Manager) CLI
-//!
-//! The main command-line interface for managing packages
-//! in the Rustica Operating System.
-
-use clap::{Parser, Subcommand};
-use rpg_core::{ops::PackageManager, sources::SourcesConfig, Error};
-use std::path::PathBuf;
-use tracing::{info, warn};
-use tracing_subscriber::EnvFilter;
-
-/// RPG - Rustica Package Manager
-#[derive(Parser, Debug)]
-#[command(name = "rpg")]
-#[command(about = "Rustica Package Manager - Manage system and application packages", long_about = None)]
-#[command(version = env!("CARGO_PKG_VERSION"))]
-struct Rpg {
-    /// Enable verbose output
-    #[arg(short, long)]
-    verbose: bool,
-
-    /// Path to configuration directory
-    #[arg(short, long, default_value = "/etc/rpg")]
-    config_dir: PathBuf,
-
-    /// Path to sources list file
-    #[arg(short, long, default_value = "/etc/rpg/sources.list")]
-    sources_file: PathBuf,
-
-    #[command(subcommand)]
-    command: Commands,
-}
-
-/// Available commands
-#[derive(Subcommand, Debug)]
-enum Commands {
-    /// Check for and install updates
-    Update {
-        /// Update in background without prompting
-        #[arg(short, long)]
-        background: bool,
-
-        /// Only check for updates, don't install
-        #[arg(long)]
-        check_only: bool,
-
-        /// Specific package to update (default: all packages)
-        #[arg(short, long)]
-        package: Option<String>,
-
-        /// Force re-download even if package exists
-        #[arg(long)]
-        force: bool,
-    },
-
-    /// Rollback to a previous version
-    Rollback {
-        /// Package to rollback (or "system" for system rollback)
-        package: String,
-
-        /// Specific version to rollback to (default: previous version)
-        #[arg(short, long)]
-        version: Option<String>,
-    },
-
-    /// Show system and package status
-    Status {
-        /// Show detailed information
-        #[arg(short, long)]
-        detailed: bool,
-
-        /// Show only installed packages
-        #[arg(long)]
-        installed: bool,
-
-        /// Show only available updates
-        #[arg(long)]
-        updates: bool,
-    },
-
-    /// Manage repository sources
-    Sources {
-        #[command(subcommand)]
-        action: SourcesCommands,
-    },
-
-    /// List available packages
-    List {
-        /// Search pattern for package names
-        #[arg(short, long)]
-        pattern: Option<String>,
-
-        /// Filter by package type
-        #[arg(short, long)]
-        kind: Option<String>,
-    },
-
-    /// Install a package
-    Install {
-        /// Package name
-        package: String,
-
-        /// Specific version to install
-        #[arg(short, long)]
-        version: Option<String>,
-
-        /// Don't install dependencies
-        #[arg(long)]
-        no_deps: bool,
-    },
-
-    /// Remove a package
-    Remove {
-        /// Package name
-        package: String,
-
-        /// Remove configuration files
-        #[arg(long)]
-        purge: bool,
-    },
-}
-
-/// Sources management commands
-#[derive(Subcommand, Debug)]
-enum SourcesCommands {
-    /// List configured sources
-    List {
-        /// Show disabled sources
-        #[arg(long)]
-        all: bool,
-    },
-
-    /// Add a new source
-    Add {
-        /// Source name
-        name: String,
-
-        /// Source URL
-        url: String,
-
-        /// Source type (kernel, system, apps)
-        kind: String,
-
-        /// Priority (lower = higher priority)
-        #[arg(short, long, default_value = "100")]
-        priority: u32,
-    },
-
-    /// Remove a source
-    Remove {
-        /// Source name
-        name: String,
-    },
-
-    /// Enable a source
-    Enable {
-        /// Source name
-        name: String,
-    },
-
-    /// Disable a source
-    Disable {
-        /// Source name
-        name: String,
-    },
-
-    /// Check if sources are reachable
-    Check {
-        /// Source name (default: all sources)
-        name: Option<String>,
-    },
-
-    /// Update repository indices from sources
-    Update,
-}
-
-#[tokio::main]
-async fn main() -> Result<(), Error> {
-    let args = Rpg::parse();
-
-    // Initialize logging
-    let log_level = if args.verbose {
-        tracing::Level::DEBUG
-    } else {
-        tracing::Level::INFO
-    };
-
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::builder()
-                .with_default_directive(log_level.into())
-                .from_env_lossy(),
-        )
-        .init();
-
-    // Execute command
-    match args.command {
-        Commands::Update {
-            background,
-            check_only,
-            package,
-            force,
-        } => {
-            cmd_update(background, check_only, package, force, &args.sources_file).await?;
-        }
-        Commands::Rollback { package, version } => {
-            cmd_rollback(package, version).await?;
-        }
-        Commands::Status {
-            detailed,
-            installed,
-            updates,
-        } => {
-            cmd_status(detailed, installed, updates, &args.sources_file).await?;
-        }
-        Commands::Sources { action } => {
-            cmd_sources(action, &args.sources_file).await?;
-        }
-        Commands::List { pattern, kind } => {
-            cmd_list(pattern, kind).await?;
-        }
-        Commands::Install {
-            package,
-            version,
-            no_deps,
-        } => {
-            cmd_install(package, version, no_deps).await?;
-        }
-        Commands::Remove { package, purge } => {
-            cmd_remove(package, purge).await?;
-        }
-    }
-
-    Ok(())
-}
-
-/// Check for and install updates
-async fn cmd_update(
-    background: bool,
-    check_only: bool,
-    package: Option<String>,
-    _force: bool,
-    _sources_file: &PathBuf,
-) -> Result<(), Error> {
-    let manager = PackageManager::new()?;
-
-    if check_only {
-        info!("Checking for available updates...");
-        let update_info = manager.check_updates().await?;
-
-        if update_info.available.is_empty() {
-            println!("No updates available.");
-        } else {
-            println!("Available updates:");
-            for update in &update_info.available {
-                println!(
-                    "  {} ({} -> {}) - {} bytes",
-                    update.name, update.current_version, update.new_version, update.size
-                );
-            }
-        }
-
-        for error in &update_info.errors {
-            warn!("Update check error: {}", error);
-        }
-
-        return Ok(());
-    }
-
-    if background {
-        info!("Running in background mode...");
-        // TODO: Implement background update mode
-    }
-
-    if let Some(pkg) = package {
-        info!("Updating package: {}", pkg);
-        // TODO: Implement single package update
-    } else {
-        info!("Updating all packages...");
-        let result = manager.update_all().await?;
-
-        if result.succeeded.is_empty() && result.failed.is_empty() {
-            println!("No updates available.");
-        } else {
-            if !result.succeeded.is_empty() {
-                println!("Successfully updated {} package(s):", result.succeeded.len());
-                for pkg in &result.succeeded {
-                    println!("  - {}", pkg);
-                }
-            }
-
-            if !result.failed.is_empty() {
-                println!("\nFailed to update {} package(s):", result.failed.len());
-                for (pkg, error) in &result.failed {
-                    println!("  - {}: {}", pkg, error);
-                }
-            }
-
-            if !result.requires_reboot.is_empty() {
-                println!("\nReboot required for: {}", result.requires_reboot.join(", "));
-            }
-        }
-    }
-
-    Ok(())
-}
-
-/// Rollback to a previous version
-async fn cmd_rollback(package: String, version: Option<String>) -> Result<(), Error> {
-    let manager = PackageManager::new()?;
-
-    info!("Rolling back {} to {:?}", package, version);
-
-    if package == "system" {
-        info!("Rolling back system...");
-        // TODO: Implement system rollback
-        println!("System rollback not yet implemented");
-    } else {
-        info!("Rolling back package: {}", package);
-        let result = manager.rollback(&package, version.as_deref()).await?;
-
-        match result {
-            rpg_core::transaction::TransactionResult::Success { activated, .. } => {
-                println!("Successfully rolled back {}", activated.join(", "));
-            }
-            rpg_core::transaction::TransactionResult::Failed { error, .. } => {
-                println!("Rollback failed: {}", error);
-                return Err(Error::Other(error));
-            }
-            rpg_core::transaction::TransactionResult::RolledBack { reason, .. } => {
-                println!("Rollback completed: {}", reason);
-            }
-        }
-    }
-
-    Ok(())
-}
-
-/// Show system and package status
-async fn cmd_status(
-    detailed: bool,
-    installed: bool,
-    updates: bool,
-    sources_file: &PathBuf,
-) -> Result<(), Error> {
-    // Load sources configuration
-    let sources = SourcesConfig::load_from_path(sources_file.to_str().unwrap())
-        .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
-
-    println!("=== Rustica Package Manager Status ===\n");
-
-    // Show sources statistics
-    let stats = sources.stats();
-    println!("Sources:");
-    println!("  Total: {}", stats.total);
-    println!("  Enabled: {}", stats.enabled);
-    println!("  Disabled: {}", stats.disabled);
-    println!("  Kernel: {}", stats.kernel_count);
-    println!("  System: {}", stats.system_count);
-    println!("  Apps: {}", stats.apps_count);
-
-    if detailed {
-        println!("\nConfigured Sources:");
-        for source in &sources.sources {
-            let status = if source.enabled { "enabled" } else { "disabled" };
-            println!("  - {} ({}, {}, priority: {})",
-                source.name, source.source_type, status, source.priority);
-            println!("    URL: {}", source.url);
-        }
-    }
-
-    // Show installed packages
-    if installed || (!updates && !installed) {
-        let manager = PackageManager::new()?;
-        let installed_packages = manager.list_installed().await?;
-
-        println!("\nInstalled Packages:");
-        if installed_packages.is_empty() {
-            println!("  (No packages installed)");
-        } else {
-            for pkg in &installed_packages {
-                println!("  {} ({})", pkg.name, pkg.version);
-                if detailed {
-                    println!("    Kind: {:?}", pkg.kind);
-                    if pkg.versions.len() > 1 {
-                        println!("    Other versions: {}", pkg.versions.iter()
-                            .filter(|v| *v != &pkg.version)
-                            .cloned()
-                            .collect::<Vec<_>>()
-                            .join(", "));
-                    }
-                }
-            }
-        }
-    }
-
-    // Show available updates
-    if updates || (!updates && !installed) {
-        let manager = PackageManager::new()?;
-        let update_info = manager.check_updates().await?;
-
-        println!("\nAvailable Updates:");
-        if update_info.available.is_empty() {
-            println!("  (No updates available)");
-        } else {
-            for update in &update_info.available {
-                println!(
-                    "  {} ({} -> {}) - {} bytes",
-                    update.name, update.current_version, update.new_version, update.size
-                );
-            }
-        }
-    }
-
-    Ok(())
-}
-
-/// Manage repository sources
-async fn cmd_sources(action: SourcesCommands, sources_file: &PathBuf) -> Result<(), Error> {
-    match action {
-        SourcesCommands::List { all } => {
-            let sources = SourcesConfig::load_from_path(sources_file.to_str().unwrap())
-                .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
-
-            println!("=== Configured Sources ===\n");
-            for source in &sources.sources {
-                if !source.enabled && !all {
-                    continue;
-                }
-                let status = if source.enabled { "enabled" } else { "disabled" };
-                println!("{} ({})", source.name, status);
-                println!("  Type: {}", source.source_type);
-                println!("  URL: {}", source.url);
-                println!("  Priority: {}", source.priority);
-                println!();
-            }
-        }
-        SourcesCommands::Add { name, url, kind, priority } => {
-            info!("Adding source: {} -> {}", name, url);
-
-            let mut sources = SourcesConfig::load_from_path(sources_file.to_str().unwrap())
-                .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
-
-            let source = rpg_core::Source::with_priority(name.clone(), url, kind, priority);
-            sources.add_source(source);
-            sources.validate()?;
-            sources.save()?;
-
-            println!("Added source: {}", name);
-        }
-        SourcesCommands::Remove { name } => {
-            info!("Removing source: {}", name);
-
-            let mut sources = SourcesConfig::load_from_path(sources_file.to_str().unwrap())
-                .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
-
-            sources.remove_source(&name);
-            sources.save()?;
-
-            println!("Removed source: {}", name);
-        }
-        SourcesCommands::Enable { name } => {
-            info!("Enabling source: {}", name);
-
-            let mut sources = SourcesConfig::load_from_path(sources_file.to_str().unwrap())
-                .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
-
-            if sources.enable_source(&name) {
-                sources.save()?;
-                println!("Enabled source: {}", name);
-            } else {
-                warn!("Source not found: {}", name);
-                return Err(Error::Other(format!("Source not found: {}", name)));
-            }
-        }
-        SourcesCommands::Disable { name } => {
-            info!("Disabling source: {}", name);
-
-            let mut sources = SourcesConfig::load_from_path(sources_file.to_str().unwrap())
-                .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
-
-            if sources.disable_source(&name) {
-                sources.save()?;
-                println!("Disabled source: {}", name);
-            } else {
-                warn!("Source not found: {}", name);
-                return Err(Error::Other(format!("Source not found: {}", name)));
-            }
-        }
-        SourcesCommands::Check { name } => {
-            let sources = SourcesConfig::load_from_path(sources_file.to_str().unwrap())
-                .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
-
-            if let Some(name) = name {
-                // Check specific source
-                let source = sources.sources.iter()
-                    .find(|s| s.name == name)
-                    .ok_or_else(|| Error::Other(format!("Source not found: {}", name)))?;
-
-                println!("Checking source: {}", source.name);
-                let reachable = source.check_reachable().await;
-                if reachable {
-                    println!("  Status: Reachable");
-                } else {
-                    println!("  Status: Not reachable");
-                }
-            } else {
-                // Check all sources
-                println!("=== Checking All Sources ===\n");
-                for source in &sources.sources {
-                    if !source.enabled {
-                        continue;
-                    }
-                    println!("{}: ", source.name);
-                    let reachable = source.check_reachable().await;
-                    if reachable {
-                        println!("  Reachable");
-                    } else {
-                        println!("  Not reachable");
-                    }
-                }
-            }
-        }
-        SourcesCommands::Update => {
-            info!("Updating repository indices from sources...");
-
-            let sources = SourcesConfig::load_from_path(sources_file.to_str().unwrap())
-                .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
-
-            // TODO: Fetch indices from all sources
-            println!("Updating indices from {} sources", sources.enabled_sources().len());
-            println!("(Not yet implemented)");
-        }
-    }
-
-    Ok(())
-}
-
-/// List available packages
-async fn cmd_list(pattern: Option<String>, kind: Option<String>) -> Result<(), Error> {
-    let manager = PackageManager::new()?;
-
-    info!("Listing packages...");
-
-    // Check what's available from sources
-    let update_info = manager.check_updates().await?;
-
-    if update_info.available.is_empty() {
-        println!("No packages available (sources may be unreachable)");
-    } else {
-        println!("Available Packages:");
-
-        for mut update in update_info.available {
-            // Filter by pattern if specified
-            if let Some(ref p) = pattern {
-                if !update.name.contains(p) {
-                    continue;
-                }
-            }
-
-            // Filter by kind if specified
-            if let Some(ref k) = kind {
-                let kind_str = match update.kind {
-                    rpg_core::PackageKind::App => "app",
-                    rpg_core::PackageKind::System => "system",
-                    rpg_core::PackageKind::Kernel => "kernel",
-                    rpg_core::PackageKind::Boot => "boot",
-                };
-                if kind_str != k {
-                    continue;
-                }
-            }
-
-            println!(
-                "  {} ({}) - {} bytes - {}",
-                update.name, update.new_version, update.size, update.kind
-            );
-        }
-    }
-
-    // Also show installed packages
-    let installed = manager.list_installed().await?;
-    if !installed.is_empty() {
-        println!("\nInstalled Packages:");
-        for pkg in installed {
-            // Apply filters
-            if let Some(ref p) = pattern {
-                if !pkg.name.contains(p) {
-                    continue;
-                }
-            }
-            if let Some(ref k) = kind {
-                let kind_str = match pkg.kind {
-                    rpg_core::PackageKind::App => "app",
-                    rpg_core::PackageKind::System => "system",
-                    rpg_core::PackageKind::Kernel => "kernel",
-                    rpg_core::PackageKind::Boot => "boot",
-                };
-                if kind_str != k {
-                    continue;
-                }
-            }
-
-            println!("  {} ({}) - {}", pkg.name, pkg.version, pkg.kind);
-        }
-    }
-
-    Ok(())
-}
-
-/// Install a package
-async fn cmd_install(package: String, version: Option<String>, _no_deps: bool) -> Result<(), Error> {
-    let manager = PackageManager::new()?;
-
-    info!("Installing package: {}", package);
-
-    // Determine package kind
-    let kind = if package == "kernel" {
-        rpg_core::PackageKind::Kernel
-    } else if package == "system" {
-        rpg_core::PackageKind::System
-    } else {
-        rpg_core::PackageKind::App
-    };
-
-    match manager.install_package(&package, version.as_deref(), kind).await? {
-        rpg_core::transaction::TransactionResult::Success { activated, requires_reboot } => {
-            if !activated.is_empty() {
-                println!("Successfully installed: {}", activated.join(", "));
-            }
-            if !requires_reboot.is_empty() {
-                println!("Reboot required for: {}", requires_reboot.join(", "));
-            }
-        }
-        rpg_core::transaction::TransactionResult::Failed { error, .. } => {
-            println!("Installation failed: {}", error);
-            return Err(Error::Other(error));
-        }
-        rpg_core::transaction::TransactionResult::RolledBack { reason, .. } => {
-            println!("Installation rolled back: {}", reason);
-            return Err(Error::Other(reason));
-        }
-    }
-
-    Ok(())
-}
-
-/// Remove a package
-async fn cmd_remove(package: String, _purge: bool) -> Result<(), Error> {
-    let manager = PackageManager::new()?;
-
-    info!("Removing package: {}", package);
-
-    match manager.remove_package(&package).await? {
-        rpg_core::transaction::TransactionResult::Success { activated, .. } => {
-            println!("Successfully removed: {}", activated.join(", "));
-        }
-        rpg_core::transaction::TransactionResult::Failed { error, .. } => {
-            println!("Removal failed: {}", error);
-            return Err(Error::Other(error));
-        }
-        rpg_core::transaction::TransactionResult::RolledBack { reason, .. } => {
-            println!("Removal rolled back: {}", reason);
-            return Err(Error::Other(reason));
-        }
-    }
-
-    Ok(())
-}
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! RPG (Rustica Package Manager) CLI
+//!
+//! The main command-line interface for managing packages
+//! in the Rustica Operating System.
+
+use clap::{Parser, Subcommand};
+use rpg_core::plan::{format_timestamp, Plan};
+use rpg_core::{
+    ops::PackageManager, sources::SourcesConfig, Channel, Error, InstallOptions, InstallReason,
+    MergeChoice, PinConfig, RebootRequired, RegistryLock, RemoveOptions, SbomFormat, Scope,
+    VersionConstraint, WorldConfig, WorldPlan,
+};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
+
+/// RPG - Rustica Package Manager
+#[derive(Parser, Debug)]
+#[command(name = "rpg")]
+#[command(about = "Rustica Package Manager - Manage system and application packages", long_about = None)]
+#[command(version = env!("CARGO_PKG_VERSION"))]
+struct Rpg {
+    /// Enable verbose output
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Path to configuration directory
+    #[arg(short, long, default_value = "/etc/rpg")]
+    config_dir: PathBuf,
+
+    /// Path to sources file
+    #[arg(short, long, default_value = rpg_core::sources::SOURCES_PATH)]
+    sources_file: PathBuf,
+
+    /// Target architecture (default: host architecture)
+    #[arg(long, global = true)]
+    arch: Option<String>,
+
+    /// Wait for another rpg process to release the registry lock
+    #[arg(long, global = true)]
+    wait: bool,
+
+    /// Manage apps for the current user instead of the system
+    #[arg(long, global = true)]
+    user: bool,
+
+    /// Keep all state under this directory instead of `/`
+    ///
+    /// Sources, pins, the registry, the cache, apps and system versions are
+    /// read and written at their usual paths below it, leaving the host
+    /// untouched. Used to stage system images and by the integration tests.
+    #[arg(long, global = true, conflicts_with = "user")]
+    root: Option<PathBuf>,
+
+    /// Print plans as JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+/// Available commands
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Check for and install updates
+    Update {
+        /// Update in background without prompting
+        #[arg(short, long)]
+        background: bool,
+
+        /// Only check for updates, don't install
+        #[arg(long)]
+        check_only: bool,
+
+        /// Specific package to update (default: all packages)
+        #[arg(short, long)]
+        package: Option<String>,
+
+        /// Force re-download even if package exists
+        #[arg(long)]
+        force: bool,
+
+        /// Show what would be updated without changing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Only install updates that fix known security advisories
+        #[arg(long)]
+        security_only: bool,
+    },
+
+    /// Rollback to a previous version
+    Rollback {
+        /// Package to rollback (or "system" for system rollback)
+        package: String,
+
+        /// Specific version to rollback to (default: previous version)
+        #[arg(short, long)]
+        version: Option<String>,
+
+        /// Show what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show system and package status
+    Status {
+        /// Show detailed information
+        #[arg(short, long)]
+        detailed: bool,
+
+        /// Show only installed packages
+        #[arg(long)]
+        installed: bool,
+
+        /// Show only available updates
+        #[arg(long)]
+        updates: bool,
+    },
+
+    /// Manage repository sources
+    Sources {
+        #[command(subcommand)]
+        action: SourcesCommands,
+    },
+
+    /// List available packages
+    List {
+        /// Search pattern for package names
+        #[arg(short, long)]
+        pattern: Option<String>,
+
+        /// Filter by package type
+        #[arg(short, long)]
+        kind: Option<String>,
+    },
+
+    /// Install a package
+    Install {
+        /// Package name
+        package: String,
+
+        /// Specific version to install
+        #[arg(short, long)]
+        version: Option<String>,
+
+        /// Don't install dependencies
+        #[arg(long)]
+        no_deps: bool,
+
+        /// Show what would be installed without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Remove a package
+    Remove {
+        /// Package name
+        package: String,
+
+        /// Also remove the app's configuration and data directories
+        #[arg(long)]
+        purge: bool,
+
+        /// Remove the package even if installed packages depend on it
+        #[arg(long)]
+        force: bool,
+
+        /// Show what would be removed without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Remove dependencies that no installed package needs anymore
+    Autoremove {
+        /// Show what would be removed without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Delete old app versions and unused cached downloads
+    Gc {
+        /// Inactive versions of each app to keep for rollbacks
+        #[arg(long, default_value_t = 1)]
+        keep: usize,
+
+        /// Show what would be deleted without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Pin a package to a version constraint
+    Pin {
+        /// Package name
+        package: String,
+
+        /// Version constraint (e.g., "~1.4", "=2.0.1")
+        constraint: String,
+
+        /// Show what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Hold a package at its installed version
+    Hold {
+        /// Package name
+        package: String,
+
+        /// Show what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Remove a pin or hold from a package
+    Unpin {
+        /// Package name
+        package: String,
+
+        /// Show what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Browse and undo past transactions
+    History {
+        #[command(subcommand)]
+        action: Option<HistoryCommands>,
+    },
+
+    /// Manage system snapshots (restore points)
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotCommands,
+    },
+
+    /// Report installed packages with known security advisories
+    Audit,
+
+    /// Export a software bill of materials of the installed packages
+    Sbom {
+        /// Document format (spdx, cyclonedx)
+        #[arg(short, long, default_value = "spdx")]
+        format: String,
+
+        /// Write the document to a file instead of standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Review config files whose new version was kept aside by an upgrade
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommands,
+    },
+
+    /// Maintain a local mirror of a source
+    Mirror {
+        #[command(subcommand)]
+        action: MirrorCommands,
+    },
+
+    /// Bring the system in line with the declared world
+    Apply {
+        /// Path to the world file
+        #[arg(short, long, default_value = "/etc/rpg/world.toml")]
+        file: PathBuf,
+
+        /// Show the plan without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+impl Commands {
+    /// Check if the command modifies the registry or pins
+    ///
+    /// Such commands hold the registry lock for their whole run, so no
+    /// other process can load and save the registry in between.
+    fn modifies_registry(&self) -> bool {
+        match self {
+            Self::Update {
+                check_only,
+                dry_run,
+                ..
+            } => !check_only && !dry_run,
+            Self::Rollback { dry_run, .. }
+            | Self::Install { dry_run, .. }
+            | Self::Remove { dry_run, .. }
+            | Self::Autoremove { dry_run }
+            | Self::Gc { dry_run, .. }
+            | Self::Apply { dry_run, .. } => !dry_run,
+            Self::Pin { dry_run, .. }
+            | Self::Hold { dry_run, .. }
+            | Self::Unpin { dry_run, .. } => !dry_run,
+            Self::History { action } => {
+                matches!(action, Some(HistoryCommands::Undo { dry_run: false, .. }))
+            }
+            Self::Snapshot { action } => match action {
+                SnapshotCommands::List => false,
+                SnapshotCommands::Create { dry_run, .. }
+                | SnapshotCommands::Restore { dry_run, .. }
+                | SnapshotCommands::Delete { dry_run, .. } => !dry_run,
+            },
+            Self::Config { action } => matches!(action, ConfigCommands::Merge { .. }),
+            Self::Status { .. }
+            | Self::Sources { .. }
+            | Self::List { .. }
+            | Self::Audit
+            | Self::Sbom { .. }
+            | Self::Mirror { .. } => false,
+        }
+    }
+}
+
+/// Snapshot commands
+#[derive(Subcommand, Debug)]
+enum SnapshotCommands {
+    /// Record the active versions of all packages
+    Create {
+        /// Snapshot name
+        name: String,
+
+        /// Description of the snapshot
+        #[arg(short, long)]
+        description: Option<String>,
+
+        /// Show what would be recorded without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// List snapshots
+    List,
+
+    /// Re-activate every version recorded in a snapshot
+    Restore {
+        /// Snapshot name
+        name: String,
+
+        /// Show what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Delete a snapshot
+    Delete {
+        /// Snapshot name
+        name: String,
+
+        /// Show what would be deleted without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Config file commands
+#[derive(Subcommand, Debug)]
+enum ConfigCommands {
+    /// Show how pending new versions differ from the installed files
+    Diff {
+        /// Only show this file
+        path: Option<PathBuf>,
+    },
+
+    /// Resolve a pending change by keeping the local file or taking the new one
+    #[command(group(clap::ArgGroup::new("choice").required(true).args(["keep_local", "use_new"])))]
+    Merge {
+        /// Config file path
+        path: PathBuf,
+
+        /// Keep the local file and discard the new version
+        #[arg(long)]
+        keep_local: bool,
+
+        /// Replace the local file with the new version
+        #[arg(long)]
+        use_new: bool,
+    },
+}
+
+/// Mirror commands
+#[derive(Subcommand, Debug)]
+enum MirrorCommands {
+    /// Bring a mirror directory up to date with a source
+    Sync {
+        /// Source name
+        source: String,
+
+        /// Mirror directory
+        dir: PathBuf,
+    },
+}
+
+/// Transaction history commands
+#[derive(Subcommand, Debug)]
+enum HistoryCommands {
+    /// List recent transactions
+    List {
+        /// Number of transactions to show
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+    },
+
+    /// Show details of a transaction or batch
+    Show {
+        /// Transaction or batch ID (a unique prefix is enough)
+        id: String,
+    },
+
+    /// Revert a transaction or a whole batch
+    Undo {
+        /// Transaction or batch ID (a unique prefix is enough)
+        id: String,
+
+        /// Show what would be reverted without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Sources management commands
+#[derive(Subcommand, Debug)]
+enum SourcesCommands {
+    /// List configured sources
+    List {
+        /// Show disabled sources
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Add a new source
+    Add {
+        /// Source name
+        name: String,
+
+        /// Source URL
+        url: String,
+
+        /// Source type (kernel, system, apps)
+        kind: String,
+
+        /// Priority (lower = higher priority)
+        #[arg(short, long, default_value = "100")]
+        priority: u32,
+
+        /// Update channel (stable, beta, nightly)
+        #[arg(long, default_value = "stable")]
+        channel: String,
+
+        /// Base64 public key packages from this source must be signed with
+        #[arg(long)]
+        key: Option<String>,
+
+        /// HTTP header sent with every request ("Name: value")
+        #[arg(long)]
+        auth_header: Option<String>,
+
+        /// Proxy URL used to reach this source
+        #[arg(long)]
+        proxy: Option<String>,
+
+        /// PEM bundle of CA certificates trusted for this source
+        #[arg(long)]
+        ca_bundle: Option<PathBuf>,
+
+        /// Show what would be added without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Set the update channel of a source or package
+    Channel {
+        /// Source name (or package name with --package)
+        target: String,
+
+        /// Update channel (stable, beta, nightly)
+        channel: String,
+
+        /// Set the channel for a single package instead of a source
+        #[arg(long)]
+        package: bool,
+    },
+
+    /// Remove a source
+    Remove {
+        /// Source name
+        name: String,
+
+        /// Show what would be removed without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Enable a source
+    Enable {
+        /// Source name
+        name: String,
+    },
+
+    /// Disable a source
+    Disable {
+        /// Source name
+        name: String,
+    },
+
+    /// Check if sources are reachable
+    Check {
+        /// Source name (default: all sources)
+        name: Option<String>,
+    },
+
+    /// Update repository indices from sources
+    Update,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let args = Rpg::parse();
+
+    // Initialize logging
+    let log_level = if args.verbose {
+        tracing::Level::DEBUG
+    } else {
+        tracing::Level::INFO
+    };
+
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::builder()
+                .with_default_directive(log_level.into())
+                .from_env_lossy(),
+        )
+        .init();
+
+    let scope = if args.user {
+        Scope::User
+    } else {
+        Scope::System
+    };
+    let opts = ManagerOptions {
+        arch: args.arch.as_deref(),
+        scope,
+        root: args.root.as_deref(),
+        sources_file: &args.sources_file,
+        json: args.json,
+    };
+    let sources_file = opts.under_root(opts.sources_file);
+
+    // Held until the command finishes
+    let _lock = if args.command.modifies_registry() {
+        Some(RegistryLock::acquire_at(
+            opts.under_root(&scope.lock_path()?),
+            args.wait,
+        )?)
+    } else {
+        None
+    };
+
+    // Execute command
+    match args.command {
+        Commands::Update {
+            background,
+            check_only,
+            package,
+            force,
+            dry_run,
+            yes,
+            security_only,
+        } => {
+            cmd_update(
+                background,
+                check_only,
+                package,
+                force,
+                dry_run,
+                yes,
+                security_only,
+                opts,
+            )
+            .await?;
+        }
+        Commands::Rollback {
+            package,
+            version,
+            dry_run,
+        } => {
+            cmd_rollback(package, version, dry_run, opts).await?;
+        }
+        Commands::Status {
+            detailed,
+            installed,
+            updates,
+        } => {
+            cmd_status(detailed, installed, updates, &sources_file, opts).await?;
+        }
+        Commands::Sources { action } => {
+            cmd_sources(action, &sources_file, opts).await?;
+        }
+        Commands::List { pattern, kind } => {
+            cmd_list(pattern, kind, opts).await?;
+        }
+        Commands::Install {
+            package,
+            version,
+            no_deps,
+            dry_run,
+        } => {
+            cmd_install(package, version, no_deps, dry_run, opts).await?;
+        }
+        Commands::Remove {
+            package,
+            purge,
+            force,
+            dry_run,
+        } => {
+            cmd_remove(package, RemoveOptions { force, purge }, dry_run, opts).await?;
+        }
+        Commands::Autoremove { dry_run } => {
+            cmd_autoremove(dry_run, opts).await?;
+        }
+        Commands::Gc { keep, dry_run } => {
+            cmd_gc(keep, dry_run, opts).await?;
+        }
+        Commands::Pin {
+            package,
+            constraint,
+            dry_run,
+        } => {
+            let pins_path = opts.under_root(&scope.pins_path()?);
+            cmd_pin(package, Some(constraint), dry_run, &pins_path)?;
+        }
+        Commands::Hold { package, dry_run } => {
+            cmd_pin(
+                package,
+                None,
+                dry_run,
+                &opts.under_root(&scope.pins_path()?),
+            )?;
+        }
+        Commands::Unpin { package, dry_run } => {
+            cmd_unpin(package, dry_run, &opts.under_root(&scope.pins_path()?))?;
+        }
+        Commands::History { action } => {
+            cmd_history(action.unwrap_or(HistoryCommands::List { limit: 20 }), opts).await?;
+        }
+        Commands::Snapshot { action } => {
+            cmd_snapshot(action, opts).await?;
+        }
+        Commands::Audit => {
+            cmd_audit(opts).await?;
+        }
+        Commands::Config { action } => {
+            cmd_config(action, opts).await?;
+        }
+        Commands::Sbom { format, output } => {
+            cmd_sbom(&format, output.as_deref(), opts).await?;
+        }
+        Commands::Mirror { action } => {
+            cmd_mirror(action, opts).await?;
+        }
+        Commands::Apply { file, dry_run } => {
+            cmd_apply(&file, dry_run, opts).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Options for opening a package manager
+#[derive(Debug, Clone, Copy)]
+struct ManagerOptions<'a> {
+    /// Target architecture (default: host architecture)
+    arch: Option<&'a str>,
+    /// Where packages are installed
+    scope: Scope,
+    /// Directory all state is kept under instead of `/`
+    root: Option<&'a Path>,
+    /// Sources file, below `root` if one was given
+    sources_file: &'a Path,
+    /// Print plans as JSON instead of text
+    json: bool,
+}
+
+impl ManagerOptions<'_> {
+    /// Get where a system path lives, below `--root` if one was given
+    fn under_root(&self, path: &Path) -> PathBuf {
+        match self.root {
+            Some(root) => root.join(path.strip_prefix("/").unwrap_or(path)),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Print a plan as text, or as JSON with `--json`
+    ///
+    /// A plan that does not fit on disk fails after it was shown.
+    fn show_plan(&self, plan: &Plan) -> Result<(), Error> {
+        if self.json {
+            self.show_json(plan)?;
+        } else {
+            print!("{}", plan);
+        }
+        plan.check_space()
+    }
+
+    /// Print a value as pretty JSON
+    fn show_json(&self, value: &impl serde::Serialize) -> Result<(), Error> {
+        let json =
+            serde_json::to_string_pretty(value).map_err(|e| Error::Serialization(e.to_string()))?;
+        println!("{}", json);
+        Ok(())
+    }
+
+    /// Tell the user a dry run changed nothing, unless printing JSON
+    fn dry_run_done(&self) {
+        if !self.json {
+            println!("\nDry run, nothing changed.");
+        }
+    }
+}
+
+/// Create a package manager for the given scope and architecture
+fn open_manager(opts: ManagerOptions<'_>) -> Result<PackageManager, Error> {
+    let manager = match opts.root {
+        Some(root) => PackageManager::at_root(root)?,
+        None => PackageManager::for_scope(opts.scope)?,
+    };
+    let manager = manager.with_sources_file(opts.under_root(opts.sources_file))?;
+
+    match opts.arch {
+        Some(arch) => manager.with_arch(arch),
+        None => Ok(manager),
+    }
+}
+
+/// Check for and install updates
+#[allow(clippy::too_many_arguments)]
+async fn cmd_update(
+    background: bool,
+    check_only: bool,
+    package: Option<String>,
+    force: bool,
+    dry_run: bool,
+    yes: bool,
+    security_only: bool,
+    opts: ManagerOptions<'_>,
+) -> Result<(), Error> {
+    let manager = &open_manager(opts)?;
+
+    if let Some(pkg) = &package {
+        let installed = manager.list_installed().await?;
+        if !installed.iter().any(|p| &p.name == pkg) {
+            return Err(Error::PackageNotFound(pkg.clone()));
+        }
+    }
+
+    // Only a real update refreshes the summary the shell and panel show
+    let package = package.as_deref();
+    let check_updates = |publish: bool| async move {
+        let mut update_info = manager.check_updates().await?;
+        if publish {
+            manager.publish_updates(&update_info).await;
+        }
+        if let Some(pkg) = package {
+            update_info = update_info.for_package(pkg);
+        }
+        Ok::<_, Error>(if security_only {
+            update_info.security_only()
+        } else {
+            update_info
+        })
+    };
+
+    if check_only {
+        info!("Checking for available updates...");
+        let update_info = check_updates(false).await?;
+
+        if update_info.available.is_empty() {
+            println!("No updates available.");
+        } else {
+            println!("Available updates:");
+            for update in &update_info.available {
+                println!(
+                    "  {} ({} -> {}) - {} bytes{}",
+                    update.name,
+                    update.current_version,
+                    update.new_version,
+                    update.size,
+                    security_note(update)
+                );
+            }
+        }
+
+        if !update_info.held.is_empty() {
+            println!("Held back:");
+            for update in &update_info.held {
+                println!(
+                    "  {} ({} -> {})",
+                    update.name, update.current_version, update.new_version
+                );
+            }
+        }
+
+        for error in &update_info.errors {
+            warn!("Update check error: {}", error);
+        }
+
+        return Ok(());
+    }
+
+    if background {
+        info!("Running in background mode...");
+        // TODO: Implement background update mode
+    }
+
+    match package {
+        Some(pkg) => info!("Updating package: {}", pkg),
+        None => info!("Updating all packages..."),
+    }
+
+    let update_info = check_updates(!dry_run).await?;
+
+    if !update_info.available.is_empty() {
+        let plan = manager.plan_updates(&update_info).await?;
+        if !opts.json {
+            println!("Updates to install:");
+        }
+        opts.show_plan(&plan)?;
+
+        if dry_run {
+            opts.dry_run_done();
+            return Ok(());
+        }
+
+        if !background && !yes && !confirm("\nProceed with the update?")? {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    if force {
+        manager.evict_cached(&update_info).await?;
+    }
+    let result = manager.install_updates(&update_info).await?;
+
+    if result.succeeded.is_empty() && result.failed.is_empty() {
+        if result.held.is_empty() {
+            println!("No updates available.");
+        }
+    } else {
+        if !result.succeeded.is_empty() {
+            println!(
+                "Successfully updated {} package(s):",
+                result.succeeded.len()
+            );
+            for pkg in &result.succeeded {
+                println!("  - {}", pkg);
+            }
+        }
+
+        if !result.failed.is_empty() {
+            println!("\nFailed to update {} package(s):", result.failed.len());
+            for (pkg, error) in &result.failed {
+                println!("  - {}: {}", pkg, error);
+            }
+        }
+
+        if !result.requires_reboot.is_empty() {
+            println!(
+                "\nReboot required for: {}",
+                result.requires_reboot.join(", ")
+            );
+        }
+    }
+
+    if !result.held.is_empty() {
+        println!("\nHeld back (pinned): {}", result.held.join(", "));
+    }
+
+    Ok(())
+}
+
+/// Describe the advisories an update fixes
+fn security_note(update: &rpg_core::PackageUpdate) -> String {
+    match update.severity {
+        Some(severity) => format!(
+            " [security: {}, fixes {}]",
+            severity,
+            update.advisories.join(", ")
+        ),
+        None => String::new(),
+    }
+}
+
+/// Report installed packages with known advisories
+async fn cmd_audit(opts: ManagerOptions<'_>) -> Result<(), Error> {
+    let manager = open_manager(opts)?;
+    let report = manager.audit().await?;
+
+    for error in &report.errors {
+        warn!("{}", error);
+    }
+
+    if report.findings.is_empty() {
+        println!("No known advisories affect installed packages.");
+        return Ok(());
+    }
+
+    println!("=== Security Advisories ===\n");
+    for finding in &report.findings {
+        let advisory = &finding.advisory;
+        println!(
+            "{} {} ({}): {}",
+            finding.package, finding.installed, advisory.severity, advisory.id
+        );
+        if let Some(summary) = &advisory.summary {
+            println!("  {}", summary);
+        }
+        match &advisory.fixed_in {
+            Some(fixed_in) => println!("  Fixed in: {}", fixed_in),
+            None => println!("  No fix available yet"),
+        }
+        if let Some(url) = &advisory.url {
+            println!("  See: {}", url);
+        }
+        println!();
+    }
+
+    println!(
+        "{} advisory finding(s); run 'rpg update --security-only' to install fixes.",
+        report.findings.len()
+    );
+
+    Ok(())
+}
+
+/// Rollback to a previous version
+async fn cmd_rollback(
+    package: String,
+    version: Option<String>,
+    dry_run: bool,
+    opts: ManagerOptions<'_>,
+) -> Result<(), Error> {
+    let manager = open_manager(opts)?;
+
+    info!("Rolling back {} to {:?}", package, version);
+
+    if package == "system" {
+        info!("Rolling back system...");
+        // TODO: Implement system rollback
+        println!("System rollback not yet implemented");
+    } else if dry_run {
+        opts.show_plan(&manager.plan_rollback(&package, version.as_deref()).await?)?;
+        opts.dry_run_done();
+    } else {
+        info!("Rolling back package: {}", package);
+        let result = manager.rollback(&package, version.as_deref()).await?;
+
+        match result {
+            rpg_core::transaction::TransactionResult::Success {
+                requires_reboot, ..
+            } => {
+                println!("Successfully rolled back {}", package);
+                if !requires_reboot.is_empty() {
+                    println!("Reboot required for: {}", requires_reboot.join(", "));
+                }
+            }
+            rpg_core::transaction::TransactionResult::Failed { error, .. } => {
+                println!("Rollback failed: {}", error);
+                return Err(Error::Other(error));
+            }
+            rpg_core::transaction::TransactionResult::RolledBack { reason, .. } => {
+                println!("Rollback completed: {}", reason);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Show system and package status
+async fn cmd_status(
+    detailed: bool,
+    installed: bool,
+    updates: bool,
+    sources_file: &Path,
+    opts: ManagerOptions<'_>,
+) -> Result<(), Error> {
+    // Load sources configuration
+    let sources = SourcesConfig::load_from_path(opts.under_root(sources_file))
+        .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
+    let manager = open_manager(opts)?;
+
+    println!("=== Rustica Package Manager Status ===\n");
+
+    // Show packages waiting for a reboot
+    if let Some(reboot) = RebootRequired::load(manager.state_dir()) {
+        println!(
+            "Reboot required for: {} (since {})\n",
+            reboot.packages.join(", "),
+            format_timestamp(reboot.since)
+        );
+    }
+
+    // Show sources statistics
+    let stats = sources.stats();
+    println!("Sources:");
+    println!("  Total: {}", stats.total);
+    println!("  Enabled: {}", stats.enabled);
+    println!("  Disabled: {}", stats.disabled);
+    println!("  Kernel: {}", stats.kernel_count);
+    println!("  System: {}", stats.system_count);
+    println!("  Apps: {}", stats.apps_count);
+
+    if detailed {
+        println!("\nConfigured Sources:");
+        for source in &sources.sources {
+            let status = if source.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            };
+            println!(
+                "  - {} ({}, {}, priority: {}, channel: {})",
+                source.name, source.source_type, status, source.priority, source.channel
+            );
+            println!("    URL: {}", source.url);
+        }
+    }
+
+    // Show installed packages
+    if installed || !updates {
+        let installed_packages = manager.list_installed().await?;
+
+        println!("\nInstalled Packages:");
+        if installed_packages.is_empty() {
+            println!("  (No packages installed)");
+        } else {
+            for pkg in &installed_packages {
+                println!("  {} ({})", pkg.name, pkg.version);
+                if detailed {
+                    println!("    Kind: {:?}", pkg.kind);
+                    if pkg.versions.len() > 1 {
+                        println!(
+                            "    Other versions: {}",
+                            pkg.versions
+                                .iter()
+                                .filter(|v| *v != &pkg.version)
+                                .cloned()
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // Show pinned and held packages
+    let pins = PinConfig::load_from_path(opts.under_root(&opts.scope.pins_path()?))?;
+    if !pins.pins.is_empty() {
+        println!("\nPinned Packages:");
+        for (name, pin) in &pins.pins {
+            println!("  {} ({})", name, pin);
+        }
+    }
+
+    // Show available updates
+    if updates || !installed {
+        let update_info = manager.check_updates().await?;
+
+        println!("\nAvailable Updates:");
+        if update_info.available.is_empty() {
+            println!("  (No updates available)");
+        } else {
+            for update in &update_info.available {
+                println!(
+                    "  {} ({} -> {}) - {} bytes",
+                    update.name, update.current_version, update.new_version, update.size
+                );
+            }
+        }
+
+        if !update_info.held.is_empty() {
+            println!("\nHeld Back Updates:");
+            for update in &update_info.held {
+                let reason = pins
+                    .get(&update.name)
+                    .map(|p| p.to_string())
+                    .unwrap_or_default();
+                println!(
+                    "  {} ({} -> {}) - {}",
+                    update.name, update.current_version, update.new_version, reason
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Manage repository sources
+async fn cmd_sources(
+    action: SourcesCommands,
+    sources_file: &Path,
+    opts: ManagerOptions<'_>,
+) -> Result<(), Error> {
+    match action {
+        SourcesCommands::List { all } => {
+            let sources = SourcesConfig::load_from_path(sources_file)
+                .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
+
+            println!("=== Configured Sources ===\n");
+            for source in &sources.sources {
+                if !source.enabled && !all {
+                    continue;
+                }
+                let status = if source.enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                };
+                println!("{} ({})", source.name, status);
+                println!("  Type: {}", source.source_type);
+                println!("  URL: {}", source.url);
+                println!("  Priority: {}", source.priority);
+                println!("  Channel: {}", source.channel);
+                if source.trusted_key.is_some() {
+                    println!("  Trusted key: yes");
+                }
+                if let Some((header, _)) = source.auth_header_parts() {
+                    println!("  Auth header: {}", header);
+                }
+                if let Some(proxy) = &source.proxy {
+                    println!("  Proxy: {}", proxy);
+                }
+                if let Some(ca_bundle) = &source.ca_bundle {
+                    println!("  CA bundle: {}", ca_bundle.display());
+                }
+                println!();
+            }
+
+            if !sources.package_channels.is_empty() {
+                println!("=== Package Channels ===\n");
+                for (package, channel) in &sources.package_channels {
+                    println!("{}: {}", package, channel);
+                }
+            }
+        }
+        SourcesCommands::Add {
+            name,
+            url,
+            kind,
+            priority,
+            channel,
+            key,
+            auth_header,
+            proxy,
+            ca_bundle,
+            dry_run,
+        } => {
+            info!("Adding source: {} -> {}", name, url);
+
+            let mut sources = SourcesConfig::load_from_path(sources_file)
+                .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
+
+            let mut source = rpg_core::Source::with_priority(name.clone(), url, kind, priority)
+                .with_channel(channel.parse()?);
+            source.trusted_key = key;
+            source.auth_header = auth_header;
+            source.proxy = proxy;
+            source.ca_bundle = ca_bundle;
+            sources.add_source(source);
+            sources.validate()?;
+
+            if dry_run {
+                println!("Would add source: {}", name);
+                opts.dry_run_done();
+                return Ok(());
+            }
+            sources.save_to_path(sources_file)?;
+
+            println!("Added source: {}", name);
+        }
+        SourcesCommands::Remove { name, dry_run } => {
+            info!("Removing source: {}", name);
+
+            let mut sources = SourcesConfig::load_from_path(sources_file)
+                .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
+
+            if dry_run {
+                if !sources.sources.iter().any(|s| s.name == name) {
+                    return Err(Error::Other(format!("Source not found: {}", name)));
+                }
+                println!("Would remove source: {}", name);
+                opts.dry_run_done();
+                return Ok(());
+            }
+            sources.remove_source(&name);
+            sources.save_to_path(sources_file)?;
+
+            println!("Removed source: {}", name);
+        }
+        SourcesCommands::Enable { name } => {
+            info!("Enabling source: {}", name);
+
+            let mut sources = SourcesConfig::load_from_path(sources_file)
+                .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
+
+            if sources.enable_source(&name) {
+                sources.save_to_path(sources_file)?;
+                println!("Enabled source: {}", name);
+            } else {
+                warn!("Source not found: {}", name);
+                return Err(Error::Other(format!("Source not found: {}", name)));
+            }
+        }
+        SourcesCommands::Disable { name } => {
+            info!("Disabling source: {}", name);
+
+            let mut sources = SourcesConfig::load_from_path(sources_file)
+                .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
+
+            if sources.disable_source(&name) {
+                sources.save_to_path(sources_file)?;
+                println!("Disabled source: {}", name);
+            } else {
+                warn!("Source not found: {}", name);
+                return Err(Error::Other(format!("Source not found: {}", name)));
+            }
+        }
+        SourcesCommands::Channel {
+            target,
+            channel,
+            package,
+        } => {
+            let channel: Channel = channel.parse()?;
+
+            let mut sources = SourcesConfig::load_from_path(sources_file)
+                .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
+
+            if package {
+                info!("Subscribing package {} to channel {}", target, channel);
+                sources.set_package_channel(&target, channel);
+            } else {
+                info!("Setting channel of source {} to {}", target, channel);
+                if !sources.set_source_channel(&target, channel) {
+                    warn!("Source not found: {}", target);
+                    return Err(Error::Other(format!("Source not found: {}", target)));
+                }
+            }
+
+            sources.save_to_path(sources_file)?;
+            println!("{} now follows the {} channel", target, channel);
+        }
+        SourcesCommands::Check { name } => {
+            let sources = SourcesConfig::load_from_path(sources_file)
+                .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
+
+            if let Some(name) = name {
+                // Check specific source
+                let source = sources
+                    .sources
+                    .iter()
+                    .find(|s| s.name == name)
+                    .ok_or_else(|| Error::Other(format!("Source not found: {}", name)))?;
+
+                println!("Checking source: {}", source.name);
+                let reachable = source.check_reachable().await;
+                if reachable {
+                    println!("  Status: Reachable");
+                } else {
+                    println!("  Status: Not reachable");
+                }
+            } else {
+                // Check all sources
+                println!("=== Checking All Sources ===\n");
+                for source in &sources.sources {
+                    if !source.enabled {
+                        continue;
+                    }
+                    println!("{}: ", source.name);
+                    let reachable = source.check_reachable().await;
+                    if reachable {
+                        println!("  Reachable");
+                    } else {
+                        println!("  Not reachable");
+                    }
+                }
+            }
+        }
+        SourcesCommands::Update => {
+            info!("Updating repository indices from sources...");
+
+            let sources = SourcesConfig::load_from_path(sources_file)
+                .map_err(|e| Error::Other(format!("Failed to load sources: {}", e)))?;
+
+            // TODO: Fetch indices from all sources
+            println!(
+                "Updating indices from {} sources",
+                sources.enabled_sources().len()
+            );
+            println!("(Not yet implemented)");
+        }
+    }
+
+    Ok(())
+}
+
+/// Export a software bill of materials
+async fn cmd_sbom(
+    format: &str,
+    output: Option<&Path>,
+    opts: ManagerOptions<'_>,
+) -> Result<(), Error> {
+    let format: SbomFormat = format.parse()?;
+    // A system staged under a root is named after it
+    let name = opts
+        .root
+        .and_then(Path::file_name)
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "rustica".to_string());
+    let manager = open_manager(opts)?;
+
+    let document = manager.sbom(format, &name).await;
+    let json =
+        serde_json::to_string_pretty(&document).map_err(|e| Error::Serialization(e.to_string()))?;
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, json + "\n")?;
+            info!("Wrote {} SBOM to {}", format, path.display());
+        }
+        None => println!("{}", json),
+    }
+
+    Ok(())
+}
+
+/// Maintain a local mirror of a source
+async fn cmd_mirror(action: MirrorCommands, opts: ManagerOptions<'_>) -> Result<(), Error> {
+    let manager = open_manager(opts)?;
+
+    match action {
+        MirrorCommands::Sync { source, dir } => {
+            info!("Syncing mirror of {} into {}", source, dir.display());
+            let report = manager.sync_mirror(&source, &dir).await?;
+
+            for path in &report.downloaded {
+                println!("  + {}", path);
+            }
+            for path in &report.pruned {
+                println!("  - {}", path);
+            }
+            println!(
+                "Mirror of {} is up to date: {} downloaded ({} bytes), {} unchanged, {} pruned",
+                source,
+                report.downloaded.len(),
+                report.bytes,
+                report.unchanged,
+                report.pruned.len()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// List available packages
+async fn cmd_list(
+    pattern: Option<String>,
+    kind: Option<String>,
+    opts: ManagerOptions<'_>,
+) -> Result<(), Error> {
+    let manager = open_manager(opts)?;
+
+    info!("Listing packages...");
+
+    // Check what's available from sources
+    let update_info = manager.check_updates().await?;
+
+    if update_info.available.is_empty() {
+        println!("No packages available (sources may be unreachable)");
+    } else {
+        println!("Available Packages:");
+
+        for update in update_info.available {
+            // Filter by pattern if specified
+            if let Some(ref p) = pattern {
+                if !update.name.contains(p) {
+                    continue;
+                }
+            }
+
+            // Filter by kind if specified
+            if let Some(ref k) = kind {
+                let kind_str = match update.kind {
+                    rpg_core::PackageKind::App => "app",
+                    rpg_core::PackageKind::System => "system",
+                    rpg_core::PackageKind::Kernel => "kernel",
+                    rpg_core::PackageKind::Boot => "boot",
+                };
+                if kind_str != k {
+                    continue;
+                }
+            }
+
+            println!(
+                "  {} ({}) - {} bytes - {}",
+                update.name, update.new_version, update.size, update.kind
+            );
+        }
+    }
+
+    // Also show installed packages
+    let installed = manager.list_installed().await?;
+    if !installed.is_empty() {
+        println!("\nInstalled Packages:");
+        for pkg in installed {
+            // Apply filters
+            if let Some(ref p) = pattern {
+                if !pkg.name.contains(p) {
+                    continue;
+                }
+            }
+            if let Some(ref k) = kind {
+                let kind_str = match pkg.kind {
+                    rpg_core::PackageKind::App => "app",
+                    rpg_core::PackageKind::System => "system",
+                    rpg_core::PackageKind::Kernel => "kernel",
+                    rpg_core::PackageKind::Boot => "boot",
+                };
+                if kind_str != k {
+                    continue;
+                }
+            }
+
+            if pkg.reason == InstallReason::Dependency {
+                println!(
+                    "  {} ({}) - {} (dependency)",
+                    pkg.name, pkg.version, pkg.kind
+                );
+            } else {
+                println!("  {} ({}) - {}", pkg.name, pkg.version, pkg.kind);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Install a package
+async fn cmd_install(
+    package: String,
+    version: Option<String>,
+    no_deps: bool,
+    dry_run: bool,
+    opts: ManagerOptions<'_>,
+) -> Result<(), Error> {
+    let manager = open_manager(opts)?;
+
+    info!("Installing package: {}", package);
+
+    // Determine package kind from the registry or the source indices
+    let kind = manager.resolve_kind(&package).await?;
+    let options = InstallOptions { no_deps };
+
+    if dry_run {
+        let plan = manager
+            .plan_install(&package, version.as_deref(), kind, options)
+            .await?;
+        opts.show_plan(&plan)?;
+        opts.dry_run_done();
+        return Ok(());
+    }
+
+    match manager
+        .install_package_with(&package, version.as_deref(), kind, options)
+        .await?
+    {
+        rpg_core::transaction::TransactionResult::Success {
+            activated,
+            requires_reboot,
+        } => {
+            if !activated.is_empty() {
+                println!("Successfully installed: {}", activated.join(", "));
+            }
+            if !requires_reboot.is_empty() {
+                println!("Reboot required for: {}", requires_reboot.join(", "));
+            }
+            if let Some(ref bin_dir) = manager.app_layout().bin_dir {
+                let on_path = std::env::var_os("PATH")
+                    .map(|path| std::env::split_paths(&path).any(|p| p == *bin_dir))
+                    .unwrap_or(false);
+                if !on_path {
+                    println!("Add {} to PATH to run user apps by name", bin_dir.display());
+                }
+            }
+        }
+        rpg_core::transaction::TransactionResult::Failed { error, .. } => {
+            println!("Installation failed: {}", error);
+            return Err(Error::Other(error));
+        }
+        rpg_core::transaction::TransactionResult::RolledBack { reason, .. } => {
+            println!("Installation rolled back: {}", reason);
+            return Err(Error::Other(reason));
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove a package
+async fn cmd_remove(
+    package: String,
+    options: RemoveOptions,
+    dry_run: bool,
+    opts: ManagerOptions<'_>,
+) -> Result<(), Error> {
+    let manager = open_manager(opts)?;
+
+    info!("Removing package: {}", package);
+
+    if dry_run {
+        opts.show_plan(&manager.plan_remove(&package, options).await?)?;
+        opts.dry_run_done();
+        return Ok(());
+    }
+
+    match manager.remove_package_with(&package, options).await? {
+        rpg_core::transaction::TransactionResult::Success { activated, .. } => {
+            println!("Successfully removed: {}", activated.join(", "));
+        }
+        rpg_core::transaction::TransactionResult::Failed { error, .. } => {
+            println!("Removal failed: {}", error);
+            return Err(Error::Other(error));
+        }
+        rpg_core::transaction::TransactionResult::RolledBack { reason, .. } => {
+            println!("Removal rolled back: {}", reason);
+            return Err(Error::Other(reason));
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove orphaned dependencies
+async fn cmd_autoremove(dry_run: bool, opts: ManagerOptions<'_>) -> Result<(), Error> {
+    let manager = open_manager(opts)?;
+
+    info!("Removing orphaned dependencies");
+
+    if dry_run {
+        opts.show_plan(&manager.plan_autoremove().await?)?;
+        opts.dry_run_done();
+        return Ok(());
+    }
+
+    match manager.autoremove().await? {
+        rpg_core::transaction::TransactionResult::Success { activated, .. } => {
+            if activated.is_empty() {
+                println!("No orphaned dependencies to remove");
+            } else {
+                println!("Successfully removed: {}", activated.join(", "));
+            }
+        }
+        rpg_core::transaction::TransactionResult::Failed { error, .. } => {
+            println!("Autoremove failed: {}", error);
+            return Err(Error::Other(error));
+        }
+        rpg_core::transaction::TransactionResult::RolledBack { reason, .. } => {
+            println!("Autoremove rolled back: {}", reason);
+            return Err(Error::Other(reason));
+        }
+    }
+
+    Ok(())
+}
+
+/// Delete old app versions and unused cached downloads
+async fn cmd_gc(keep: usize, dry_run: bool, opts: ManagerOptions<'_>) -> Result<(), Error> {
+    let manager = open_manager(opts)?;
+
+    if dry_run {
+        opts.show_plan(&manager.plan_gc(keep).await?)?;
+        opts.dry_run_done();
+        return Ok(());
+    }
+
+    let plan = manager.gc(keep).await?;
+    if plan.is_empty() {
+        println!("Nothing to clean up");
+    } else {
+        print!("{}", plan);
+    }
+
+    Ok(())
+}
+
+/// Pin a package to a constraint, or hold it when no constraint is given
+fn cmd_pin(
+    package: String,
+    constraint: Option<String>,
+    dry_run: bool,
+    pins_path: &Path,
+) -> Result<(), Error> {
+    let mut pins = PinConfig::load_from_path(pins_path)?;
+
+    match constraint {
+        Some(constraint) => {
+            let parsed = VersionConstraint::new(&constraint)?;
+            if dry_run {
+                println!("Would pin {} to {}", package, constraint);
+                return Ok(());
+            }
+            info!("Pinning {} to {}", package, constraint);
+            pins.pin(&package, parsed);
+            pins.save_to_path(pins_path)?;
+            println!("Pinned {} to {}", package, constraint);
+        }
+        None => {
+            if dry_run {
+                println!("Would hold {} at its installed version", package);
+                return Ok(());
+            }
+            info!("Holding {}", package);
+            pins.hold(&package);
+            pins.save_to_path(pins_path)?;
+            println!("Holding {} at its installed version", package);
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove a pin or hold from a package
+fn cmd_unpin(package: String, dry_run: bool, pins_path: &Path) -> Result<(), Error> {
+    let mut pins = PinConfig::load_from_path(pins_path)?;
+
+    if !pins.unpin(&package) {
+        return Err(Error::Other(format!("Package is not pinned: {}", package)));
+    }
+
+    if dry_run {
+        println!("Would unpin {}", package);
+    } else {
+        pins.save_to_path(pins_path)?;
+        println!("Unpinned {}", package);
+    }
+    Ok(())
+}
+
+/// Browse and undo past transactions
+async fn cmd_history(action: HistoryCommands, opts: ManagerOptions<'_>) -> Result<(), Error> {
+    let manager = open_manager(opts)?;
+
+    match action {
+        HistoryCommands::List { limit } => {
+            let transactions = manager.history(limit).await;
+
+            if transactions.is_empty() {
+                println!("No transactions recorded.");
+                return Ok(());
+            }
+
+            println!(
+                "{:<26}  {:<20}  {:<13}  {:<10}  Packages",
+                "ID", "Date", "Kind", "State"
+            );
+            for tx in transactions.iter().rev() {
+                println!(
+                    "{:<26}  {:<20}  {:<13}  {:<10}  {}",
+                    tx.id,
+                    format_timestamp(tx.created_at),
+                    tx.kind,
+                    tx.state,
+                    transaction_packages(tx)
+                );
+            }
+        }
+        HistoryCommands::Show { id } => {
+            for tx in manager.find_transactions(&id).await? {
+                println!("Transaction {}", tx.id);
+                println!("  Date: {}", format_timestamp(tx.created_at));
+                println!("  Kind: {}", tx.kind);
+                println!("  State: {}", tx.state);
+                if let Some(ref batch) = tx.batch {
+                    println!("  Batch: {}", batch);
+                }
+                if let Some(ref error) = tx.error {
+                    println!("  Error: {}", error);
+                }
+
+                if !tx.packages.is_empty() {
+                    println!("  Packages:");
+                    for pkg in &tx.packages {
+                        println!("    {} ({})", pkg.reference().id(), pkg.kind());
+                    }
+                }
+
+                let info = &tx.rollback_info;
+                if !info.previous_app_versions.is_empty()
+                    || info.previous_system_version.is_some()
+                    || !info.previous_package_versions.is_empty()
+                {
+                    println!("  Previous versions:");
+                    for (name, version) in info
+                        .previous_app_versions
+                        .iter()
+                        .chain(&info.previous_package_versions)
+                    {
+                        println!("    {}@{}", name, version);
+                    }
+                    if let Some(ref version) = info.previous_system_version {
+                        println!("    system@{}", version);
+                    }
+                }
+
+                if !tx.triggers.is_empty() {
+                    println!("  Triggers:");
+                    for run in &tx.triggers {
+                        let status = match run.status {
+                            Some(0) => "ok".to_string(),
+                            Some(code) => format!("exit {}", code),
+                            None => "failed".to_string(),
+                        };
+                        println!(
+                            "    {} ({}, {} paths): {}",
+                            run.name, run.owner, run.matched, status
+                        );
+                        for line in run.output.lines() {
+                            println!("      {}", line);
+                        }
+                    }
+                }
+                println!();
+            }
+        }
+        HistoryCommands::Undo { id, dry_run: true } => {
+            let transactions = manager.plan_undo(&id).await?;
+            if opts.json {
+                return opts.show_json(&transactions);
+            }
+
+            println!("Undoing {} would revert:", id);
+            for tx in &transactions {
+                println!("  {}  {:<13}  {}", tx.id, tx.kind, transaction_packages(tx));
+            }
+            opts.dry_run_done();
+        }
+        HistoryCommands::Undo { id, dry_run: false } => {
+            info!("Undoing transaction: {}", id);
+
+            match manager.undo(&id).await? {
+                rpg_core::transaction::TransactionResult::Success {
+                    activated,
+                    requires_reboot,
+                } => {
+                    println!("Undid {}", id);
+                    if !activated.is_empty() {
+                        println!("  Affected: {}", activated.join(", "));
+                    }
+                    if !requires_reboot.is_empty() {
+                        println!("Reboot required for: {}", requires_reboot.join(", "));
+                    }
+                }
+                rpg_core::transaction::TransactionResult::Failed { error, .. } => {
+                    println!("Undo failed: {}", error);
+                    return Err(Error::Other(error));
+                }
+                rpg_core::transaction::TransactionResult::RolledBack { reason, .. } => {
+                    println!("Undo rolled back: {}", reason);
+                    return Err(Error::Other(reason));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Review and resolve pending config file changes
+async fn cmd_config(action: ConfigCommands, opts: ManagerOptions<'_>) -> Result<(), Error> {
+    let manager = open_manager(opts)?;
+
+    match action {
+        ConfigCommands::Diff { path } => {
+            let pending: Vec<_> = manager
+                .pending_conffiles()
+                .await
+                .into_iter()
+                .filter(|p| path.as_ref().is_none_or(|path| p.path == *path))
+                .collect();
+
+            if pending.is_empty() {
+                println!("No pending configuration changes.");
+                return Ok(());
+            }
+
+            for conffile in &pending {
+                println!("# {} ({})", conffile.path.display(), conffile.package);
+                print!("{}", conffile.diff()?);
+            }
+            println!("\nResolve with 'rpg config merge <path> --keep-local' or '--use-new'.");
+        }
+        ConfigCommands::Merge {
+            path,
+            keep_local,
+            use_new: _,
+        } => {
+            let choice = if keep_local {
+                MergeChoice::KeepLocal
+            } else {
+                MergeChoice::UseNew
+            };
+            manager.merge_conffile(&path, choice).await?;
+
+            match choice {
+                MergeChoice::KeepLocal => println!("Kept local {}", path.display()),
+                MergeChoice::UseNew => println!("Installed new {}", path.display()),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Manage system snapshots
+async fn cmd_snapshot(action: SnapshotCommands, opts: ManagerOptions<'_>) -> Result<(), Error> {
+    let manager = open_manager(opts)?;
+
+    match action {
+        SnapshotCommands::Create {
+            name,
+            description,
+            dry_run: true,
+        } => {
+            let snapshot = manager.plan_snapshot(&name, description).await?;
+            println!(
+                "Would create snapshot '{}' ({} packages)",
+                snapshot.name,
+                snapshot.versions.len()
+            );
+            opts.dry_run_done();
+        }
+        SnapshotCommands::Create {
+            name,
+            description,
+            dry_run: false,
+        } => {
+            let snapshot = manager.create_snapshot(&name, description).await?;
+            println!(
+                "Created snapshot '{}' ({} packages)",
+                snapshot.name,
+                snapshot.versions.len()
+            );
+        }
+        SnapshotCommands::List => {
+            let snapshots = manager.list_snapshots().await;
+
+            if snapshots.is_empty() {
+                println!("No snapshots.");
+                return Ok(());
+            }
+
+            println!(
+                "{:<24}  {:<20}  {:>8}  Description",
+                "Name", "Date", "Packages"
+            );
+            for snapshot in &snapshots {
+                println!(
+                    "{:<24}  {:<20}  {:>8}  {}",
+                    snapshot.name,
+                    format_timestamp(snapshot.created_at),
+                    snapshot.versions.len(),
+                    snapshot.description.as_deref().unwrap_or("")
+                );
+            }
+        }
+        SnapshotCommands::Restore { name, dry_run } => {
+            let diff = manager.snapshot_diff(&name).await?;
+
+            if diff.changed.is_empty() {
+                println!("Active versions already match snapshot '{}'", name);
+                return Ok(());
+            }
+
+            println!("Restoring snapshot '{}':", name);
+            for (package, current, version) in &diff.changed {
+                match current {
+                    Some(current) => println!("  {} {} -> {}", package, current, version),
+                    None => println!("  {} (inactive) -> {}", package, version),
+                }
+            }
+
+            if dry_run {
+                println!("\nDry run, nothing changed.");
+                return Ok(());
+            }
+
+            match manager.restore_snapshot(&name).await? {
+                rpg_core::transaction::TransactionResult::Success {
+                    requires_reboot, ..
+                } => {
+                    println!("Restored snapshot '{}'", name);
+                    if !diff.extra.is_empty() {
+                        println!(
+                            "Not part of the snapshot, left as is: {}",
+                            diff.extra.join(", ")
+                        );
+                    }
+                    if !requires_reboot.is_empty() {
+                        println!("Reboot required for: {}", requires_reboot.join(", "));
+                    }
+                }
+                rpg_core::transaction::TransactionResult::Failed { error, .. } => {
+                    println!("Restore failed: {}", error);
+                    return Err(Error::Other(error));
+                }
+                rpg_core::transaction::TransactionResult::RolledBack { reason, .. } => {
+                    println!("Restore rolled back, no versions changed: {}", reason);
+                    return Err(Error::Other(reason));
+                }
+            }
+        }
+        SnapshotCommands::Delete {
+            name,
+            dry_run: true,
+        } => {
+            if !manager
+                .list_snapshots()
+                .await
+                .iter()
+                .any(|s| s.name == name)
+            {
+                return Err(Error::Other(format!("Snapshot not found: {}", name)));
+            }
+            println!("Would delete snapshot '{}'", name);
+            opts.dry_run_done();
+        }
+        SnapshotCommands::Delete {
+            name,
+            dry_run: false,
+        } => {
+            manager.delete_snapshot(&name).await?;
+            println!("Deleted snapshot '{}'", name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply the declared world
+async fn cmd_apply(file: &Path, dry_run: bool, opts: ManagerOptions<'_>) -> Result<(), Error> {
+    let world = WorldConfig::load_from_path(file)?;
+    let manager = open_manager(opts)?;
+
+    info!("Planning changes for {}", file.display());
+    let plan = manager.plan_world(&world).await?;
+
+    if opts.json {
+        opts.show_json(&plan)?;
+    } else {
+        print_world_plan(file, &plan);
+    }
+
+    if plan.is_empty() {
+        return Ok(());
+    }
+    if dry_run {
+        opts.dry_run_done();
+        return Ok(());
+    }
+
+    match manager.apply_world(&plan).await? {
+        rpg_core::transaction::TransactionResult::Success {
+            requires_reboot, ..
+        } => {
+            println!("\nApplied {} change(s)", plan.changes.len());
+            if !requires_reboot.is_empty() {
+                println!("Reboot required for: {}", requires_reboot.join(", "));
+            }
+        }
+        rpg_core::transaction::TransactionResult::Failed { error, .. } => {
+            println!("\nApply failed: {}", error);
+            return Err(Error::Other(error));
+        }
+        rpg_core::transaction::TransactionResult::RolledBack { reason, .. } => {
+            println!("\nApply rolled back, no changes kept: {}", reason);
+            return Err(Error::Other(reason));
+        }
+    }
+
+    Ok(())
+}
+
+/// Print what applying the world would change
+fn print_world_plan(file: &Path, plan: &WorldPlan) {
+    if !plan.kept.is_empty() {
+        println!(
+            "Keeping pinned or held apps not listed in {}: {}",
+            file.display(),
+            plan.kept.join(", ")
+        );
+    }
+
+    if plan.is_empty() {
+        println!("System already matches {}", file.display());
+        return;
+    }
+
+    println!("Plan for {}:", file.display());
+    for name in &plan.sources {
+        println!("  source {}", name);
+    }
+    for (name, channel) in &plan.channels {
+        match channel {
+            Some(channel) => println!("  channel {} -> {}", name, channel),
+            None => println!("  channel {} -> source default", name),
+        }
+    }
+    for change in &plan.changes {
+        println!("  {}", change);
+    }
+    for (name, pin) in &plan.pins {
+        match pin {
+            Some(pin) => println!("  pin {} ({})", name, pin),
+            None => println!("  unpin {}", name),
+        }
+    }
+}
+
+/// Ask a yes/no question on the terminal, defaulting to no
+///
+/// Without a terminal to ask on, or when input ends before an answer, this
+/// fails instead of silently declining.
+fn confirm(question: &str) -> Result<bool, Error> {
+    use std::io::IsTerminal;
+
+    if !std::io::stdin().is_terminal() {
+        return Err(Error::Other(
+            "Cannot ask for confirmation without a terminal (use --yes)".to_string(),
+        ));
+    }
+
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer)? == 0 {
+        return Err(Error::Other(
+            "No answer to the confirmation prompt".to_string(),
+        ));
+    }
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Summarize the packages touched by a transaction
+fn transaction_packages(tx: &rpg_core::Transaction) -> String {
+    if !tx.packages.is_empty() {
+        return tx
+            .packages
+            .iter()
+            .map(|p| p.reference().id())
+            .collect::<Vec<_>>()
+            .join(", ");
+    }
+
+    tx.rollback_info
+        .previous_app_versions
+        .iter()
+        .chain(&tx.rollback_info.previous_package_versions)
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,318 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! End-to-end tests of the `rpg` command line against a local repository
+
+mod common;
+
+use common::{TestSystem, SOURCE};
+use rpg_core::InstallReason;
+
+#[test]
+fn test_install_update_rollback_remove() {
+    let mut system = TestSystem::new();
+    system.repo.publish("editor", "1.0.0", &[]);
+
+    system.run(&["install", "editor"]);
+    assert_eq!(system.active("editor").as_deref(), Some("1.0.0"));
+    assert_eq!(system.current("editor").as_deref(), Some("1.0.0"));
+    assert_eq!(
+        std::fs::read_to_string(system.app_dir("editor", "1.0.0").join("editor")).unwrap(),
+        "1.0.0"
+    );
+
+    system.repo.publish("editor", "1.1.0", &[]);
+    let plan = system.run(&["--json", "update", "--dry-run"]);
+    let plan: serde_json::Value = serde_json::from_str(&plan).unwrap();
+    assert_eq!(plan["steps"].as_array().map(|s| s.is_empty()), Some(false));
+
+    // Looking for updates leaves the summary the shell and panel show alone
+    let summary = system
+        .path(rpg_core::layout::STATE_DIR)
+        .join(rpg_core::state::UPDATES_FILE);
+    system.run(&["update", "--check-only"]);
+    assert!(system.run(&["status"]).contains("editor (1.0.0 -> 1.1.0)"));
+    assert!(!summary.exists());
+
+    // Without a terminal to confirm on, nothing is updated unless --yes is given
+    assert!(!system.rpg(&["update"]).status.success());
+    assert_eq!(system.active("editor").as_deref(), Some("1.0.0"));
+
+    system.run(&["update", "--yes"]);
+    assert_eq!(system.active("editor").as_deref(), Some("1.1.0"));
+    let summary: rpg_core::UpdateSummary =
+        serde_json::from_slice(&std::fs::read(&summary).unwrap()).unwrap();
+    assert_eq!(summary.available, 0);
+    assert_eq!(system.current("editor").as_deref(), Some("1.1.0"));
+    assert!(system.app_dir("editor", "1.0.0").is_dir());
+
+    system.run(&["rollback", "editor"]);
+    assert_eq!(system.active("editor").as_deref(), Some("1.0.0"));
+    assert_eq!(system.current("editor").as_deref(), Some("1.0.0"));
+
+    system.run(&["remove", "editor"]);
+    assert_eq!(system.active("editor"), None);
+    assert_eq!(system.current("editor"), None);
+
+    // Every step is in the history
+    assert_eq!(system.registry().get_transactions().len(), 4);
+}
+
+#[test]
+fn test_dependencies_and_autoremove() {
+    let mut system = TestSystem::new();
+    system.repo.publish("libfont", "0.3.0", &[]);
+    system.repo.publish("libui", "1.2.0", &["libfont"]);
+    system.repo.publish("editor", "1.0.0", &["libui >=1.0, <2"]);
+
+    system.run(&["install", "editor"]);
+    let registry = system.registry();
+    assert_eq!(registry.get_reason("editor"), InstallReason::Explicit);
+    assert_eq!(registry.get_reason("libui"), InstallReason::Dependency);
+    assert_eq!(system.current("libfont").as_deref(), Some("0.3.0"));
+
+    // A dependency cannot be removed from under the package needing it
+    assert!(!system.rpg(&["remove", "libui"]).status.success());
+    assert_eq!(system.active("libui").as_deref(), Some("1.2.0"));
+
+    system.run(&["remove", "editor"]);
+    assert_eq!(system.active("libui").as_deref(), Some("1.2.0"));
+
+    system.run(&["autoremove"]);
+    assert_eq!(system.active("libui"), None);
+    assert_eq!(system.active("libfont"), None);
+    assert_eq!(system.current("libfont"), None);
+}
+
+#[test]
+fn test_recovery_after_crash_during_update() {
+    let mut system = TestSystem::new();
+    system.repo.publish("libui", "1.0.0", &[]);
+    system.repo.publish("editor", "1.0.0", &[]);
+    system.run(&["install", "libui"]);
+    system.run(&["install", "editor"]);
+
+    system.repo.publish("libui", "1.1.0", &[]);
+    system.repo.publish("editor", "1.1.0", &[]);
+
+    // Kill rpg while it downloads the second update of the batch
+    let stalled = system.repo.url_path("editor", "1.1.0");
+    system.server.stall(&stalled);
+    let mut child = system.spawn(&["update", "--yes"]);
+    system.server.wait_for_request(&stalled);
+    child.kill().unwrap();
+    child.wait().unwrap();
+    system.server.release(&stalled);
+
+    // Finished updates stay, the interrupted one never became active
+    assert_eq!(system.active("libui").as_deref(), Some("1.1.0"));
+    assert_eq!(system.current("libui").as_deref(), Some("1.1.0"));
+    assert_eq!(system.active("editor").as_deref(), Some("1.0.0"));
+    assert_eq!(system.current("editor").as_deref(), Some("1.0.0"));
+    assert!(!system.app_dir("editor", "1.1.0").exists());
+
+    // The lock still names the dead process, which must not block the next run
+    let lock = std::fs::read_to_string(system.lock_path()).unwrap();
+    assert_eq!(lock.trim(), child.id().to_string());
+
+    system.run(&["update", "--yes"]);
+    assert_eq!(system.active("editor").as_deref(), Some("1.1.0"));
+    assert_eq!(system.current("editor").as_deref(), Some("1.1.0"));
+    assert!(std::fs::read_to_string(system.lock_path())
+        .unwrap()
+        .is_empty());
+
+    // Nothing staged is left next to the installed versions
+    let leftovers: Vec<_> = std::fs::read_dir(system.app_dir("editor", ""))
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with('.'))
+        .collect();
+    assert!(leftovers.is_empty());
+}
+
+#[test]
+fn test_mirror_rejects_untrusted_signatures() {
+    let mut system = TestSystem::new();
+    system.repo.publish("editor", "1.0.0", &[]);
+
+    let mirror = system.scratch("mirror");
+    let mirror_arg = mirror.to_str().unwrap();
+    system.run(&["mirror", "sync", SOURCE, mirror_arg]);
+    assert!(mirror.join("index.json").is_file());
+    assert!(mirror.join("editor/1.0.0.rpg").is_file());
+
+    system.repo.publish("editor", "1.1.0", &[]);
+    system.repo.forge_signature("editor", "1.1.0");
+    assert!(!system
+        .rpg(&["mirror", "sync", SOURCE, mirror_arg])
+        .status
+        .success());
+    assert!(!mirror.join("editor/1.1.0.rpg").exists());
+    assert!(mirror.join("editor/1.0.0.rpg").is_file());
+}
+
+#[test]
+fn test_sources_file_option() {
+    let mut system = TestSystem::new();
+    system.repo.publish("editor", "1.0.0", &[]);
+
+    // Only the given sources file knows about the repository
+    let default = system.path(rpg_core::sources::SOURCES_PATH);
+    std::fs::rename(&default, system.path("/etc/rpg/other.toml")).unwrap();
+    std::fs::write(&default, "").unwrap();
+    assert!(!system.rpg(&["install", "editor"]).status.success());
+
+    system.run(&["--sources-file", "/etc/rpg/other.toml", "install", "editor"]);
+    assert_eq!(system.active("editor").as_deref(), Some("1.0.0"));
+}
+
+#[test]
+fn test_install_rejects_forged_signatures() {
+    let mut system = TestSystem::new();
+    system.repo.publish("editor", "1.0.0", &[]);
+    system.repo.forge_signature("editor", "1.0.0");
+
+    assert!(!system.rpg(&["install", "editor"]).status.success());
+    assert_eq!(system.active("editor"), None);
+    assert_eq!(system.current("editor"), None);
+    assert!(!system.app_dir("editor", "1.0.0").exists());
+
+    // Nor does an update signed by another key replace a trusted version
+    system.repo.publish("editor", "1.0.0", &[]);
+    system.run(&["install", "editor"]);
+    system.repo.publish("editor", "1.1.0", &[]);
+    system.repo.forge_signature("editor", "1.1.0");
+    assert!(system
+        .run(&["update", "--yes"])
+        .contains("Failed to update 1 package(s)"));
+    assert_eq!(system.active("editor").as_deref(), Some("1.0.0"));
+    assert_eq!(system.current("editor").as_deref(), Some("1.0.0"));
+    assert!(!system.app_dir("editor", "1.1.0").exists());
+}
+
+#[test]
+fn test_update_single_package() {
+    let mut system = TestSystem::new();
+    system.repo.publish("editor", "1.0.0", &[]);
+    system.repo.publish("viewer", "1.0.0", &[]);
+    system.run(&["install", "editor"]);
+    system.run(&["install", "viewer"]);
+    system.repo.publish("editor", "1.1.0", &[]);
+    system.repo.publish("viewer", "1.1.0", &[]);
+
+    // A dry run plans the named package only and changes nothing
+    let plan = system.run(&["--json", "update", "--package", "editor", "--dry-run"]);
+    let plan: serde_json::Value = serde_json::from_str(&plan).unwrap();
+    let steps = plan["steps"].as_array().unwrap();
+    assert!(steps.iter().any(|step| step["name"] == "editor"));
+    assert!(steps.iter().all(|step| step["name"] != "viewer"));
+    assert_eq!(system.active("editor").as_deref(), Some("1.0.0"));
+
+    system.run(&["update", "--package", "editor", "--yes"]);
+    assert_eq!(system.active("editor").as_deref(), Some("1.1.0"));
+    assert_eq!(system.active("viewer").as_deref(), Some("1.0.0"));
+
+    // --force downloads a cached archive again
+    system.run(&["rollback", "editor"]);
+    let archive = system.repo.url_path("editor", "1.1.0");
+    let downloads = || {
+        system
+            .server
+            .requests()
+            .iter()
+            .filter(|path| path.ends_with(&archive))
+            .count()
+    };
+    let before = downloads();
+    system.run(&["update", "--package", "editor", "--force", "--yes"]);
+    assert_eq!(downloads(), before + 1);
+    assert_eq!(system.current("editor").as_deref(), Some("1.1.0"));
+
+    assert!(!system
+        .rpg(&["update", "--package", "missing", "--yes"])
+        .status
+        .success());
+}
+
+#[test]
+fn test_rollback_after_crash_during_activation() {
+    let mut system = TestSystem::new();
+    system.repo.publish("libui", "1.0.0", &[]);
+    system.repo.publish("editor", "1.0.0", &["libui"]);
+
+    // Kill rpg once the dependency is active, before the package itself is
+    let stalled = system.repo.url_path("editor", "1.0.0");
+    system.server.stall(&stalled);
+    let mut child = system.spawn(&["install", "editor"]);
+    system.server.wait_for_request(&stalled);
+    child.kill().unwrap();
+    child.wait().unwrap();
+    system.server.release(&stalled);
+    assert_eq!(system.current("libui").as_deref(), Some("1.0.0"));
+    assert_eq!(system.active("editor"), None);
+
+    // The half-done install is undone as a whole
+    let batch = system
+        .registry()
+        .get_transactions()
+        .last()
+        .unwrap()
+        .batch
+        .clone()
+        .unwrap();
+    system.run(&["history", "undo", &batch]);
+    assert_eq!(system.active("libui"), None);
+    assert_eq!(system.current("libui"), None);
+
+    // An activation that fails takes back the dependencies installed for it
+    let in_the_way = system.app_dir("editor", "current");
+    std::fs::create_dir_all(in_the_way.join("leftover")).unwrap();
+    assert!(!system.rpg(&["install", "editor"]).status.success());
+    assert_eq!(system.active("editor"), None);
+    assert_eq!(system.active("libui"), None);
+    assert_eq!(system.current("libui"), None);
+}
+
+#[test]
+fn test_gc() {
+    let mut system = TestSystem::new();
+    for version in ["1.0.0", "1.1.0", "1.2.0"] {
+        system.repo.publish("editor", version, &[]);
+        system.run(&["install", "--version", version, "editor"]);
+    }
+    system.run(&["remove", "editor"]);
+    system.repo.publish("viewer", "0.1.0", &[]);
+    system.run(&["install", "viewer"]);
+    system.run(&["install", "editor"]);
+    let cache = system.path(rpg_core::layout::CACHE_DIR);
+    let cached = || std::fs::read_dir(&cache).unwrap().count();
+    assert_eq!(cached(), 4);
+
+    // A dry run only lists what would go
+    let plan = system.run(&["gc", "--dry-run"]);
+    assert!(plan.contains("editor 1.0.0"));
+    assert!(system.app_dir("editor", "1.0.0").is_dir());
+
+    // The active version and the newest other one stay, with their archives
+    system.run(&["gc"]);
+    assert_eq!(system.current("editor").as_deref(), Some("1.2.0"));
+    assert!(system.app_dir("editor", "1.1.0").is_dir());
+    assert!(!system.app_dir("editor", "1.0.0").exists());
+    assert_eq!(
+        system.registry().list_versions("editor"),
+        vec![
+            rpg_core::Version::new(1, 1, 0),
+            rpg_core::Version::new(1, 2, 0)
+        ]
+    );
+    assert_eq!(cached(), 3);
+
+    system.run(&["gc", "--keep", "0"]);
+    assert!(!system.app_dir("editor", "1.1.0").exists());
+    assert_eq!(system.current("viewer").as_deref(), Some("0.1.0"));
+    assert_eq!(cached(), 2);
+}
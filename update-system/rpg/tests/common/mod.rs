@@ -0,0 +1,442 @@
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Hermetic test systems for driving the `rpg` binary
+//!
+//! A [`TestSystem`] is a temporary root plus a repository of signed
+//! packages served over HTTP on localhost. Tests publish packages, run the
+//! real `rpg` binary with `--root` pointed at the temporary root, and check
+//! the registry and `current` symlinks it leaves behind. Nothing outside
+//! the temporary directory is read or written, and the network is never
+//! used.
+//!
+//! The server can stall a download halfway through, which lets a test
+//! kill `rpg` in the middle of an operation and check that the next run
+//! recovers.
+
+#![allow(dead_code)]
+
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use rpg_core::archive::{create_package, PackageManifest};
+use rpg_core::fetch::{self, PackageEntry, RepositoryIndex};
+use rpg_core::registry::PackageRegistry;
+use rpg_core::signature::KeyPair;
+use rpg_core::{PackageKind, Source, SourcesConfig};
+use tempfile::TempDir;
+
+/// Name of the source the test repository is added as
+pub const SOURCE: &str = "apps";
+
+/// How long to wait for `rpg` to reach a point a test is waiting for
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A temporary root with a repository `rpg` is configured to use
+pub struct TestSystem {
+    /// Keeps the root, repository and home directory alive
+    temp: TempDir,
+    /// Directory `rpg` treats as `/`
+    root: PathBuf,
+    /// The repository served to `rpg`
+    pub repo: Repo,
+    /// HTTP server for the repository
+    pub server: Server,
+}
+
+impl TestSystem {
+    /// Create a root whose only source is the repository, with its key trusted
+    pub fn new() -> Self {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(temp.path().join("home")).unwrap();
+
+        let served = temp.path().join("served");
+        let repo = Repo::new(served.join(SOURCE));
+        let server = Server::start(&served);
+
+        // Only the test repository, so nothing reaches the network
+        let source = Source::new(SOURCE.to_string(), server.url(SOURCE), "apps".to_string())
+            .with_trusted_key(repo.public_key());
+        let sources = SourcesConfig {
+            sources: vec![source],
+            package_channels: Default::default(),
+        };
+        let sources_path = root.join(rpg_core::sources::SOURCES_PATH.trim_start_matches('/'));
+        std::fs::create_dir_all(sources_path.parent().unwrap()).unwrap();
+        sources.save_to_path(sources_path).unwrap();
+
+        Self {
+            temp,
+            root,
+            repo,
+            server,
+        }
+    }
+
+    /// Get the directory `rpg` treats as `/`
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Get a path below the root
+    pub fn path(&self, path: &str) -> PathBuf {
+        self.root.join(path.trim_start_matches('/'))
+    }
+
+    /// Get a scratch directory outside the root
+    pub fn scratch(&self, name: &str) -> PathBuf {
+        self.temp.path().join(name)
+    }
+
+    /// Build an `rpg` command that operates on the root
+    pub fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_rpg"));
+        command
+            .arg("--root")
+            .arg(&self.root)
+            .args(args)
+            .env_clear()
+            .env("HOME", self.temp.path().join("home"))
+            .env("NO_PROXY", "127.0.0.1")
+            .env("RUST_LOG", "warn")
+            .stdin(Stdio::null());
+        command
+    }
+
+    /// Run `rpg` and return its output, whether or not it succeeded
+    pub fn rpg(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    /// Run `rpg`, fail the test if it fails, and return its standard output
+    pub fn run(&self, args: &[&str]) -> String {
+        let output = self.rpg(args);
+        assert!(
+            output.status.success(),
+            "rpg {} failed\nstdout:\n{}\nstderr:\n{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    /// Start `rpg` without waiting for it to finish
+    pub fn spawn(&self, args: &[&str]) -> Child {
+        self.command(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap()
+    }
+
+    /// Load the registry `rpg` saved under the root
+    pub fn registry(&self) -> PackageRegistry {
+        PackageRegistry::load_from_path(self.path(rpg_core::layout::META_DIR).join("registry.json"))
+            .unwrap_or_default()
+    }
+
+    /// Get the active version of a package according to the registry
+    pub fn active(&self, name: &str) -> Option<String> {
+        self.registry().get_active(name).map(|v| v.to_string())
+    }
+
+    /// Get the directory an app version is installed in
+    pub fn app_dir(&self, name: &str, version: &str) -> PathBuf {
+        self.path(rpg_core::layout::APPS_BASE)
+            .join(name)
+            .join(version)
+    }
+
+    /// Get the version an app's `current` symlink points at
+    pub fn current(&self, name: &str) -> Option<String> {
+        let link = self
+            .path(rpg_core::layout::APPS_BASE)
+            .join(name)
+            .join("current");
+        let target = link.read_link().ok()?;
+        Some(target.file_name()?.to_string_lossy().into_owned())
+    }
+
+    /// Get the registry lock file
+    pub fn lock_path(&self) -> PathBuf {
+        self.path(rpg_core::layout::STATE_DIR)
+            .join(rpg_core::lock::LOCK_FILE)
+    }
+}
+
+/// A repository of signed app packages on disk
+pub struct Repo {
+    /// Directory holding `index.json` and the packages
+    dir: PathBuf,
+    /// Key every package is signed with
+    key: KeyPair,
+    /// Packages listed in the index
+    packages: Vec<PackageEntry>,
+}
+
+impl Repo {
+    /// Create an empty repository
+    pub fn new(dir: PathBuf) -> Self {
+        let repo = Self {
+            dir,
+            key: KeyPair::generate(),
+            packages: Vec::new(),
+        };
+        repo.write_index();
+        repo
+    }
+
+    /// Get the key packages are signed with, as `sources add --key` takes it
+    pub fn public_key(&self) -> String {
+        self.key.export_public()
+    }
+
+    /// Build, sign and list an app package
+    ///
+    /// The package installs a single file named after it holding its
+    /// version. Dependencies use the index syntax, e.g. `"libui >=1.0"`.
+    pub fn publish(&mut self, name: &str, version: &str, dependencies: &[&str]) -> PackageEntry {
+        let build_dir = self.dir.join(".build").join(name).join(version);
+        let files_dir = build_dir.join("files");
+        std::fs::create_dir_all(&files_dir).unwrap();
+        std::fs::write(files_dir.join(name), version).unwrap();
+
+        let mut manifest = PackageManifest::new(
+            name.to_string(),
+            version.to_string(),
+            PackageKind::App,
+            rpg_core::arch::host_arch(),
+            0,
+            "0".repeat(64),
+            String::new(),
+            self.key.sign(name.as_bytes()),
+        );
+        manifest.dependencies = dependencies.iter().map(|d| d.to_string()).collect();
+
+        let path = format!("{}/{}.rpg", name, version);
+        let archive = self.dir.join(&path);
+        std::fs::create_dir_all(archive.parent().unwrap()).unwrap();
+        create_package(&files_dir, &archive, manifest).unwrap();
+        std::fs::remove_dir_all(&build_dir).unwrap();
+
+        let bytes = std::fs::read(&archive).unwrap();
+        let entry = PackageEntry {
+            name: name.to_string(),
+            version: version.to_string(),
+            description: None,
+            kind: Some(PackageKind::App),
+            arch: rpg_core::arch::host_arch(),
+            size: bytes.len() as u64,
+            installed_size: None,
+            sha256: fetch::compute_checksum(&archive).unwrap(),
+            signature: self.key.sign(&bytes).to_base64(),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            path,
+            source: String::new(),
+        };
+
+        self.packages
+            .retain(|p| !(p.name == entry.name && p.version == entry.version));
+        self.packages.push(entry.clone());
+        self.write_index();
+        entry
+    }
+
+    /// Re-sign a listed package with a key the source does not trust
+    pub fn forge_signature(&mut self, name: &str, version: &str) {
+        let entry = self
+            .packages
+            .iter_mut()
+            .find(|p| p.name == name && p.version == version)
+            .expect("package is not published");
+        let bytes = std::fs::read(self.dir.join(&entry.path)).unwrap();
+        entry.signature = KeyPair::generate().sign(&bytes).to_base64();
+        self.write_index();
+    }
+
+    /// Stop listing a package
+    pub fn unpublish(&mut self, name: &str, version: &str) {
+        self.packages
+            .retain(|p| !(p.name == name && p.version == version));
+        self.write_index();
+    }
+
+    /// Get the path of a package relative to the server root
+    pub fn url_path(&self, name: &str, version: &str) -> String {
+        format!("{}/{}/{}.rpg", SOURCE, name, version)
+    }
+
+    fn write_index(&self) {
+        let index = RepositoryIndex {
+            name: SOURCE.to_string(),
+            version: "1".to_string(),
+            last_updated: None,
+            packages: self.packages.clone(),
+            source: String::new(),
+        };
+        std::fs::create_dir_all(&self.dir).unwrap();
+        std::fs::write(
+            self.dir.join("index.json"),
+            serde_json::to_vec_pretty(&index).unwrap(),
+        )
+        .unwrap();
+    }
+}
+
+/// A minimal HTTP/1.1 server for static files on localhost
+///
+/// Every connection is answered once and closed. Paths can be stalled:
+/// the server then sends the headers and half of the body and holds the
+/// connection open until the path is released.
+pub struct Server {
+    addr: SocketAddr,
+    state: Arc<ServerState>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct ServerState {
+    /// Directory files are served from
+    dir: PathBuf,
+    /// Paths requested so far, in order
+    requests: Mutex<Vec<String>>,
+    /// Paths whose responses are held back
+    stalled: Mutex<HashSet<String>>,
+    /// Set when the server shuts down
+    stopped: AtomicBool,
+}
+
+impl Server {
+    /// Serve a directory on an unused local port
+    pub fn start(dir: &Path) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(ServerState {
+            dir: dir.to_path_buf(),
+            requests: Mutex::new(Vec::new()),
+            stalled: Mutex::new(HashSet::new()),
+            stopped: AtomicBool::new(false),
+        });
+
+        let accept_state = Arc::clone(&state);
+        let thread = std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if accept_state.stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let state = Arc::clone(&accept_state);
+                std::thread::spawn(move || {
+                    let _ = respond(&state, stream);
+                });
+            }
+        });
+
+        Self {
+            addr,
+            state,
+            thread: Some(thread),
+        }
+    }
+
+    /// Get the URL of a path on the server
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}/{}", self.addr, path.trim_start_matches('/'))
+    }
+
+    /// Get the paths requested so far
+    pub fn requests(&self) -> Vec<String> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    /// Hold back responses for a path until it is released
+    pub fn stall(&self, path: &str) {
+        self.state.stalled.lock().unwrap().insert(path.to_string());
+    }
+
+    /// Answer requests for a stalled path normally again
+    pub fn release(&self, path: &str) {
+        self.state.stalled.lock().unwrap().remove(path);
+    }
+
+    /// Wait until a path has been requested
+    pub fn wait_for_request(&self, path: &str) {
+        let start = Instant::now();
+        while !self.requests().iter().any(|r| r == path) {
+            assert!(start.elapsed() < TIMEOUT, "{} was never requested", path);
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.state.stopped.store(true, Ordering::SeqCst);
+        self.state.stalled.lock().unwrap().clear();
+        // Wake the accept loop so it sees the flag
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Answer a single request
+fn respond(state: &ServerState, mut stream: TcpStream) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header == "\r\n" {
+            break;
+        }
+    }
+
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .trim_start_matches('/')
+        .to_string();
+    state.requests.lock().unwrap().push(path.clone());
+
+    let Ok(body) = std::fs::read(state.dir.join(&path)) else {
+        return stream.write_all(
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        );
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+
+    if state.stalled.lock().unwrap().contains(&path) {
+        stream.write_all(&body[..body.len() / 2])?;
+        stream.flush()?;
+        while state.stalled.lock().unwrap().contains(&path) && !state.stopped.load(Ordering::SeqCst)
+        {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        // The client sees the connection close short of Content-Length
+        return Ok(());
+    }
+
+    stream.write_all(&body)
+}
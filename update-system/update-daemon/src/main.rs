@@ -1,36 +1,172 @@
-// Copyright 2025 The Rustux Authors
-//
-// Use of this source code is governed by a MIT-style
-// license that can be found in the LICENSE file or at
-// https://opensource.org/licenses/MIT
-
-//! Rustica Update Daemon
-//!
-//! Background service for managing system updates
-
-use tracing::{info, error};
-use tracing_subscriber::EnvFilter;
-
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize logging
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::builder()
-                .with_default_directive(tracing::Level::INFO.into())
-                .from_env_lossy(),
-        )
-        .init();
-
-    info!("Rustica Update Daemon starting...");
-
-    // TODO: Implement daemon functionality
-    // - Periodic update checks
-    // - Background downloads
-    // - User preference handling
-    // - Transaction queue management
-
-    info!("Update daemon not yet implemented");
-
-    Ok(())
-}
+// Copyright 2025 The Rustux Authors
+//
+// Use of this source code is governed by a MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT
+
+//! Rustica Update Daemon
+//!
+//! Background service for managing system updates
+
+use std::time::Duration;
+
+use rpg_core::config::UserPreferences;
+use rpg_core::{PackageManager, RebootRequired, RegistryLock, Scope, UpdateConfig, UpdateSummary};
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
+
+/// How long to wait between update checks
+const CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// How long to wait before retrying a failed update check
+const RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Initialize logging
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::builder()
+                .with_default_directive(tracing::Level::INFO.into())
+                .from_env_lossy(),
+        )
+        .init();
+
+    info!("Rustica Update Daemon starting...");
+
+    loop {
+        // Reloaded every time, so configuration changes apply to the next check
+        let config = UpdateConfig::load().unwrap_or_else(|e| {
+            warn!("Using default update configuration: {}", e);
+            UpdateConfig::default()
+        });
+
+        let wait = if !config.live_updates_enabled() {
+            info!("Live updates are disabled, not checking for updates");
+            CHECK_INTERVAL
+        } else {
+            match check_security_updates(&config).await {
+                Ok(()) => CHECK_INTERVAL,
+                Err(e) => {
+                    warn!(
+                        "Update check failed, retrying in {} minutes: {}",
+                        RETRY_INTERVAL.as_secs() / 60,
+                        e
+                    );
+                    RETRY_INTERVAL
+                }
+            }
+        };
+
+        if let Err(e) = notify_update_state() {
+            warn!("Failed to read the update state: {}", e);
+        }
+
+        // TODO: Implement remaining daemon functionality
+        // - Background downloads
+        // - Scheduled installs at the preferred time
+        // - Transaction queue management
+
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Check for updates and install security fixes ahead of everything else
+async fn check_security_updates(config: &UpdateConfig) -> Result<(), Box<dyn std::error::Error>> {
+    // Checking only reads the registry, so it runs without the lock and
+    // doesn't get in the way of rpg while indices are fetched
+    let manager = PackageManager::new()?;
+
+    let update_info = manager.check_updates().await?;
+    manager.publish_updates(&update_info).await;
+    for error in &update_info.errors {
+        warn!("Update check error: {}", error);
+    }
+
+    let security = update_info.clone().security_only();
+    for update in &security.available {
+        warn!(
+            "Security update for {}: {} -> {} ({}, fixes {})",
+            update.name,
+            update.current_version,
+            update.new_version,
+            update.severity.map(|s| s.to_string()).unwrap_or_default(),
+            update.advisories.join(", ")
+        );
+    }
+
+    let deferred = update_info.available.len() - security.available.len();
+    if deferred > 0 {
+        info!(
+            "{} other update(s) wait for the scheduled update time",
+            deferred
+        );
+    }
+
+    if security.available.is_empty() || !config.security_updates_immediately {
+        return Ok(());
+    }
+
+    // Hold the registry lock from loading the registry until saving it;
+    // waiting for it blocks, so it is taken off the runtime's threads
+    let _lock = tokio::task::spawn_blocking(|| RegistryLock::acquire(true)).await??;
+    let manager = PackageManager::new()?;
+
+    // rpg may have changed packages while the daemon waited, so the plan is
+    // made again against the registry as it is now
+    let security = manager.check_updates().await?.security_only();
+    if security.available.is_empty() {
+        return Ok(());
+    }
+
+    let result = manager.install_updates(&security).await?;
+    for name in &result.succeeded {
+        info!("Installed security update for {}", name);
+    }
+    for (name, error) in &result.failed {
+        warn!("Security update for {} failed: {}", name, error);
+    }
+    if !result.requires_reboot.is_empty() {
+        warn!("Reboot required for: {}", result.requires_reboot.join(", "));
+    }
+
+    Ok(())
+}
+
+/// Tell the user about pending updates and reboots, unless they opted out
+///
+/// Reads the state files rpg-core publishes, so it reports the same thing
+/// the shell prompt and the panel show.
+fn notify_update_state() -> Result<(), Box<dyn std::error::Error>> {
+    let preferences = UserPreferences::load().unwrap_or_else(|e| {
+        warn!("Using default user preferences: {}", e);
+        UserPreferences::default()
+    });
+    if !preferences.show_notifications {
+        return Ok(());
+    }
+
+    let state_dir = Scope::System.state_dir()?;
+    if let Some(summary) = UpdateSummary::load(&state_dir) {
+        if summary.security > 0 {
+            info!(
+                target: "notification",
+                "{} update(s) available, {} fixing security issues ({})",
+                summary.available,
+                summary.security,
+                summary.severity.map(|s| s.to_string()).unwrap_or_default()
+            );
+        } else if summary.available > 0 {
+            info!(target: "notification", "{} update(s) available", summary.available);
+        }
+    }
+    if let Some(reboot) = RebootRequired::load(&state_dir) {
+        info!(
+            target: "notification",
+            "Restart to finish updating {}",
+            reboot.packages.join(", ")
+        );
+    }
+
+    Ok(())
+}